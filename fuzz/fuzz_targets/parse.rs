@@ -0,0 +1,259 @@
+//! Make a plausible ELF file, and use everything in the API on it.
+//!
+//! Run with `cargo fuzz run parse`, from the top of the repository.
+//!
+//! Every iterator is walked to the end, errors and all, and every lookup is
+//! tried, so that anything which can panic on a hostile file gets the
+//! chance to. The file is tried on its own, and from inside a TAR archive -
+//! and the rest of the fuzzer's bytes are tried as a TAR archive too.
+
+#![no_main]
+
+use std::cell::Cell;
+
+use libfuzzer_sys::fuzz_target;
+use neotron_loader::symbols::IterSymbols;
+use neotron_loader::tar::TarSource;
+use neotron_loader::{Error, FuzzElf, SectionHeader, Source};
+use neotron_loader::{LoadOptions, LoadProgress, Loader, MemoryRegion, Note, SectionChecksum};
+
+/// The names we look up, in the symbol and section tables
+const LOOKUP_NAMES: [&str; 4] = ["", "main", "_start", ".text"];
+
+/// What we call the file, when we put it in a TAR archive
+const MEMBER_NAME: &str = "app.elf";
+
+/// The size of a TAR header, and the unit that member data is padded to
+const TAR_BLOCK_SIZE: usize = 512;
+
+/// The most bytes we let each load write - like an OS with a little RAM, so
+/// a hostile `p_memsz` doesn't make us write gigabytes of zeroes
+const MAX_LOAD_LEN: u32 = 1024 * 1024;
+
+fuzz_target!(|input: (FuzzElf, &[u8])| {
+    let (elf, rest) = input;
+    if let Ok(member) = TarSource::open(rest, MEMBER_NAME) {
+        if let Ok(loader) = Loader::new(member) {
+            exercise_loader(&loader);
+        }
+    }
+
+    let Ok(loader) = Loader::new(elf.as_bytes()) else {
+        return;
+    };
+    exercise_loader(&loader);
+
+    let archive = to_tar(elf.as_bytes());
+    let member = TarSource::open(&archive[..], MEMBER_NAME).expect("we made this archive");
+    if let Ok(loader) = Loader::new(member) {
+        exercise_loader(&loader);
+    }
+});
+
+/// Use every part of the API on a file.
+fn exercise_loader<DS>(loader: &Loader<DS>)
+where
+    DS: Source,
+{
+    let mut buffer = [0u8; 64];
+    let mut big_buffer = [0u8; 1024];
+
+    for sh in loader.iter_section_headers() {
+        let Ok(sh) = sh else {
+            continue;
+        };
+        let _ = sh.sh_name(loader, &mut buffer);
+        let _ = sh.sh_name_len(loader);
+        sh.name_bytes(loader).for_each(drop);
+        let _ = sh.linked_section(loader);
+        let _ = sh.info_section(loader);
+        let _ = sh.compression(loader);
+        let _ = sh.read_data(loader, 0, &mut buffer);
+        let _ = loader.section_bytes_vec(&sh);
+        if let Ok(notes) = sh.iter_notes(loader) {
+            notes.for_each(|note| exercise_note(loader, note));
+        }
+        if let Ok(relocations) = sh.iter_relocations(loader) {
+            relocations.for_each(drop);
+        }
+        if let Ok(members) = sh.iter_group_members(loader) {
+            members.for_each(drop);
+        }
+        let _ = sh.group_signature(loader);
+        if let Ok(mut strings) = sh.iter_strings(loader, &mut buffer) {
+            while strings.next_string().is_some() {}
+        }
+        if let Ok(symbols) = loader.iter_symbols_in(&sh) {
+            exercise_symbols(loader, symbols);
+        }
+    }
+    for ph in loader.iter_program_headers().rev() {
+        let Ok(ph) = ph else {
+            continue;
+        };
+        let _ = ph.file_range();
+        let _ = ph.mem_range();
+        let _ = loader.copy_segment(&ph, &mut big_buffer);
+        let _ = loader.segment_bytes_vec(&ph);
+    }
+
+    let mut sections = loader.iter_named_sections(&mut buffer);
+    while sections.next_section().is_some() {}
+    loader
+        .iter_notes()
+        .for_each(|note| exercise_note(loader, note));
+    if let Ok(symbols) = loader.iter_symbols() {
+        exercise_symbols(loader, symbols);
+    }
+    if let Ok(symbols) = loader.iter_dynamic_symbols() {
+        exercise_symbols(loader, symbols);
+    }
+    for name in LOOKUP_NAMES {
+        let _ = loader.find_symbol_by_name(name, &mut buffer[0..3]);
+        let _ = loader.hash_lookup(name, &mut buffer[0..3]);
+        let _ = loader.find_section_by_name(name);
+    }
+    for addr in [0, loader.e_entry(), loader.e_entry() & !1, u32::MAX] {
+        let _ = loader.symbolize(addr, &mut buffer);
+        let _ = loader.exidx_entry_for(addr);
+    }
+    let _ = loader.arm_exidx();
+    for sh_type in [
+        SectionHeader::SHT_SYMTAB,
+        SectionHeader::SHT_NOTE,
+        SectionHeader::SHT_GROUP,
+    ] {
+        loader.iter_sections_of_type(sh_type).for_each(drop);
+    }
+    if let Ok(tags) = loader.iter_dynamic_tags() {
+        tags.for_each(drop);
+    }
+    let _ = loader.dynamic_value(1);
+    if let Ok(needed) = loader.iter_needed() {
+        for library in needed {
+            let Ok(library) = library else {
+                continue;
+            };
+            let _ = library.name(loader, &mut buffer);
+        }
+    }
+    let _ = loader.has_needed_libraries();
+    if let Ok(functions) = loader.iter_init_functions() {
+        functions.for_each(drop);
+    }
+    if let Ok(functions) = loader.iter_fini_functions() {
+        functions.for_each(drop);
+    }
+    if let Ok(segments) = loader.iter_load_segments_sorted() {
+        segments.for_each(drop);
+    }
+    if let Ok(headers) = loader.program_headers_buffered(&mut big_buffer) {
+        headers.for_each(drop);
+    }
+    if let Ok(headers) = loader.section_headers_buffered(&mut big_buffer) {
+        headers.for_each(drop);
+    }
+    let _ = loader.interpreter(&mut buffer);
+    let _ = loader.phdr_info();
+    let _ = loader.copy_phdr_table(&mut big_buffer);
+    let _ = loader.check_vector_table(MemoryRegion::new(0x2000_0000, 0x1_0000));
+    let _ = loader.core_registers();
+    let _ = loader.neotron_metadata();
+    let _ = loader.arm_attributes();
+    let _ = loader.toolchain_comment(&mut buffer);
+    let _ = loader.toolchain_comment_lossy(&mut buffer);
+    let _ = loader.build_id(&mut buffer);
+    let mut checksums = [SectionChecksum::default(); 4];
+    let _ = loader.section_checksums(&mut checksums);
+    let _ = loader.apply_relocations(&mut big_buffer, 0x2000_0000, 0x2001_0000, |_name| Some(0));
+
+    let _ = loader.load(limited_writer(&Cell::new(0)));
+    let _ = loader.load_with_options(
+        limited_writer(&Cell::new(0)),
+        &LoadOptions::new().fill_gaps(0),
+    );
+    let written = Cell::new(0);
+    let Ok(mut state) = loader.load_state(limited_writer(&written)) else {
+        return;
+    };
+    // Stop part way through, then do the rest
+    if let Ok(LoadProgress::InProgress { .. }) = state.step(256) {
+        let _ = state.step(u32::MAX);
+    }
+}
+
+/// Make a write function for loading, which fails after `MAX_LOAD_LEN` bytes.
+fn limited_writer<E>(written: &Cell<u32>) -> impl FnMut(u32, &[u8]) -> Result<(), Error<E>> + '_
+where
+    E: core::fmt::Debug,
+{
+    |_addr, bytes| {
+        written.set(written.get().saturating_add(bytes.len() as u32));
+        if written.get() > MAX_LOAD_LEN {
+            return Err(Error::BufferTooSmall {
+                needed: written.get() as usize,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Walk through every symbol in a table, reading each one's name.
+fn exercise_symbols<DS>(loader: &Loader<DS>, symbols: IterSymbols<'_, DS>)
+where
+    DS: Source,
+{
+    let table = symbols.table().clone();
+    let mut buffer = [0u8; 64];
+    for symbol in symbols {
+        let Ok(symbol) = symbol else {
+            continue;
+        };
+        let _ = symbol.st_name(loader, &table, &mut buffer);
+        let _ = symbol.section();
+    }
+}
+
+/// Read a note's name and descriptor.
+fn exercise_note<DS, E>(loader: &Loader<DS>, note: Result<Note, E>)
+where
+    DS: Source,
+{
+    let mut buffer = [0u8; 64];
+    if let Ok(note) = note {
+        let _ = note.name(loader, &mut buffer);
+        let _ = note.descriptor(loader, &mut buffer);
+    }
+}
+
+/// Make a TAR archive, with `elf` as its only member, called `app.elf`.
+fn to_tar(elf: &[u8]) -> Vec<u8> {
+    let mut header = [0u8; TAR_BLOCK_SIZE];
+    header[0..MEMBER_NAME.len()].copy_from_slice(MEMBER_NAME.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    write_octal(&mut header[124..136], elf.len() as u32);
+    header[156] = b'0';
+    header[257..265].copy_from_slice(b"ustar\x0000");
+    // The checksum is calculated with the checksum field as spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|b| u32::from(*b)).sum();
+    write_octal(&mut header[148..155], checksum);
+
+    let padded_len = elf.len().next_multiple_of(TAR_BLOCK_SIZE);
+    let mut archive = Vec::with_capacity(padded_len + 3 * TAR_BLOCK_SIZE);
+    archive.extend_from_slice(&header);
+    archive.extend_from_slice(elf);
+    // Pad the member, then add the two empty blocks which end the archive
+    archive.resize(padded_len + 3 * TAR_BLOCK_SIZE, 0);
+    archive
+}
+
+/// Write `value` as zero-padded octal digits, and a null, filling `field`.
+fn write_octal(field: &mut [u8], mut value: u32) {
+    let (last, digits) = field.split_last_mut().expect("field is not empty");
+    *last = 0;
+    for digit in digits.iter_mut().rev() {
+        *digit = b'0' + (value % 8) as u8;
+        value /= 8;
+    }
+}
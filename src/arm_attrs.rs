@@ -0,0 +1,379 @@
+//! Code and types for handling ARM build attributes.
+//!
+//! These live in the `.ARM.attributes` section, and say which architecture
+//! and floating-point unit a file was built for. This lets us refuse to run a
+//! file which needs (say) a Cortex-M4F on a Cortex-M0+.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The only version of the attributes format
+const FORMAT_VERSION: u8 = b'A';
+
+/// The vendor name of the standard attributes
+const AEABI_VENDOR: &[u8] = b"aeabi";
+
+/// A sub-subsection which applies to the whole file
+const TAG_FILE: u32 = 1;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// The build attributes we care about, from the `aeabi` subsection.
+///
+/// Each is `None` if the file didn't say.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ArmAttributes {
+    cpu_arch: Option<u32>,
+    cpu_arch_profile: Option<u32>,
+    fp_arch: Option<u32>,
+    abi_vfp_args: Option<u32>,
+}
+
+impl ArmAttributes {
+    /// The CPU name, as given to the compiler
+    pub const TAG_CPU_RAW_NAME: u32 = 4;
+    /// The CPU name, as given by the architecture
+    pub const TAG_CPU_NAME: u32 = 5;
+    /// The architecture version
+    pub const TAG_CPU_ARCH: u32 = 6;
+    /// The architecture profile
+    pub const TAG_CPU_ARCH_PROFILE: u32 = 7;
+    /// The floating-point architecture
+    pub const TAG_FP_ARCH: u32 = 10;
+    /// How floating-point arguments are passed
+    pub const TAG_ABI_VFP_ARGS: u32 = 28;
+    /// Which toolchains this file is compatible with
+    pub const TAG_COMPATIBILITY: u32 = 32;
+    /// Which other architectures this file is compatible with
+    pub const TAG_ALSO_COMPATIBLE_WITH: u32 = 65;
+    /// Which version of the ABI this file conforms to
+    pub const TAG_CONFORMANCE: u32 = 67;
+
+    /// `Tag_CPU_arch` value for ARMv7 (see the profile for v7-M)
+    pub const CPU_ARCH_V7: u32 = 10;
+    /// `Tag_CPU_arch` value for ARMv6-M (e.g. Cortex-M0)
+    pub const CPU_ARCH_V6_M: u32 = 11;
+    /// `Tag_CPU_arch` value for ARMv6S-M (e.g. Cortex-M0+)
+    pub const CPU_ARCH_V6S_M: u32 = 12;
+    /// `Tag_CPU_arch` value for ARMv7E-M (e.g. Cortex-M4)
+    pub const CPU_ARCH_V7E_M: u32 = 13;
+    /// `Tag_CPU_arch` value for ARMv8-M Baseline (e.g. Cortex-M23)
+    pub const CPU_ARCH_V8_M_BASELINE: u32 = 16;
+    /// `Tag_CPU_arch` value for ARMv8-M Mainline (e.g. Cortex-M33)
+    pub const CPU_ARCH_V8_M_MAINLINE: u32 = 17;
+
+    /// `Tag_CPU_arch_profile` value for the Microcontroller profile
+    pub const PROFILE_MICROCONTROLLER: u32 = b'M' as u32;
+
+    /// `Tag_ABI_VFP_args` value for passing arguments in integer registers
+    pub const VFP_ARGS_BASE: u32 = 0;
+    /// `Tag_ABI_VFP_args` value for passing arguments in VFP registers
+    pub const VFP_ARGS_VFP: u32 = 1;
+
+    /// Parse the `aeabi` attributes from an `.ARM.attributes` section.
+    fn parse<DS: Source>(
+        loader: &Loader<DS>,
+        start: u32,
+        end: u32,
+    ) -> Result<ArmAttributes, Error<DS::Error>> {
+        let mut reader = Reader {
+            loader,
+            offset: start,
+            end,
+        };
+        if reader.read_u8()? != FORMAT_VERSION {
+            return Err(Error::BadAttributes);
+        }
+
+        let mut attributes = ArmAttributes::default();
+        while reader.offset < reader.end {
+            // Each subsection starts with its length, then the vendor name
+            let subsection_start = reader.offset;
+            let subsection_end = reader.read_length(subsection_start)?;
+            let is_aeabi = reader.ntbs_eq(AEABI_VENDOR)?;
+            while is_aeabi && reader.offset < subsection_end {
+                // Each sub-subsection starts with its scope tag, then its
+                // length (which counts the tag too)
+                let tag_start = reader.offset;
+                let scope = reader.read_uleb128()?;
+                let tag_end = reader.read_length(tag_start)?;
+                if tag_end > subsection_end {
+                    return Err(Error::BadAttributes);
+                }
+                if scope == TAG_FILE {
+                    let mut tags = Reader {
+                        loader,
+                        offset: reader.offset,
+                        end: tag_end,
+                    };
+                    attributes.parse_tags(&mut tags)?;
+                }
+                reader.offset = tag_end;
+            }
+            reader.offset = subsection_end;
+        }
+        Ok(attributes)
+    }
+
+    /// Parse a list of tag/value pairs.
+    fn parse_tags<DS: Source>(
+        &mut self,
+        reader: &mut Reader<'_, DS>,
+    ) -> Result<(), Error<DS::Error>> {
+        while reader.offset < reader.end {
+            let tag = reader.read_uleb128()?;
+            match tag {
+                Self::TAG_CPU_RAW_NAME
+                | Self::TAG_CPU_NAME
+                | Self::TAG_ALSO_COMPATIBLE_WITH
+                | Self::TAG_CONFORMANCE => reader.skip_ntbs()?,
+                Self::TAG_COMPATIBILITY => {
+                    reader.read_uleb128()?;
+                    reader.skip_ntbs()?;
+                }
+                Self::TAG_CPU_ARCH => self.cpu_arch = Some(reader.read_uleb128()?),
+                Self::TAG_CPU_ARCH_PROFILE => self.cpu_arch_profile = Some(reader.read_uleb128()?),
+                Self::TAG_FP_ARCH => self.fp_arch = Some(reader.read_uleb128()?),
+                Self::TAG_ABI_VFP_ARGS => self.abi_vfp_args = Some(reader.read_uleb128()?),
+                // Above 32, odd tags are strings and even tags are numbers.
+                // Below that, they are all numbers unless listed above.
+                tag if tag > 32 && tag % 2 == 1 => reader.skip_ntbs()?,
+                _ => {
+                    reader.read_uleb128()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Get `Tag_CPU_arch`, e.g. `CPU_ARCH_V6S_M`.
+    pub fn cpu_arch(&self) -> Option<u32> {
+        self.cpu_arch
+    }
+
+    /// Get `Tag_CPU_arch_profile`, e.g. `PROFILE_MICROCONTROLLER`.
+    pub fn cpu_arch_profile(&self) -> Option<u32> {
+        self.cpu_arch_profile
+    }
+
+    /// Get `Tag_FP_arch`. Zero, or `None`, means no floating-point unit is
+    /// used.
+    pub fn fp_arch(&self) -> Option<u32> {
+        self.fp_arch
+    }
+
+    /// Get `Tag_ABI_VFP_args`, e.g. `VFP_ARGS_VFP` for the hard-float ABI.
+    pub fn abi_vfp_args(&self) -> Option<u32> {
+        self.abi_vfp_args
+    }
+
+    /// Could this file run on the given CPU?
+    ///
+    /// Files which don't say what they were built for are assumed to be
+    /// compatible.
+    pub fn compatible_with(&self, cpu: CpuProfile) -> bool {
+        if let Some(profile) = self.cpu_arch_profile {
+            if profile != 0 && profile != Self::PROFILE_MICROCONTROLLER {
+                // Built for an A or R profile core
+                return false;
+            }
+        }
+        let arch_ok = match self.cpu_arch {
+            None => true,
+            Some(Self::CPU_ARCH_V6_M | Self::CPU_ARCH_V6S_M) => true,
+            Some(Self::CPU_ARCH_V7) => cpu.has_v7m(),
+            Some(Self::CPU_ARCH_V7E_M) => cpu.has_v7em(),
+            Some(Self::CPU_ARCH_V8_M_BASELINE) => cpu.has_v8m_baseline(),
+            Some(Self::CPU_ARCH_V8_M_MAINLINE) => cpu.has_v8m_mainline(),
+            Some(_) => false,
+        };
+        let uses_fpu =
+            self.fp_arch.unwrap_or(0) != 0 || self.abi_vfp_args == Some(Self::VFP_ARGS_VFP);
+        arch_ok && (!uses_fpu || cpu.has_fpu())
+    }
+}
+
+/// The kinds of CPU that Neotron might be running on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CpuProfile {
+    /// ARMv6-M, e.g. Cortex-M0 or Cortex-M0+
+    ArmV6M,
+    /// ARMv7-M, e.g. Cortex-M3
+    ArmV7M,
+    /// ARMv7E-M with no FPU, e.g. Cortex-M4
+    ArmV7EM,
+    /// ARMv7E-M with an FPU, e.g. Cortex-M4F or Cortex-M7F
+    ArmV7EMFp,
+    /// ARMv8-M Baseline, e.g. Cortex-M23
+    ArmV8MBaseline,
+    /// ARMv8-M Mainline with no FPU, e.g. Cortex-M33
+    ArmV8MMainline,
+    /// ARMv8-M Mainline with an FPU, e.g. Cortex-M33F
+    ArmV8MMainlineFp,
+}
+
+impl CpuProfile {
+    /// Can this CPU run ARMv7-M code?
+    fn has_v7m(self) -> bool {
+        matches!(
+            self,
+            CpuProfile::ArmV7M
+                | CpuProfile::ArmV7EM
+                | CpuProfile::ArmV7EMFp
+                | CpuProfile::ArmV8MMainline
+                | CpuProfile::ArmV8MMainlineFp
+        )
+    }
+
+    /// Can this CPU run ARMv7E-M code?
+    fn has_v7em(self) -> bool {
+        matches!(
+            self,
+            CpuProfile::ArmV7EM
+                | CpuProfile::ArmV7EMFp
+                | CpuProfile::ArmV8MMainline
+                | CpuProfile::ArmV8MMainlineFp
+        )
+    }
+
+    /// Can this CPU run ARMv8-M Baseline code?
+    fn has_v8m_baseline(self) -> bool {
+        matches!(
+            self,
+            CpuProfile::ArmV8MBaseline | CpuProfile::ArmV8MMainline | CpuProfile::ArmV8MMainlineFp
+        )
+    }
+
+    /// Can this CPU run ARMv8-M Mainline code?
+    fn has_v8m_mainline(self) -> bool {
+        matches!(
+            self,
+            CpuProfile::ArmV8MMainline | CpuProfile::ArmV8MMainlineFp
+        )
+    }
+
+    /// Does this CPU have a floating-point unit?
+    pub fn has_fpu(self) -> bool {
+        matches!(self, CpuProfile::ArmV7EMFp | CpuProfile::ArmV8MMainlineFp)
+    }
+}
+
+/// Reads bytes from part of the attributes section.
+struct Reader<'a, DS> {
+    loader: &'a Loader<DS>,
+    offset: u32,
+    end: u32,
+}
+
+impl<'a, DS> Reader<'a, DS>
+where
+    DS: Source,
+{
+    /// Read one byte.
+    fn read_u8(&mut self) -> Result<u8, Error<DS::Error>> {
+        if self.offset >= self.end {
+            return Err(Error::BadAttributes);
+        }
+        let value = self.loader.data_source.read_u8(self.offset)?;
+        self.offset += 1;
+        Ok(value)
+    }
+
+    /// Read a length field, and return the offset it says we end at.
+    ///
+    /// The length counts from `start`.
+    fn read_length(&mut self, start: u32) -> Result<u32, Error<DS::Error>> {
+        if self.end.saturating_sub(self.offset) < 4 {
+            return Err(Error::BadAttributes);
+        }
+        let length = self.loader.data_source.read_u32_le(self.offset)?;
+        self.offset += 4;
+        let end = start.saturating_add(length);
+        if end < self.offset || end > self.end {
+            return Err(Error::BadAttributes);
+        }
+        Ok(end)
+    }
+
+    /// Read an unsigned LEB128 number.
+    ///
+    /// Bits that don't fit in 32 bits are dropped.
+    fn read_uleb128(&mut self) -> Result<u32, Error<DS::Error>> {
+        let mut value = 0u32;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift < 32 {
+                value |= u32::from(byte & 0x7F) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Skip over a null-terminated string.
+    fn skip_ntbs(&mut self) -> Result<(), Error<DS::Error>> {
+        while self.read_u8()? != 0 {}
+        Ok(())
+    }
+
+    /// Read a null-terminated string, and check if it is equal to `expected`.
+    fn ntbs_eq(&mut self, expected: &[u8]) -> Result<bool, Error<DS::Error>> {
+        let mut matches = true;
+        let mut idx = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if byte == 0 {
+                return Ok(matches && idx == expected.len());
+            }
+            if expected.get(idx) != Some(&byte) {
+                matches = false;
+            }
+            idx += 1;
+        }
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Get the ARM build attributes for this file.
+    ///
+    /// Returns `None` if there is no `.ARM.attributes` section.
+    pub fn arm_attributes(&self) -> Result<Option<ArmAttributes>, Error<DS::Error>> {
+        let Some((_idx, sh)) = self.first_section_of_type(SectionHeader::SHT_ARM_ATTRIBUTES)?
+        else {
+            return Ok(None);
+        };
+        let end = sh.sh_offset().saturating_add(sh.sh_size());
+        ArmAttributes::parse(self, sh.sh_offset(), end).map(Some)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
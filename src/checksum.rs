@@ -0,0 +1,159 @@
+//! Code for checksumming the sections of a file.
+//!
+//! A CRC32 for each allocated section lets you work out which part of an
+//! image has been corrupted, rather than just that something has.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The reversed IEEE 802.3 CRC32 polynomial, as used by zlib.
+const CRC32_POLYNOMIAL: u32 = 0xEDB8_8320;
+
+/// How many bytes we read at a time when checksumming.
+const CHUNK_SIZE: usize = 64;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+/// Lookup table for the CRC32, one entry per byte value.
+static CRC32_TABLE: [u32; 256] = make_crc32_table();
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// The checksum of one allocated section.
+///
+/// Filled in by `loader.section_checksums()`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct SectionChecksum {
+    index: u16,
+    addr: u32,
+    len: u32,
+    crc: u32,
+}
+
+impl SectionChecksum {
+    /// The index of the section in the section header table.
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// The address of the section (its `sh_addr`).
+    pub fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    /// The number of bytes checksummed (its `sh_size`).
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Were there no bytes to checksum?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The CRC32 (as used by zlib and Ethernet) of the section contents.
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Calculate a CRC32 for every allocated (`SHF_ALLOC`) section.
+    ///
+    /// The checksums are written to `out` in section index order, and we
+    /// return how many there were. If `out` is too short, you get
+    /// `Error::BufferTooSmall`, which says how many there are.
+    ///
+    /// `SHT_NOBITS` sections (like `.bss`) have no contents in the file, so
+    /// they are skipped.
+    pub fn section_checksums(
+        &self,
+        out: &mut [SectionChecksum],
+    ) -> Result<usize, Error<DS::Error>> {
+        let mut count = 0;
+        for (index, sh) in self.iter_section_headers().enumerate() {
+            let sh = sh?;
+            if !sh.flags().is_alloc() || sh.is_nobits() {
+                continue;
+            }
+            // Once we're out of room, we just count the rest
+            if let Some(slot) = out.get_mut(count) {
+                *slot = SectionChecksum {
+                    index: index as u16,
+                    addr: sh.sh_addr(),
+                    len: sh.sh_size(),
+                    crc: self.crc32(sh.sh_offset(), sh.sh_size())?,
+                };
+            }
+            count += 1;
+        }
+        if count > out.len() {
+            return Err(Error::BufferTooSmall { needed: count });
+        }
+        Ok(count)
+    }
+
+    /// Calculate the CRC32 of `len` bytes starting at `offset`.
+    fn crc32(&self, offset: u32, len: u32) -> Result<u32, Error<DS::Error>> {
+        let mut chunk = [0u8; CHUNK_SIZE];
+        let mut crc = !0u32;
+        let mut done = 0;
+        while done < len {
+            let chunk_len = CHUNK_SIZE.min((len - done) as usize);
+            let chunk = &mut chunk[0..chunk_len];
+            self.data_source.read(offset + done, chunk)?;
+            for b in chunk.iter() {
+                crc = CRC32_TABLE[usize::from((crc as u8) ^ b)] ^ (crc >> 8);
+            }
+            done += chunk_len as u32;
+        }
+        Ok(!crc)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Build the CRC32 lookup table.
+const fn make_crc32_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut idx = 0;
+    while idx < 256 {
+        let mut value = idx as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            value = if value & 1 != 0 {
+                (value >> 1) ^ CRC32_POLYNOMIAL
+            } else {
+                value >> 1
+            };
+            bit += 1;
+        }
+        table[idx] = value;
+        idx += 1;
+    }
+    table
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,116 @@
+//! Code for reading the `.comment` section.
+//!
+//! Compilers and linkers write their name and version here, so it tells you
+//! which toolchain built a file.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The name of the section holding the toolchain identification strings.
+const COMMENT_SECTION: &str = ".comment";
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Get the toolchain identification string from the `.comment` section,
+    /// e.g. `Linker: LLD 15.0.7`.
+    ///
+    /// The section can hold several null-terminated strings (one from each
+    /// tool), and this returns the first one that isn't empty. If there is
+    /// no `.comment` section, you get `None`.
+    ///
+    /// If `buffer` is too small, you get `Error::BufferTooSmall`, which says
+    /// how big it needs to be. If the string isn't valid UTF-8, you get
+    /// `Error::InvalidString` - see `toolchain_comment_lossy`.
+    pub fn toolchain_comment<'a>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<Option<&'a str>, Error<DS::Error>> {
+        self.read_comment(buffer, false)
+    }
+
+    /// Like `toolchain_comment`, but any invalid UTF-8 is replaced with `?`
+    /// instead of giving an error.
+    pub fn toolchain_comment_lossy<'a>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<Option<&'a str>, Error<DS::Error>> {
+        self.read_comment(buffer, true)
+    }
+
+    /// Read the first non-empty string in the `.comment` section.
+    fn read_comment<'a>(
+        &self,
+        buffer: &'a mut [u8],
+        lossy: bool,
+    ) -> Result<Option<&'a str>, Error<DS::Error>> {
+        let Some(sh) = self.find_section_by_name(COMMENT_SECTION)? else {
+            return Ok(None);
+        };
+        let end = sh.sh_offset().saturating_add(sh.sh_size());
+        let mut offset = sh.sh_offset();
+        let len = loop {
+            if offset >= end {
+                // Nothing but empty strings
+                return Ok(None);
+            }
+            let len = self.str_len(offset, end)?;
+            if len > 0 {
+                break len;
+            }
+            offset += 1;
+        };
+        let Some(buffer) = buffer.get_mut(0..len) else {
+            return Err(Error::BufferTooSmall { needed: len });
+        };
+        self.data_source.read(offset, buffer)?;
+        if lossy {
+            replace_invalid_utf8(buffer);
+        }
+        match core::str::from_utf8(buffer) {
+            Ok(s) => Ok(Some(s)),
+            Err(_) => Err(Error::InvalidString),
+        }
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Replace each invalid UTF-8 sequence in `bytes` with `?` characters.
+fn replace_invalid_utf8(bytes: &mut [u8]) {
+    let mut pos = 0;
+    while let Err(e) = core::str::from_utf8(&bytes[pos..]) {
+        let bad_start = pos + e.valid_up_to();
+        let bad_len = e.error_len().unwrap_or(bytes.len() - bad_start);
+        for b in &mut bytes[bad_start..bad_start + bad_len] {
+            *b = b'?';
+        }
+        pos = bad_start + bad_len;
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,117 @@
+//! Code for reading core dumps.
+//!
+//! A core dump (`ET_CORE`) holds the memory of a crashed program in `PT_LOAD`
+//! segments, and its register state in an `NT_PRSTATUS` note. You need
+//! `LoaderOptions::allow_core` to open one.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, Note, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// How many bytes the registers take up, in a `prstatus` descriptor
+const PRSTATUS_REGS_LEN: usize = ArmCoreRegs::PRSTATUS_NUM_REGS * 4;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// The ARM general-purpose registers, from a core dump.
+///
+/// Created with `loader.core_registers()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArmCoreRegs {
+    regs: [u32; 16],
+    xpsr: u32,
+}
+
+impl ArmCoreRegs {
+    /// Where the registers start, in an ARM `prstatus` descriptor
+    pub const PRSTATUS_REGS_OFFSET: usize = 72;
+
+    /// How many registers are saved in a `prstatus` descriptor (r0-r15, the
+    /// status register, and the original r0)
+    pub const PRSTATUS_NUM_REGS: usize = 18;
+
+    /// Get r0 to r15.
+    pub fn regs(&self) -> &[u32; 16] {
+        &self.regs
+    }
+
+    /// Get the stack pointer (r13).
+    pub fn sp(&self) -> u32 {
+        self.regs[13]
+    }
+
+    /// Get the link register (r14).
+    pub fn lr(&self) -> u32 {
+        self.regs[14]
+    }
+
+    /// Get the program counter (r15).
+    pub fn pc(&self) -> u32 {
+        self.regs[15]
+    }
+
+    /// Get the program status register.
+    pub fn xpsr(&self) -> u32 {
+        self.xpsr
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Get the registers from the first `NT_PRSTATUS` note in the `PT_NOTE`
+    /// segments.
+    ///
+    /// Returns `None` if there isn't one. A note too short to hold the
+    /// registers gives `Error::BadNote`.
+    pub fn core_registers(&self) -> Result<Option<ArmCoreRegs>, Error<DS::Error>> {
+        for note in self.iter_notes() {
+            let note = note?;
+            if note.n_type() != Note::NT_PRSTATUS || !note.name_eq(self, "CORE")? {
+                continue;
+            }
+            let end = ArmCoreRegs::PRSTATUS_REGS_OFFSET + PRSTATUS_REGS_LEN;
+            if (note.desc_len() as usize) < end {
+                return Err(Error::BadNote);
+            }
+            let mut raw = [0u8; PRSTATUS_REGS_LEN];
+            let offset = note.desc_offset() + ArmCoreRegs::PRSTATUS_REGS_OFFSET as u32;
+            self.data_source.read(offset, &mut raw)?;
+            let mut words = raw
+                .chunks_exact(4)
+                .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]));
+            let mut regs = [0u32; 16];
+            for (reg, value) in regs.iter_mut().zip(words.by_ref()) {
+                *reg = value;
+            }
+            let xpsr = words.next().unwrap_or_default();
+            return Ok(Some(ArmCoreRegs { regs, xpsr }));
+        }
+        Ok(None)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
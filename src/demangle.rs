@@ -0,0 +1,98 @@
+//! Code for demangling Rust symbol names.
+//!
+//! Only available with the `demangle` feature, which uses the
+//! `rustc-demangle` crate. Anything which isn't a Rust symbol (e.g. a C
+//! name) is passed through unchanged.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{
+    relocations::MAX_SYMBOL_NAME_LEN, symbols::SymbolMatch, Loader, SectionHeader, Source, Symbol,
+};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Displays a symbol name, demangled if it is a Rust symbol.
+///
+/// The hash on the end of a Rust symbol is left out, so
+/// `_ZN4core9panicking5panic17h12f26557d2236b3cE` is shown as
+/// `core::panicking::panic`.
+#[derive(Debug, Clone, Copy)]
+pub struct Demangle<'a> {
+    name: &'a str,
+}
+
+impl<'a> Demangle<'a> {
+    /// Wrap a raw symbol name.
+    pub fn new(name: &'a str) -> Demangle<'a> {
+        Demangle { name }
+    }
+
+    /// Is this a Rust symbol we know how to demangle?
+    pub fn is_rust(&self) -> bool {
+        rustc_demangle::try_demangle(self.name).is_ok()
+    }
+}
+
+impl<'a> core::fmt::Display for Demangle<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match rustc_demangle::try_demangle(self.name) {
+            // The alternate form leaves out the hash
+            Ok(demangled) => write!(f, "{:#}", demangled),
+            Err(_) => f.write_str(self.name),
+        }
+    }
+}
+
+impl Symbol {
+    /// Write the name of this symbol to `w`, demangled if it is a Rust
+    /// symbol.
+    ///
+    /// Other names (e.g. C functions) are written unchanged. Names longer
+    /// than `MAX_SYMBOL_NAME_LEN` bytes, or which can't be read, give
+    /// `core::fmt::Error`.
+    pub fn demangled_name<DS: Source, W: core::fmt::Write>(
+        &self,
+        loader: &Loader<DS>,
+        symtab: &SectionHeader,
+        w: &mut W,
+    ) -> core::fmt::Result {
+        let mut buffer = [0u8; MAX_SYMBOL_NAME_LEN];
+        let name = self
+            .st_name(loader, symtab, &mut buffer)
+            .map_err(|_| core::fmt::Error)?;
+        write!(w, "{}", Demangle::new(name))
+    }
+}
+
+impl<'a> SymbolMatch<'a> {
+    /// The name of the function containing the address, demangled if it is
+    /// a Rust function.
+    pub fn demangled_name(&self) -> Demangle<'a> {
+        Demangle::new(self.name())
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,324 @@
+//! Code and types for handling the dynamic section.
+//!
+//! This lives in the `PT_DYNAMIC` segment, and is an array of tag/value pairs
+//! terminated by a `DT_NULL` tag. It is useful for position-independent
+//! executables and plugins.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::{iter::FusedIterator, ops::Range};
+
+use crate::{checked_range, Error, Loader, ProgramHeader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Represents an entry in the dynamic section.
+#[derive(Debug, Clone)]
+pub struct DynamicTag {
+    tag: i32,
+    value: u32,
+}
+
+impl DynamicTag {
+    /// Size of a dynamic section entry
+    pub const SIZE_IN_BYTES: u16 = 0x08;
+
+    /// Marks the end of the dynamic section.
+    pub const DT_NULL: i32 = 0;
+    /// String table offset of the name of a needed library.
+    pub const DT_NEEDED: i32 = 1;
+    /// Total size of the PLT relocation entries.
+    pub const DT_PLTRELSZ: i32 = 2;
+    /// Address of the PLT and/or GOT.
+    pub const DT_PLTGOT: i32 = 3;
+    /// Address of the symbol hash table.
+    pub const DT_HASH: i32 = 4;
+    /// Address of the string table.
+    pub const DT_STRTAB: i32 = 5;
+    /// Address of the symbol table.
+    pub const DT_SYMTAB: i32 = 6;
+    /// Address of the relocation table with addends.
+    pub const DT_RELA: i32 = 7;
+    /// Total size of the relocation table with addends.
+    pub const DT_RELASZ: i32 = 8;
+    /// Size of one relocation entry with addend.
+    pub const DT_RELAENT: i32 = 9;
+    /// Size of the string table.
+    pub const DT_STRSZ: i32 = 10;
+    /// Size of one symbol table entry.
+    pub const DT_SYMENT: i32 = 11;
+    /// Address of the initialisation function.
+    pub const DT_INIT: i32 = 12;
+    /// Address of the termination function.
+    pub const DT_FINI: i32 = 13;
+    /// String table offset of the name of this shared object.
+    pub const DT_SONAME: i32 = 14;
+    /// Address of the relocation table without addends.
+    pub const DT_REL: i32 = 17;
+    /// Total size of the relocation table without addends.
+    pub const DT_RELSZ: i32 = 18;
+    /// Size of one relocation entry without addend.
+    pub const DT_RELENT: i32 = 19;
+    /// Type of relocation used for the PLT (`DT_REL` or `DT_RELA`).
+    pub const DT_PLTREL: i32 = 20;
+    /// Relocations may modify a non-writable segment.
+    pub const DT_TEXTREL: i32 = 22;
+    /// Address of the PLT relocation entries.
+    pub const DT_JMPREL: i32 = 23;
+    /// Process all relocations before starting.
+    pub const DT_BIND_NOW: i32 = 24;
+    /// Address of the array of initialisation functions.
+    pub const DT_INIT_ARRAY: i32 = 25;
+    /// Address of the array of termination functions.
+    pub const DT_FINI_ARRAY: i32 = 26;
+    /// Size of the array of initialisation functions.
+    pub const DT_INIT_ARRAYSZ: i32 = 27;
+    /// Size of the array of termination functions.
+    pub const DT_FINI_ARRAYSZ: i32 = 28;
+    /// Flags for this object.
+    pub const DT_FLAGS: i32 = 30;
+
+    /// Get the `d_tag` field.
+    ///
+    /// This says what the entry is, e.g. `DT_STRTAB`.
+    pub fn tag(&self) -> i32 {
+        self.tag
+    }
+
+    /// Get the `d_val` / `d_ptr` field.
+    ///
+    /// This is either an integer or an address, depending on the tag.
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+}
+
+/// A shared library that a dynamically linked file depends on.
+///
+/// Created by iterating through `loader.iter_needed()`.
+#[derive(Debug, Clone)]
+pub struct NeededLibrary {
+    name_offset: u32,
+    strings_end: u32,
+}
+
+impl NeededLibrary {
+    /// The offset within the file of the name of the library.
+    pub fn name_offset(&self) -> u32 {
+        self.name_offset
+    }
+
+    /// Get the name of the library, e.g. `libc.so.6`.
+    ///
+    /// We never read beyond the end of the dynamic string table, however big
+    /// `buffer` is.
+    pub fn name<'a, DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a str, Error<DS::Error>> {
+        loader.read_str_in(self.name_offset, self.strings_end, buffer)
+    }
+}
+
+/// Allows you to iterate through the entries in the dynamic section.
+///
+/// If an entry can't be read, you get that error and then the iterator
+/// stops, rather than trying (and probably failing) to read the rest.
+///
+/// Created with `loader.iter_dynamic_tags()`.
+pub struct IterDynamicTags<'a, DS> {
+    parent: &'a Loader<DS>,
+    next_offset: u32,
+    end_offset: u32,
+}
+
+impl<'a, DS> Iterator for IterDynamicTags<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<DynamicTag, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.end_offset - self.next_offset < u32::from(DynamicTag::SIZE_IN_BYTES) {
+            // Ran out of segment without finding DT_NULL
+            return None;
+        }
+
+        let offset = self.next_offset;
+        self.next_offset += u32::from(DynamicTag::SIZE_IN_BYTES);
+
+        let result = self.parent.data_source.read_u32_le(offset).and_then(|tag| {
+            let value = self.parent.data_source.read_u32_le(offset + 0x04)?;
+            Ok(DynamicTag {
+                tag: tag as i32,
+                value,
+            })
+        });
+
+        match result {
+            Ok(entry) if entry.tag == DynamicTag::DT_NULL => {
+                self.next_offset = self.end_offset;
+                None
+            }
+            Ok(entry) => Some(Ok(entry)),
+            Err(e) => {
+                // Don't keep reading a segment we can't read
+                self.next_offset = self.end_offset;
+                Some(Err(Error::Source(e)))
+            }
+        }
+    }
+}
+
+impl<'a, DS> FusedIterator for IterDynamicTags<'a, DS> where DS: Source {}
+
+/// Allows you to iterate through the libraries a file depends on.
+///
+/// Created with `loader.iter_needed()`.
+pub struct IterNeeded<'a, DS> {
+    tags: IterDynamicTags<'a, DS>,
+    strings: Option<Range<u32>>,
+}
+
+impl<'a, DS> Iterator for IterNeeded<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<NeededLibrary, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for entry in self.tags.by_ref() {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => return Some(Err(e)),
+            };
+            if entry.tag() != DynamicTag::DT_NEEDED {
+                continue;
+            }
+            let Some(strings) = &self.strings else {
+                // There's a name, but nowhere to find it
+                return Some(Err(Error::BadDynamicSection));
+            };
+            if entry.value() >= strings.len() as u32 {
+                return Some(Err(Error::StringOffsetOutOfRange));
+            }
+            return Some(Ok(NeededLibrary {
+                // Can't overflow, as the offset is within the table
+                name_offset: strings.start + entry.value(),
+                strings_end: strings.end,
+            }));
+        }
+        None
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Create a dynamic section iterator.
+    ///
+    /// This walks the `PT_DYNAMIC` segment, stopping at the `DT_NULL` entry or
+    /// the end of the segment, whichever comes first. If there is no
+    /// `PT_DYNAMIC` segment, the iterator is empty.
+    pub fn iter_dynamic_tags(&self) -> Result<IterDynamicTags<'_, DS>, Error<DS::Error>> {
+        let range = match self.find_program_header_by_type(ProgramHeader::PT_DYNAMIC)? {
+            Some(ph) => ph.file_range().ok_or(Error::BadDynamicSection)?,
+            None => 0..0,
+        };
+        Ok(IterDynamicTags {
+            parent: self,
+            next_offset: range.start,
+            end_offset: range.end,
+        })
+    }
+
+    /// Get the value of the first dynamic section entry with the given tag.
+    ///
+    /// Returns `None` if there is no such entry.
+    pub fn dynamic_value(&self, tag: i32) -> Result<Option<u32>, Error<DS::Error>> {
+        for entry in self.iter_dynamic_tags()? {
+            let entry = entry?;
+            if entry.tag() == tag {
+                return Ok(Some(entry.value()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Create an iterator through the shared libraries this file depends on.
+    ///
+    /// These are the `DT_NEEDED` entries in the dynamic section. Their names
+    /// live in the string table given by `DT_STRTAB`, which is an address we
+    /// convert to a file offset using the program headers. The size of the
+    /// string table is `DT_STRSZ`, or if that is missing, the size of the
+    /// string table section at that offset.
+    pub fn iter_needed(&self) -> Result<IterNeeded<'_, DS>, Error<DS::Error>> {
+        let strtab_offset = match self.dynamic_value(DynamicTag::DT_STRTAB)? {
+            Some(strtab_addr) => self.vaddr_to_offset(strtab_addr)?,
+            None => None,
+        };
+        let strings = match strtab_offset {
+            Some(offset) => Some(self.dynamic_strings(offset)?),
+            None => None,
+        };
+        Ok(IterNeeded {
+            tags: self.iter_dynamic_tags()?,
+            strings,
+        })
+    }
+
+    /// Does this file depend on any shared libraries?
+    ///
+    /// Neotron cannot load shared libraries, so files which need them cannot
+    /// be run.
+    pub fn has_needed_libraries(&self) -> Result<bool, Error<DS::Error>> {
+        Ok(self.dynamic_value(DynamicTag::DT_NEEDED)?.is_some())
+    }
+
+    /// Get the range of file offsets occupied by the dynamic string table,
+    /// which starts at `offset`.
+    fn dynamic_strings(&self, offset: u32) -> Result<Range<u32>, Error<DS::Error>> {
+        let size = match self.dynamic_value(DynamicTag::DT_STRSZ)? {
+            Some(size) => Some(size),
+            None => {
+                let mut size = None;
+                for sh in self.iter_section_headers() {
+                    let sh = sh?;
+                    if sh.sh_type() == SectionHeader::SHT_STRTAB && sh.sh_offset() == offset {
+                        size = Some(sh.sh_size());
+                        break;
+                    }
+                }
+                size
+            }
+        };
+        size.and_then(|size| checked_range(offset, size))
+            .ok_or(Error::BadDynamicSection)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
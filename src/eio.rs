@@ -0,0 +1,114 @@
+//! Code for reading an ELF file through `embedded-io`'s `Read` and `Seek`.
+//!
+//! Only available with the `embedded-io` feature. Any filesystem crate which
+//! gives you an `embedded_io` file can be loaded from, through an
+//! [`EioSource`].
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::cell::RefCell;
+
+use embedded_io::{Read, ReadExactError, Seek, SeekFrom};
+
+use crate::Source;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// A [`Source`] which reads from anything that implements
+/// `embedded_io::Read` and `embedded_io::Seek`.
+///
+/// Each read seeks to the offset and then uses `read_exact`, which keeps
+/// reading until the buffer is full, so short reads are fine. The cursor
+/// lives in a `RefCell`, as `Source::read` only gets `&self`.
+#[derive(Debug)]
+pub struct EioSource<T> {
+    inner: RefCell<T>,
+}
+
+impl<T> EioSource<T>
+where
+    T: Read + Seek,
+{
+    /// Read from `inner`.
+    pub fn new(inner: T) -> EioSource<T> {
+        EioSource {
+            inner: RefCell::new(inner),
+        }
+    }
+
+    /// Get the inner reader back.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T> Source for EioSource<T>
+where
+    T: Read + Seek,
+{
+    type Error = EioSourceError<T::Error>;
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+        inner
+            .seek(SeekFrom::Start(u64::from(offset)))
+            .map_err(EioSourceError::Seek)?;
+        inner.read_exact(buffer)?;
+        Ok(())
+    }
+
+    fn len(&self) -> Option<u32> {
+        let len = self.inner.borrow_mut().seek(SeekFrom::End(0)).ok()?;
+        Some(u32::try_from(len).unwrap_or(u32::MAX))
+    }
+}
+
+/// The error raised if you are reading from an [`EioSource`].
+#[derive(Debug, Clone)]
+pub enum EioSourceError<E>
+where
+    E: core::fmt::Debug,
+{
+    /// Seeking to the offset failed
+    Seek(E),
+    /// Reading failed
+    Read(E),
+    /// The file ended before the buffer was full
+    UnexpectedEof,
+}
+
+impl<E> From<ReadExactError<E>> for EioSourceError<E>
+where
+    E: core::fmt::Debug,
+{
+    fn from(value: ReadExactError<E>) -> EioSourceError<E> {
+        match value {
+            ReadExactError::UnexpectedEof => EioSourceError::UnexpectedEof,
+            ReadExactError::Other(e) => EioSourceError::Read(e),
+        }
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,826 @@
+//! The parts of the loader which don't depend on the type of data source.
+//!
+//! Everything generic over a [`Source`] costs flash for each kind of source
+//! you use, so the parsing, string handling and load planning live here
+//! instead, reading through a `&dyn ReadAt`. The generic API wraps its
+//! source in a [`Shim`], calls in here, and then uses the shim to turn our
+//! errors back into `Error<DS::Error>`.
+//!
+//! When the source (or a caller's write function) fails, the shim keeps the
+//! real error and gives us a [`Stashed`] marker to pass up instead.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::cell::Cell;
+use core::ops::Range;
+
+use crate::{Error, IndexKind, LoaderOptions, ProgramHeader, SectionHeader, SectionNames, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// Standard ELF magic header
+const ELF_MAGIC: u32 = 0x7F454C46;
+
+/// 32-bit, little-endian, version 1, SysV
+const DESIRED_ELF_VERSION: u32 = 0x01010100;
+
+/// Indicates ARM machine
+pub(crate) const EM_ARM: u16 = 0x0028;
+
+/// Indicates a relocatable object file
+pub(crate) const ET_REL: u16 = 0x0001;
+
+/// Indicates a binary
+pub(crate) const ET_EXEC: u16 = 0x0002;
+
+/// Indicates a position-independent binary or shared object
+pub(crate) const ET_DYN: u16 = 0x0003;
+
+/// Indicates a core dump
+pub(crate) const ET_CORE: u16 = 0x0004;
+
+/// The most bytes we read from the source in one go, when loading.
+pub(crate) const CHUNK_SIZE: usize = 512;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Says that something failed, and the [`Shim`] has the real error.
+///
+/// Only a `Shim` can make one of these.
+#[derive(Debug, Clone)]
+pub(crate) struct Stashed(());
+
+/// The errors the non-generic code returns.
+pub(crate) type RawError = Error<Stashed>;
+
+/// A write function, with the type of its error hidden.
+pub(crate) type WriteFn<'a> = dyn FnMut(u32, &[u8]) -> Result<(), Stashed> + 'a;
+
+/// Something we can read bytes from, without knowing its type.
+pub(crate) trait ReadAt {
+    /// Fill `buffer` with the bytes at `offset`.
+    fn read_at(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Stashed>;
+}
+
+impl dyn ReadAt + '_ {
+    /// Read a 32-bit big-endian value.
+    fn read_u32_be(&self, offset: u32) -> Result<u32, Stashed> {
+        let mut bytes = [0; 4];
+        self.read_at(offset, &mut bytes)?;
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    /// Read a 32-bit little-endian value.
+    fn read_u32_le(&self, offset: u32) -> Result<u32, Stashed> {
+        let mut bytes = [0; 4];
+        self.read_at(offset, &mut bytes)?;
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    /// Read a 16-bit little-endian value.
+    fn read_u16_le(&self, offset: u32) -> Result<u16, Stashed> {
+        let mut bytes = [0; 2];
+        self.read_at(offset, &mut bytes)?;
+        Ok(u16::from_le_bytes(bytes))
+    }
+
+    /// Fill `buffer` a word at a time, so each header field is its own read.
+    fn read_words(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Stashed> {
+        let mut word_offset = offset;
+        for word in buffer.chunks_mut(4) {
+            self.read_at(word_offset, word)?;
+            word_offset += 4;
+        }
+        Ok(())
+    }
+}
+
+/// Wraps a [`Source`] so the non-generic code can read from it, and keeps
+/// hold of any error until we're back in generic code.
+pub(crate) struct Shim<'a, DS>
+where
+    DS: Source,
+{
+    source: &'a DS,
+    stash: Cell<Option<Error<DS::Error>>>,
+}
+
+impl<'a, DS> Shim<'a, DS>
+where
+    DS: Source,
+{
+    /// Wrap `source`.
+    pub(crate) fn new(source: &'a DS) -> Shim<'a, DS> {
+        Shim {
+            source,
+            stash: Cell::new(None),
+        }
+    }
+
+    /// Keep hold of `error`, and get a marker to pass up instead.
+    pub(crate) fn stash(&self, error: Error<DS::Error>) -> Stashed {
+        self.stash.set(Some(error));
+        Stashed(())
+    }
+
+    /// Put the real error back into `result`.
+    #[inline(never)]
+    pub(crate) fn finish<T>(self, result: Result<T, RawError>) -> Result<T, Error<DS::Error>> {
+        result.map_err(|e| e.unstash(self.stash.into_inner()))
+    }
+}
+
+impl<DS> ReadAt for Shim<'_, DS>
+where
+    DS: Source,
+{
+    fn read_at(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Stashed> {
+        self.source
+            .read(offset, buffer)
+            .map_err(|e| self.stash(Error::Source(e)))
+    }
+}
+
+impl Error<Stashed> {
+    /// Swap the marker for the error it stands for.
+    ///
+    /// This is the one big piece of generic code, so we only want one copy
+    /// of it for each kind of source.
+    #[inline(never)]
+    fn unstash<E>(self, stashed: Option<Error<E>>) -> Error<E>
+    where
+        E: core::fmt::Debug,
+    {
+        match self {
+            // Only a `Shim` makes a `Stashed`, and it stashes the error first
+            Error::Source(Stashed(())) => stashed.unwrap_or(Error::DataOutOfBounds),
+            Error::NotAnElfFile => Error::NotAnElfFile,
+            Error::WrongElfFile => Error::WrongElfFile,
+            Error::BufferTooSmall { needed } => Error::BufferTooSmall { needed },
+            Error::InvalidString => Error::InvalidString,
+            Error::BadHashTable => Error::BadHashTable,
+            Error::WrongSectionType => Error::WrongSectionType,
+            Error::BadDynamicSection => Error::BadDynamicSection,
+            Error::BadNote => Error::BadNote,
+            Error::BadRelocationSection => Error::BadRelocationSection,
+            Error::BadArraySection => Error::BadArraySection,
+            Error::BadAttributes => Error::BadAttributes,
+            Error::BadGroupSection => Error::BadGroupSection,
+            Error::CompressedSection => Error::CompressedSection,
+            Error::BadCompressionHeader => Error::BadCompressionHeader,
+            Error::StringOffsetOutOfRange => Error::StringOffsetOutOfRange,
+            Error::BadStringTable => Error::BadStringTable,
+            Error::DataOutOfBounds => Error::DataOutOfBounds,
+            Error::TooManySegments => Error::TooManySegments,
+            Error::BadSegment => Error::BadSegment,
+            Error::SegmentRejected { p_type } => Error::SegmentRejected { p_type },
+            Error::UnmappedAddress { vaddr } => Error::UnmappedAddress { vaddr },
+            Error::BadVectorTable(fault) => Error::BadVectorTable(fault),
+            Error::BadNeotronMeta => Error::BadNeotronMeta,
+            Error::SectionIndexOutOfRange { index } => Error::SectionIndexOutOfRange { index },
+            Error::WriteFailed => Error::WriteFailed,
+            Error::AddressNotMapped { addr } => Error::AddressNotMapped { addr },
+            Error::SegmentNotContiguous { p_paddr } => Error::SegmentNotContiguous { p_paddr },
+            Error::BadIndex { kind, index, count } => Error::BadIndex { kind, index, count },
+        }
+    }
+}
+
+/// The fields of the ELF file header that a `Loader` keeps.
+pub(crate) struct FileHeader {
+    pub(crate) e_type: u16,
+    pub(crate) e_machine: u16,
+    pub(crate) e_entry: u32,
+    pub(crate) e_phoff: u32,
+    pub(crate) e_shoff: u32,
+    pub(crate) e_phnum: u16,
+    pub(crate) e_shnum: u16,
+    pub(crate) e_shstrndx: u16,
+    pub(crate) section_names: SectionNames,
+}
+
+/// Where a `LoadState` has got to.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LoadCursor {
+    /// The program header we're working on (or, when filling gaps, our
+    /// position in the sorted list)
+    pub(crate) segment: u16,
+    /// How far through that segment we are (the file part, then the zeroes)
+    pub(crate) offset_in_segment: u32,
+    /// How many bytes we've written, in total
+    pub(crate) done: u32,
+    /// Where the gap before the next segment starts, when filling gaps
+    pub(crate) fill_from: Option<u32>,
+}
+
+/// Which segments a `LoadState` writes, and in what order.
+pub(crate) struct LoadPlan<'a> {
+    pub(crate) e_phoff: u32,
+    pub(crate) e_phnum: u16,
+    /// When filling gaps, the byte to fill them with, and the indices of the
+    /// `PT_LOAD` segments in address order
+    pub(crate) fill: Option<(u8, &'a [u16])>,
+}
+
+impl LoadPlan<'_> {
+    /// How many steps of `LoadCursor::segment` there are.
+    pub(crate) fn len(&self) -> u16 {
+        match self.fill {
+            // There are at most `MAX_SORTED_LOAD_SEGMENTS`
+            Some((_, order)) => order.len() as u16,
+            None => self.e_phnum,
+        }
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Read and check the ELF file header.
+pub(crate) fn parse_file_header(
+    read: &dyn ReadAt,
+    options: &LoaderOptions,
+) -> Result<FileHeader, RawError> {
+    let elf_header = read.read_u32_be(0x00)?;
+    if elf_header != ELF_MAGIC {
+        // File doesn't start 0x7F E L F
+        debug!("rejected: magic is 0x{:08x}", elf_header);
+        return Err(Error::NotAnElfFile);
+    }
+    trace!("magic ok");
+    let class_endian_version_abi = read.read_u32_be(0x04)?;
+    if class_endian_version_abi != DESIRED_ELF_VERSION {
+        debug!(
+            "rejected: class/endian/version/ABI is 0x{:08x}",
+            class_endian_version_abi
+        );
+        return Err(Error::WrongElfFile);
+    }
+    trace!("class/endian/version/ABI ok");
+
+    // Ignore ABI version at 0x08..0x10
+
+    let elf_type = read.read_u16_le(0x10)?;
+    let is_object = elf_type == ET_REL && options.allow_object;
+    let is_core = elf_type == ET_CORE && options.allow_core;
+    if elf_type != ET_EXEC && elf_type != ET_DYN && !is_object && !is_core {
+        // File is not a binary
+        debug!("rejected: e_type is 0x{:04x}", elf_type);
+        return Err(Error::WrongElfFile);
+    }
+    trace!("e_type 0x{:04x} ok", elf_type);
+
+    let elf_machine = read.read_u16_le(0x12)?;
+    if elf_machine != EM_ARM {
+        // File is not a ARM
+        debug!("rejected: e_machine is 0x{:04x}", elf_machine);
+        return Err(Error::WrongElfFile);
+    }
+    trace!("e_machine ok");
+
+    let elf_version = read.read_u32_le(0x14)?;
+    if elf_version != 1 {
+        // File is not a ELF
+        debug!("rejected: e_version is 0x{:08x}", elf_version);
+        return Err(Error::WrongElfFile);
+    }
+    trace!("e_version ok");
+
+    let e_entry = read.read_u32_le(0x18)?;
+    let e_shoff = read.read_u32_le(0x20)?;
+
+    let (e_phoff, e_phnum) = if is_object {
+        // Object files have no program headers, whatever the header says
+        (0, 0)
+    } else {
+        let e_phoff = read.read_u32_le(0x1C)?;
+        let e_phentsize = read.read_u16_le(0x2A)?;
+        if e_phentsize != ProgramHeader::SIZE_IN_BYTES {
+            debug!("rejected: e_phentsize is 0x{:04x}", e_phentsize);
+            return Err(Error::WrongElfFile);
+        }
+        trace!("e_phentsize ok");
+        let e_phnum = read.read_u16_le(0x2C)?;
+        (e_phoff, e_phnum)
+    };
+    let e_shentsize = read.read_u16_le(0x2E)?;
+    let e_shnum = read.read_u16_le(0x30)?;
+
+    // Core dumps with no sections may not say how big a section is
+    let no_sections_ok = is_core && e_shnum == 0;
+    if e_shentsize != SectionHeader::SIZE_IN_BYTES && !no_sections_ok {
+        debug!("rejected: e_shentsize is 0x{:04x}", e_shentsize);
+        return Err(Error::WrongElfFile);
+    }
+    trace!("e_shentsize ok");
+
+    let e_shstrndx = read.read_u16_le(0x32)?;
+
+    // The header tables mustn't wrap past the end of the address space
+    let ph_table_len = u32::from(e_phnum) * u32::from(ProgramHeader::SIZE_IN_BYTES);
+    let sh_table_len = u32::from(e_shnum) * u32::from(SectionHeader::SIZE_IN_BYTES);
+    if crate::checked_range(e_phoff, ph_table_len).is_none()
+        || crate::checked_range(e_shoff, sh_table_len).is_none()
+    {
+        debug!(
+            "rejected: header tables at 0x{:08x} and 0x{:08x} wrap",
+            e_phoff, e_shoff
+        );
+        return Err(Error::DataOutOfBounds);
+    }
+    trace!("header tables ok");
+
+    debug!(
+        "parsed header: e_type=0x{:04x} e_machine=0x{:04x} e_entry=0x{:08x} e_phoff=0x{:08x} e_phnum={} e_shoff=0x{:08x} e_shnum={} e_shstrndx={}",
+        elf_type, elf_machine, e_entry, e_phoff, e_phnum, e_shoff, e_shnum, e_shstrndx
+    );
+    let section_names = find_section_names(read, e_shoff, e_shnum, e_shstrndx);
+    match section_names {
+        SectionNames::Missing => {
+            trace!("section names: none");
+        }
+        SectionNames::Bad => {
+            debug!(
+                "section names: section {} is not a string table",
+                e_shstrndx
+            );
+        }
+        SectionNames::Table { offset, size } => {
+            trace!(
+                "section names: section {} at offset 0x{:08x}, size 0x{:x}",
+                e_shstrndx,
+                offset,
+                size
+            );
+        }
+    }
+
+    Ok(FileHeader {
+        e_type: elf_type,
+        e_machine: elf_machine,
+        e_entry,
+        e_phoff,
+        e_shoff,
+        e_phnum,
+        e_shnum,
+        e_shstrndx,
+        section_names,
+    })
+}
+
+/// Find the section name string table, and check it is a string table.
+fn find_section_names(
+    read: &dyn ReadAt,
+    e_shoff: u32,
+    e_shnum: u16,
+    e_shstrndx: u16,
+) -> SectionNames {
+    if e_shstrndx == 0 {
+        return SectionNames::Missing;
+    }
+    match read_section_header(read, e_shoff, e_shnum, e_shstrndx) {
+        Ok(sh) if sh.sh_type() == SectionHeader::SHT_STRTAB => {
+            // A table which wraps past 4 GiB is no use to anyone
+            match crate::checked_range(sh.sh_offset(), sh.sh_size()) {
+                Some(_) => SectionNames::Table {
+                    offset: sh.sh_offset(),
+                    size: sh.sh_size(),
+                },
+                None => SectionNames::Bad,
+            }
+        }
+        _ => SectionNames::Bad,
+    }
+}
+
+/// Read the program header at `idx`, from the table at `e_phoff`.
+pub(crate) fn read_program_header(
+    read: &dyn ReadAt,
+    e_phoff: u32,
+    e_phnum: u16,
+    idx: u16,
+) -> Result<ProgramHeader, RawError> {
+    let mut bytes = [0u8; ProgramHeader::SIZE_IN_BYTES as usize];
+    let offset = entry_offset(
+        IndexKind::ProgramHeader,
+        e_phoff,
+        e_phnum,
+        ProgramHeader::SIZE_IN_BYTES,
+        idx,
+    )?;
+    read.read_words(offset, &mut bytes)?;
+    Ok(ProgramHeader::from_bytes(&bytes))
+}
+
+/// Read the section header at `idx`, from the table at `e_shoff`.
+pub(crate) fn read_section_header(
+    read: &dyn ReadAt,
+    e_shoff: u32,
+    e_shnum: u16,
+    idx: u16,
+) -> Result<SectionHeader, RawError> {
+    let mut bytes = [0u8; SectionHeader::SIZE_IN_BYTES as usize];
+    let offset = entry_offset(
+        IndexKind::Section,
+        e_shoff,
+        e_shnum,
+        SectionHeader::SIZE_IN_BYTES,
+        idx,
+    )?;
+    read.read_words(offset, &mut bytes)?;
+    Ok(SectionHeader::from_bytes(&bytes))
+}
+
+/// Find the offset of entry `idx` in a table of `count` entries.
+fn entry_offset(
+    kind: IndexKind,
+    table_offset: u32,
+    count: u16,
+    entry_size: u16,
+    idx: u16,
+) -> Result<u32, RawError> {
+    if idx >= count {
+        return Err(Error::BadIndex {
+            kind,
+            index: idx,
+            count,
+        });
+    }
+    // `Loader::new` checked the whole table fits below 4 GiB
+    Ok(table_offset + u32::from(entry_size) * u32::from(idx))
+}
+
+/// Read a null-terminated string from the given offset, without reading at
+/// or beyond `end`.
+///
+/// The string must fit in `buffer`, including the null terminator, and must
+/// be terminated before `end`. If it doesn't fit, you get `BufferTooSmall`,
+/// which says how big `buffer` needs to be.
+pub(crate) fn read_str_in<'a>(
+    read: &dyn ReadAt,
+    offset: u32,
+    end: u32,
+    buffer: &'a mut [u8],
+) -> Result<&'a str, RawError> {
+    if offset >= end {
+        return Err(Error::InvalidString);
+    }
+    let available = (end - offset) as usize;
+    let len = buffer.len().min(available);
+    let buffer = &mut buffer[0..len];
+    read.read_at(offset, buffer)?;
+    let Ok(cstr) = core::ffi::CStr::from_bytes_until_nul(buffer) else {
+        if len < available {
+            let needed = str_len(read, offset, end)? + 1;
+            return Err(Error::BufferTooSmall { needed });
+        }
+        // The string runs off the end of the table
+        return Err(Error::InvalidString);
+    };
+    cstr.to_str().map_err(|_| Error::InvalidString)
+}
+
+/// Find the length of the null-terminated string at `offset`, not including
+/// the null terminator.
+///
+/// The string is read in small chunks, and we never read at or beyond `end`.
+/// If there is no null terminator before `end`, you get
+/// `Error::InvalidString`.
+pub(crate) fn str_len(read: &dyn ReadAt, offset: u32, end: u32) -> Result<usize, RawError> {
+    let mut chunk = [0u8; 16];
+    let mut chunk_offset = offset;
+    while chunk_offset < end {
+        let chunk_len = chunk.len().min((end - chunk_offset) as usize);
+        let chunk = &mut chunk[0..chunk_len];
+        read.read_at(chunk_offset, chunk)?;
+        if let Some(nul) = chunk.iter().position(|b| *b == 0) {
+            return Ok((chunk_offset - offset) as usize + nul);
+        }
+        chunk_offset += chunk_len as u32;
+    }
+    // The string runs off the end of the table
+    Err(Error::InvalidString)
+}
+
+/// Check whether the null-terminated string at `offset` is equal to
+/// `expected`.
+///
+/// The string is read in chunks the size of `scratch`, and we stop reading as
+/// soon as we find a mismatch. We never read at or beyond `end`.
+pub(crate) fn str_eq(
+    read: &dyn ReadAt,
+    offset: u32,
+    end: u32,
+    expected: &[u8],
+    scratch: &mut [u8],
+) -> Result<bool, RawError> {
+    if scratch.is_empty() {
+        return Err(Error::BufferTooSmall { needed: 1 });
+    }
+    // We need to check the null terminator too
+    let total_len = expected.len() + 1;
+    let mut checked = 0;
+    while checked < total_len {
+        let chunk_offset = offset + checked as u32;
+        if chunk_offset >= end {
+            // The string runs off the end of the table
+            return Ok(false);
+        }
+        let chunk_len = scratch
+            .len()
+            .min(total_len - checked)
+            .min((end - chunk_offset) as usize);
+        let chunk = &mut scratch[0..chunk_len];
+        read.read_at(chunk_offset, chunk)?;
+        for (idx, b) in chunk.iter().enumerate() {
+            let wanted = expected.get(checked + idx).cloned().unwrap_or(0);
+            if *b != wanted {
+                return Ok(false);
+            }
+        }
+        checked += chunk_len;
+    }
+    Ok(true)
+}
+
+/// Find the first section called `name`, given the offset and size of the
+/// section name string table.
+pub(crate) fn find_section_by_name(
+    read: &dyn ReadAt,
+    e_shoff: u32,
+    e_shnum: u16,
+    (strings_start, strings_size): (u32, u32),
+    name: &str,
+) -> Result<Option<SectionHeader>, RawError> {
+    let strings_end = strings_start
+        .checked_add(strings_size)
+        .ok_or(Error::DataOutOfBounds)?;
+    let mut chunk = [0u8; 16];
+    for idx in 0..e_shnum {
+        let sh = read_section_header(read, e_shoff, e_shnum, idx)?;
+        if sh.sh_name_offset() >= strings_size {
+            continue;
+        }
+        let name_start = strings_start
+            .checked_add(sh.sh_name_offset())
+            .ok_or(Error::DataOutOfBounds)?;
+        if str_eq(read, name_start, strings_end, name.as_bytes(), &mut chunk)? {
+            return Ok(Some(sh));
+        }
+    }
+    Ok(None)
+}
+
+/// Check the `PT_LOAD` segments before loading them a step at a time.
+///
+/// Returns how many bytes we'll write, in total.
+pub(crate) fn plan_load(read: &dyn ReadAt, e_phoff: u32, e_phnum: u16) -> Result<u32, RawError> {
+    let mut total = 0u32;
+    for idx in 0..e_phnum {
+        let ph = read_program_header(read, e_phoff, e_phnum, idx)?;
+        if ph.p_type() != ProgramHeader::PT_LOAD {
+            continue;
+        }
+        if ph.file_range().is_none() || ph.mem_range().is_none() || ph.p_filesz() > ph.p_memsz() {
+            debug!(
+                "segment {}: bad ranges, offset=0x{:08x} filesz=0x{:x} paddr=0x{:08x} memsz=0x{:x}",
+                idx,
+                ph.p_offset(),
+                ph.p_filesz(),
+                ph.p_paddr(),
+                ph.p_memsz()
+            );
+            return Err(Error::BadSegment);
+        }
+        trace!(
+            "segment {}: will load 0x{:x} bytes at 0x{:08x}",
+            idx,
+            ph.p_memsz(),
+            ph.p_paddr()
+        );
+        total = total.checked_add(ph.p_memsz()).ok_or(Error::BadSegment)?;
+    }
+    Ok(total)
+}
+
+/// Write up to `budget_bytes` more bytes of the `PT_LOAD` segments, carrying
+/// on from `cursor`.
+///
+/// When `plan.fill` is set, we go through the segments in address order, and
+/// fill the gap before each one. We only move `cursor` on once a write has
+/// succeeded.
+pub(crate) fn load_step(
+    read: &dyn ReadAt,
+    plan: &LoadPlan,
+    cursor: &mut LoadCursor,
+    budget_bytes: u32,
+    write_fn: &mut WriteFn,
+) -> Result<(), RawError> {
+    let mut budget = budget_bytes;
+    let mut chunk = [0u8; CHUNK_SIZE];
+    while budget > 0 && cursor.segment < plan.len() {
+        let idx = match plan.fill {
+            Some((_, order)) => order[cursor.segment as usize],
+            None => cursor.segment,
+        };
+        let ph = read_program_header(read, plan.e_phoff, plan.e_phnum, idx)?;
+        // We don't care where segments we don't load are
+        if ph.p_type() != ProgramHeader::PT_LOAD || cursor.offset_in_segment >= ph.p_memsz() {
+            if plan.fill.is_some() && ph.p_memsz() != 0 {
+                cursor.fill_from = ph.mem_range().map(|r| r.end);
+            }
+            cursor.segment += 1;
+            cursor.offset_in_segment = 0;
+            continue;
+        }
+        let (Some(file_range), Some(mem_range)) = (ph.file_range(), ph.mem_range()) else {
+            return Err(Error::BadSegment);
+        };
+        if let (Some((fill, _)), Some(fill_from)) = (plan.fill, cursor.fill_from) {
+            if mem_range.start < fill_from {
+                return Err(Error::BadSegment);
+            }
+            if fill_from < mem_range.start {
+                let chunk_len = (CHUNK_SIZE as u32)
+                    .min(budget)
+                    .min(mem_range.start - fill_from);
+                let chunk = &mut chunk[0..chunk_len as usize];
+                chunk.fill(fill);
+                write_fn(fill_from, chunk)?;
+                cursor.fill_from = Some(fill_from + chunk_len);
+                cursor.done += chunk_len;
+                budget -= chunk_len;
+                continue;
+            }
+        }
+        if cursor.offset_in_segment == 0 {
+            trace!(
+                "segment {}: starting at 0x{:08x}",
+                cursor.segment,
+                mem_range.start
+            );
+        }
+        let address = mem_range.start + cursor.offset_in_segment;
+        let chunk_len = if cursor.offset_in_segment < ph.p_filesz() {
+            let chunk_len = (CHUNK_SIZE as u32)
+                .min(budget)
+                .min(ph.p_filesz() - cursor.offset_in_segment);
+            let chunk = &mut chunk[0..chunk_len as usize];
+            read.read_at(file_range.start + cursor.offset_in_segment, chunk)?;
+            write_fn(address, chunk)?;
+            chunk_len
+        } else {
+            let chunk_len = (CHUNK_SIZE as u32)
+                .min(budget)
+                .min(ph.p_memsz() - cursor.offset_in_segment);
+            let chunk = &mut chunk[0..chunk_len as usize];
+            chunk.fill(0);
+            write_fn(address, chunk)?;
+            chunk_len
+        };
+        cursor.offset_in_segment += chunk_len;
+        cursor.done += chunk_len;
+        budget -= chunk_len;
+    }
+    Ok(())
+}
+
+/// Copy a segment into `dest`: the file contents, then zeroes up to
+/// `p_memsz`.
+///
+/// A segment whose contents wrap past the end of the address space gives
+/// `BadSegment`.
+pub(crate) fn copy_segment(
+    read: &dyn ReadAt,
+    ph: &ProgramHeader,
+    dest: &mut [u8],
+) -> Result<(), RawError> {
+    let memsz = ph.p_memsz() as usize;
+    let filesz = ph.p_filesz() as usize;
+    if filesz > memsz {
+        debug!(
+            "segment at 0x{:08x}: p_filesz 0x{:x} > p_memsz 0x{:x}",
+            ph.p_paddr(),
+            filesz,
+            memsz
+        );
+        return Err(Error::BadSegment);
+    }
+    let Some(file_range) = ph.file_range() else {
+        debug!(
+            "segment at 0x{:08x}: file contents wrap at offset 0x{:08x}",
+            ph.p_paddr(),
+            ph.p_offset()
+        );
+        return Err(Error::BadSegment);
+    };
+    debug!(
+        "copying 0x{:x} bytes from offset 0x{:08x} for segment at 0x{:08x}, then 0x{:x} zeroes",
+        filesz,
+        file_range.start,
+        ph.p_paddr(),
+        memsz - filesz
+    );
+    let dest = dest
+        .get_mut(0..memsz)
+        .ok_or(Error::BufferTooSmall { needed: memsz })?;
+    let (file_part, zero_part) = dest.split_at_mut(filesz);
+    let mut offset = file_range.start;
+    for chunk in file_part.chunks_mut(CHUNK_SIZE) {
+        read.read_at(offset, chunk)?;
+        // Can't overflow, as we stay within `file_range`
+        offset += chunk.len() as u32;
+    }
+    zero_part.fill(0);
+    Ok(())
+}
+
+/// Pass the contents of a segment to `write_fn`, in chunks.
+pub(crate) fn stream_segment(
+    read: &dyn ReadAt,
+    ph: &ProgramHeader,
+    write_fn: &mut WriteFn,
+) -> Result<(), RawError> {
+    let (Some(file_range), Some(mem_range)) = (ph.file_range(), ph.mem_range()) else {
+        return Err(Error::BadSegment);
+    };
+    let memsz = ph.p_memsz();
+    let filesz = ph.p_filesz();
+    if filesz > memsz {
+        return Err(Error::BadSegment);
+    }
+    let mut chunk = [0u8; CHUNK_SIZE];
+    let mut done = 0;
+    while done < filesz {
+        let chunk_len = CHUNK_SIZE.min((filesz - done) as usize);
+        let chunk = &mut chunk[0..chunk_len];
+        read.read_at(file_range.start + done, chunk)?;
+        write_fn(mem_range.start + done, chunk)?;
+        done += chunk_len as u32;
+    }
+    chunk.fill(0);
+    while done < memsz {
+        let chunk_len = CHUNK_SIZE.min((memsz - done) as usize);
+        write_fn(mem_range.start + done, &chunk[0..chunk_len])?;
+        done += chunk_len as u32;
+    }
+    Ok(())
+}
+
+/// Pass `write_fn` the `fill` bytes for `start..end`.
+pub(crate) fn fill_gap(
+    start: u32,
+    end: u32,
+    fill: u8,
+    write_fn: &mut WriteFn,
+) -> Result<(), RawError> {
+    if start < end {
+        debug!("filling 0x{:x} bytes at 0x{:08x}", end - start, start);
+    }
+    let chunk = [fill; CHUNK_SIZE];
+    let mut address = start;
+    while address < end {
+        let chunk_len = CHUNK_SIZE.min((end - address) as usize);
+        write_fn(address, &chunk[0..chunk_len])?;
+        address += chunk_len as u32;
+    }
+    Ok(())
+}
+
+/// Check a segment at `mem_range`, whose first and last bytes translate to
+/// `run_start` and `run_last`, maps to one contiguous block.
+pub(crate) fn check_contiguous(
+    mem_range: Range<u32>,
+    run_start: u32,
+    run_last: u32,
+) -> Result<(), RawError> {
+    if run_last.checked_sub(run_start) != Some(mem_range.end - 1 - mem_range.start) {
+        debug!(
+            "segment at 0x{:08x} maps to 0x{:08x}..=0x{:08x}, which isn't contiguous",
+            mem_range.start, run_start, run_last
+        );
+        return Err(Error::SegmentNotContiguous {
+            p_paddr: mem_range.start,
+        });
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,221 @@
+//! Code and types for handling the ARM exception index table.
+//!
+//! This is the `.ARM.exidx` section, which is covered by the `PT_ARM_EXIDX`
+//! segment. It is used to unwind the stack, e.g. to print a backtrace.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{checked_range, Error, Loader, ProgramHeader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// An entry in the ARM exception index table.
+///
+/// Each entry covers the code from its function start up to the start of the
+/// next entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExidxEntry {
+    address: u32,
+    function_start: u32,
+    function_end: Option<u32>,
+    data: u32,
+}
+
+impl ExidxEntry {
+    /// Size of an exception index table entry
+    pub const SIZE_IN_BYTES: u16 = 0x08;
+
+    /// The second word of an entry for a function which can't be unwound
+    pub const EXIDX_CANTUNWIND: u32 = 0x0000_0001;
+
+    /// The address of this entry within the table.
+    pub fn address(&self) -> u32 {
+        self.address
+    }
+
+    /// The address of the first instruction this entry covers.
+    pub fn function_start(&self) -> u32 {
+        self.function_start
+    }
+
+    /// The address just past the last instruction this entry covers.
+    ///
+    /// This is the start of the next entry. The last entry in the table has
+    /// no end.
+    pub fn function_end(&self) -> Option<u32> {
+        self.function_end
+    }
+
+    /// The raw second word of the entry.
+    pub fn data(&self) -> u32 {
+        self.data
+    }
+
+    /// Is this function marked as not unwindable?
+    pub fn is_cant_unwind(&self) -> bool {
+        self.data == Self::EXIDX_CANTUNWIND
+    }
+
+    /// Is the unwinding information held within the entry itself?
+    ///
+    /// If so, `data()` holds the compact model unwinding instructions.
+    pub fn is_inline(&self) -> bool {
+        self.data & 0x8000_0000 != 0
+    }
+
+    /// Get the address of the `.ARM.extab` entry for this function, if the
+    /// unwinding information is held there.
+    pub fn extab_address(&self) -> Option<u32> {
+        if self.is_cant_unwind() || self.is_inline() {
+            None
+        } else {
+            Some(prel31(self.address + 4, self.data))
+        }
+    }
+}
+
+/// Where the exception index table is.
+struct ExidxTable {
+    vaddr: u32,
+    offset: u32,
+    size: u32,
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Find the ARM exception index table.
+    ///
+    /// Returns its address and its size in bytes. We look for the
+    /// `PT_ARM_EXIDX` segment, and if there isn't one, the `SHT_ARM_EXIDX`
+    /// section. Returns `None` if there is neither.
+    pub fn arm_exidx(&self) -> Result<Option<(u32, u32)>, Error<DS::Error>> {
+        Ok(self.exidx_table()?.map(|table| (table.vaddr, table.size)))
+    }
+
+    /// Find the exception index table entry covering the given address.
+    ///
+    /// This is a binary search over the table (which is sorted by address),
+    /// so it only takes around `log2(n)` reads. Returns `None` if there is no
+    /// table, or if the address is before the first entry.
+    pub fn exidx_entry_for(&self, pc: u32) -> Result<Option<ExidxEntry>, Error<DS::Error>> {
+        let Some(ExidxTable {
+            vaddr,
+            offset,
+            size,
+        }) = self.exidx_table()?
+        else {
+            return Ok(None);
+        };
+        let entry_size = u32::from(ExidxEntry::SIZE_IN_BYTES);
+        let num_entries = size / entry_size;
+
+        // Find the last entry which starts at or before `pc`
+        let mut low = 0;
+        let mut high = num_entries;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let start = self.exidx_function_start(vaddr, offset, mid)?;
+            if start <= pc {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        if low == 0 {
+            return Ok(None);
+        }
+        let idx = low - 1;
+
+        let function_start = self.exidx_function_start(vaddr, offset, idx)?;
+        let function_end = if idx + 1 < num_entries {
+            Some(self.exidx_function_start(vaddr, offset, idx + 1)?)
+        } else {
+            None
+        };
+        let data = self
+            .data_source
+            .read_u32_le(offset + idx * entry_size + 4)?;
+        Ok(Some(ExidxEntry {
+            address: vaddr + idx * entry_size,
+            function_start,
+            function_end,
+            data,
+        }))
+    }
+
+    /// Find the exception index table.
+    ///
+    /// If the table wraps past `0xFFFF_FFFF`, either in the file or in
+    /// memory, you get `Error::DataOutOfBounds`. Otherwise, every entry in the
+    /// table can be found without overflowing.
+    fn exidx_table(&self) -> Result<Option<ExidxTable>, Error<DS::Error>> {
+        if let Some(ph) = self.find_program_header_by_type(ProgramHeader::PT_ARM_EXIDX)? {
+            let file = ph.file_range().ok_or(Error::DataOutOfBounds)?;
+            let memory = ph.vaddr_range().ok_or(Error::DataOutOfBounds)?;
+            return Ok(Some(ExidxTable {
+                vaddr: memory.start,
+                offset: file.start,
+                // Only the part which is both in the file and in memory
+                size: ph.p_filesz().min(ph.p_memsz()),
+            }));
+        }
+        if let Some((_idx, sh)) = self.first_section_of_type(SectionHeader::SHT_ARM_EXIDX)? {
+            let file = sh.file_range().ok_or(Error::DataOutOfBounds)?;
+            let memory = checked_range(sh.sh_addr(), sh.sh_size()).ok_or(Error::DataOutOfBounds)?;
+            return Ok(Some(ExidxTable {
+                vaddr: memory.start,
+                offset: file.start,
+                size: sh.sh_size(),
+            }));
+        }
+        Ok(None)
+    }
+
+    /// Read the function start address from entry `idx` of the table.
+    fn exidx_function_start(
+        &self,
+        vaddr: u32,
+        offset: u32,
+        idx: u32,
+    ) -> Result<u32, Error<DS::Error>> {
+        // Can't overflow, as `exidx_table` checked the whole table fits
+        let entry_offset = u32::from(ExidxEntry::SIZE_IN_BYTES) * idx;
+        let word = self.data_source.read_u32_le(offset + entry_offset)?;
+        Ok(prel31(vaddr + entry_offset, word))
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Decode a 31-bit place-relative offset, found at address `place`.
+///
+/// Bit 31 of `word` is ignored, and bit 30 is the sign bit.
+fn prel31(place: u32, word: u32) -> u32 {
+    // Shift the sign bit up to bit 31, then arithmetic shift back down
+    let offset = ((word << 1) as i32) >> 1;
+    place.wrapping_add(offset as u32)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,320 @@
+//! Code for making plausible ELF files out of random bytes, for fuzzing.
+//!
+//! Only available with the `arbitrary` feature. A fuzzer's raw bytes rarely
+//! get past the magic number, so [`FuzzElf`] uses them to make a choice at
+//! each step of building a file instead. The result always has a valid ELF
+//! identity, and tables that mostly point somewhere sensible - but sometimes
+//! holding hostile values like `u32::MAX`, to find the code that can't cope.
+//!
+//! The fuzz target which uses it is in `fuzz/`.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use alloc::vec::Vec;
+
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::{ProgramHeader, SectionHeader};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The size of the ELF file header
+const FILE_HEADER_SIZE: u32 = 0x34;
+
+/// The size of each program header
+const PROGRAM_HEADER_SIZE: u32 = 0x20;
+
+/// The size of each section header
+const SECTION_HEADER_SIZE: u32 = 0x28;
+
+/// The most program headers we make
+const MAX_PROGRAM_HEADERS: u32 = 8;
+
+/// The most section headers we make, including the null one
+const MAX_SECTION_HEADERS: u32 = 16;
+
+/// The most bytes of section and segment contents we make
+const MAX_DATA_LEN: u32 = 4096;
+
+/// Values which are likely to upset arithmetic on offsets and sizes
+const HOSTILE_VALUES: [u32; 6] = [0, 1, 0x7FFF_FFFF, 0x8000_0000, 0xFFFF_FFFC, 0xFFFF_FFFF];
+
+/// The section types we know something about
+const SECTION_TYPES: [u32; 15] = [
+    SectionHeader::SHT_NULL,
+    SectionHeader::SHT_PROGBITS,
+    SectionHeader::SHT_SYMTAB,
+    SectionHeader::SHT_STRTAB,
+    SectionHeader::SHT_RELA,
+    SectionHeader::SHT_HASH,
+    SectionHeader::SHT_DYNAMIC,
+    SectionHeader::SHT_NOTE,
+    SectionHeader::SHT_NOBITS,
+    SectionHeader::SHT_REL,
+    SectionHeader::SHT_DYNSYM,
+    SectionHeader::SHT_INIT_ARRAY,
+    SectionHeader::SHT_GROUP,
+    SectionHeader::SHT_ARM_EXIDX,
+    SectionHeader::SHT_ARM_ATTRIBUTES,
+];
+
+/// The segment types we know something about
+const SEGMENT_TYPES: [u32; 7] = [
+    ProgramHeader::PT_NULL,
+    ProgramHeader::PT_LOAD,
+    ProgramHeader::PT_DYNAMIC,
+    ProgramHeader::PT_INTERP,
+    ProgramHeader::PT_NOTE,
+    ProgramHeader::PT_PHDR,
+    ProgramHeader::PT_ARM_EXIDX,
+];
+
+/// The names we give sections, in the section name table
+const SECTION_NAMES: &[u8] =
+    b"\0.text\0.data\0.bss\0.symtab\0.strtab\0.shstrtab\0.rel.text\0.note\0";
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// A plausible ELF file, made from a fuzzer's random bytes.
+///
+/// ```ignore
+/// fuzz_target!(|elf: FuzzElf| {
+///     if let Ok(loader) = Loader::new(elf.as_bytes()) {
+///         // poke at it
+///     }
+/// });
+/// ```
+///
+/// When the fuzzer's bytes run out, every remaining choice is zero, so any
+/// input makes a file. A zero choice is always the plausible one, so an
+/// empty input makes a valid (if empty) executable.
+#[derive(Debug, Clone)]
+pub struct FuzzElf {
+    bytes: Vec<u8>,
+}
+
+impl FuzzElf {
+    /// Get the file, as bytes.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Get the file, as a `Vec`.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl<'a> Arbitrary<'a> for FuzzElf {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<FuzzElf> {
+        let mut u = Choices { u };
+
+        let e_type = match u.below(8) {
+            5 => 1, // ET_REL
+            6 => 4, // ET_CORE
+            7 => u.u16(),
+            _ => 2, // ET_EXEC
+        };
+        let e_machine = if u.sometimes() { u.u16() } else { 0x28 };
+        let e_phnum = u.below(MAX_PROGRAM_HEADERS + 1);
+        let e_shnum = u.below(MAX_SECTION_HEADERS + 1);
+        let data_len = u.below(MAX_DATA_LEN + 1);
+
+        // The tables go after the file header, then the contents
+        let e_phoff = FILE_HEADER_SIZE;
+        let e_shoff = e_phoff + e_phnum * PROGRAM_HEADER_SIZE;
+        let data_start = e_shoff + e_shnum * SECTION_HEADER_SIZE;
+        let file_len = data_start + data_len;
+
+        // Most of the time, the section names go at the start of the contents
+        let e_shstrndx = if e_shnum > 1 && u.below(4) != 3 {
+            1 + u.below(e_shnum - 1)
+        } else {
+            u.hostile_or(e_shnum)
+        };
+
+        let mut bytes = Vec::with_capacity(file_len as usize);
+        bytes.extend_from_slice(&[0x7F, b'E', b'L', b'F', 1, 1, 1, 0]);
+        bytes.extend_from_slice(&[0; 8]);
+        put_u16(&mut bytes, e_type);
+        put_u16(&mut bytes, e_machine);
+        put_u32(&mut bytes, if u.sometimes() { u.u32() } else { 1 });
+        put_u32(&mut bytes, u.u32());
+        put_u32(&mut bytes, u.hostile_or_value(e_phoff));
+        put_u32(&mut bytes, u.hostile_or_value(e_shoff));
+        put_u32(&mut bytes, 0x0500_0200);
+        put_u16(&mut bytes, FILE_HEADER_SIZE as u16);
+        put_u16(&mut bytes, PROGRAM_HEADER_SIZE as u16);
+        put_u16(&mut bytes, e_phnum as u16);
+        put_u16(&mut bytes, SECTION_HEADER_SIZE as u16);
+        put_u16(&mut bytes, e_shnum as u16);
+        put_u16(&mut bytes, e_shstrndx as u16);
+
+        for _ in 0..e_phnum {
+            let p_type = u.pick(&SEGMENT_TYPES);
+            let p_offset = u.offset_in(data_start, file_len);
+            let p_filesz = u.size_up_to(file_len.saturating_sub(p_offset));
+            put_u32(&mut bytes, p_type);
+            put_u32(&mut bytes, p_offset);
+            put_u32(&mut bytes, u.address());
+            put_u32(&mut bytes, u.address());
+            let p_memsz = p_filesz.wrapping_add(u.below(64));
+            put_u32(&mut bytes, p_filesz);
+            put_u32(&mut bytes, u.hostile_or_value(p_memsz));
+            put_u32(&mut bytes, u.below(8));
+            put_u32(&mut bytes, u.pick(&[0, 4, 0x1000]));
+        }
+
+        for index in 0..e_shnum {
+            if index == 0 && u.below(8) != 7 {
+                bytes.extend_from_slice(&[0; SECTION_HEADER_SIZE as usize]);
+                continue;
+            }
+            let (sh_type, sh_offset, sh_size) = if index == e_shstrndx {
+                (
+                    SectionHeader::SHT_STRTAB,
+                    data_start,
+                    SECTION_NAMES.len() as u32,
+                )
+            } else {
+                let sh_offset = u.offset_in(data_start, file_len);
+                (
+                    u.pick(&SECTION_TYPES),
+                    sh_offset,
+                    u.size_up_to(file_len.saturating_sub(sh_offset)),
+                )
+            };
+            put_u32(&mut bytes, u.hostile_or(SECTION_NAMES.len() as u32));
+            put_u32(&mut bytes, sh_type);
+            put_u32(&mut bytes, u.below(8));
+            put_u32(&mut bytes, u.address());
+            put_u32(&mut bytes, sh_offset);
+            put_u32(&mut bytes, sh_size);
+            put_u32(&mut bytes, u.hostile_or(e_shnum));
+            put_u32(&mut bytes, u.hostile_or(e_shnum));
+            put_u32(&mut bytes, u.pick(&[0, 1, 4]));
+            put_u32(&mut bytes, u.pick(&[0, 1, 8, 12, 16, 0xFFFF_FFFF]));
+        }
+
+        // The contents: section names, then whatever the fuzzer gave us
+        let names = SECTION_NAMES
+            .get(0..data_len as usize)
+            .unwrap_or(SECTION_NAMES);
+        bytes.extend_from_slice(names);
+        while bytes.len() < file_len as usize {
+            bytes.push(u.u8());
+        }
+
+        Ok(FuzzElf { bytes })
+    }
+}
+
+/// Hands out the fuzzer's bytes, a choice at a time.
+struct Choices<'u, 'a> {
+    u: &'u mut Unstructured<'a>,
+}
+
+impl Choices<'_, '_> {
+    /// Take one byte, or zero if we've run out.
+    fn u8(&mut self) -> u8 {
+        // This can only fail by running out, and then it's zero
+        u8::arbitrary(self.u).unwrap_or(0)
+    }
+
+    /// Take a little-endian `u16`.
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes([self.u8(), self.u8()])
+    }
+
+    /// Take a little-endian `u32`.
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes([self.u8(), self.u8(), self.u8(), self.u8()])
+    }
+
+    /// A value in `0..limit`, or zero if `limit` is zero.
+    fn below(&mut self, limit: u32) -> u32 {
+        if limit == 0 {
+            return 0;
+        }
+        let value = if limit <= 256 {
+            u32::from(self.u8())
+        } else {
+            self.u32()
+        };
+        value % limit
+    }
+
+    /// One of `choices`.
+    fn pick(&mut self, choices: &[u32]) -> u32 {
+        choices[self.below(choices.len() as u32) as usize]
+    }
+
+    /// True, one time in sixteen.
+    fn sometimes(&mut self) -> bool {
+        self.below(16) == 15
+    }
+
+    /// Usually a value in `0..limit`, sometimes a hostile one.
+    fn hostile_or(&mut self, limit: u32) -> u32 {
+        let value = self.below(limit);
+        self.hostile_or_value(value)
+    }
+
+    /// Usually `value`, sometimes a hostile one.
+    fn hostile_or_value(&mut self, value: u32) -> u32 {
+        match self.below(16) {
+            14 => self.pick(&HOSTILE_VALUES),
+            15 => self.u32(),
+            _ => value,
+        }
+    }
+
+    /// Usually an offset in `start..end`, sometimes a hostile one.
+    fn offset_in(&mut self, start: u32, end: u32) -> u32 {
+        let offset = start + self.below(end - start);
+        self.hostile_or_value(offset)
+    }
+
+    /// Usually a size up to `limit` bytes, sometimes a hostile one.
+    fn size_up_to(&mut self, limit: u32) -> u32 {
+        self.hostile_or(limit + 1)
+    }
+
+    /// An address, in flash or RAM.
+    fn address(&mut self) -> u32 {
+        let address = self.pick(&[0, 0x1000_0000, 0x2000_0000]) + self.below(0x1_0000);
+        self.hostile_or_value(address)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Put a little-endian `u16` on the end of `bytes`.
+fn put_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Put a little-endian `u32` on the end of `bytes`.
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
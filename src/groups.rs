@@ -0,0 +1,152 @@
+//! Code and types for handling section groups.
+//!
+//! These live in `SHT_GROUP` sections in object files, and say which sections
+//! must be kept or discarded together - for example, the COMDAT groups made
+//! for inline functions and template instances.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::iter::FusedIterator;
+
+use crate::{Error, Loader, SectionHeader, Source, Symbol};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Allows you to iterate through the indices of the sections in a group.
+///
+/// Created with `section_header.iter_group_members()`.
+pub struct IterGroupMembers<'a, DS> {
+    parent: &'a Loader<DS>,
+    flags: u32,
+    next_offset: u32,
+    end_offset: u32,
+    symbol_table: u32,
+    signature: u32,
+}
+
+impl<'a, DS> IterGroupMembers<'a, DS> {
+    /// Size of each entry in a group section
+    pub const ENTRY_SIZE: u32 = 4;
+
+    /// The flags word at the start of the group, e.g. `GRP_COMDAT`.
+    pub fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Is this a COMDAT group?
+    ///
+    /// The linker keeps only one copy of each COMDAT group with a given
+    /// signature.
+    pub fn is_comdat(&self) -> bool {
+        self.flags & SectionHeader::GRP_COMDAT != 0
+    }
+
+    /// The index of the symbol table holding the signature symbol (from
+    /// `sh_link`).
+    pub fn symbol_table(&self) -> u32 {
+        self.symbol_table
+    }
+
+    /// The index of the signature symbol within the symbol table (from
+    /// `sh_info`).
+    pub fn signature(&self) -> u32 {
+        self.signature
+    }
+}
+
+impl<'a, DS> Iterator for IterGroupMembers<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<u32, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.end_offset.saturating_sub(self.next_offset) < Self::ENTRY_SIZE {
+            return None;
+        }
+        let offset = self.next_offset;
+        self.next_offset += Self::ENTRY_SIZE;
+        let result = self.parent.data_source.read_u32_le(offset);
+        if result.is_err() {
+            // Don't keep reading a section we can't read
+            self.next_offset = self.end_offset;
+        }
+        Some(result.map_err(Error::Source))
+    }
+}
+
+impl<'a, DS> FusedIterator for IterGroupMembers<'a, DS> where DS: Source {}
+
+impl SectionHeader {
+    /// Create an iterator through the indices of the sections in this group.
+    ///
+    /// The section must be an `SHT_GROUP` section. The flags word at the
+    /// start of the section is read now, and is available from the
+    /// iterator.
+    pub fn iter_group_members<'a, DS: Source>(
+        &self,
+        loader: &'a Loader<DS>,
+    ) -> Result<IterGroupMembers<'a, DS>, Error<DS::Error>> {
+        if self.sh_type() != SectionHeader::SHT_GROUP {
+            return Err(Error::WrongSectionType);
+        }
+        if self.sh_entsize() != IterGroupMembers::<DS>::ENTRY_SIZE
+            || self.sh_size() < IterGroupMembers::<DS>::ENTRY_SIZE
+        {
+            return Err(Error::BadGroupSection);
+        }
+        let range = self.file_range().ok_or(Error::BadGroupSection)?;
+        let flags = loader.data_source.read_u32_le(range.start)?;
+        Ok(IterGroupMembers {
+            parent: loader,
+            flags,
+            next_offset: range.start + IterGroupMembers::<DS>::ENTRY_SIZE,
+            end_offset: range.end,
+            symbol_table: self.sh_link(),
+            signature: self.sh_info(),
+        })
+    }
+
+    /// Get the signature symbol for this group.
+    ///
+    /// The name of this symbol identifies the group. The section must be an
+    /// `SHT_GROUP` section.
+    pub fn group_signature<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+    ) -> Result<Symbol, Error<DS::Error>> {
+        if self.sh_type() != SectionHeader::SHT_GROUP {
+            return Err(Error::WrongSectionType);
+        }
+        let symtab = self.linked_section(loader)?.ok_or(Error::BadGroupSection)?;
+        if self.sh_info() >= symtab.sh_size() / u32::from(Symbol::SIZE_IN_BYTES) {
+            return Err(Error::BadGroupSection);
+        }
+        let shndx = loader.symtab_shndx(self.sh_link())?;
+        Symbol::new(loader, &symtab, self.sh_info(), shndx.as_ref())
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,264 @@
+//! Code for looking up symbols using hash tables.
+//!
+//! Dynamic symbol tables usually come with a hash table, which lets you find
+//! a symbol by name without reading every entry in the table. We support both
+//! the classic SysV `SHT_HASH` table and the newer `SHT_GNU_HASH` table.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, SectionHeader, Source, Symbol};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Find a symbol with the given name, using the symbol hash table.
+    ///
+    /// This uses the `SHT_GNU_HASH` section, or if there isn't one the
+    /// `SHT_HASH` section, to find the symbol in the symbol table the hash
+    /// table is linked to. If there is no hash table, we fall back to searching
+    /// the dynamic symbol table (or if there isn't one, the normal symbol
+    /// table) one symbol at a time.
+    ///
+    /// The `scratch` buffer is used to compare the names in chunks, so it can
+    /// be any non-zero length. Longer buffers mean fewer reads.
+    pub fn hash_lookup(
+        &self,
+        name: &str,
+        scratch: &mut [u8],
+    ) -> Result<Option<Symbol>, Error<DS::Error>> {
+        if let Some((_idx, hash_table)) = self.first_section_of_type(SectionHeader::SHT_GNU_HASH)? {
+            return self.gnu_hash_lookup(&hash_table, name, scratch);
+        }
+        if let Some((_idx, hash_table)) = self.first_section_of_type(SectionHeader::SHT_HASH)? {
+            return self.sysv_hash_lookup(&hash_table, name, scratch);
+        }
+        match self.first_section_of_type(SectionHeader::SHT_DYNSYM)? {
+            Some((idx, dynsym)) => {
+                let symbols = self.symbols_in(dynsym, Some(u32::from(idx)))?;
+                self.find_symbol_in(symbols, name, scratch)
+            }
+            None => self.find_symbol_by_name(name, scratch),
+        }
+    }
+
+    /// Find a symbol using a SysV `SHT_HASH` table.
+    ///
+    /// The table is `nbucket`, `nchain`, then the buckets, then the chains.
+    /// Each bucket holds the index of the first symbol with that hash (modulo
+    /// `nbucket`), and each chain entry holds the index of the next.
+    ///
+    /// If the buckets and chains don't fit in the section, you get
+    /// `Error::BadHashTable`.
+    fn sysv_hash_lookup(
+        &self,
+        hash_table: &SectionHeader,
+        name: &str,
+        scratch: &mut [u8],
+    ) -> Result<Option<Symbol>, Error<DS::Error>> {
+        let symtab = hash_table
+            .linked_section(self)?
+            .ok_or(Error::BadHashTable)?;
+        let strtab = self.string_table_for(&symtab)?;
+        let shndx = self.symtab_shndx(hash_table.sh_link())?;
+
+        let table = hash_table.file_range().ok_or(Error::BadHashTable)?;
+        if word_at(table.start, 1, table.end).is_none() {
+            return Err(Error::BadHashTable);
+        }
+        let nbucket = self.data_source.read_u32_le(table.start)?;
+        let nchain = self.data_source.read_u32_le(table.start + 0x04)?;
+        // The buckets and chains must all fit in the section
+        let num_words = nbucket
+            .checked_add(nchain)
+            .and_then(|n| n.checked_add(2))
+            .ok_or(Error::BadHashTable)?;
+        if word_at(table.start, num_words - 1, table.end).is_none() {
+            return Err(Error::BadHashTable);
+        }
+        if nbucket == 0 {
+            return Ok(None);
+        }
+        let buckets_offset = table.start + 0x08;
+        let chains_offset = buckets_offset + nbucket * 4;
+
+        let hash = elf_hash(name.as_bytes());
+        // Can't overflow, as every bucket is within the table
+        let mut idx = self
+            .data_source
+            .read_u32_le(buckets_offset + (hash % nbucket) * 4)?;
+        // Each symbol can only appear once in a chain, so this bounds the
+        // walk even if the table contains a loop.
+        for _ in 0..nchain {
+            if idx == 0 {
+                // STN_UNDEF marks the end of the chain
+                return Ok(None);
+            }
+            if idx >= nchain {
+                return Err(Error::BadHashTable);
+            }
+            let symbol = Symbol::new(self, &symtab, idx, shndx.as_ref())?;
+            if self.symbol_name_eq(&symbol, &strtab, name, scratch)? {
+                return Ok(Some(symbol));
+            }
+            // Can't overflow, as every chain entry is within the table
+            idx = self.data_source.read_u32_le(chains_offset + idx * 4)?;
+        }
+
+        if idx == 0 {
+            Ok(None)
+        } else {
+            Err(Error::BadHashTable)
+        }
+    }
+
+    /// Find a symbol using a GNU `SHT_GNU_HASH` table.
+    ///
+    /// The table is `nbuckets`, `symoffset`, `bloom_size` and `bloom_shift`,
+    /// then the bloom filter words, then the buckets, then the chain. The
+    /// bloom filter lets us reject most missing names with a single read.
+    ///
+    /// Symbols from `symoffset` onwards are sorted by bucket. Each bucket holds
+    /// the index of the first symbol in that bucket, and each chain entry holds
+    /// the hash of the corresponding symbol, with bit 0 set on the last symbol
+    /// in the bucket.
+    ///
+    /// If the bloom filter, buckets or chain entries we need don't fit in the
+    /// section, you get `Error::BadHashTable`.
+    fn gnu_hash_lookup(
+        &self,
+        hash_table: &SectionHeader,
+        name: &str,
+        scratch: &mut [u8],
+    ) -> Result<Option<Symbol>, Error<DS::Error>> {
+        let table = hash_table.file_range().ok_or(Error::BadHashTable)?;
+        if word_at(table.start, 3, table.end).is_none() {
+            return Err(Error::BadHashTable);
+        }
+        let base = table.start;
+        let nbuckets = self.data_source.read_u32_le(base)?;
+        let symoffset = self.data_source.read_u32_le(base + 0x04)?;
+        let bloom_size = self.data_source.read_u32_le(base + 0x08)?;
+        let bloom_shift = self.data_source.read_u32_le(base + 0x0C)?;
+        if nbuckets == 0 || bloom_size == 0 {
+            return Ok(None);
+        }
+        let bloom_offset = base + 0x10;
+        let buckets_offset = bloom_offset
+            .checked_add(bloom_size.checked_mul(4).ok_or(Error::BadHashTable)?)
+            .ok_or(Error::BadHashTable)?;
+        if word_at(buckets_offset, nbuckets - 1, table.end).is_none() {
+            return Err(Error::BadHashTable);
+        }
+        // Can't overflow, as the last bucket is within the table
+        let chain_offset = buckets_offset + nbuckets * 4;
+
+        let hash = gnu_hash(name.as_bytes());
+
+        let bloom_word = self
+            .data_source
+            .read_u32_le(bloom_offset + ((hash / 32) % bloom_size) * 4)?;
+        let mask = (1 << (hash % 32)) | (1 << ((hash >> (bloom_shift % 32)) % 32));
+        if bloom_word & mask != mask {
+            return Ok(None);
+        }
+
+        let mut idx = self
+            .data_source
+            .read_u32_le(buckets_offset + (hash % nbuckets) * 4)?;
+        if idx == 0 {
+            return Ok(None);
+        }
+        if idx < symoffset {
+            return Err(Error::BadHashTable);
+        }
+
+        let symtab = hash_table
+            .linked_section(self)?
+            .ok_or(Error::BadHashTable)?;
+        let strtab = self.string_table_for(&symtab)?;
+        let shndx = self.symtab_shndx(hash_table.sh_link())?;
+        let num_symbols = symtab.sh_size() / u32::from(Symbol::SIZE_IN_BYTES);
+        loop {
+            if idx >= num_symbols {
+                // The chain didn't end before the symbol table did
+                return Err(Error::BadHashTable);
+            }
+            let chain_entry =
+                word_at(chain_offset, idx - symoffset, table.end).ok_or(Error::BadHashTable)?;
+            let chain_hash = self.data_source.read_u32_le(chain_entry)?;
+            if (chain_hash | 1) == (hash | 1) {
+                let symbol = Symbol::new(self, &symtab, idx, shndx.as_ref())?;
+                if self.symbol_name_eq(&symbol, &strtab, name, scratch)? {
+                    return Ok(Some(symbol));
+                }
+            }
+            if chain_hash & 1 != 0 {
+                // End of the chain
+                return Ok(None);
+            }
+            idx += 1;
+        }
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Get the offset of word `idx` in an array of words starting at `base`.
+///
+/// Returns `None` if the word doesn't end at or before `end`.
+fn word_at(base: u32, idx: u32, end: u32) -> Option<u32> {
+    let offset = idx.checked_mul(4)?.checked_add(base)?;
+    if offset.checked_add(4)? <= end {
+        Some(offset)
+    } else {
+        None
+    }
+}
+
+/// The standard SysV ELF hash function, as used by `SHT_HASH` sections.
+pub fn elf_hash(name: &[u8]) -> u32 {
+    let mut hash: u32 = 0;
+    for b in name {
+        hash = (hash << 4).wrapping_add(u32::from(*b));
+        let high = hash & 0xF000_0000;
+        if high != 0 {
+            hash ^= high >> 24;
+        }
+        hash &= !high;
+    }
+    hash
+}
+
+/// The GNU hash function (DJB2), as used by `SHT_GNU_HASH` sections.
+pub fn gnu_hash(name: &[u8]) -> u32 {
+    let mut hash: u32 = 5381;
+    for b in name {
+        hash = hash.wrapping_mul(33).wrapping_add(u32::from(*b));
+    }
+    hash
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
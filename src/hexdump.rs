@@ -0,0 +1,151 @@
+//! Code for showing the contents of a section or segment as a hex dump.
+//!
+//! Each line is an address, sixteen bytes in hex, and those bytes as ASCII:
+//!
+//! ```text
+//! 20001444  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 0a 00 00 00  |Hello, world....|
+//! ```
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::ops::Range;
+
+use crate::{Error, Loader, ProgramHeader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// How many bytes we show on each line.
+const BYTES_PER_LINE: u32 = 16;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Write a hex dump of part of a section to `out`.
+    ///
+    /// The `range` is of offsets from the start of the section, and is
+    /// clamped to the size of the section. The addresses shown are from
+    /// `sh_addr`. An `SHT_NOBITS` section (like `.bss`) is shown as zeroes.
+    ///
+    /// If writing to `out` fails, you get `Error::WriteFailed`.
+    pub fn hexdump_section<W>(
+        &self,
+        sh: &SectionHeader,
+        range: Range<u32>,
+        out: W,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        W: core::fmt::Write,
+    {
+        let file_range = sh.file_range().ok_or(Error::DataOutOfBounds)?;
+        self.hexdump(sh.sh_addr(), clamp(range, sh.sh_size()), file_range, out)
+    }
+
+    /// Write a hex dump of part of a segment to `out`.
+    ///
+    /// The `range` is of offsets from the start of the segment, and is
+    /// clamped to `p_memsz`. The addresses shown are from `p_paddr`. The part
+    /// beyond `p_filesz` is shown as zeroes.
+    ///
+    /// If writing to `out` fails, you get `Error::WriteFailed`.
+    pub fn hexdump_segment<W>(
+        &self,
+        ph: &ProgramHeader,
+        range: Range<u32>,
+        out: W,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        W: core::fmt::Write,
+    {
+        let file_range = ph.file_range().ok_or(Error::BadSegment)?;
+        self.hexdump(ph.p_paddr(), clamp(range, ph.p_memsz()), file_range, out)
+    }
+
+    /// Dump `range`, which is relative to `file_range`. Anything beyond the
+    /// end of `file_range` is zero.
+    fn hexdump<W>(
+        &self,
+        base_addr: u32,
+        range: Range<u32>,
+        file_range: Range<u32>,
+        mut out: W,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        W: core::fmt::Write,
+    {
+        let file_len = file_range.end - file_range.start;
+        let mut line = [0u8; BYTES_PER_LINE as usize];
+        let mut offset = range.start;
+        while offset < range.end {
+            let line_len = BYTES_PER_LINE.min(range.end - offset);
+            let line = &mut line[0..line_len as usize];
+            line.fill(0);
+            if offset < file_len {
+                let read_len = line_len.min(file_len - offset);
+                self.data_source
+                    .read(file_range.start + offset, &mut line[0..read_len as usize])?;
+            }
+            write_line(&mut out, base_addr.wrapping_add(offset), line)
+                .map_err(|_| Error::WriteFailed)?;
+            offset += line_len;
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Clamp `range` to `0..len`.
+fn clamp(range: Range<u32>, len: u32) -> Range<u32> {
+    let end = range.end.min(len);
+    range.start.min(end)..end
+}
+
+/// Write one line of the dump.
+fn write_line<W>(out: &mut W, addr: u32, bytes: &[u8]) -> core::fmt::Result
+where
+    W: core::fmt::Write,
+{
+    write!(out, "{:08x} ", addr)?;
+    for idx in 0..BYTES_PER_LINE as usize {
+        if idx % 8 == 0 {
+            out.write_char(' ')?;
+        }
+        match bytes.get(idx) {
+            Some(b) => write!(out, "{:02x} ", b)?,
+            None => out.write_str("   ")?,
+        }
+    }
+    out.write_str(" |")?;
+    for b in bytes {
+        let c = if b.is_ascii_graphic() || *b == b' ' {
+            char::from(*b)
+        } else {
+            '.'
+        };
+        out.write_char(c)?;
+    }
+    out.write_str("|\n")
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,170 @@
+//! Code for writing the loadable image as Intel HEX.
+//!
+//! Only available with the `std` feature.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use std::io::Write;
+
+use crate::{Error, Loader, ProgramHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The most data bytes we put in one record
+const RECORD_DATA_LEN: u32 = 16;
+
+/// A record holding data
+const RECORD_DATA: u8 = 0x00;
+
+/// The record at the end of the file
+const RECORD_EOF: u8 = 0x01;
+
+/// A record holding the upper 16 bits of the address for the data records
+/// which follow
+const RECORD_EXTENDED_LINEAR_ADDRESS: u8 = 0x04;
+
+/// A record holding the entry point
+const RECORD_START_LINEAR_ADDRESS: u8 = 0x05;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// The ways writing Intel HEX can fail
+#[derive(Debug)]
+pub enum IhexError<E>
+where
+    E: core::fmt::Debug,
+{
+    /// We couldn't read or parse the file
+    Loader(Error<E>),
+    /// We couldn't write the output
+    Io(std::io::Error),
+}
+
+impl<E> From<Error<E>> for IhexError<E>
+where
+    E: core::fmt::Debug,
+{
+    fn from(value: Error<E>) -> IhexError<E> {
+        IhexError::Loader(value)
+    }
+}
+
+impl<E> From<std::io::Error> for IhexError<E>
+where
+    E: core::fmt::Debug,
+{
+    fn from(value: std::io::Error) -> IhexError<E> {
+        IhexError::Io(value)
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Write the `PT_LOAD` segments out as Intel HEX.
+    ///
+    /// Each segment's file contents go at its `p_paddr`, in records of up to
+    /// 16 bytes. Gaps between segments, and the zeroed part of a segment past
+    /// `p_filesz`, are left out. The entry point is written as a start linear
+    /// address record.
+    ///
+    /// A segment which wraps past the end of memory, or of the file, gives
+    /// `Error::BadSegment`.
+    pub fn write_ihex<W>(&self, mut out: W) -> Result<(), IhexError<DS::Error>>
+    where
+        W: Write,
+    {
+        // The upper 16 bits of the address, as last written
+        let mut upper_address = None;
+        for ph in self.iter_program_headers() {
+            let ph = ph?;
+            if ph.p_type() != ProgramHeader::PT_LOAD {
+                continue;
+            }
+            let (Some(file_range), Some(mem_range)) = (ph.file_range(), ph.mem_range()) else {
+                return Err(Error::BadSegment.into());
+            };
+            if ph.p_filesz() > ph.p_memsz() {
+                return Err(Error::BadSegment.into());
+            }
+            let mut done = 0;
+            let mut chunk = [0u8; RECORD_DATA_LEN as usize];
+            while done < ph.p_filesz() {
+                let address = mem_range.start + done;
+                // Records can't cross a 64 KiB boundary
+                let to_boundary = 0x1_0000 - (address & 0xFFFF);
+                let chunk_len = RECORD_DATA_LEN.min(ph.p_filesz() - done).min(to_boundary);
+                let chunk = &mut chunk[0..chunk_len as usize];
+                self.data_source
+                    .read(file_range.start + done, chunk)
+                    .map_err(Error::Source)?;
+                let upper = (address >> 16) as u16;
+                if upper_address != Some(upper) {
+                    write_record(
+                        &mut out,
+                        RECORD_EXTENDED_LINEAR_ADDRESS,
+                        0,
+                        &upper.to_be_bytes(),
+                    )?;
+                    upper_address = Some(upper);
+                }
+                write_record(&mut out, RECORD_DATA, address as u16, chunk)?;
+                done += chunk_len;
+            }
+        }
+        write_record(
+            &mut out,
+            RECORD_START_LINEAR_ADDRESS,
+            0,
+            &self.e_entry.to_be_bytes(),
+        )?;
+        write_record(&mut out, RECORD_EOF, 0, &[])?;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Write one Intel HEX record, with its checksum.
+fn write_record<W>(
+    out: &mut W,
+    record_type: u8,
+    address: u16,
+    data: &[u8],
+) -> Result<(), std::io::Error>
+where
+    W: Write,
+{
+    let [address_hi, address_lo] = address.to_be_bytes();
+    let mut sum = (data.len() as u8)
+        .wrapping_add(address_hi)
+        .wrapping_add(address_lo)
+        .wrapping_add(record_type);
+    write!(out, ":{:02X}{:04X}{:02X}", data.len(), address, record_type)?;
+    for b in data {
+        write!(out, "{:02X}", b)?;
+        sum = sum.wrapping_add(*b);
+    }
+    write!(out, "{:02X}\r\n", sum.wrapping_neg())
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,155 @@
+//! Code and types for handling constructors and destructors.
+//!
+//! These are arrays of function pointers in the `SHT_PREINIT_ARRAY`,
+//! `SHT_INIT_ARRAY` and `SHT_FINI_ARRAY` sections. The constructors must be
+//! called before the application's entry point, and the destructors after it
+//! returns.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::iter::FusedIterator;
+
+use crate::{Error, Loader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// A range of 32-bit words in the file.
+#[derive(Debug, Clone, Default)]
+struct WordArray {
+    start_offset: u32,
+    end_offset: u32,
+}
+
+impl WordArray {
+    /// Size of a function pointer
+    const ENTRY_SIZE: u32 = 4;
+
+    /// Get the array in the first section of the given type, if any.
+    fn find<DS: Source>(loader: &Loader<DS>, sh_type: u32) -> Result<Self, Error<DS::Error>> {
+        let Some((_idx, sh)) = loader.first_section_of_type(sh_type)? else {
+            return Ok(WordArray::default());
+        };
+        if sh.sh_entsize() != Self::ENTRY_SIZE {
+            return Err(Error::BadArraySection);
+        }
+        let range = sh.file_range().ok_or(Error::BadArraySection)?;
+        Ok(WordArray {
+            start_offset: range.start,
+            end_offset: range.end,
+        })
+    }
+
+    /// Take the offset of the first word, if any are left.
+    fn take_front(&mut self) -> Option<u32> {
+        if self.end_offset.saturating_sub(self.start_offset) < Self::ENTRY_SIZE {
+            return None;
+        }
+        let offset = self.start_offset;
+        self.start_offset += Self::ENTRY_SIZE;
+        Some(offset)
+    }
+
+    /// Take the offset of the last word, if any are left.
+    fn take_back(&mut self) -> Option<u32> {
+        if self.end_offset.saturating_sub(self.start_offset) < Self::ENTRY_SIZE {
+            return None;
+        }
+        // Ignore any partial word at the end
+        let len = self.end_offset - self.start_offset;
+        self.end_offset = self.start_offset + len - (len % Self::ENTRY_SIZE);
+        self.end_offset -= Self::ENTRY_SIZE;
+        Some(self.end_offset)
+    }
+}
+
+/// Allows you to iterate through constructor or destructor addresses.
+///
+/// Created with `loader.iter_init_functions()` or
+/// `loader.iter_fini_functions()`.
+pub struct IterFunctions<'a, DS> {
+    parent: &'a Loader<DS>,
+    first: WordArray,
+    second: WordArray,
+    reverse: bool,
+}
+
+impl<'a, DS> Iterator for IterFunctions<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<u32, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = if self.reverse {
+            self.first.take_back().or_else(|| self.second.take_back())
+        } else {
+            self.first.take_front().or_else(|| self.second.take_front())
+        }?;
+        let result = self.parent.data_source.read_u32_le(offset);
+        if result.is_err() {
+            // Don't keep reading arrays we can't read
+            self.first = WordArray::default();
+            self.second = WordArray::default();
+        }
+        Some(result.map_err(Error::Source))
+    }
+}
+
+impl<'a, DS> FusedIterator for IterFunctions<'a, DS> where DS: Source {}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Create an iterator through the addresses of the constructors.
+    ///
+    /// These are the functions in the `SHT_PREINIT_ARRAY` section, followed
+    /// by those in the `SHT_INIT_ARRAY` section, in the order they should be
+    /// called. If there are no such sections, the iterator is empty.
+    pub fn iter_init_functions(&self) -> Result<IterFunctions<'_, DS>, Error<DS::Error>> {
+        Ok(IterFunctions {
+            parent: self,
+            first: WordArray::find(self, SectionHeader::SHT_PREINIT_ARRAY)?,
+            second: WordArray::find(self, SectionHeader::SHT_INIT_ARRAY)?,
+            reverse: false,
+        })
+    }
+
+    /// Create an iterator through the addresses of the destructors.
+    ///
+    /// These are the functions in the `SHT_FINI_ARRAY` section, in the order
+    /// they should be called - which is from the end of the array to the
+    /// start. If there is no such section, the iterator is empty.
+    pub fn iter_fini_functions(&self) -> Result<IterFunctions<'_, DS>, Error<DS::Error>> {
+        Ok(IterFunctions {
+            parent: self,
+            first: WordArray::find(self, SectionHeader::SHT_FINI_ARRAY)?,
+            second: WordArray::default(),
+            reverse: true,
+        })
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -4,23 +4,134 @@
 
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+extern crate std;
+
 // ============================================================================
 // Imports
 // ============================================================================
 
+use core::iter::FusedIterator;
+
+use erased::Shim;
+
+/// Emit a `log::debug!` record, if the `log` feature is on.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        ::log::debug!($($arg)*);
+        // Check the arguments, but generate no code
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+/// Emit a `log::trace!` record, if the `log` feature is on.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        ::log::trace!($($arg)*);
+        // Check the arguments, but generate no code
+        #[cfg(not(feature = "log"))]
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+pub mod arm_attrs;
+pub mod checksum;
+pub mod comment;
+pub mod coredump;
+#[cfg(feature = "demangle")]
+pub mod demangle;
+pub mod dynamic;
+#[cfg(feature = "embedded-io")]
+pub mod eio;
+mod erased;
+pub mod exidx;
+#[cfg(feature = "arbitrary")]
+pub mod fuzz;
+pub mod groups;
+pub mod hash;
+pub mod hexdump;
+#[cfg(feature = "std")]
+pub mod ihex;
+pub mod init;
+pub mod load;
+pub mod meta;
+pub mod notes;
+#[cfg(feature = "alloc")]
+pub mod owned;
+pub mod relocations;
 pub mod sections;
 pub mod segments;
+pub mod slice;
+#[cfg(feature = "heapless")]
+pub mod snapshot;
+pub mod strings;
+pub mod symbols;
+#[cfg(feature = "tar")]
+pub mod tar;
 pub mod traits;
+pub mod uf2;
+pub mod vectors;
+
+#[doc(inline)]
+pub use arm_attrs::{ArmAttributes, CpuProfile};
+
+#[doc(inline)]
+pub use checksum::SectionChecksum;
+
+#[doc(inline)]
+pub use coredump::ArmCoreRegs;
+
+#[doc(inline)]
+pub use dynamic::{DynamicTag, NeededLibrary};
+
+#[doc(inline)]
+pub use exidx::ExidxEntry;
+
+#[cfg(feature = "arbitrary")]
+#[doc(inline)]
+pub use fuzz::FuzzElf;
+
+#[doc(inline)]
+pub use load::{LoadOptions, LoadProgress, LoadState, MapError, SegmentDisposition};
+
+#[doc(inline)]
+pub use meta::NeotronMeta;
+
+#[doc(inline)]
+pub use notes::Note;
+
+#[doc(inline)]
+pub use relocations::{Rel, Rela, RelocError, Relocation, RelocationType};
+
+#[doc(inline)]
+pub use sections::{CompressionHeader, Header as SectionHeader, SectionFlags, SectionType};
+
+#[doc(inline)]
+pub use segments::{Header as ProgramHeader, PhdrInfo, SegmentFlags, SegmentType};
 
+#[cfg(feature = "heapless")]
 #[doc(inline)]
-pub use sections::Header as SectionHeader;
+pub use snapshot::HeaderSnapshot;
 
 #[doc(inline)]
-pub use segments::Header as ProgramHeader;
+pub use symbols::{Symbol, SymbolMatch, SymbolSection};
 
 #[doc(inline)]
 pub use traits::Source;
 
+#[doc(inline)]
+pub use vectors::{MemoryRegion, VectorTableFault, VectorTableInfo};
+
 // ============================================================================
 // Constants
 // ============================================================================
@@ -45,10 +156,80 @@ where
     WrongElfFile,
     /// There was a problem with the data source.
     Source(E),
-    /// Couldn't fit string into given buffer
-    NotEnoughSpace,
+    /// The given buffer was too small. This is how big it needs to be (in
+    /// bytes, or in items for a buffer of something else).
+    BufferTooSmall { needed: usize },
     /// Section name wasn't UTF-8
     InvalidString,
+    /// A symbol hash table was malformed
+    BadHashTable,
+    /// The section given was not of the right type for this operation
+    WrongSectionType,
+    /// The dynamic section was malformed
+    BadDynamicSection,
+    /// A note ran past the end of its segment
+    BadNote,
+    /// A relocation section had the wrong entry size, or referred to a
+    /// symbol that doesn't exist
+    BadRelocationSection,
+    /// A constructor or destructor array had the wrong entry size, or ran
+    /// past 4 GiB
+    BadArraySection,
+    /// The ARM build attributes were malformed
+    BadAttributes,
+    /// A section group had the wrong entry size, ran past 4 GiB, or had a
+    /// bad signature
+    BadGroupSection,
+    /// The section is compressed, so its contents can't be read directly
+    CompressedSection,
+    /// A compressed section was too small to hold its compression header
+    BadCompressionHeader,
+    /// A name offset was beyond the end of its string table
+    StringOffsetOutOfRange,
+    /// A section that should hold names wasn't a string table
+    BadStringTable,
+    /// A section or segment runs past the end of the file
+    DataOutOfBounds,
+    /// There were too many segments to handle without an allocator
+    TooManySegments,
+    /// A segment was malformed, e.g. it had more bytes in the file than in
+    /// memory
+    BadSegment,
+    /// A segment handler refused to load a segment of this type
+    SegmentRejected { p_type: u32 },
+    /// This virtual address isn't backed by data in the file
+    UnmappedAddress { vaddr: u32 },
+    /// The Cortex-M vector table was wrong
+    BadVectorTable(VectorTableFault),
+    /// The `.neotron_meta` section was malformed
+    BadNeotronMeta,
+    /// A section index taken from the file was beyond the end of the section
+    /// header table
+    SectionIndexOutOfRange { index: u32 },
+    /// Writing to a `core::fmt::Write` failed
+    WriteFailed,
+    /// An address translation function couldn't map this address, or it
+    /// was outside the memory given
+    AddressNotMapped { addr: u32 },
+    /// The segment (or gap between segments) at this address didn't map to
+    /// one contiguous block
+    SegmentNotContiguous { p_paddr: u32 },
+    /// A header index was beyond the end of its table, which holds `count`
+    /// headers
+    BadIndex {
+        kind: IndexKind,
+        index: u16,
+        count: u16,
+    },
+}
+
+/// Which table a header index was for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IndexKind {
+    /// The section header table
+    Section,
+    /// The program header table
+    ProgramHeader,
 }
 
 impl<E> From<E> for Error<E>
@@ -60,10 +241,52 @@ where
     }
 }
 
+/// Options which change what `Loader::new_with_options` will accept.
+///
+/// The default options accept the same files as `Loader::new`.
+#[derive(Debug, Clone, Default)]
+pub struct LoaderOptions {
+    allow_object: bool,
+    allow_core: bool,
+}
+
+impl LoaderOptions {
+    /// Make the default set of options.
+    pub fn new() -> LoaderOptions {
+        LoaderOptions::default()
+    }
+
+    /// Accept relocatable object files (`ET_REL`), as well as executables.
+    ///
+    /// Object files have no program headers, so anything that needs them
+    /// will act as though there are none.
+    pub fn allow_object(mut self, allow: bool) -> LoaderOptions {
+        self.allow_object = allow;
+        self
+    }
+
+    /// Accept core dumps (`ET_CORE`), as well as executables.
+    ///
+    /// Core dumps usually have no sections - their program headers describe
+    /// the memory of the crashed program, and a note holding its registers.
+    pub fn allow_core(mut self, allow: bool) -> LoaderOptions {
+        self.allow_core = allow;
+        self
+    }
+}
+
 /// An object that can load and parse an ELF file.
+///
+/// A `Loader` can be cloned if its data source can, which for a slice of
+/// bytes is cheap.
+#[derive(Clone)]
 pub struct Loader<DS> {
     /// Where we get the bytes from
     data_source: DS,
+    /// The type of file (e.g. `ET_EXEC`)
+    e_type: u16,
+    /// The machine this file was built for
+    e_machine: u16,
     /// The memory address of the entry point
     e_entry: u32,
     /// The offset of the program header table
@@ -76,6 +299,47 @@ pub struct Loader<DS> {
     e_shnum: u16,
     /// The index of the section header containing section names.
     e_shstrndx: u16,
+    /// Where the section names are
+    section_names: SectionNames,
+}
+
+/// Where the section name string table is.
+///
+/// We look this up once, when the loader is made.
+#[derive(Debug, Clone, Copy)]
+enum SectionNames {
+    /// The file has no section names (`e_shstrndx` is zero)
+    Missing,
+    /// `e_shstrndx` doesn't point at a string table we can read
+    Bad,
+    /// The offset and size of the section name string table
+    Table { offset: u32, size: u32 },
+}
+
+impl<DS> Loader<DS> {
+    /// Swap the data source for another, keeping the header fields we have
+    /// already parsed.
+    ///
+    /// This is useful for wrapping the source in something else (like a
+    /// cache) after the loader has been made. The new source must hold the
+    /// same bytes as the old one.
+    pub fn map_source<F, DS2>(self, f: F) -> Loader<DS2>
+    where
+        F: FnOnce(DS) -> DS2,
+    {
+        Loader {
+            data_source: f(self.data_source),
+            e_type: self.e_type,
+            e_machine: self.e_machine,
+            e_entry: self.e_entry,
+            e_phoff: self.e_phoff,
+            e_shoff: self.e_shoff,
+            e_phnum: self.e_phnum,
+            e_shnum: self.e_shnum,
+            e_shstrndx: self.e_shstrndx,
+            section_names: self.section_names,
+        }
+    }
 }
 
 impl<DS> Loader<DS>
@@ -83,92 +347,112 @@ where
     DS: Source,
 {
     /// Indicates ARM machine
-    const EM_ARM: u16 = 0x0028;
+    pub const EM_ARM: u16 = erased::EM_ARM;
+    /// For offset 0x10, indicates a relocatable object file
+    pub const ET_REL: u16 = erased::ET_REL;
     /// For offset 0x10, indicates a binary
-    const ET_EXEC: u16 = 0x0002;
-    /// Standard ELF magic header
-    const ELF_MAGIC: u32 = 0x7F454C46;
-    /// 32-bit, little-endian, version 1, SysV
-    const DESIRED_ELF_VERSION: u32 = 0x01010100;
+    pub const ET_EXEC: u16 = erased::ET_EXEC;
+    /// For offset 0x10, indicates a position-independent binary or shared
+    /// object
+    pub const ET_DYN: u16 = erased::ET_DYN;
+    /// For offset 0x10, indicates a core dump
+    pub const ET_CORE: u16 = erased::ET_CORE;
 
     /// Make a new loader
     pub fn new(data_source: DS) -> Result<Loader<DS>, Error<DS::Error>> {
-        let elf_header = data_source.read_u32_be(0x00)?;
-        if elf_header != Self::ELF_MAGIC {
-            // File doesn't start 0x7F E L F
-            return Err(Error::NotAnElfFile);
-        }
-        let class_endian_version_abi = data_source.read_u32_be(0x04)?;
-        if class_endian_version_abi != Self::DESIRED_ELF_VERSION {
-            return Err(Error::WrongElfFile);
-        }
+        Self::new_with_options(data_source, &LoaderOptions::new())
+    }
 
-        // Ignore ABI version at 0x08..0x10
+    /// Make a new loader for a relocatable object file (a `.o` file).
+    ///
+    /// Executables are accepted too.
+    pub fn new_object(data_source: DS) -> Result<Loader<DS>, Error<DS::Error>> {
+        Self::new_with_options(data_source, &LoaderOptions::new().allow_object(true))
+    }
 
-        let elf_type = data_source.read_u16_le(0x10)?;
-        if elf_type != Self::ET_EXEC {
-            // File is not a binary
-            return Err(Error::WrongElfFile);
-        }
+    /// Make a new loader, with the given options
+    pub fn new_with_options(
+        data_source: DS,
+        options: &LoaderOptions,
+    ) -> Result<Loader<DS>, Error<DS::Error>> {
+        let shim = Shim::new(&data_source);
+        let result = erased::parse_file_header(&shim, options);
+        let header = shim.finish(result)?;
+        Ok(Loader {
+            data_source,
+            e_type: header.e_type,
+            e_machine: header.e_machine,
+            e_entry: header.e_entry,
+            e_phoff: header.e_phoff,
+            e_shoff: header.e_shoff,
+            e_phnum: header.e_phnum,
+            e_shnum: header.e_shnum,
+            e_shstrndx: header.e_shstrndx,
+            section_names: header.section_names,
+        })
+    }
 
-        let elf_machine = data_source.read_u16_le(0x12)?;
-        if elf_machine != Self::EM_ARM {
-            // File is not a ARM
-            return Err(Error::WrongElfFile);
+    /// Create a section header iterator.
+    pub fn iter_section_headers(&self) -> IterSectionHeaders<'_, DS> {
+        IterSectionHeaders {
+            parent: self,
+            cursor: HeaderCursor::new(self.e_shnum),
         }
+    }
 
-        let elf_version = data_source.read_u32_le(0x14)?;
-        if elf_version != 1 {
-            // File is not a ELF
-            return Err(Error::WrongElfFile);
+    /// Create a program header iterator.
+    pub fn iter_program_headers(&self) -> IterProgramHeaders<'_, DS> {
+        IterProgramHeaders {
+            parent: self,
+            cursor: HeaderCursor::new(self.e_phnum),
         }
+    }
 
-        let e_entry = data_source.read_u32_le(0x18)?;
-        let e_phoff = data_source.read_u32_le(0x1C)?;
-        let e_shoff = data_source.read_u32_le(0x20)?;
-        let e_phentsize = data_source.read_u16_le(0x2A)?;
-
-        if e_phentsize != ProgramHeader::SIZE_IN_BYTES {
-            return Err(Error::WrongElfFile);
+    /// Create a section header iterator which owns this loader.
+    ///
+    /// Unlike `iter_section_headers`, this doesn't borrow the loader, so you
+    /// can keep it in a struct or hand it to another task. Get the loader
+    /// back with `into_inner`.
+    pub fn into_section_headers(self) -> OwnedIterSectionHeaders<DS> {
+        OwnedIterSectionHeaders {
+            cursor: HeaderCursor::new(self.e_shnum),
+            loader: self,
         }
+    }
 
-        let e_phnum = data_source.read_u16_le(0x2C)?;
-        let e_shentsize = data_source.read_u16_le(0x2E)?;
-
-        if e_shentsize != SectionHeader::SIZE_IN_BYTES {
-            return Err(Error::WrongElfFile);
+    /// Create a program header iterator which owns this loader.
+    ///
+    /// Unlike `iter_program_headers`, this doesn't borrow the loader, so you
+    /// can keep it in a struct or hand it to another task. Get the loader
+    /// back with `into_inner`.
+    pub fn into_program_headers(self) -> OwnedIterProgramHeaders<DS> {
+        OwnedIterProgramHeaders {
+            cursor: HeaderCursor::new(self.e_phnum),
+            loader: self,
         }
+    }
 
-        let e_shnum = data_source.read_u16_le(0x30)?;
-
-        let e_shstrndx = data_source.read_u16_le(0x32)?;
+    /// The type of file, e.g. `ET_EXEC`
+    pub fn e_type(&self) -> u16 {
+        self.e_type
+    }
 
-        let loader = Loader {
-            data_source,
-            e_entry,
-            e_phoff,
-            e_shoff,
-            e_phnum,
-            e_shnum,
-            e_shstrndx,
-        };
-        Ok(loader)
+    /// Is this a relocatable object file (`ET_REL`)?
+    ///
+    /// These have no program headers, so cannot be run, but you can look at
+    /// their sections, symbols and relocations.
+    pub fn is_object(&self) -> bool {
+        self.e_type == Self::ET_REL
     }
 
-    /// Create a section header iterator.
-    pub fn iter_section_headers(&self) -> IterSectionHeaders<DS> {
-        IterSectionHeaders {
-            parent: self,
-            next_section: 0,
-        }
+    /// Is this a core dump (`ET_CORE`)?
+    pub fn is_core(&self) -> bool {
+        self.e_type == Self::ET_CORE
     }
 
-    /// Create a program header iterator.
-    pub fn iter_program_headers(&self) -> IterProgramHeaders<DS> {
-        IterProgramHeaders {
-            parent: self,
-            next_program_header: 0,
-        }
+    /// The machine this file was built for, e.g. `EM_ARM`
+    pub fn e_machine(&self) -> u16 {
+        self.e_machine
     }
 
     /// The memory address of the entry point
@@ -202,14 +486,170 @@ where
     pub fn segment_start_offset(&self) -> u32 {
         self.e_phoff() + u32::from(self.e_phnum()) * u32::from(ProgramHeader::SIZE_IN_BYTES)
     }
+
+    /// Get the offset and size of the section name string table.
+    ///
+    /// If the file has no section names, the table is empty.
+    fn section_names(&self) -> Result<(u32, u32), Error<DS::Error>> {
+        match self.section_names {
+            SectionNames::Missing => Ok((0, 0)),
+            SectionNames::Bad => Err(Error::BadStringTable),
+            SectionNames::Table { offset, size } => Ok((offset, size)),
+        }
+    }
+
+    /// Get the string table linked to the given section (e.g. a symbol
+    /// table), checking it is a string table.
+    fn string_table_for(&self, table: &SectionHeader) -> Result<SectionHeader, Error<DS::Error>> {
+        match table.linked_section_lenient(self)? {
+            Some(strtab) if strtab.sh_type() == SectionHeader::SHT_STRTAB => Ok(strtab),
+            _ => Err(Error::BadStringTable),
+        }
+    }
+
+    /// Find the first section of the given type (e.g. `SHT_SYMTAB`).
+    ///
+    /// Returns the index of the section, and its header.
+    pub fn first_section_of_type(
+        &self,
+        sh_type: u32,
+    ) -> Result<Option<(u16, SectionHeader)>, Error<DS::Error>> {
+        for (idx, sh) in self.iter_section_headers().enumerate() {
+            let sh = sh?;
+            if sh.sh_type() == sh_type {
+                return Ok(Some((idx as u16, sh)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find the first program header of the given type.
+    fn find_program_header_by_type(
+        &self,
+        p_type: u32,
+    ) -> Result<Option<ProgramHeader>, Error<DS::Error>> {
+        for ph in self.iter_program_headers() {
+            let ph = ph?;
+            if ph.p_type() == p_type {
+                return Ok(Some(ph));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Convert a virtual address into an offset within the file.
+    ///
+    /// This uses the `PT_LOAD` segments. Returns `None` if the address isn't
+    /// backed by data in the file.
+    pub fn vaddr_to_offset(&self, vaddr: u32) -> Result<Option<u32>, Error<DS::Error>> {
+        for ph in self.iter_program_headers() {
+            let ph = ph?;
+            if ph.p_type() != ProgramHeader::PT_LOAD || !ph.contains_vaddr(vaddr) {
+                continue;
+            }
+            let offset_in_segment = vaddr - ph.p_vaddr();
+            if let Some(file_range) = ph.file_range() {
+                if offset_in_segment < file_range.len() as u32 {
+                    return Ok(Some(file_range.start + offset_in_segment));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read memory at the given virtual address, from the `PT_LOAD`
+    /// segments.
+    ///
+    /// The read can span segments, as long as every byte is backed by data
+    /// in the file - otherwise you get `Error::UnmappedAddress` with the
+    /// first address that isn't. Bytes beyond `p_filesz` aren't in the file
+    /// (and in a core dump, weren't saved), so they count as unmapped.
+    pub fn read_at_vaddr(&self, vaddr: u32, buffer: &mut [u8]) -> Result<(), Error<DS::Error>> {
+        let mut done = 0;
+        while done < buffer.len() {
+            let addr = vaddr.wrapping_add(done as u32);
+            let unmapped = Error::UnmappedAddress { vaddr: addr };
+            let mut mapped = None;
+            for ph in self.iter_program_headers() {
+                let ph = ph?;
+                if ph.p_type() != ProgramHeader::PT_LOAD || !ph.contains_vaddr(addr) {
+                    continue;
+                }
+                let offset_in_segment = addr - ph.p_vaddr();
+                if let Some(file_range) = ph.file_range() {
+                    if offset_in_segment < file_range.len() as u32 {
+                        let available = file_range.len() as u32 - offset_in_segment;
+                        mapped = Some((file_range.start + offset_in_segment, available));
+                        break;
+                    }
+                }
+            }
+            let (offset, available) = mapped.ok_or(unmapped)?;
+            let len = (buffer.len() - done).min(available as usize);
+            self.data_source
+                .read(offset, &mut buffer[done..done + len])?;
+            done += len;
+        }
+        Ok(())
+    }
+
+    /// Read a null-terminated string from the given offset, without reading
+    /// at or beyond `end`.
+    ///
+    /// The string must fit in `buffer`, including the null terminator, and
+    /// must be terminated before `end`.
+    fn read_str_in<'a>(
+        &self,
+        offset: u32,
+        end: u32,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a str, Error<DS::Error>> {
+        let shim = Shim::new(&self.data_source);
+        let result = erased::read_str_in(&shim, offset, end, buffer);
+        shim.finish(result)
+    }
+
+    /// Find the length of the null-terminated string at `offset`, not
+    /// including the null terminator.
+    ///
+    /// The string is read in small chunks, and we never read at or beyond
+    /// `end`. If there is no null terminator before `end`, you get
+    /// `Error::InvalidString`.
+    fn str_len(&self, offset: u32, end: u32) -> Result<usize, Error<DS::Error>> {
+        let shim = Shim::new(&self.data_source);
+        let result = erased::str_len(&shim, offset, end);
+        shim.finish(result)
+    }
+
+    /// Check whether the null-terminated string at `offset` is equal to
+    /// `expected`.
+    ///
+    /// The string is read in chunks the size of `scratch`, and we stop
+    /// reading as soon as we find a mismatch. We never read at or beyond
+    /// `end`.
+    fn str_eq(
+        &self,
+        offset: u32,
+        end: u32,
+        expected: &[u8],
+        scratch: &mut [u8],
+    ) -> Result<bool, Error<DS::Error>> {
+        let shim = Shim::new(&self.data_source);
+        let result = erased::str_eq(&shim, offset, end, expected, scratch);
+        shim.finish(result)
+    }
 }
 
 /// Allows you to iterate through the section headers.
 ///
+/// If a header can't be read, you get that error and then the iterator
+/// stops, rather than trying (and probably failing) to read the rest. Use
+/// `SectionHeader::new` if you want to skip over bad entries.
+///
 /// Created with `loader.iter_section_headers()`.
 pub struct IterSectionHeaders<'a, DS> {
     parent: &'a Loader<DS>,
-    next_section: u16,
+    cursor: HeaderCursor,
 }
 
 impl<'a, DS> Iterator for IterSectionHeaders<'a, DS>
@@ -219,23 +659,45 @@ where
     type Item = Result<SectionHeader, Error<DS::Error>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.next_section == self.parent.e_shnum {
-            return None;
-        }
+        self.cursor.next(|idx| SectionHeader::new(self.parent, idx))
+    }
 
-        let current_section = self.next_section;
-        self.next_section = self.next_section.wrapping_add(1);
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.cursor.remaining();
+        (remaining, Some(remaining))
+    }
 
-        Some(SectionHeader::new(self.parent, current_section))
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // Skip without reading the headers we skip over
+        self.cursor.skip(n);
+        self.next()
     }
 }
 
+impl<'a, DS> DoubleEndedIterator for IterSectionHeaders<'a, DS>
+where
+    DS: Source,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cursor
+            .next_back(|idx| SectionHeader::new(self.parent, idx))
+    }
+}
+
+impl<'a, DS> ExactSizeIterator for IterSectionHeaders<'a, DS> where DS: Source {}
+
+impl<'a, DS> FusedIterator for IterSectionHeaders<'a, DS> where DS: Source {}
+
 /// Allows you to iterate through the program headers.
 ///
+/// If a header can't be read, you get that error and then the iterator
+/// stops, rather than trying (and probably failing) to read the rest. Use
+/// `ProgramHeader::new` if you want to skip over bad entries.
+///
 /// Created with `loader.iter_program_headers()`.
 pub struct IterProgramHeaders<'a, DS> {
     parent: &'a Loader<DS>,
-    next_program_header: u16,
+    cursor: HeaderCursor,
 }
 
 impl<'a, DS> Iterator for IterProgramHeaders<'a, DS>
@@ -245,14 +707,211 @@ where
     type Item = Result<ProgramHeader, Error<DS::Error>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.next_program_header == self.parent.e_phnum {
+        self.cursor.next(|idx| ProgramHeader::new(self.parent, idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.cursor.remaining();
+        (remaining, Some(remaining))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // Skip without reading the headers we skip over
+        self.cursor.skip(n);
+        self.next()
+    }
+}
+
+impl<'a, DS> DoubleEndedIterator for IterProgramHeaders<'a, DS>
+where
+    DS: Source,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cursor
+            .next_back(|idx| ProgramHeader::new(self.parent, idx))
+    }
+}
+
+impl<'a, DS> ExactSizeIterator for IterProgramHeaders<'a, DS> where DS: Source {}
+
+impl<'a, DS> FusedIterator for IterProgramHeaders<'a, DS> where DS: Source {}
+
+/// Allows you to iterate through the section headers, while owning the
+/// loader.
+///
+/// Like `IterSectionHeaders`, this stops after the first error.
+///
+/// Created with `loader.into_section_headers()`.
+pub struct OwnedIterSectionHeaders<DS> {
+    loader: Loader<DS>,
+    cursor: HeaderCursor,
+}
+
+impl<DS> OwnedIterSectionHeaders<DS> {
+    /// Get the loader we're iterating through.
+    pub fn loader(&self) -> &Loader<DS> {
+        &self.loader
+    }
+
+    /// Stop iterating, and get the loader back.
+    pub fn into_inner(self) -> Loader<DS> {
+        self.loader
+    }
+}
+
+impl<DS> Iterator for OwnedIterSectionHeaders<DS>
+where
+    DS: Source,
+{
+    type Item = Result<SectionHeader, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor
+            .next(|idx| SectionHeader::new(&self.loader, idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.cursor.remaining();
+        (remaining, Some(remaining))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // Skip without reading the headers we skip over
+        self.cursor.skip(n);
+        self.next()
+    }
+}
+
+impl<DS> DoubleEndedIterator for OwnedIterSectionHeaders<DS>
+where
+    DS: Source,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cursor
+            .next_back(|idx| SectionHeader::new(&self.loader, idx))
+    }
+}
+
+impl<DS> ExactSizeIterator for OwnedIterSectionHeaders<DS> where DS: Source {}
+
+impl<DS> FusedIterator for OwnedIterSectionHeaders<DS> where DS: Source {}
+
+/// Allows you to iterate through the program headers, while owning the
+/// loader.
+///
+/// Like `IterProgramHeaders`, this stops after the first error.
+///
+/// Created with `loader.into_program_headers()`.
+pub struct OwnedIterProgramHeaders<DS> {
+    loader: Loader<DS>,
+    cursor: HeaderCursor,
+}
+
+impl<DS> OwnedIterProgramHeaders<DS> {
+    /// Get the loader we're iterating through.
+    pub fn loader(&self) -> &Loader<DS> {
+        &self.loader
+    }
+
+    /// Stop iterating, and get the loader back.
+    pub fn into_inner(self) -> Loader<DS> {
+        self.loader
+    }
+}
+
+impl<DS> Iterator for OwnedIterProgramHeaders<DS>
+where
+    DS: Source,
+{
+    type Item = Result<ProgramHeader, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor
+            .next(|idx| ProgramHeader::new(&self.loader, idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.cursor.remaining();
+        (remaining, Some(remaining))
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // Skip without reading the headers we skip over
+        self.cursor.skip(n);
+        self.next()
+    }
+}
+
+impl<DS> DoubleEndedIterator for OwnedIterProgramHeaders<DS>
+where
+    DS: Source,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.cursor
+            .next_back(|idx| ProgramHeader::new(&self.loader, idx))
+    }
+}
+
+impl<DS> ExactSizeIterator for OwnedIterProgramHeaders<DS> where DS: Source {}
+
+impl<DS> FusedIterator for OwnedIterProgramHeaders<DS> where DS: Source {}
+
+/// Tracks which entries of a header table the header iterators have still to
+/// yield, from the front and from the back.
+///
+/// If reading a header fails, the cursor is emptied, so the iterator stops
+/// after that error.
+struct HeaderCursor {
+    next: u16,
+    /// One past the last header we haven't yielded from the back
+    end: u16,
+}
+
+impl HeaderCursor {
+    fn new(count: u16) -> HeaderCursor {
+        HeaderCursor {
+            next: 0,
+            end: count,
+        }
+    }
+
+    /// Read the header at the front with `read`.
+    fn next<T, E>(&mut self, read: impl FnOnce(u16) -> Result<T, E>) -> Option<Result<T, E>> {
+        if self.next >= self.end {
+            return None;
+        }
+        let idx = self.next;
+        self.next += 1;
+        let result = read(idx);
+        if result.is_err() {
+            // Don't keep reading a table we can't read
+            self.next = self.end;
+        }
+        Some(result)
+    }
+
+    /// Read the header at the back with `read`.
+    fn next_back<T, E>(&mut self, read: impl FnOnce(u16) -> Result<T, E>) -> Option<Result<T, E>> {
+        if self.end <= self.next {
             return None;
         }
+        self.end -= 1;
+        let result = read(self.end);
+        if result.is_err() {
+            self.end = self.next;
+        }
+        Some(result)
+    }
 
-        let current_program_header = self.next_program_header;
-        self.next_program_header = self.next_program_header.wrapping_add(1);
+    /// Skip `n` headers at the front, without reading them.
+    fn skip(&mut self, n: usize) {
+        let skip = u16::try_from(n).unwrap_or(u16::MAX);
+        self.next = self.next.saturating_add(skip).min(self.end);
+    }
 
-        Some(ProgramHeader::new(self.parent, current_program_header))
+    /// How many headers are left.
+    fn remaining(&self) -> usize {
+        usize::from(self.end.saturating_sub(self.next))
     }
 }
 
@@ -260,6 +919,11 @@ where
 // Functions
 // ============================================================================
 
+/// Make the range `start..start + len`, if it doesn't wrap.
+fn checked_range(start: u32, len: u32) -> Option<core::ops::Range<u32>> {
+    Some(start..start.checked_add(len)?)
+}
+
 // ============================================================================
 // Tests
 // ============================================================================
@@ -0,0 +1,549 @@
+//! Code for copying segments into memory.
+//!
+//! You can either say where each segment should go, and we copy in the file
+//! contents and zero the rest, or you give us a function which writes bytes
+//! to memory and we call it for every segment that needs loading. If you
+//! can't do it all in one go, a [`LoadState`] does it a few bytes at a time.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::ops::Range;
+
+use crate::erased::{self, LoadCursor, LoadPlan, Shim};
+use crate::segments::MAX_SORTED_LOAD_SEGMENTS;
+use crate::{Error, Loader, ProgramHeader, SegmentType, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// What to do with a segment that isn't `PT_LOAD`.
+///
+/// Returned by the handler passed to `loader.load_with_handler()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SegmentDisposition {
+    /// Don't load the segment
+    Skip,
+    /// Load the segment as if it were `PT_LOAD`
+    LoadAnyway,
+    /// Stop loading, with `Error::SegmentRejected`
+    Fail,
+}
+
+/// Options which change how segments are loaded.
+///
+/// Taken by `loader.load_with_options()`, `loader.load_translated_with_options()`
+/// and `loader.load_state_with_options()`. The default options load the same
+/// way as `loader.load()`.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    fill_gaps: Option<u8>,
+}
+
+impl LoadOptions {
+    /// Make the default set of options.
+    pub fn new() -> LoadOptions {
+        LoadOptions::default()
+    }
+
+    /// Fill the gaps between `PT_LOAD` segments with `byte`.
+    ///
+    /// Only the space between the lowest and highest segments is filled -
+    /// nothing before the first one or after the last. The segments are
+    /// loaded in order of address, and if any overlap you get
+    /// `Error::BadSegment`.
+    pub fn fill_gaps(mut self, byte: u8) -> LoadOptions {
+        self.fill_gaps = Some(byte);
+        self
+    }
+}
+
+/// Returned by the translation function given to `loader.load_translated()`,
+/// when an address doesn't map to anywhere.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MapError;
+
+/// How far a [`LoadState`] has got.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadProgress {
+    /// There is more to do. We have written `done` bytes out of `total`.
+    InProgress { done: u32, total: u32 },
+    /// Every segment has been loaded. Here's the entry point.
+    Complete { entry: u32 },
+}
+
+/// Loads the `PT_LOAD` segments a step at a time.
+///
+/// This does the same as `loader.load_with_options()`, but each call to
+/// `step` only writes as many bytes as you allow, so you can do other things
+/// (like feeding a watchdog) between steps. All the state is in here, so you
+/// can move it about between steps.
+///
+/// Created with `loader.load_state()` or `loader.load_state_with_options()`.
+pub struct LoadState<'a, DS, W> {
+    loader: &'a Loader<DS>,
+    write_fn: W,
+    /// Where we've got to
+    cursor: LoadCursor,
+    /// How many bytes we'll write, in total
+    total: u32,
+    /// What to fill the gaps between segments with, if anything
+    fill: Option<u8>,
+    /// When filling gaps, the `PT_LOAD` segments in address order
+    order: [u16; MAX_SORTED_LOAD_SEGMENTS],
+    /// How much of `order` is used
+    order_len: usize,
+}
+
+impl<'a, DS, W> LoadState<'a, DS, W>
+where
+    DS: Source,
+    W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+{
+    /// Write up to `budget_bytes` more bytes.
+    ///
+    /// Zeroes count towards the budget, just like bytes from the file. We only
+    /// move on once a write has succeeded, so if this returns an error
+    /// (from the source, or from your write function) you can call `step`
+    /// again to retry from the same place.
+    pub fn step(&mut self, budget_bytes: u32) -> Result<LoadProgress, Error<DS::Error>> {
+        let loader = self.loader;
+        let plan = LoadPlan {
+            e_phoff: loader.e_phoff,
+            e_phnum: loader.e_phnum,
+            fill: self.fill.map(|fill| (fill, &self.order[0..self.order_len])),
+        };
+        let shim = Shim::new(&loader.data_source);
+        let write_fn = &mut self.write_fn;
+        let mut write = |addr: u32, bytes: &[u8]| write_fn(addr, bytes).map_err(|e| shim.stash(e));
+        let result = erased::load_step(&shim, &plan, &mut self.cursor, budget_bytes, &mut write);
+        shim.finish(result)?;
+        let progress = self.progress();
+        if let LoadProgress::Complete { entry } = progress {
+            debug!("load complete, entry point is 0x{:08x}", entry);
+        }
+        Ok(progress)
+    }
+
+    /// Say how far we've got, without doing anything.
+    ///
+    /// We don't know we're complete until a `step` has moved past the last
+    /// segment, so this can say `InProgress` with `done == total`.
+    pub fn progress(&self) -> LoadProgress {
+        let steps = match self.fill {
+            Some(_) => self.order_len as u16,
+            None => self.loader.e_phnum,
+        };
+        if self.cursor.segment >= steps {
+            LoadProgress::Complete {
+                entry: self.loader.e_entry,
+            }
+        } else {
+            LoadProgress::InProgress {
+                done: self.cursor.done,
+                total: self.total,
+            }
+        }
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Copy a segment into `dest`, which stands in for the memory it will
+    /// occupy.
+    ///
+    /// The first `p_filesz` bytes come from the file, and the rest up to
+    /// `p_memsz` are zeroed. Anything in `dest` beyond `p_memsz` is left
+    /// alone. We read at most 512 bytes at a time, so this is fine for slow
+    /// sources and large segments.
+    ///
+    /// If `dest` is shorter than `p_memsz`, you get `Error::BufferTooSmall`,
+    /// and if `p_filesz` is larger than `p_memsz`, you get
+    /// `Error::BadSegment`.
+    pub fn copy_segment(
+        &self,
+        ph: &ProgramHeader,
+        dest: &mut [u8],
+    ) -> Result<(), Error<DS::Error>> {
+        let shim = Shim::new(&self.data_source);
+        let result = erased::copy_segment(&shim, ph, dest);
+        shim.finish(result)
+    }
+
+    /// Load the `PT_LOAD` segments, by passing their contents to `write_fn`.
+    ///
+    /// `write_fn` is called with an address (from `p_paddr`) and some bytes
+    /// to write there. Each segment is passed in chunks of at most 512 bytes,
+    /// with zeroes for the part beyond `p_filesz`. Other segments are
+    /// skipped. A segment which wraps past the end of memory, or of the file,
+    /// gives `Error::BadSegment`.
+    ///
+    /// This is `load_with_options` with the default options.
+    pub fn load<W>(&self, write_fn: W) -> Result<(), Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+    {
+        self.load_with_options(write_fn, &LoadOptions::new())
+    }
+
+    /// Like `load`, but `handler` decides what to do with segments of
+    /// non-standard types (like processor-specific ones).
+    ///
+    /// Segments of standard types (like `PT_PHDR`, `PT_NOTE` and
+    /// `PT_GNU_STACK`) are skipped without asking `handler` - use
+    /// `load_with_handler_all` if you want to see those too.
+    pub fn load_with_handler<W, H>(&self, write_fn: W, handler: H) -> Result<(), Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+        H: FnMut(&ProgramHeader) -> SegmentDisposition,
+    {
+        self.load_segments(write_fn, handler, false)
+    }
+
+    /// Like `load_with_handler`, but `handler` is asked about every segment
+    /// which isn't `PT_LOAD`.
+    pub fn load_with_handler_all<W, H>(
+        &self,
+        write_fn: W,
+        handler: H,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+        H: FnMut(&ProgramHeader) -> SegmentDisposition,
+    {
+        self.load_segments(write_fn, handler, true)
+    }
+
+    /// Like `load`, but every address is passed through `translate` first.
+    ///
+    /// This is `load_translated_with_options` with the default options.
+    pub fn load_translated<W, T>(&self, write_fn: W, translate: T) -> Result<u32, Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+        T: FnMut(u32) -> Result<u32, MapError>,
+    {
+        self.load_translated_with_options(write_fn, translate, &LoadOptions::new())
+    }
+
+    /// Like `load_with_options`, but every address is passed through
+    /// `translate` first.
+    ///
+    /// This is for when a file is linked for one memory map but runs in
+    /// another - e.g. linked for contiguous RAM, but loaded into two banks.
+    /// Each segment (and each gap, if you're filling them) must map to one
+    /// contiguous block, so we check the first and last byte of each
+    /// translate to addresses the right distance apart, and if not you get
+    /// `Error::SegmentNotContiguous`. An address that `translate` rejects
+    /// gives `Error::AddressNotMapped`. Everything is checked before anything
+    /// is written.
+    ///
+    /// Returns the translated entry point.
+    pub fn load_translated_with_options<W, T>(
+        &self,
+        mut write_fn: W,
+        mut translate: T,
+        options: &LoadOptions,
+    ) -> Result<u32, Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+        T: FnMut(u32) -> Result<u32, MapError>,
+    {
+        for ph in self.iter_program_headers() {
+            let ph = ph?;
+            if ph.p_type() == ProgramHeader::PT_LOAD {
+                let mem_range = ph.mem_range().ok_or(Error::BadSegment)?;
+                self.check_translation(mem_range, &mut translate)?;
+            }
+        }
+        if options.fill_gaps.is_some() {
+            self.for_each_gap(|gap| self.check_translation(gap, &mut translate))?;
+        }
+        // Each write is within a block we've checked, so we can translate
+        // just its start
+        self.load_with_options(
+            |addr, bytes| {
+                let run_addr =
+                    translate(addr).map_err(|MapError| Error::AddressNotMapped { addr })?;
+                write_fn(run_addr, bytes)
+            },
+            options,
+        )?;
+        translate(self.e_entry).map_err(|MapError| Error::AddressNotMapped { addr: self.e_entry })
+    }
+
+    /// Check the block of memory at `mem_range` maps to one contiguous block.
+    fn check_translation<T>(
+        &self,
+        mem_range: Range<u32>,
+        translate: &mut T,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        T: FnMut(u32) -> Result<u32, MapError>,
+    {
+        if mem_range.is_empty() {
+            return Ok(());
+        }
+        let mut map = |addr| translate(addr).map_err(|MapError| Error::AddressNotMapped { addr });
+        let run_start = map(mem_range.start)?;
+        let run_last = map(mem_range.end - 1)?;
+        let shim = Shim::new(&self.data_source);
+        shim.finish(erased::check_contiguous(mem_range, run_start, run_last))
+    }
+
+    /// Call `f` with each gap between the `PT_LOAD` segments, in address
+    /// order.
+    ///
+    /// Segments which overlap give `Error::BadSegment`.
+    fn for_each_gap<F>(&self, mut f: F) -> Result<(), Error<DS::Error>>
+    where
+        F: FnMut(Range<u32>) -> Result<(), Error<DS::Error>>,
+    {
+        let mut prev_end = None;
+        for ph in self.iter_load_segments_sorted()? {
+            let mem_range = ph?.mem_range().ok_or(Error::BadSegment)?;
+            if mem_range.is_empty() {
+                continue;
+            }
+            if let Some(prev_end) = prev_end {
+                if mem_range.start < prev_end {
+                    debug!(
+                        "segment at 0x{:08x} overlaps the one ending at 0x{:08x}",
+                        mem_range.start, prev_end
+                    );
+                    return Err(Error::BadSegment);
+                }
+                if prev_end < mem_range.start {
+                    f(prev_end..mem_range.start)?;
+                }
+            }
+            prev_end = Some(mem_range.end);
+        }
+        Ok(())
+    }
+
+    /// Like `load`, but changed by `options`.
+    ///
+    /// With `options.fill_gaps()`, `write_fn` is also given the fill bytes
+    /// for each gap between segments, in chunks of at most 512 bytes.
+    pub fn load_with_options<W>(
+        &self,
+        mut write_fn: W,
+        options: &LoadOptions,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+    {
+        let Some(fill) = options.fill_gaps else {
+            return self.load_segments(write_fn, |_ph| SegmentDisposition::Skip, false);
+        };
+        let mut prev_end = None;
+        for ph in self.iter_load_segments_sorted()? {
+            let ph = ph?;
+            let mem_range = ph.mem_range().ok_or(Error::BadSegment)?;
+            if mem_range.is_empty() {
+                continue;
+            }
+            if let Some(prev_end) = prev_end {
+                if mem_range.start < prev_end {
+                    debug!(
+                        "segment at 0x{:08x} overlaps the one ending at 0x{:08x}",
+                        mem_range.start, prev_end
+                    );
+                    return Err(Error::BadSegment);
+                }
+                self.fill_gap(prev_end, mem_range.start, fill, &mut write_fn)?;
+            }
+            self.stream_segment(&ph, &mut write_fn)?;
+            prev_end = Some(mem_range.end);
+        }
+        Ok(())
+    }
+
+    /// Get ready to load the `PT_LOAD` segments, a step at a time.
+    ///
+    /// This is `load_state_with_options` with the default options.
+    pub fn load_state<W>(&self, write_fn: W) -> Result<LoadState<'_, DS, W>, Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+    {
+        self.load_state_with_options(write_fn, &LoadOptions::new())
+    }
+
+    /// Get ready to load the `PT_LOAD` segments a step at a time, changed by
+    /// `options`.
+    ///
+    /// `write_fn` works as it does for `load_with_options`. We check the
+    /// segments up front, so a segment which wraps, or has more bytes in the
+    /// file than in memory, gives `Error::BadSegment` here rather than part
+    /// way through. So do overlapping segments, if you're filling the gaps.
+    pub fn load_state_with_options<W>(
+        &self,
+        write_fn: W,
+        options: &LoadOptions,
+    ) -> Result<LoadState<'_, DS, W>, Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+    {
+        let shim = Shim::new(&self.data_source);
+        let result = erased::plan_load(&shim, self.e_phoff, self.e_phnum);
+        let mut total = shim.finish(result)?;
+        let mut order = [0u16; MAX_SORTED_LOAD_SEGMENTS];
+        let mut order_len = 0;
+        if options.fill_gaps.is_some() {
+            self.for_each_gap(|gap| {
+                total = total
+                    .checked_add(gap.end - gap.start)
+                    .ok_or(Error::BadSegment)?;
+                Ok(())
+            })?;
+            for (slot, idx) in order
+                .iter_mut()
+                .zip(self.iter_load_segments_sorted()?.indices())
+            {
+                *slot = idx;
+                order_len += 1;
+            }
+        }
+        Ok(LoadState {
+            loader: self,
+            write_fn,
+            cursor: LoadCursor::default(),
+            total,
+            fill: options.fill_gaps,
+            order,
+            order_len,
+        })
+    }
+
+    /// Copy a segment into `dest`, which stands in for the memory starting at
+    /// `dest_base_addr`.
+    ///
+    /// The segment is copied to offset `p_paddr - dest_base_addr` in `dest`.
+    /// If the segment starts before `dest_base_addr`, you get
+    /// `Error::AddressNotMapped`, and if it runs past the end of `dest`, you
+    /// get `Error::BufferTooSmall`, which says how big `dest` needs to be.
+    /// See `copy_segment` for the rest.
+    pub fn copy_segment_at(
+        &self,
+        ph: &ProgramHeader,
+        dest: &mut [u8],
+        dest_base_addr: u32,
+    ) -> Result<(), Error<DS::Error>> {
+        let start = ph
+            .p_paddr()
+            .checked_sub(dest_base_addr)
+            .ok_or(Error::AddressNotMapped { addr: ph.p_paddr() })?;
+        let needed = (start as usize).saturating_add(ph.p_memsz() as usize);
+        if dest.len() < needed {
+            return Err(Error::BufferTooSmall { needed });
+        }
+        self.copy_segment(ph, &mut dest[start as usize..])
+    }
+
+    /// Load every segment that is `PT_LOAD`, or which `handler` says to load.
+    ///
+    /// The handler only sees segments of standard types if `ask_all` is set.
+    fn load_segments<W, H>(
+        &self,
+        mut write_fn: W,
+        mut handler: H,
+        ask_all: bool,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+        H: FnMut(&ProgramHeader) -> SegmentDisposition,
+    {
+        for (idx, ph) in self.iter_program_headers().enumerate() {
+            let ph = ph?;
+            let disposition = match ph.segment_type() {
+                SegmentType::Load => SegmentDisposition::LoadAnyway,
+                SegmentType::Os(_) | SegmentType::Proc(_) | SegmentType::Unknown(_) => handler(&ph),
+                _ if ask_all => handler(&ph),
+                _ => SegmentDisposition::Skip,
+            };
+            trace!(
+                "segment {}: p_type=0x{:08x} {:?}",
+                idx,
+                ph.p_type(),
+                disposition
+            );
+            match disposition {
+                SegmentDisposition::Skip => {}
+                SegmentDisposition::LoadAnyway => {
+                    debug!(
+                        "segment {}: loading 0x{:x} bytes from offset 0x{:08x} to 0x{:08x}, then 0x{:x} zeroes",
+                        idx,
+                        ph.p_filesz(),
+                        ph.p_offset(),
+                        ph.p_paddr(),
+                        ph.p_memsz().saturating_sub(ph.p_filesz())
+                    );
+                    self.stream_segment(&ph, &mut write_fn)?
+                }
+                SegmentDisposition::Fail => {
+                    debug!("segment {}: rejected by handler", idx);
+                    return Err(Error::SegmentRejected {
+                        p_type: ph.p_type(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Pass the contents of a segment to `write_fn`, in chunks.
+    fn stream_segment<W>(
+        &self,
+        ph: &ProgramHeader,
+        write_fn: &mut W,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+    {
+        let shim = Shim::new(&self.data_source);
+        let mut write = |addr: u32, bytes: &[u8]| write_fn(addr, bytes).map_err(|e| shim.stash(e));
+        let result = erased::stream_segment(&shim, ph, &mut write);
+        shim.finish(result)
+    }
+
+    /// Pass `write_fn` the `fill` bytes for `start..end`.
+    fn fill_gap<W>(
+        &self,
+        start: u32,
+        end: u32,
+        fill: u8,
+        write_fn: &mut W,
+    ) -> Result<(), Error<DS::Error>>
+    where
+        W: FnMut(u32, &[u8]) -> Result<(), Error<DS::Error>>,
+    {
+        let shim = Shim::new(&self.data_source);
+        let mut write = |addr: u32, bytes: &[u8]| write_fn(addr, bytes).map_err(|e| shim.stash(e));
+        let result = erased::fill_gap(start, end, fill, &mut write);
+        shim.finish(result)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,149 @@
+//! Code for reading Neotron application metadata.
+//!
+//! Neotron apps can embed a fixed-layout metadata blob in a section called
+//! `.neotron_meta`. All the fields are little-endian:
+//!
+//! | Offset | Size | Field                                  |
+//! |--------|------|----------------------------------------|
+//! | 0x00   | 4    | Magic (`NTRN`)                         |
+//! | 0x04   | 4    | Structure version (1)                  |
+//! | 0x08   | 4    | Required heap size, in bytes           |
+//! | 0x0C   | 4    | Required stack size, in bytes          |
+//! | 0x10   | 32   | App name, UTF-8, padded with nulls     |
+//! | 0x30   | 6    | Version (major, minor, patch, as u16s) |
+//! | 0x36   | 2    | Reserved                               |
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The longest app name we can hold
+pub const MAX_APP_NAME_LEN: usize = 32;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// The metadata from a `.neotron_meta` section.
+///
+/// Created with `loader.neotron_metadata()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeotronMeta {
+    heap_size: u32,
+    stack_size: u32,
+    name: [u8; MAX_APP_NAME_LEN],
+    name_len: u8,
+    version: (u16, u16, u16),
+}
+
+impl NeotronMeta {
+    /// The name of the section holding the metadata
+    pub const SECTION_NAME: &'static str = ".neotron_meta";
+
+    /// The magic bytes at the start of the metadata
+    pub const MAGIC: [u8; 4] = *b"NTRN";
+
+    /// The version of the structure that we understand
+    pub const STRUCTURE_VERSION: u32 = 1;
+
+    /// The size of the structure, in bytes
+    pub const SIZE_IN_BYTES: usize = 0x38;
+
+    /// How much heap the app needs, in bytes.
+    pub fn heap_size(&self) -> u32 {
+        self.heap_size
+    }
+
+    /// How much stack the app needs, in bytes.
+    pub fn stack_size(&self) -> u32 {
+        self.stack_size
+    }
+
+    /// The name of the app.
+    pub fn name(&self) -> &str {
+        // Checked when we made the struct
+        core::str::from_utf8(&self.name[0..usize::from(self.name_len)]).unwrap_or_default()
+    }
+
+    /// The version of the app, as (major, minor, patch).
+    pub fn version(&self) -> (u16, u16, u16) {
+        self.version
+    }
+
+    /// Decode the metadata from the raw bytes.
+    fn parse(raw: &[u8; Self::SIZE_IN_BYTES]) -> Option<NeotronMeta> {
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                raw[offset],
+                raw[offset + 1],
+                raw[offset + 2],
+                raw[offset + 3],
+            ])
+        };
+        let half = |offset: usize| u16::from_le_bytes([raw[offset], raw[offset + 1]]);
+        if raw[0..4] != Self::MAGIC || word(0x04) != Self::STRUCTURE_VERSION {
+            return None;
+        }
+        let mut name = [0u8; MAX_APP_NAME_LEN];
+        name.copy_from_slice(&raw[0x10..0x10 + MAX_APP_NAME_LEN]);
+        let name_len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        // Everything after the name must be padding
+        if name[name_len..].iter().any(|b| *b != 0) {
+            return None;
+        }
+        core::str::from_utf8(&name[0..name_len]).ok()?;
+        Some(NeotronMeta {
+            heap_size: word(0x08),
+            stack_size: word(0x0C),
+            name,
+            name_len: name_len as u8,
+            version: (half(0x30), half(0x32), half(0x34)),
+        })
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Read the metadata from the `.neotron_meta` section.
+    ///
+    /// Returns `None` if there is no such section. If the section is too
+    /// small, has the wrong magic or structure version, or has a bad name,
+    /// you get `Error::BadNeotronMeta`.
+    pub fn neotron_metadata(&self) -> Result<Option<NeotronMeta>, Error<DS::Error>> {
+        let Some(sh) = self.find_section_by_name(NeotronMeta::SECTION_NAME)? else {
+            return Ok(None);
+        };
+        let mut raw = [0u8; NeotronMeta::SIZE_IN_BYTES];
+        let data = sh.read_data(self, 0, &mut raw)?;
+        if data.len() < NeotronMeta::SIZE_IN_BYTES {
+            return Err(Error::BadNeotronMeta);
+        }
+        NeotronMeta::parse(&raw)
+            .map(Some)
+            .ok_or(Error::BadNeotronMeta)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
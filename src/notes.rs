@@ -0,0 +1,366 @@
+//! Code and types for handling Notes.
+//!
+//! These live in `PT_NOTE` segments and/or `SHT_NOTE` sections, and carry extra
+//! information about a file, such as a build ID.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, ProgramHeader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Represents a note record.
+///
+/// A note has a name (which says who defined the note type, e.g. `GNU`), a
+/// type, and a descriptor (the contents of the note).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Note {
+    name_offset: u32,
+    name_len: u32,
+    desc_offset: u32,
+    desc_len: u32,
+    n_type: u32,
+}
+
+impl Note {
+    /// Size of the header at the start of each note
+    pub const HEADER_SIZE_IN_BYTES: u16 = 0x0C;
+
+    /// A `CORE` note containing the state of a process, including its
+    /// registers.
+    pub const NT_PRSTATUS: u32 = 1;
+
+    /// A `GNU` note containing a unique build ID.
+    pub const NT_GNU_BUILD_ID: u32 = 3;
+
+    /// The offset within the file of the note's name.
+    pub fn name_offset(&self) -> u32 {
+        self.name_offset
+    }
+
+    /// The length of the note's name, including the null terminator.
+    pub fn name_len(&self) -> u32 {
+        self.name_len
+    }
+
+    /// The offset within the file of the note's descriptor.
+    pub fn desc_offset(&self) -> u32 {
+        self.desc_offset
+    }
+
+    /// The length of the note's descriptor.
+    pub fn desc_len(&self) -> u32 {
+        self.desc_len
+    }
+
+    /// Get the `n_type` field.
+    ///
+    /// The meaning of this depends on the name of the note.
+    pub fn n_type(&self) -> u32 {
+        self.n_type
+    }
+
+    /// Get the name of this note, e.g. `GNU`.
+    ///
+    /// If `buffer` is too small, you get `Error::BufferTooSmall`, which says
+    /// how big it needs to be.
+    pub fn name<'a, DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a str, Error<DS::Error>> {
+        let name = self.read(loader, self.name_offset, self.name_len, buffer)?;
+        // Drop the null terminator
+        let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+        core::str::from_utf8(&name[0..len]).map_err(|_| Error::InvalidString)
+    }
+
+    /// Get the descriptor of this note.
+    ///
+    /// If `buffer` is too small, you get `Error::BufferTooSmall`, which says
+    /// how big it needs to be.
+    pub fn descriptor<'a, DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a [u8], Error<DS::Error>> {
+        self.read(loader, self.desc_offset, self.desc_len, buffer)
+    }
+
+    /// Check if the name of this note is `expected`, without needing a
+    /// buffer.
+    pub(crate) fn name_eq<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+        expected: &str,
+    ) -> Result<bool, Error<DS::Error>> {
+        if self.name_len as usize != expected.len() + 1 {
+            return Ok(false);
+        }
+        let mut chunk = [0u8; 16];
+        loader.str_eq(
+            self.name_offset,
+            self.name_offset + self.name_len,
+            expected.as_bytes(),
+            &mut chunk,
+        )
+    }
+
+    /// Read part of the note into the start of the buffer.
+    fn read<'a, DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+        offset: u32,
+        len: u32,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a [u8], Error<DS::Error>> {
+        let needed = len as usize;
+        let Some(buffer) = buffer.get_mut(0..needed) else {
+            return Err(Error::BufferTooSmall { needed });
+        };
+        loader.data_source.read(offset, buffer)?;
+        Ok(buffer)
+    }
+}
+
+/// Parses the notes in a region of the file.
+#[derive(Debug, Clone)]
+pub(crate) struct NoteReader {
+    next_offset: u32,
+    end_offset: u32,
+}
+
+impl NoteReader {
+    /// Make a reader for the notes between `start` and `end`.
+    pub(crate) fn new(start: u32, end: u32) -> NoteReader {
+        NoteReader {
+            next_offset: start,
+            end_offset: end.max(start),
+        }
+    }
+
+    /// Make a reader which has no notes.
+    pub(crate) fn empty() -> NoteReader {
+        NoteReader::new(0, 0)
+    }
+
+    /// Parse the next note.
+    ///
+    /// If the note is malformed, we return an error and then stop.
+    pub(crate) fn next_note<DS: Source>(
+        &mut self,
+        loader: &Loader<DS>,
+    ) -> Option<Result<Note, Error<DS::Error>>> {
+        if self.next_offset == self.end_offset {
+            return None;
+        }
+        let result = self.parse(loader);
+        match &result {
+            Ok((_note, next_offset)) => self.next_offset = *next_offset,
+            Err(_) => self.next_offset = self.end_offset,
+        }
+        Some(result.map(|(note, _next_offset)| note))
+    }
+
+    /// Parse the note at `next_offset`, returning it and the offset of the
+    /// note after it.
+    fn parse<DS: Source>(&self, loader: &Loader<DS>) -> Result<(Note, u32), Error<DS::Error>> {
+        let offset = self.next_offset;
+        if self.end_offset - offset < u32::from(Note::HEADER_SIZE_IN_BYTES) {
+            return Err(Error::BadNote);
+        }
+        let name_len = loader.data_source.read_u32_le(offset)?;
+        let desc_len = loader.data_source.read_u32_le(offset + 0x04)?;
+        let n_type = loader.data_source.read_u32_le(offset + 0x08)?;
+
+        // The name and the descriptor are each padded to four bytes
+        let name_offset = offset + u32::from(Note::HEADER_SIZE_IN_BYTES);
+        let desc_offset = align4(name_offset, name_len).ok_or(Error::BadNote)?;
+        let desc_end = desc_offset.checked_add(desc_len).ok_or(Error::BadNote)?;
+        if desc_end > self.end_offset {
+            return Err(Error::BadNote);
+        }
+        // The padding after the last note might be missing
+        let next_offset = align4(desc_offset, desc_len)
+            .unwrap_or(self.end_offset)
+            .min(self.end_offset);
+
+        let note = Note {
+            name_offset,
+            name_len,
+            desc_offset,
+            desc_len,
+            n_type,
+        };
+        Ok((note, next_offset))
+    }
+}
+
+/// Allows you to iterate through the notes in the `PT_NOTE` segments.
+///
+/// Created with `loader.iter_notes()`.
+pub struct IterNotes<'a, DS> {
+    parent: &'a Loader<DS>,
+    next_program_header: u16,
+    reader: NoteReader,
+}
+
+impl<'a, DS> Iterator for IterNotes<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<Note, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(result) = self.reader.next_note(self.parent) {
+                return Some(result);
+            }
+
+            // Find the next note segment
+            if self.next_program_header == self.parent.e_phnum {
+                return None;
+            }
+            let current_program_header = self.next_program_header;
+            self.next_program_header = self.next_program_header.wrapping_add(1);
+            let ph = match ProgramHeader::new(self.parent, current_program_header) {
+                Ok(ph) => ph,
+                Err(e) => return Some(Err(e)),
+            };
+            if ph.p_type() == ProgramHeader::PT_NOTE {
+                self.reader =
+                    NoteReader::new(ph.p_offset(), ph.p_offset().saturating_add(ph.p_filesz()));
+            }
+        }
+    }
+}
+
+/// Allows you to iterate through the notes in an `SHT_NOTE` section.
+///
+/// Created with `section_header.iter_notes()`.
+pub struct IterSectionNotes<'a, DS> {
+    parent: &'a Loader<DS>,
+    reader: NoteReader,
+}
+
+impl<'a, DS> Iterator for IterSectionNotes<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<Note, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.reader.next_note(self.parent)
+    }
+}
+
+impl SectionHeader {
+    /// Create an iterator through the notes in this section.
+    ///
+    /// The section must be an `SHT_NOTE` section.
+    pub fn iter_notes<'a, DS: Source>(
+        &self,
+        loader: &'a Loader<DS>,
+    ) -> Result<IterSectionNotes<'a, DS>, Error<DS::Error>> {
+        if self.sh_type() != SectionHeader::SHT_NOTE {
+            return Err(Error::WrongSectionType);
+        }
+        Ok(IterSectionNotes {
+            parent: loader,
+            reader: NoteReader::new(
+                self.sh_offset(),
+                self.sh_offset().saturating_add(self.sh_size()),
+            ),
+        })
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Create a note iterator.
+    ///
+    /// This walks every note in every `PT_NOTE` segment.
+    pub fn iter_notes(&self) -> IterNotes<'_, DS> {
+        IterNotes {
+            parent: self,
+            next_program_header: 0,
+            reader: NoteReader::empty(),
+        }
+    }
+
+    /// Get the unique build ID for this file, if it has one.
+    ///
+    /// This is the descriptor of the `GNU` note of type `NT_GNU_BUILD_ID`,
+    /// which is usually 20 bytes long (a SHA-1 hash). We look in the
+    /// `PT_NOTE` segments first, and then in the `SHT_NOTE` sections (e.g.
+    /// `.note.gnu.build-id`).
+    ///
+    /// If `buffer` is too small, you get `Error::BufferTooSmall`, which says
+    /// how big it needs to be.
+    pub fn build_id<'a>(&self, buffer: &'a mut [u8]) -> Result<Option<&'a [u8]>, Error<DS::Error>> {
+        for ph in self.iter_program_headers() {
+            let ph = ph?;
+            if ph.p_type() != ProgramHeader::PT_NOTE {
+                continue;
+            }
+            let reader =
+                NoteReader::new(ph.p_offset(), ph.p_offset().saturating_add(ph.p_filesz()));
+            if let Some(note) = self.find_build_id_note(reader)? {
+                return note.descriptor(self, buffer).map(Some);
+            }
+        }
+
+        for sh in self.iter_sections_of_type(SectionHeader::SHT_NOTE) {
+            let sh = sh?;
+            let reader =
+                NoteReader::new(sh.sh_offset(), sh.sh_offset().saturating_add(sh.sh_size()));
+            if let Some(note) = self.find_build_id_note(reader)? {
+                return note.descriptor(self, buffer).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Look for the build ID note amongst the notes in the given reader.
+    fn find_build_id_note(&self, mut reader: NoteReader) -> Result<Option<Note>, Error<DS::Error>> {
+        while let Some(note) = reader.next_note(self) {
+            let note = note?;
+            if note.n_type() == Note::NT_GNU_BUILD_ID && note.name_eq(self, "GNU")? {
+                return Ok(Some(note));
+            }
+        }
+        Ok(None)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Add `len`, rounded up to a multiple of four, to `offset`.
+fn align4(offset: u32, len: u32) -> Option<u32> {
+    offset.checked_add(len.checked_next_multiple_of(4)?)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
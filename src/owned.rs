@@ -0,0 +1,95 @@
+//! Helpers which return heap-allocated data.
+//!
+//! These are only available with the `alloc` feature, and save you from
+//! working out how big a buffer you need.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use alloc::{string::String, vec, vec::Vec};
+use core::ops::Range;
+
+use crate::{Error, Loader, ProgramHeader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Read the contents of a section into a `Vec`.
+    ///
+    /// `SHT_NOBITS` sections (like `.bss`) have no contents in the file, so
+    /// you get an empty `Vec`. Compressed sections give
+    /// `Error::CompressedSection`.
+    ///
+    /// If the source knows how big it is, a section which runs past the end
+    /// of it gives `Error::DataOutOfBounds`, before anything is allocated.
+    pub fn section_bytes_vec(&self, sh: &SectionHeader) -> Result<Vec<u8>, Error<DS::Error>> {
+        if sh.is_compressed() {
+            return Err(Error::CompressedSection);
+        }
+        if !sh.occupies_file_space() {
+            return Ok(Vec::new());
+        }
+        self.read_vec(sh.file_range())
+    }
+
+    /// Read the contents of a segment from the file into a `Vec`.
+    ///
+    /// This is the `p_filesz` bytes held in the file. If `p_memsz` is larger,
+    /// the rest of the segment is zero when loaded, but isn't included here.
+    ///
+    /// If the source knows how big it is, a segment which runs past the end
+    /// of it gives `Error::DataOutOfBounds`, before anything is allocated.
+    pub fn segment_bytes_vec(&self, ph: &ProgramHeader) -> Result<Vec<u8>, Error<DS::Error>> {
+        self.read_vec(ph.file_range())
+    }
+
+    /// Read a range of bytes into a `Vec`.
+    ///
+    /// `None` is for a range which wrapped.
+    fn read_vec(&self, range: Option<Range<u32>>) -> Result<Vec<u8>, Error<DS::Error>> {
+        let range = range.ok_or(Error::DataOutOfBounds)?;
+        if self.data_source.len().is_some_and(|len| range.end > len) {
+            return Err(Error::DataOutOfBounds);
+        }
+        let mut buffer = vec![0u8; range.len()];
+        self.data_source.read(range.start, &mut buffer)?;
+        Ok(buffer)
+    }
+}
+
+impl SectionHeader {
+    /// Get the name of this section, as a `String`.
+    pub fn sh_name_string<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+    ) -> Result<String, Error<DS::Error>> {
+        let mut buffer = vec![0u8; self.sh_name_len(loader)? + 1];
+        self.sh_name(loader, &mut buffer).map(String::from)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
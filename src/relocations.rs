@@ -0,0 +1,797 @@
+//! Code and types for handling relocations.
+//!
+//! These live in `SHT_REL` and `SHT_RELA` sections, and say which words in
+//! the file need fixing up when it is loaded somewhere other than where it was
+//! linked.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::iter::FusedIterator;
+
+use crate::{symbols::SymbolSection, Error, Loader, SectionHeader, Source, Symbol};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The longest symbol name (including the null terminator) we can pass to a
+/// relocation resolver.
+pub const MAX_SYMBOL_NAME_LEN: usize = 256;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// A relocation entry without an addend, from an `SHT_REL` section.
+///
+/// The addend is whatever is already stored at the location being relocated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rel {
+    r_offset: u32,
+    r_type: u8,
+    r_sym: u32,
+}
+
+impl Rel {
+    /// Size of a relocation entry without an addend
+    pub const SIZE_IN_BYTES: u16 = 0x08;
+
+    /// Get the `r_offset` field.
+    ///
+    /// In an executable this is the address to relocate. In a relocatable
+    /// object it is the offset within the section being relocated.
+    pub fn r_offset(&self) -> u32 {
+        self.r_offset
+    }
+
+    /// Get the relocation type, from the bottom 8 bits of `r_info`.
+    pub fn r_type(&self) -> u8 {
+        self.r_type
+    }
+
+    /// Get the symbol table index, from the top 24 bits of `r_info`.
+    pub fn r_sym(&self) -> u32 {
+        self.r_sym
+    }
+}
+
+/// A relocation entry with an addend, from an `SHT_RELA` section.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rela {
+    r_offset: u32,
+    r_type: u8,
+    r_sym: u32,
+    r_addend: i32,
+}
+
+impl Rela {
+    /// Size of a relocation entry with an addend
+    pub const SIZE_IN_BYTES: u16 = 0x0C;
+
+    /// Get the `r_offset` field.
+    ///
+    /// In an executable this is the address to relocate. In a relocatable
+    /// object it is the offset within the section being relocated.
+    pub fn r_offset(&self) -> u32 {
+        self.r_offset
+    }
+
+    /// Get the relocation type, from the bottom 8 bits of `r_info`.
+    pub fn r_type(&self) -> u8 {
+        self.r_type
+    }
+
+    /// Get the symbol table index, from the top 24 bits of `r_info`.
+    pub fn r_sym(&self) -> u32 {
+        self.r_sym
+    }
+
+    /// Get the `r_addend` field.
+    pub fn r_addend(&self) -> i32 {
+        self.r_addend
+    }
+}
+
+/// A relocation entry, of either kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Relocation {
+    /// From an `SHT_REL` section
+    Rel(Rel),
+    /// From an `SHT_RELA` section
+    Rela(Rela),
+}
+
+impl Relocation {
+    /// No relocation
+    pub const R_ARM_NONE: u8 = 0;
+    /// Deprecated ARM `B`/`BL` branch
+    pub const R_ARM_PC24: u8 = 1;
+    /// Direct 32-bit: `S + A`
+    pub const R_ARM_ABS32: u8 = 2;
+    /// PC-relative 32-bit: `S + A - P`
+    pub const R_ARM_REL32: u8 = 3;
+    /// Direct 16-bit
+    pub const R_ARM_ABS16: u8 = 5;
+    /// Direct 12-bit
+    pub const R_ARM_ABS12: u8 = 6;
+    /// Direct 5-bit Thumb
+    pub const R_ARM_THM_ABS5: u8 = 7;
+    /// Direct 8-bit
+    pub const R_ARM_ABS8: u8 = 8;
+    /// Static-base relative 32-bit
+    pub const R_ARM_SBREL32: u8 = 9;
+    /// Thumb `BL`
+    pub const R_ARM_THM_CALL: u8 = 10;
+    /// Thumb PC-relative 8-bit
+    pub const R_ARM_THM_PC8: u8 = 11;
+    /// Copy the symbol's data at load time
+    pub const R_ARM_COPY: u8 = 20;
+    /// Set a GOT entry to a symbol address: `S`
+    pub const R_ARM_GLOB_DAT: u8 = 21;
+    /// Set a PLT GOT entry to a symbol address: `S`
+    pub const R_ARM_JUMP_SLOT: u8 = 22;
+    /// Adjust by the load address: `B + A`
+    pub const R_ARM_RELATIVE: u8 = 23;
+    /// Offset from the GOT: `S + A - GOT_ORG`
+    pub const R_ARM_GOTOFF32: u8 = 24;
+    /// PC-relative offset to the GOT
+    pub const R_ARM_BASE_PREL: u8 = 25;
+    /// Offset of a GOT entry from the GOT
+    pub const R_ARM_GOT_BREL: u8 = 26;
+    /// Deprecated ARM `BL` via the PLT
+    pub const R_ARM_PLT32: u8 = 27;
+    /// ARM `BL`/`BLX`
+    pub const R_ARM_CALL: u8 = 28;
+    /// ARM `B`
+    pub const R_ARM_JUMP24: u8 = 29;
+    /// Thumb `B.W`
+    pub const R_ARM_THM_JUMP24: u8 = 30;
+    /// Address of the GOT
+    pub const R_ARM_BASE_ABS: u8 = 31;
+    /// Static constructor pointer, treated like `R_ARM_ABS32`
+    pub const R_ARM_TARGET1: u8 = 38;
+    /// Marks a `BX` instruction for ARMv4 interworking
+    pub const R_ARM_V4BX: u8 = 40;
+    /// Exception table reference, platform-specific
+    pub const R_ARM_TARGET2: u8 = 41;
+    /// 31-bit PC-relative, for exception tables
+    pub const R_ARM_PREL31: u8 = 42;
+    /// ARM `MOVW` of the bottom half of an address
+    pub const R_ARM_MOVW_ABS_NC: u8 = 43;
+    /// ARM `MOVT` of the top half of an address
+    pub const R_ARM_MOVT_ABS: u8 = 44;
+    /// ARM PC-relative `MOVW`
+    pub const R_ARM_MOVW_PREL_NC: u8 = 45;
+    /// ARM PC-relative `MOVT`
+    pub const R_ARM_MOVT_PREL: u8 = 46;
+    /// Thumb `MOVW` of the bottom half of an address
+    pub const R_ARM_THM_MOVW_ABS_NC: u8 = 47;
+    /// Thumb `MOVT` of the top half of an address
+    pub const R_ARM_THM_MOVT_ABS: u8 = 48;
+    /// Thumb 16-bit `B`
+    pub const R_ARM_THM_JUMP11: u8 = 102;
+    /// Thumb 16-bit conditional `B`
+    pub const R_ARM_THM_JUMP8: u8 = 103;
+
+    /// Get the `r_offset` field.
+    pub fn r_offset(&self) -> u32 {
+        match self {
+            Relocation::Rel(r) => r.r_offset,
+            Relocation::Rela(r) => r.r_offset,
+        }
+    }
+
+    /// Get the relocation type, from the bottom 8 bits of `r_info`.
+    pub fn r_type(&self) -> u8 {
+        match self {
+            Relocation::Rel(r) => r.r_type,
+            Relocation::Rela(r) => r.r_type,
+        }
+    }
+
+    /// Get the symbol table index, from the top 24 bits of `r_info`.
+    pub fn r_sym(&self) -> u32 {
+        match self {
+            Relocation::Rel(r) => r.r_sym,
+            Relocation::Rela(r) => r.r_sym,
+        }
+    }
+
+    /// Get the explicit addend, if this entry has one.
+    pub fn r_addend(&self) -> Option<i32> {
+        match self {
+            Relocation::Rel(_) => None,
+            Relocation::Rela(r) => Some(r.r_addend),
+        }
+    }
+
+    /// Get the relocation type, if it is one we know about.
+    pub fn relocation_type(&self) -> Option<RelocationType> {
+        RelocationType::from_u8(self.r_type())
+    }
+}
+
+/// The ARM relocation types.
+///
+/// See the *ELF for the Arm Architecture* specification for the details of
+/// each one.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum RelocationType {
+    /// `R_ARM_NONE`
+    None = Relocation::R_ARM_NONE,
+    /// `R_ARM_PC24`
+    Pc24 = Relocation::R_ARM_PC24,
+    /// `R_ARM_ABS32`
+    Abs32 = Relocation::R_ARM_ABS32,
+    /// `R_ARM_REL32`
+    Rel32 = Relocation::R_ARM_REL32,
+    /// `R_ARM_ABS16`
+    Abs16 = Relocation::R_ARM_ABS16,
+    /// `R_ARM_ABS12`
+    Abs12 = Relocation::R_ARM_ABS12,
+    /// `R_ARM_THM_ABS5`
+    ThmAbs5 = Relocation::R_ARM_THM_ABS5,
+    /// `R_ARM_ABS8`
+    Abs8 = Relocation::R_ARM_ABS8,
+    /// `R_ARM_SBREL32`
+    Sbrel32 = Relocation::R_ARM_SBREL32,
+    /// `R_ARM_THM_CALL`
+    ThmCall = Relocation::R_ARM_THM_CALL,
+    /// `R_ARM_THM_PC8`
+    ThmPc8 = Relocation::R_ARM_THM_PC8,
+    /// `R_ARM_COPY`
+    Copy = Relocation::R_ARM_COPY,
+    /// `R_ARM_GLOB_DAT`
+    GlobDat = Relocation::R_ARM_GLOB_DAT,
+    /// `R_ARM_JUMP_SLOT`
+    JumpSlot = Relocation::R_ARM_JUMP_SLOT,
+    /// `R_ARM_RELATIVE`
+    Relative = Relocation::R_ARM_RELATIVE,
+    /// `R_ARM_GOTOFF32`
+    Gotoff32 = Relocation::R_ARM_GOTOFF32,
+    /// `R_ARM_BASE_PREL`
+    BasePrel = Relocation::R_ARM_BASE_PREL,
+    /// `R_ARM_GOT_BREL`
+    GotBrel = Relocation::R_ARM_GOT_BREL,
+    /// `R_ARM_PLT32`
+    Plt32 = Relocation::R_ARM_PLT32,
+    /// `R_ARM_CALL`
+    Call = Relocation::R_ARM_CALL,
+    /// `R_ARM_JUMP24`
+    Jump24 = Relocation::R_ARM_JUMP24,
+    /// `R_ARM_THM_JUMP24`
+    ThmJump24 = Relocation::R_ARM_THM_JUMP24,
+    /// `R_ARM_BASE_ABS`
+    BaseAbs = Relocation::R_ARM_BASE_ABS,
+    /// `R_ARM_TARGET1`
+    Target1 = Relocation::R_ARM_TARGET1,
+    /// `R_ARM_V4BX`
+    V4bx = Relocation::R_ARM_V4BX,
+    /// `R_ARM_TARGET2`
+    Target2 = Relocation::R_ARM_TARGET2,
+    /// `R_ARM_PREL31`
+    Prel31 = Relocation::R_ARM_PREL31,
+    /// `R_ARM_MOVW_ABS_NC`
+    MovwAbsNc = Relocation::R_ARM_MOVW_ABS_NC,
+    /// `R_ARM_MOVT_ABS`
+    MovtAbs = Relocation::R_ARM_MOVT_ABS,
+    /// `R_ARM_MOVW_PREL_NC`
+    MovwPrelNc = Relocation::R_ARM_MOVW_PREL_NC,
+    /// `R_ARM_MOVT_PREL`
+    MovtPrel = Relocation::R_ARM_MOVT_PREL,
+    /// `R_ARM_THM_MOVW_ABS_NC`
+    ThmMovwAbsNc = Relocation::R_ARM_THM_MOVW_ABS_NC,
+    /// `R_ARM_THM_MOVT_ABS`
+    ThmMovtAbs = Relocation::R_ARM_THM_MOVT_ABS,
+    /// `R_ARM_THM_JUMP11`
+    ThmJump11 = Relocation::R_ARM_THM_JUMP11,
+    /// `R_ARM_THM_JUMP8`
+    ThmJump8 = Relocation::R_ARM_THM_JUMP8,
+}
+
+impl RelocationType {
+    /// Convert a raw relocation type, if it is one we know about.
+    pub fn from_u8(value: u8) -> Option<RelocationType> {
+        match value {
+            Relocation::R_ARM_NONE => Some(RelocationType::None),
+            Relocation::R_ARM_PC24 => Some(RelocationType::Pc24),
+            Relocation::R_ARM_ABS32 => Some(RelocationType::Abs32),
+            Relocation::R_ARM_REL32 => Some(RelocationType::Rel32),
+            Relocation::R_ARM_ABS16 => Some(RelocationType::Abs16),
+            Relocation::R_ARM_ABS12 => Some(RelocationType::Abs12),
+            Relocation::R_ARM_THM_ABS5 => Some(RelocationType::ThmAbs5),
+            Relocation::R_ARM_ABS8 => Some(RelocationType::Abs8),
+            Relocation::R_ARM_SBREL32 => Some(RelocationType::Sbrel32),
+            Relocation::R_ARM_THM_CALL => Some(RelocationType::ThmCall),
+            Relocation::R_ARM_THM_PC8 => Some(RelocationType::ThmPc8),
+            Relocation::R_ARM_COPY => Some(RelocationType::Copy),
+            Relocation::R_ARM_GLOB_DAT => Some(RelocationType::GlobDat),
+            Relocation::R_ARM_JUMP_SLOT => Some(RelocationType::JumpSlot),
+            Relocation::R_ARM_RELATIVE => Some(RelocationType::Relative),
+            Relocation::R_ARM_GOTOFF32 => Some(RelocationType::Gotoff32),
+            Relocation::R_ARM_BASE_PREL => Some(RelocationType::BasePrel),
+            Relocation::R_ARM_GOT_BREL => Some(RelocationType::GotBrel),
+            Relocation::R_ARM_PLT32 => Some(RelocationType::Plt32),
+            Relocation::R_ARM_CALL => Some(RelocationType::Call),
+            Relocation::R_ARM_JUMP24 => Some(RelocationType::Jump24),
+            Relocation::R_ARM_THM_JUMP24 => Some(RelocationType::ThmJump24),
+            Relocation::R_ARM_BASE_ABS => Some(RelocationType::BaseAbs),
+            Relocation::R_ARM_TARGET1 => Some(RelocationType::Target1),
+            Relocation::R_ARM_V4BX => Some(RelocationType::V4bx),
+            Relocation::R_ARM_TARGET2 => Some(RelocationType::Target2),
+            Relocation::R_ARM_PREL31 => Some(RelocationType::Prel31),
+            Relocation::R_ARM_MOVW_ABS_NC => Some(RelocationType::MovwAbsNc),
+            Relocation::R_ARM_MOVT_ABS => Some(RelocationType::MovtAbs),
+            Relocation::R_ARM_MOVW_PREL_NC => Some(RelocationType::MovwPrelNc),
+            Relocation::R_ARM_MOVT_PREL => Some(RelocationType::MovtPrel),
+            Relocation::R_ARM_THM_MOVW_ABS_NC => Some(RelocationType::ThmMovwAbsNc),
+            Relocation::R_ARM_THM_MOVT_ABS => Some(RelocationType::ThmMovtAbs),
+            Relocation::R_ARM_THM_JUMP11 => Some(RelocationType::ThmJump11),
+            Relocation::R_ARM_THM_JUMP8 => Some(RelocationType::ThmJump8),
+            _ => None,
+        }
+    }
+
+    /// Get the raw relocation type.
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    /// Get the symbolic name, e.g. `R_ARM_ABS32`.
+    pub fn name(self) -> &'static str {
+        match self {
+            RelocationType::None => "R_ARM_NONE",
+            RelocationType::Pc24 => "R_ARM_PC24",
+            RelocationType::Abs32 => "R_ARM_ABS32",
+            RelocationType::Rel32 => "R_ARM_REL32",
+            RelocationType::Abs16 => "R_ARM_ABS16",
+            RelocationType::Abs12 => "R_ARM_ABS12",
+            RelocationType::ThmAbs5 => "R_ARM_THM_ABS5",
+            RelocationType::Abs8 => "R_ARM_ABS8",
+            RelocationType::Sbrel32 => "R_ARM_SBREL32",
+            RelocationType::ThmCall => "R_ARM_THM_CALL",
+            RelocationType::ThmPc8 => "R_ARM_THM_PC8",
+            RelocationType::Copy => "R_ARM_COPY",
+            RelocationType::GlobDat => "R_ARM_GLOB_DAT",
+            RelocationType::JumpSlot => "R_ARM_JUMP_SLOT",
+            RelocationType::Relative => "R_ARM_RELATIVE",
+            RelocationType::Gotoff32 => "R_ARM_GOTOFF32",
+            RelocationType::BasePrel => "R_ARM_BASE_PREL",
+            RelocationType::GotBrel => "R_ARM_GOT_BREL",
+            RelocationType::Plt32 => "R_ARM_PLT32",
+            RelocationType::Call => "R_ARM_CALL",
+            RelocationType::Jump24 => "R_ARM_JUMP24",
+            RelocationType::ThmJump24 => "R_ARM_THM_JUMP24",
+            RelocationType::BaseAbs => "R_ARM_BASE_ABS",
+            RelocationType::Target1 => "R_ARM_TARGET1",
+            RelocationType::V4bx => "R_ARM_V4BX",
+            RelocationType::Target2 => "R_ARM_TARGET2",
+            RelocationType::Prel31 => "R_ARM_PREL31",
+            RelocationType::MovwAbsNc => "R_ARM_MOVW_ABS_NC",
+            RelocationType::MovtAbs => "R_ARM_MOVT_ABS",
+            RelocationType::MovwPrelNc => "R_ARM_MOVW_PREL_NC",
+            RelocationType::MovtPrel => "R_ARM_MOVT_PREL",
+            RelocationType::ThmMovwAbsNc => "R_ARM_THM_MOVW_ABS_NC",
+            RelocationType::ThmMovtAbs => "R_ARM_THM_MOVT_ABS",
+            RelocationType::ThmJump11 => "R_ARM_THM_JUMP11",
+            RelocationType::ThmJump8 => "R_ARM_THM_JUMP8",
+        }
+    }
+
+    /// Can the loader apply this kind of relocation?
+    ///
+    /// Only the word-sized, absolute kinds you find in an executable or plugin
+    /// are supported. Branch and instruction-encoding relocations have to be
+    /// dealt with by the linker.
+    pub fn is_supported_by_loader(self) -> bool {
+        matches!(
+            self,
+            RelocationType::None
+                | RelocationType::Abs32
+                | RelocationType::Rel32
+                | RelocationType::GlobDat
+                | RelocationType::JumpSlot
+                | RelocationType::Relative
+                | RelocationType::Target1
+        )
+    }
+}
+
+impl core::fmt::Display for RelocationType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A symbol name, truncated to fit in a fixed-size buffer.
+///
+/// Used to report which symbol a relocation failed on, without needing an
+/// allocator.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ShortName {
+    bytes: [u8; ShortName::CAPACITY],
+    len: u8,
+}
+
+impl ShortName {
+    /// The most bytes of a name we keep.
+    pub const CAPACITY: usize = 32;
+
+    /// Make a short name, truncating at a character boundary if required.
+    pub fn new(name: &str) -> ShortName {
+        let mut len = name.len().min(Self::CAPACITY);
+        while !name.is_char_boundary(len) {
+            len -= 1;
+        }
+        let mut bytes = [0u8; Self::CAPACITY];
+        bytes[0..len].copy_from_slice(&name.as_bytes()[0..len]);
+        ShortName {
+            bytes,
+            len: len as u8,
+        }
+    }
+
+    /// Get the (possibly truncated) name.
+    pub fn as_str(&self) -> &str {
+        // We only ever store whole characters
+        core::str::from_utf8(&self.bytes[0..usize::from(self.len)]).unwrap_or("")
+    }
+}
+
+impl core::fmt::Debug for ShortName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl core::fmt::Display for ShortName {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The ways applying relocations can fail
+#[derive(Debug, Clone)]
+pub enum RelocError<E>
+where
+    E: core::fmt::Debug,
+{
+    /// We couldn't read or parse the file
+    Loader(Error<E>),
+    /// The resolver didn't know the address of this undefined symbol
+    UnresolvedSymbol(ShortName),
+    /// We don't know how to apply this kind of relocation
+    UnsupportedType {
+        /// The raw relocation type
+        r_type: u8,
+        /// The symbol it was against, if any
+        symbol: Option<ShortName>,
+    },
+    /// The relocation points outside of the image
+    OutOfBounds {
+        /// The `r_offset` field of the relocation
+        r_offset: u32,
+    },
+}
+
+impl<E> From<Error<E>> for RelocError<E>
+where
+    E: core::fmt::Debug,
+{
+    fn from(value: Error<E>) -> RelocError<E> {
+        RelocError::Loader(value)
+    }
+}
+
+/// Allows you to iterate through the entries in a relocation section.
+///
+/// Created with `section_header.iter_relocations()`.
+pub struct IterRelocations<'a, DS> {
+    parent: &'a Loader<DS>,
+    with_addend: bool,
+    next_offset: u32,
+    end_offset: u32,
+    target_section: u32,
+    symbol_table: u32,
+}
+
+impl<'a, DS> IterRelocations<'a, DS> {
+    /// The index of the section these relocations apply to (from `sh_info`).
+    pub fn target_section(&self) -> u32 {
+        self.target_section
+    }
+
+    /// The index of the symbol table the entries refer to (from `sh_link`).
+    pub fn symbol_table(&self) -> u32 {
+        self.symbol_table
+    }
+
+    fn entry_size(&self) -> u32 {
+        if self.with_addend {
+            u32::from(Rela::SIZE_IN_BYTES)
+        } else {
+            u32::from(Rel::SIZE_IN_BYTES)
+        }
+    }
+}
+
+impl<'a, DS> Iterator for IterRelocations<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<Relocation, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry_size = self.entry_size();
+        if self.end_offset.saturating_sub(self.next_offset) < entry_size {
+            return None;
+        }
+
+        let offset = self.next_offset;
+        self.next_offset += entry_size;
+
+        let source = &self.parent.data_source;
+        let result = source.read_u32_le(offset).and_then(|r_offset| {
+            let r_info = source.read_u32_le(offset + 0x04)?;
+            let r_type = (r_info & 0xFF) as u8;
+            let r_sym = r_info >> 8;
+            if self.with_addend {
+                let r_addend = source.read_u32_le(offset + 0x08)? as i32;
+                Ok(Relocation::Rela(Rela {
+                    r_offset,
+                    r_type,
+                    r_sym,
+                    r_addend,
+                }))
+            } else {
+                Ok(Relocation::Rel(Rel {
+                    r_offset,
+                    r_type,
+                    r_sym,
+                }))
+            }
+        });
+
+        if result.is_err() {
+            // Don't keep reading a table we can't read
+            self.next_offset = self.end_offset;
+        }
+        Some(result.map_err(Error::Source))
+    }
+}
+
+impl<'a, DS> FusedIterator for IterRelocations<'a, DS> where DS: Source {}
+
+impl SectionHeader {
+    /// Create an iterator through the entries in this relocation section.
+    ///
+    /// The section must be an `SHT_REL` or `SHT_RELA` section, and its
+    /// `sh_entsize` must match the size of that kind of entry.
+    pub fn iter_relocations<'a, DS: Source>(
+        &self,
+        loader: &'a Loader<DS>,
+    ) -> Result<IterRelocations<'a, DS>, Error<DS::Error>> {
+        let (with_addend, entry_size) = match self.sh_type() {
+            SectionHeader::SHT_REL => (false, Rel::SIZE_IN_BYTES),
+            SectionHeader::SHT_RELA => (true, Rela::SIZE_IN_BYTES),
+            _ => return Err(Error::WrongSectionType),
+        };
+        if self.sh_entsize() != u32::from(entry_size) {
+            return Err(Error::BadRelocationSection);
+        }
+        Ok(IterRelocations {
+            parent: loader,
+            with_addend,
+            next_offset: self.sh_offset(),
+            end_offset: self.sh_offset().saturating_add(self.sh_size()),
+            target_section: self.sh_info(),
+            symbol_table: self.sh_link(),
+        })
+    }
+}
+
+/// The symbol table a relocation section uses.
+struct SymbolTable {
+    symtab: SectionHeader,
+    /// The `SHT_SYMTAB_SHNDX` section for `symtab`, if it has one
+    shndx: Option<SectionHeader>,
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Apply the relocations in the `SHT_REL` and `SHT_RELA` sections to an
+    /// image of this file which has been copied into memory.
+    ///
+    /// The `image` holds the loadable part of the file, as linked to run at
+    /// `image_base`, but which is going to run at `load_base` instead. The
+    /// `resolve` function is called with the name of each undefined symbol
+    /// (e.g. something the OS exports), and should return its address.
+    ///
+    /// Returns the number of relocations applied. Object files (`ET_REL`)
+    /// can't be relocated this way, as their relocations are relative to
+    /// each section, rather than to the image.
+    pub fn apply_relocations<F>(
+        &self,
+        image: &mut [u8],
+        image_base: u32,
+        load_base: u32,
+        mut resolve: F,
+    ) -> Result<u32, RelocError<DS::Error>>
+    where
+        F: FnMut(&str) -> Option<u32>,
+    {
+        if self.is_object() {
+            return Err(RelocError::Loader(Error::WrongElfFile));
+        }
+        let mut count = 0;
+        for sh in self.iter_section_headers() {
+            let sh = sh?;
+            if sh.sh_type() != SectionHeader::SHT_REL && sh.sh_type() != SectionHeader::SHT_RELA {
+                continue;
+            }
+            let symbols = match sh.linked_section(self)? {
+                Some(symtab) => Some(SymbolTable {
+                    shndx: self.symtab_shndx(sh.sh_link())?,
+                    symtab,
+                }),
+                None => None,
+            };
+            for reloc in sh.iter_relocations(self)? {
+                let reloc = reloc?;
+                if self.apply_relocation(
+                    &reloc,
+                    symbols.as_ref(),
+                    image,
+                    image_base,
+                    load_base,
+                    &mut resolve,
+                )? {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Apply one relocation to the image.
+    ///
+    /// Returns `false` if there was nothing to do.
+    fn apply_relocation<F>(
+        &self,
+        reloc: &Relocation,
+        symbols: Option<&SymbolTable>,
+        image: &mut [u8],
+        image_base: u32,
+        load_base: u32,
+        resolve: &mut F,
+    ) -> Result<bool, RelocError<DS::Error>>
+    where
+        F: FnMut(&str) -> Option<u32>,
+    {
+        let mut name_buffer = [0u8; MAX_SYMBOL_NAME_LEN];
+
+        let ty = match reloc.relocation_type() {
+            Some(RelocationType::None) | Some(RelocationType::V4bx) => return Ok(false),
+            Some(ty) if ty.is_supported_by_loader() => ty,
+            _ => {
+                let symbol = match self.relocation_symbol(reloc, symbols)? {
+                    Some(symbol) => Some(ShortName::new(self.relocation_symbol_name(
+                        &symbol,
+                        symbols,
+                        &mut name_buffer,
+                    )?)),
+                    None => None,
+                };
+                return Err(RelocError::UnsupportedType {
+                    r_type: reloc.r_type(),
+                    symbol,
+                });
+            }
+        };
+
+        let place = reloc.r_offset().wrapping_sub(image_base) as usize;
+        let Some(word) = image.get_mut(place..place.wrapping_add(4)) else {
+            return Err(RelocError::OutOfBounds {
+                r_offset: reloc.r_offset(),
+            });
+        };
+        let addend = match reloc.r_addend() {
+            Some(addend) => addend as u32,
+            None => u32::from_le_bytes([word[0], word[1], word[2], word[3]]),
+        };
+        let delta = load_base.wrapping_sub(image_base);
+
+        let symbol_value = match self.relocation_symbol(reloc, symbols)? {
+            None => 0,
+            Some(symbol) => match symbol.section() {
+                SymbolSection::Undefined => {
+                    // Must be provided by the OS
+                    let name = self.relocation_symbol_name(&symbol, symbols, &mut name_buffer)?;
+                    resolve(name)
+                        .ok_or_else(|| RelocError::UnresolvedSymbol(ShortName::new(name)))?
+                }
+                // Absolute symbols don't move
+                SymbolSection::Absolute => symbol.st_value(),
+                _ => symbol.st_value().wrapping_add(delta),
+            },
+        };
+
+        let value = match ty {
+            RelocationType::Abs32 | RelocationType::Target1 => symbol_value.wrapping_add(addend),
+            RelocationType::Rel32 => {
+                let place_address = load_base.wrapping_add(place as u32);
+                symbol_value
+                    .wrapping_add(addend)
+                    .wrapping_sub(place_address)
+            }
+            RelocationType::GlobDat | RelocationType::JumpSlot => symbol_value,
+            RelocationType::Relative => addend.wrapping_add(delta),
+            _ => unreachable!("checked by is_supported_by_loader"),
+        };
+        word.copy_from_slice(&value.to_le_bytes());
+        Ok(true)
+    }
+
+    /// Get the symbol a relocation refers to, if any.
+    fn relocation_symbol(
+        &self,
+        reloc: &Relocation,
+        symbols: Option<&SymbolTable>,
+    ) -> Result<Option<Symbol>, Error<DS::Error>> {
+        if reloc.r_sym() == 0 {
+            return Ok(None);
+        }
+        let Some(symbols) = symbols else {
+            return Err(Error::BadRelocationSection);
+        };
+        if reloc.r_sym() >= symbols.symtab.sh_size() / u32::from(Symbol::SIZE_IN_BYTES) {
+            return Err(Error::BadRelocationSection);
+        }
+        Ok(Some(Symbol::new(
+            self,
+            &symbols.symtab,
+            reloc.r_sym(),
+            symbols.shndx.as_ref(),
+        )?))
+    }
+
+    /// Get the name of a symbol a relocation refers to.
+    fn relocation_symbol_name<'a>(
+        &self,
+        symbol: &Symbol,
+        symbols: Option<&SymbolTable>,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a str, Error<DS::Error>> {
+        let Some(symbols) = symbols else {
+            return Err(Error::BadRelocationSection);
+        };
+        let strtab = self.string_table_for(&symbols.symtab)?;
+        let end = strtab.sh_offset().saturating_add(strtab.sh_size());
+        self.read_str_in(
+            strtab.sh_offset().saturating_add(symbol.st_name_offset()),
+            end,
+            buffer,
+        )
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -6,7 +6,11 @@
 // Imports
 // ============================================================================
 
-use crate::{Error, Loader, Source};
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use crate::erased::{self, Shim};
+use crate::{checked_range, Error, IterSectionHeaders, Loader, Source};
 
 // ============================================================================
 // Constants
@@ -21,7 +25,9 @@ use crate::{Error, Loader, Source};
 // ============================================================================
 
 /// Represents a section in the section table.
-#[derive(Debug, Clone)]
+///
+/// The default value is the null section header, as found at index 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Header {
     sh_name_offset: u32,
     sh_type: u32,
@@ -69,6 +75,9 @@ impl Header {
     /// Relocation data, no addends
     pub const SHT_REL: u32 = 0x9;
 
+    /// Reserved
+    pub const SHT_SHLIB: u32 = 0x0A;
+
     /// Dynamic linker symbol table
     pub const SHT_DYNSYM: u32 = 0x0B;
 
@@ -87,54 +96,126 @@ impl Header {
     /// Extended section indicies
     pub const SHT_SYMTAB_SHNDX: u32 = 0x12;
 
+    /// Start of the OS-specific range
+    pub const SHT_LOOS: u32 = 0x6000_0000;
+
+    /// GNU-style symbol hash table
+    pub const SHT_GNU_HASH: u32 = 0x6FFF_FFF6;
+
+    /// End of the OS-specific range
+    pub const SHT_HIOS: u32 = 0x6FFF_FFFF;
+
+    /// Start of the processor-specific range
+    pub const SHT_LOPROC: u32 = 0x7000_0000;
+
+    /// ARM exception unwinding index table
+    pub const SHT_ARM_EXIDX: u32 = 0x7000_0001;
+
+    /// ARM DLL dynamic linking pre-emption map
+    pub const SHT_ARM_PREEMPTMAP: u32 = 0x7000_0002;
+
+    /// ARM build attributes
+    pub const SHT_ARM_ATTRIBUTES: u32 = 0x7000_0003;
+
+    /// ARM debug overlay information
+    pub const SHT_ARM_DEBUGOVERLAY: u32 = 0x7000_0004;
+
+    /// ARM overlay table
+    pub const SHT_ARM_OVERLAYSECTION: u32 = 0x7000_0005;
+
+    /// End of the processor-specific range
+    pub const SHT_HIPROC: u32 = 0x7FFF_FFFF;
+
+    /// Start of the application-specific range
+    pub const SHT_LOUSER: u32 = 0x8000_0000;
+
+    /// Group flag for a COMDAT group
+    pub const GRP_COMDAT: u32 = 0x1;
+
+    /// Section index for an undefined symbol
+    pub const SHN_UNDEF: u16 = 0x0000;
+
+    /// Start of the reserved section indices
+    pub const SHN_LORESERVE: u16 = 0xFF00;
+
+    /// Section index for an absolute symbol, which is not relocated
+    pub const SHN_ABS: u16 = 0xFFF1;
+
+    /// Section index for a common block symbol, which is not yet allocated
+    pub const SHN_COMMON: u16 = 0xFFF2;
+
+    /// Section index meaning the real index is in an `SHT_SYMTAB_SHNDX`
+    /// section
+    pub const SHN_XINDEX: u16 = 0xFFFF;
+
+    /// Section is writable at run-time
+    pub const SHF_WRITE: u32 = 0x001;
+
+    /// Section occupies memory at run-time
+    pub const SHF_ALLOC: u32 = 0x002;
+
+    /// Section contains executable instructions
+    pub const SHF_EXECINSTR: u32 = 0x004;
+
+    /// Section contains data which may be merged to remove duplicates
+    pub const SHF_MERGE: u32 = 0x010;
+
+    /// Section contains null-terminated strings
+    pub const SHF_STRINGS: u32 = 0x020;
+
+    /// The `sh_info` field holds a section index
+    pub const SHF_INFO_LINK: u32 = 0x040;
+
+    /// Section must stay in the same order relative to the linked section
+    pub const SHF_LINK_ORDER: u32 = 0x080;
+
+    /// Section needs OS-specific processing
+    pub const SHF_OS_NONCONFORMING: u32 = 0x100;
+
+    /// Section is a member of a section group
+    pub const SHF_GROUP: u32 = 0x200;
+
+    /// Section holds Thread-Local Storage
+    pub const SHF_TLS: u32 = 0x400;
+
+    /// Section contains compressed data
+    pub const SHF_COMPRESSED: u32 = 0x800;
+
     /// Create a new section header.
     pub fn new<DS>(loader: &Loader<DS>, idx: u16) -> Result<Self, Error<DS::Error>>
     where
         DS: Source,
     {
-        let section_table_offset = loader.e_shoff + u32::from(Self::SIZE_IN_BYTES) * u32::from(idx);
-
-        let sh_name_offset = loader.data_source.read_u32_le(section_table_offset)?;
-        let sh_type = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x04)?;
-        let sh_flags = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x08)?;
-        let sh_addr = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x0C)?;
-        let sh_offset = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x10)?;
-        let sh_size = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x14)?;
-        let sh_link = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x18)?;
-        let sh_info = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x1C)?;
-        let sh_addralign = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x20)?;
-        let sh_entsize = loader
-            .data_source
-            .read_u32_le(section_table_offset + 0x24)?;
-
-        Ok(Self {
-            sh_name_offset,
-            sh_type,
-            sh_flags,
-            sh_addr,
-            sh_offset,
-            sh_size,
-            sh_link,
-            sh_info,
-            sh_addralign,
-            sh_entsize,
-        })
+        let shim = Shim::new(&loader.data_source);
+        let result = erased::read_section_header(&shim, loader.e_shoff, loader.e_shnum, idx);
+        shim.finish(result)
+    }
+
+    /// Decode a section header from the bytes of a section header table
+    /// entry.
+    ///
+    /// `bytes` must hold a whole entry, of `SIZE_IN_BYTES` bytes.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        Self {
+            sh_name_offset: word(0x00),
+            sh_type: word(0x04),
+            sh_flags: word(0x08),
+            sh_addr: word(0x0C),
+            sh_offset: word(0x10),
+            sh_size: word(0x14),
+            sh_link: word(0x18),
+            sh_info: word(0x1C),
+            sh_addralign: word(0x20),
+            sh_entsize: word(0x24),
+        }
     }
 
     /// Return the `sh_name_offset` field    
@@ -143,30 +224,230 @@ impl Header {
     }
 
     /// Get the string name for this section.
+    ///
+    /// We never read beyond the end of the section name string table. If
+    /// the name offset is outside the table, you get
+    /// `Error::StringOffsetOutOfRange`, and if `e_shstrndx` doesn't point at
+    /// a string table, you get `Error::BadStringTable`. If the name doesn't fit in `buffer`,
+    /// you get `Error::BufferTooSmall`, which says how big it needs to be.
     pub fn sh_name<'a, DS: Source>(
         &self,
         loader: &Loader<DS>,
         buffer: &'a mut [u8],
     ) -> Result<&'a str, Error<DS::Error>> {
-        let string_section_idx = loader.e_shstrndx;
-        let string_section_header = Self::new(loader, string_section_idx)?;
-        let string_start = string_section_header.sh_offset + self.sh_name_offset;
+        let (string_start, string_end) = self.name_range(loader)?;
+        loader.read_str_in(string_start, string_end, buffer)
+    }
+
+    /// Get the length of the name of this section, in bytes.
+    ///
+    /// This doesn't include the null terminator, so a buffer for `sh_name()`
+    /// must be at least one byte longer than this. The name is read in small
+    /// chunks, so you don't need a buffer to call this.
+    pub fn sh_name_len<DS: Source>(&self, loader: &Loader<DS>) -> Result<usize, Error<DS::Error>> {
+        let (string_start, string_end) = self.name_range(loader)?;
+        loader.str_len(string_start, string_end)
+    }
 
-        for b in buffer.iter_mut() {
-            *b = 0x00;
+    /// Check whether this section is called `expected`.
+    ///
+    /// This doesn't need a buffer. The name is read in small chunks, and we
+    /// stop reading as soon as we find a difference.
+    pub fn name_eq<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+        expected: &str,
+    ) -> Result<bool, Error<DS::Error>> {
+        let (string_start, string_end) = self.name_range(loader)?;
+        let mut chunk = [0u8; 16];
+        loader.str_eq(string_start, string_end, expected.as_bytes(), &mut chunk)
+    }
+
+    /// Get the bytes of the name of this section, one at a time.
+    ///
+    /// The name is read in small chunks, so this works for names of any
+    /// length without a buffer. The iterator stops at the null terminator.
+    /// If there isn't one before the end of the section name string table,
+    /// the last item is `Error::InvalidString`.
+    pub fn name_bytes<'a, DS: Source>(&self, loader: &'a Loader<DS>) -> IterNameBytes<'a, DS> {
+        let (next_offset, end_offset, pending) = match self.name_range(loader) {
+            Ok((start, end)) => (start, end, None),
+            Err(e) => (0, 0, Some(e)),
+        };
+        IterNameBytes {
+            parent: loader,
+            chunk: [0u8; 16],
+            chunk_len: 0,
+            chunk_pos: 0,
+            next_offset,
+            end_offset,
+            pending,
+            done: false,
         }
+    }
 
-        loader.data_source.read(string_start, buffer)?;
+    /// Write the name of this section to `w`.
+    ///
+    /// Invalid UTF-8 sequences are replaced with U+FFFD, like
+    /// `String::from_utf8_lossy`. If we can't read the name, you get
+    /// `core::fmt::Error`.
+    pub fn write_name<DS: Source, W: core::fmt::Write>(
+        &self,
+        loader: &Loader<DS>,
+        w: &mut W,
+    ) -> core::fmt::Result {
+        let mut pending = [0u8; 4];
+        let mut pending_len = 0;
+        for b in self.name_bytes(loader) {
+            pending[pending_len] = b.map_err(|_| core::fmt::Error)?;
+            pending_len += 1;
+            while pending_len > 0 {
+                match core::str::from_utf8(&pending[0..pending_len]) {
+                    Ok(s) => {
+                        w.write_str(s)?;
+                        pending_len = 0;
+                    }
+                    Err(e) => {
+                        let Some(error_len) = e.error_len() else {
+                            // Wait for the rest of the character
+                            break;
+                        };
+                        let valid = e.valid_up_to();
+                        // The valid part was checked by from_utf8
+                        w.write_str(core::str::from_utf8(&pending[0..valid]).unwrap_or(""))?;
+                        w.write_char(char::REPLACEMENT_CHARACTER)?;
+                        pending.copy_within(valid + error_len..pending_len, 0);
+                        pending_len -= valid + error_len;
+                    }
+                }
+            }
+        }
+        if pending_len > 0 {
+            // The name ended part-way through a character
+            w.write_char(char::REPLACEMENT_CHARACTER)?;
+        }
+        Ok(())
+    }
 
-        // If this returns an error, our buffer doesn't have a null in it. Which means we used all the bytes.
-        let cstr =
-            core::ffi::CStr::from_bytes_until_nul(buffer).map_err(|_| Error::NotEnoughSpace)?;
+    /// Get something which displays this section header, with its name.
+    ///
+    /// Unlike `sh_name()`, long names are never truncated.
+    pub fn display<'a, DS: Source>(&'a self, loader: &'a Loader<DS>) -> DisplayHeader<'a, DS> {
+        DisplayHeader {
+            header: self,
+            parent: loader,
+        }
+    }
 
-        if let Ok(s) = cstr.to_str() {
-            Ok(s)
-        } else {
-            Err(Error::InvalidString)
+    /// Find where our name starts in the file, and where the section name
+    /// string table ends.
+    fn name_range<DS: Source>(&self, loader: &Loader<DS>) -> Result<(u32, u32), Error<DS::Error>> {
+        let (strings_start, strings_size) = loader.section_names()?;
+        if self.sh_name_offset >= strings_size {
+            return Err(Error::StringOffsetOutOfRange);
         }
+        let string_start = strings_start
+            .checked_add(self.sh_name_offset)
+            .ok_or(Error::DataOutOfBounds)?;
+        let string_end = strings_start
+            .checked_add(strings_size)
+            .ok_or(Error::DataOutOfBounds)?;
+        Ok((string_start, string_end))
+    }
+
+    /// Get the section that `sh_link` refers to, or `None` if it is zero.
+    ///
+    /// An index beyond the end of the section header table gives
+    /// `Error::SectionIndexOutOfRange`. For kinds of section where we know
+    /// what the link should be (e.g. a symbol table links to its string
+    /// table), a section of the wrong type gives `Error::WrongSectionType`.
+    pub fn linked_section<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+    ) -> Result<Option<Header>, Error<DS::Error>> {
+        Self::resolve_index(loader, self.sh_link, self.link_kind())
+    }
+
+    /// Like `linked_section`, but without checking the type of the linked
+    /// section.
+    pub fn linked_section_lenient<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+    ) -> Result<Option<Header>, Error<DS::Error>> {
+        Self::resolve_index(loader, self.sh_link, LinkKind::Any)
+    }
+
+    /// Get the section that `sh_info` refers to, or `None` if it is zero.
+    ///
+    /// This is the section a relocation section patches. For kinds of
+    /// section where `sh_info` isn't a section index at all (e.g. a symbol
+    /// table, where it counts the local symbols) you get
+    /// `Error::WrongSectionType`. An index beyond the end of the section
+    /// header table gives `Error::SectionIndexOutOfRange`.
+    pub fn info_section<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+    ) -> Result<Option<Header>, Error<DS::Error>> {
+        Self::resolve_index(loader, self.sh_info, self.info_kind())
+    }
+
+    /// Like `info_section`, but treats `sh_info` as a section index whatever
+    /// kind of section this is.
+    pub fn info_section_lenient<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+    ) -> Result<Option<Header>, Error<DS::Error>> {
+        Self::resolve_index(loader, self.sh_info, LinkKind::Any)
+    }
+
+    /// What our `sh_link` should point at.
+    fn link_kind(&self) -> LinkKind {
+        match self.sh_type {
+            Header::SHT_SYMTAB | Header::SHT_DYNSYM | Header::SHT_DYNAMIC => {
+                LinkKind::OneOf(&[Header::SHT_STRTAB])
+            }
+            Header::SHT_REL
+            | Header::SHT_RELA
+            | Header::SHT_HASH
+            | Header::SHT_GNU_HASH
+            | Header::SHT_GROUP => LinkKind::OneOf(&[Header::SHT_SYMTAB, Header::SHT_DYNSYM]),
+            Header::SHT_SYMTAB_SHNDX => LinkKind::OneOf(&[Header::SHT_SYMTAB]),
+            _ => LinkKind::Any,
+        }
+    }
+
+    /// What our `sh_info` should point at.
+    fn info_kind(&self) -> LinkKind {
+        match self.sh_type {
+            Header::SHT_REL | Header::SHT_RELA => LinkKind::Any,
+            _ if self.sh_flags & Header::SHF_INFO_LINK != 0 => LinkKind::Any,
+            Header::SHT_SYMTAB | Header::SHT_DYNSYM | Header::SHT_GROUP => LinkKind::NotASection,
+            _ => LinkKind::Any,
+        }
+    }
+
+    /// Get the section with this index, taken from a field of a header.
+    fn resolve_index<DS: Source>(
+        loader: &Loader<DS>,
+        index: u32,
+        kind: LinkKind,
+    ) -> Result<Option<Header>, Error<DS::Error>> {
+        if let LinkKind::NotASection = kind {
+            return Err(Error::WrongSectionType);
+        }
+        if index == 0 {
+            return Ok(None);
+        }
+        if index >= u32::from(loader.e_shnum) {
+            return Err(Error::SectionIndexOutOfRange { index });
+        }
+        let sh = Header::new(loader, index as u16)?;
+        if let LinkKind::OneOf(types) = kind {
+            if !types.contains(&sh.sh_type) {
+                return Err(Error::WrongSectionType);
+            }
+        }
+        Ok(Some(sh))
     }
 
     /// Return the `sh_type` field        
@@ -174,11 +455,21 @@ impl Header {
         self.sh_type
     }
 
+    /// Return the `sh_type` field, as a `SectionType`
+    pub fn section_type(&self) -> SectionType {
+        SectionType::from(self.sh_type)
+    }
+
     /// Return the `sh_flags` field        
     pub fn sh_flags(&self) -> u32 {
         self.sh_flags
     }
 
+    /// Return the `sh_flags` field, as a `SectionFlags`
+    pub fn flags(&self) -> SectionFlags {
+        SectionFlags(self.sh_flags)
+    }
+
     /// Return the `sh_addr` field        
     pub fn sh_addr(&self) -> u32 {
         self.sh_addr
@@ -213,6 +504,695 @@ impl Header {
     pub fn sh_entsize(&self) -> u32 {
         self.sh_entsize
     }
+
+    /// Does this section hold compressed data?
+    ///
+    /// Compressed sections start with a compression header - see
+    /// `compression()`.
+    pub fn is_compressed(&self) -> bool {
+        self.flags().contains(Self::SHF_COMPRESSED)
+    }
+
+    /// Is this an `SHT_NOBITS` section (like `.bss`)?
+    ///
+    /// These take up space in memory, but have no contents in the file -
+    /// their `sh_offset` and `sh_size` don't describe any bytes in the file.
+    pub fn is_nobits(&self) -> bool {
+        self.sh_type == Self::SHT_NOBITS
+    }
+
+    /// Does this section have any contents in the file?
+    pub fn occupies_file_space(&self) -> bool {
+        !self.is_nobits() && self.sh_size != 0
+    }
+
+    /// Get the range of file offsets this section's contents occupy.
+    ///
+    /// This is `sh_offset..sh_offset + sh_size`, or an empty range at
+    /// `sh_offset` for `SHT_NOBITS` sections. Returns `None` if the range
+    /// would wrap past `0xFFFF_FFFF`.
+    pub fn file_range(&self) -> Option<Range<u32>> {
+        if self.is_nobits() {
+            return Some(self.sh_offset..self.sh_offset);
+        }
+        checked_range(self.sh_offset, self.sh_size)
+    }
+
+    /// Get the range of addresses this section occupies in memory.
+    ///
+    /// This is `sh_addr..sh_addr + sh_size`, or an empty range if `sh_addr`
+    /// is zero (as it is for sections which aren't loaded). Returns `None` if
+    /// the range would wrap past `0xFFFF_FFFF`.
+    pub fn addr_range(&self) -> Option<Range<u32>> {
+        if self.sh_addr == 0 {
+            return Some(0..0);
+        }
+        checked_range(self.sh_addr, self.sh_size)
+    }
+
+    /// Is this address within the section?
+    ///
+    /// Always false if the section has no address, or its range wraps.
+    pub fn contains_addr(&self, addr: u32) -> bool {
+        self.addr_range().is_some_and(|r| r.contains(&addr))
+    }
+
+    /// Does this section overlap `other` in memory?
+    ///
+    /// Empty sections, and those with no address, overlap nothing. A section
+    /// whose range wraps is treated as overlapping everything, as we can't
+    /// say where it ends.
+    pub fn overlaps(&self, other: &Header) -> bool {
+        match (self.addr_range(), other.addr_range()) {
+            (Some(a), Some(b)) => {
+                !a.is_empty() && !b.is_empty() && a.start < b.end && b.start < a.end
+            }
+            _ => true,
+        }
+    }
+
+    /// Get the compression header for this section.
+    ///
+    /// Returns `None` if the section isn't compressed. We don't decompress
+    /// anything - use `CompressionHeader::payload_offset` to find the
+    /// compressed bytes and decompress them yourself.
+    pub fn compression<DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+    ) -> Result<Option<CompressionHeader>, Error<DS::Error>> {
+        if !self.is_compressed() {
+            return Ok(None);
+        }
+        if self.sh_size < u32::from(CompressionHeader::SIZE_IN_BYTES) {
+            return Err(Error::BadCompressionHeader);
+        }
+        let ch_type = loader.data_source.read_u32_le(self.sh_offset)?;
+        let ch_size = loader.data_source.read_u32_le(self.sh_offset + 0x04)?;
+        let ch_addralign = loader.data_source.read_u32_le(self.sh_offset + 0x08)?;
+        Ok(Some(CompressionHeader {
+            ch_type,
+            ch_size,
+            ch_addralign,
+            payload_offset: self.sh_offset + u32::from(CompressionHeader::SIZE_IN_BYTES),
+            payload_size: self.sh_size - u32::from(CompressionHeader::SIZE_IN_BYTES),
+        }))
+    }
+
+    /// Read the contents of this section, starting `offset` bytes in.
+    ///
+    /// We read as much as fits in `buffer`, without going past the end of the
+    /// section, and return the bytes read. `SHT_NOBITS` sections have no
+    /// contents in the file, so you always get an empty slice.
+    ///
+    /// Compressed sections give `Error::CompressedSection`, because their
+    /// contents aren't what you expect - see `compression()`. Sections which
+    /// wrap past the end of the file give `Error::DataOutOfBounds`.
+    pub fn read_data<'a, DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+        offset: u32,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a [u8], Error<DS::Error>> {
+        if self.is_compressed() {
+            return Err(Error::CompressedSection);
+        }
+        let range = self.file_range().ok_or(Error::DataOutOfBounds)?;
+        let Some(start) = range.start.checked_add(offset).filter(|s| *s < range.end) else {
+            return Ok(&buffer[0..0]);
+        };
+        let len = buffer.len().min((range.end - start) as usize);
+        let buffer = &mut buffer[0..len];
+        loader.data_source.read(start, buffer)?;
+        Ok(buffer)
+    }
+}
+
+impl core::fmt::Display for Header {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:<18} addr=0x{:08x} off=0x{:06x} size=0x{:06x} flags={}",
+            self.section_type().name(),
+            self.sh_addr,
+            self.sh_offset,
+            self.sh_size,
+            self.flags()
+        )
+    }
+}
+
+/// Allows you to iterate through the bytes in a section name.
+///
+/// Created with `section_header.name_bytes()`.
+pub struct IterNameBytes<'a, DS>
+where
+    DS: Source,
+{
+    parent: &'a Loader<DS>,
+    chunk: [u8; 16],
+    chunk_len: usize,
+    chunk_pos: usize,
+    next_offset: u32,
+    end_offset: u32,
+    pending: Option<Error<DS::Error>>,
+    done: bool,
+}
+
+impl<'a, DS> Iterator for IterNameBytes<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<u8, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if let Some(e) = self.pending.take() {
+            self.done = true;
+            return Some(Err(e));
+        }
+        if self.chunk_pos == self.chunk_len {
+            if self.next_offset >= self.end_offset {
+                // The name runs off the end of the table
+                self.done = true;
+                return Some(Err(Error::InvalidString));
+            }
+            let chunk_len = self
+                .chunk
+                .len()
+                .min((self.end_offset - self.next_offset) as usize);
+            if let Err(e) = self
+                .parent
+                .data_source
+                .read(self.next_offset, &mut self.chunk[0..chunk_len])
+            {
+                self.done = true;
+                return Some(Err(Error::Source(e)));
+            }
+            self.next_offset += chunk_len as u32;
+            self.chunk_len = chunk_len;
+            self.chunk_pos = 0;
+        }
+        let b = self.chunk[self.chunk_pos];
+        self.chunk_pos += 1;
+        if b == 0 {
+            self.done = true;
+            return None;
+        }
+        Some(Ok(b))
+    }
+}
+
+/// Displays a section header, with its name.
+///
+/// Created with `section_header.display()`.
+pub struct DisplayHeader<'a, DS> {
+    header: &'a Header,
+    parent: &'a Loader<DS>,
+}
+
+impl<'a, DS> core::fmt::Display for DisplayHeader<'a, DS>
+where
+    DS: Source,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        self.header.write_name(self.parent, f)?;
+        write!(f, " {}", self.header)
+    }
+}
+
+/// The header at the start of a compressed (`SHF_COMPRESSED`) section.
+///
+/// Created with `section_header.compression()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompressionHeader {
+    ch_type: u32,
+    ch_size: u32,
+    ch_addralign: u32,
+    payload_offset: u32,
+    payload_size: u32,
+}
+
+impl CompressionHeader {
+    /// Size of an ELF32 compression header
+    pub const SIZE_IN_BYTES: u16 = 0x0C;
+
+    /// Data is compressed with zlib (deflate)
+    pub const ELFCOMPRESS_ZLIB: u32 = 1;
+
+    /// Data is compressed with Zstandard
+    pub const ELFCOMPRESS_ZSTD: u32 = 2;
+
+    /// Return the `ch_type` field
+    pub fn ch_type(&self) -> u32 {
+        self.ch_type
+    }
+
+    /// Return the `ch_size` field
+    ///
+    /// This is the size of the data once it has been decompressed.
+    pub fn ch_size(&self) -> u32 {
+        self.ch_size
+    }
+
+    /// Return the `ch_addralign` field
+    ///
+    /// This is the alignment of the data once it has been decompressed.
+    pub fn ch_addralign(&self) -> u32 {
+        self.ch_addralign
+    }
+
+    /// The offset in the file of the compressed data, just after this header.
+    pub fn payload_offset(&self) -> u32 {
+        self.payload_offset
+    }
+
+    /// The size of the compressed data, in bytes.
+    pub fn payload_size(&self) -> u32 {
+        self.payload_size
+    }
+}
+
+/// The kinds of section.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SectionType {
+    /// `SHT_NULL`
+    Null,
+    /// `SHT_PROGBITS`
+    Progbits,
+    /// `SHT_SYMTAB`
+    Symtab,
+    /// `SHT_STRTAB`
+    Strtab,
+    /// `SHT_RELA`
+    Rela,
+    /// `SHT_HASH`
+    Hash,
+    /// `SHT_DYNAMIC`
+    Dynamic,
+    /// `SHT_NOTE`
+    Note,
+    /// `SHT_NOBITS`
+    Nobits,
+    /// `SHT_REL`
+    Rel,
+    /// `SHT_SHLIB`
+    Shlib,
+    /// `SHT_DYNSYM`
+    Dynsym,
+    /// `SHT_INIT_ARRAY`
+    InitArray,
+    /// `SHT_FINI_ARRAY`
+    FiniArray,
+    /// `SHT_PREINIT_ARRAY`
+    PreinitArray,
+    /// `SHT_GROUP`
+    Group,
+    /// `SHT_SYMTAB_SHNDX`
+    SymtabShndx,
+    /// `SHT_GNU_HASH`
+    GnuHash,
+    /// `SHT_ARM_EXIDX`
+    ArmExidx,
+    /// `SHT_ARM_PREEMPTMAP`
+    ArmPreemptmap,
+    /// `SHT_ARM_ATTRIBUTES`
+    ArmAttributes,
+    /// `SHT_ARM_DEBUGOVERLAY`
+    ArmDebugoverlay,
+    /// `SHT_ARM_OVERLAYSECTION`
+    ArmOverlaysection,
+    /// Some other type in the OS-specific range
+    Os(u32),
+    /// Some other type in the processor-specific range
+    Proc(u32),
+    /// Some type in the application-specific range
+    User(u32),
+    /// A type we don't know about
+    Unknown(u32),
+}
+
+impl SectionType {
+    /// Get the raw `sh_type` value.
+    pub fn as_u32(self) -> u32 {
+        match self {
+            SectionType::Null => Header::SHT_NULL,
+            SectionType::Progbits => Header::SHT_PROGBITS,
+            SectionType::Symtab => Header::SHT_SYMTAB,
+            SectionType::Strtab => Header::SHT_STRTAB,
+            SectionType::Rela => Header::SHT_RELA,
+            SectionType::Hash => Header::SHT_HASH,
+            SectionType::Dynamic => Header::SHT_DYNAMIC,
+            SectionType::Note => Header::SHT_NOTE,
+            SectionType::Nobits => Header::SHT_NOBITS,
+            SectionType::Rel => Header::SHT_REL,
+            SectionType::Shlib => Header::SHT_SHLIB,
+            SectionType::Dynsym => Header::SHT_DYNSYM,
+            SectionType::InitArray => Header::SHT_INIT_ARRAY,
+            SectionType::FiniArray => Header::SHT_FINI_ARRAY,
+            SectionType::PreinitArray => Header::SHT_PREINIT_ARRAY,
+            SectionType::Group => Header::SHT_GROUP,
+            SectionType::SymtabShndx => Header::SHT_SYMTAB_SHNDX,
+            SectionType::GnuHash => Header::SHT_GNU_HASH,
+            SectionType::ArmExidx => Header::SHT_ARM_EXIDX,
+            SectionType::ArmPreemptmap => Header::SHT_ARM_PREEMPTMAP,
+            SectionType::ArmAttributes => Header::SHT_ARM_ATTRIBUTES,
+            SectionType::ArmDebugoverlay => Header::SHT_ARM_DEBUGOVERLAY,
+            SectionType::ArmOverlaysection => Header::SHT_ARM_OVERLAYSECTION,
+            SectionType::Os(value)
+            | SectionType::Proc(value)
+            | SectionType::User(value)
+            | SectionType::Unknown(value) => value,
+        }
+    }
+
+    /// Get the name of the type, e.g. `SHT_PROGBITS`.
+    pub fn name(self) -> &'static str {
+        match self {
+            SectionType::Null => "SHT_NULL",
+            SectionType::Progbits => "SHT_PROGBITS",
+            SectionType::Symtab => "SHT_SYMTAB",
+            SectionType::Strtab => "SHT_STRTAB",
+            SectionType::Rela => "SHT_RELA",
+            SectionType::Hash => "SHT_HASH",
+            SectionType::Dynamic => "SHT_DYNAMIC",
+            SectionType::Note => "SHT_NOTE",
+            SectionType::Nobits => "SHT_NOBITS",
+            SectionType::Rel => "SHT_REL",
+            SectionType::Shlib => "SHT_SHLIB",
+            SectionType::Dynsym => "SHT_DYNSYM",
+            SectionType::InitArray => "SHT_INIT_ARRAY",
+            SectionType::FiniArray => "SHT_FINI_ARRAY",
+            SectionType::PreinitArray => "SHT_PREINIT_ARRAY",
+            SectionType::Group => "SHT_GROUP",
+            SectionType::SymtabShndx => "SHT_SYMTAB_SHNDX",
+            SectionType::GnuHash => "SHT_GNU_HASH",
+            SectionType::ArmExidx => "SHT_ARM_EXIDX",
+            SectionType::ArmPreemptmap => "SHT_ARM_PREEMPTMAP",
+            SectionType::ArmAttributes => "SHT_ARM_ATTRIBUTES",
+            SectionType::ArmDebugoverlay => "SHT_ARM_DEBUGOVERLAY",
+            SectionType::ArmOverlaysection => "SHT_ARM_OVERLAYSECTION",
+            SectionType::Os(_) => "SHT_LOOS+",
+            SectionType::Proc(_) => "SHT_LOPROC+",
+            SectionType::User(_) => "SHT_LOUSER+",
+            SectionType::Unknown(_) => "SHT_???",
+        }
+    }
+}
+
+impl From<u32> for SectionType {
+    fn from(value: u32) -> SectionType {
+        match value {
+            Header::SHT_NULL => SectionType::Null,
+            Header::SHT_PROGBITS => SectionType::Progbits,
+            Header::SHT_SYMTAB => SectionType::Symtab,
+            Header::SHT_STRTAB => SectionType::Strtab,
+            Header::SHT_RELA => SectionType::Rela,
+            Header::SHT_HASH => SectionType::Hash,
+            Header::SHT_DYNAMIC => SectionType::Dynamic,
+            Header::SHT_NOTE => SectionType::Note,
+            Header::SHT_NOBITS => SectionType::Nobits,
+            Header::SHT_REL => SectionType::Rel,
+            Header::SHT_SHLIB => SectionType::Shlib,
+            Header::SHT_DYNSYM => SectionType::Dynsym,
+            Header::SHT_INIT_ARRAY => SectionType::InitArray,
+            Header::SHT_FINI_ARRAY => SectionType::FiniArray,
+            Header::SHT_PREINIT_ARRAY => SectionType::PreinitArray,
+            Header::SHT_GROUP => SectionType::Group,
+            Header::SHT_SYMTAB_SHNDX => SectionType::SymtabShndx,
+            Header::SHT_GNU_HASH => SectionType::GnuHash,
+            Header::SHT_ARM_EXIDX => SectionType::ArmExidx,
+            Header::SHT_ARM_PREEMPTMAP => SectionType::ArmPreemptmap,
+            Header::SHT_ARM_ATTRIBUTES => SectionType::ArmAttributes,
+            Header::SHT_ARM_DEBUGOVERLAY => SectionType::ArmDebugoverlay,
+            Header::SHT_ARM_OVERLAYSECTION => SectionType::ArmOverlaysection,
+            Header::SHT_LOOS..=Header::SHT_HIOS => SectionType::Os(value),
+            Header::SHT_LOPROC..=Header::SHT_HIPROC => SectionType::Proc(value),
+            Header::SHT_LOUSER.. => SectionType::User(value),
+            _ => SectionType::Unknown(value),
+        }
+    }
+}
+
+impl core::fmt::Display for SectionType {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The flags for a section.
+///
+/// Displays like the flags column in `readelf -S`, e.g. `AX` for code.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SectionFlags(u32);
+
+impl SectionFlags {
+    /// The letters `readelf` uses for each flag, in the order it prints them.
+    const LETTERS: [(u32, char); 11] = [
+        (Header::SHF_WRITE, 'W'),
+        (Header::SHF_ALLOC, 'A'),
+        (Header::SHF_EXECINSTR, 'X'),
+        (Header::SHF_MERGE, 'M'),
+        (Header::SHF_STRINGS, 'S'),
+        (Header::SHF_INFO_LINK, 'I'),
+        (Header::SHF_LINK_ORDER, 'L'),
+        (Header::SHF_OS_NONCONFORMING, 'O'),
+        (Header::SHF_GROUP, 'G'),
+        (Header::SHF_TLS, 'T'),
+        (Header::SHF_COMPRESSED, 'C'),
+    ];
+
+    /// Get the raw flags.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Are all the given flags set?
+    pub fn contains(self, flags: u32) -> bool {
+        self.0 & flags == flags
+    }
+
+    /// Does this section occupy memory at run-time?
+    pub fn is_alloc(self) -> bool {
+        self.contains(Header::SHF_ALLOC)
+    }
+
+    /// Is this section writable at run-time?
+    pub fn is_writable(self) -> bool {
+        self.contains(Header::SHF_WRITE)
+    }
+
+    /// Does this section contain code?
+    pub fn is_executable(self) -> bool {
+        self.contains(Header::SHF_EXECINSTR)
+    }
+
+    /// Does this section hold Thread-Local Storage?
+    pub fn is_tls(self) -> bool {
+        self.contains(Header::SHF_TLS)
+    }
+}
+
+impl core::fmt::Display for SectionFlags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use core::fmt::Write;
+        let mut known = 0;
+        for (flag, letter) in Self::LETTERS {
+            known |= flag;
+            if self.contains(flag) {
+                f.write_char(letter)?;
+            }
+        }
+        if self.0 & !known != 0 {
+            // Flags readelf would print as OS or processor specific
+            f.write_char('x')?;
+        }
+        Ok(())
+    }
+}
+
+/// What the `sh_link` or `sh_info` field of a section should refer to.
+#[derive(Debug, Copy, Clone)]
+enum LinkKind {
+    /// Any section
+    Any,
+    /// A section of one of these types
+    OneOf(&'static [u32]),
+    /// The field isn't a section index
+    NotASection,
+}
+
+/// A section header, and the name of the section.
+pub type NamedSection<'a> = (Header, &'a str);
+
+/// Allows you to iterate through the section headers, and their names.
+///
+/// Each name is read into the scratch buffer, so this can't be an
+/// `Iterator` - call `next_section()` until it returns `None`.
+///
+/// Created with `loader.iter_named_sections()`.
+pub struct IterNamedSections<'a, DS> {
+    headers: IterSectionHeaders<'a, DS>,
+    scratch: &'a mut [u8],
+    next_index: u16,
+}
+
+impl<'a, DS> IterNamedSections<'a, DS>
+where
+    DS: Source,
+{
+    /// Get the next section header, and its name.
+    ///
+    /// If a name doesn't fit in the scratch buffer, you get
+    /// `Error::BufferTooSmall`, which says how big it needs to be, and then
+    /// we carry on with the next section. `current_index()` says which
+    /// section it was.
+    pub fn next_section(&mut self) -> Option<Result<NamedSection<'_>, Error<DS::Error>>> {
+        let sh = self.headers.next()?;
+        self.next_index = self.next_index.wrapping_add(1);
+        let sh = match sh {
+            Ok(sh) => sh,
+            Err(e) => return Some(Err(e)),
+        };
+        let loader = self.headers.parent;
+        Some(sh.sh_name(loader, self.scratch).map(|name| (sh, name)))
+    }
+
+    /// The index of the section most recently returned by `next_section()`.
+    pub fn current_index(&self) -> u16 {
+        self.next_index.wrapping_sub(1)
+    }
+}
+
+/// An iterator through the sections of one type.
+///
+/// Created with `loader.iter_sections_of_type()`.
+pub struct IterSectionsOfType<'a, DS> {
+    headers: IterSectionHeaders<'a, DS>,
+    sh_type: u32,
+}
+
+impl<'a, DS> Iterator for IterSectionsOfType<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<Header, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for sh in self.headers.by_ref() {
+            match sh {
+                Ok(sh) if sh.sh_type() != self.sh_type => continue,
+                result => return Some(result),
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.headers.size_hint().1)
+    }
+}
+
+/// Allows you to iterate through a copy of the section header table, held
+/// in a buffer.
+///
+/// This does no I/O, so it gives headers rather than `Result`s.
+///
+/// Created with `loader.section_headers_buffered()`.
+#[derive(Debug, Clone)]
+pub struct BufferedSectionHeaders<'a> {
+    entries: core::slice::ChunksExact<'a, u8>,
+}
+
+impl Iterator for BufferedSectionHeaders<'_> {
+    type Item = Header;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(Header::from_bytes)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.entries.nth(n).map(Header::from_bytes)
+    }
+}
+
+impl DoubleEndedIterator for BufferedSectionHeaders<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(Header::from_bytes)
+    }
+}
+
+impl ExactSizeIterator for BufferedSectionHeaders<'_> {}
+
+impl FusedIterator for BufferedSectionHeaders<'_> {}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Create an iterator through the sections of the given type (e.g.
+    /// `SHT_NOTE`).
+    ///
+    /// Errors reading a section header are passed on, whatever the section's
+    /// type.
+    pub fn iter_sections_of_type(&self, sh_type: u32) -> IterSectionsOfType<'_, DS> {
+        IterSectionsOfType {
+            headers: self.iter_section_headers(),
+            sh_type,
+        }
+    }
+
+    /// Read the whole section header table into `buffer`, and iterate
+    /// through it.
+    ///
+    /// This is one read from the source, rather than one for each field of
+    /// each header. The table is `e_shnum * 0x28` bytes, and if `buffer` is
+    /// smaller than that you get `Error::BufferTooSmall`, which says how big
+    /// it needs to be. If you don't have the memory to spare, use
+    /// `iter_section_headers`.
+    pub fn section_headers_buffered<'a>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<BufferedSectionHeaders<'a>, Error<DS::Error>> {
+        let needed = usize::from(self.e_shnum) * usize::from(Header::SIZE_IN_BYTES);
+        let Some(buffer) = buffer.get_mut(0..needed) else {
+            return Err(Error::BufferTooSmall { needed });
+        };
+        self.data_source.read(self.e_shoff, buffer)?;
+        let buffer: &'a [u8] = buffer;
+        let entries = buffer.chunks_exact(usize::from(Header::SIZE_IN_BYTES));
+        Ok(BufferedSectionHeaders { entries })
+    }
+
+    /// Create an iterator through the section headers, which also gives you
+    /// the name of each section.
+    ///
+    /// The names are read into `scratch`, which must be big enough for the
+    /// longest name you want to see, plus its null terminator.
+    pub fn iter_named_sections<'a>(&'a self, scratch: &'a mut [u8]) -> IterNamedSections<'a, DS> {
+        IterNamedSections {
+            headers: self.iter_section_headers(),
+            scratch,
+            next_index: 0,
+        }
+    }
+
+    /// Find the first section with the given name.
+    pub fn find_section_by_name(&self, name: &str) -> Result<Option<Header>, Error<DS::Error>> {
+        if self.e_shstrndx == 0 {
+            // No section names
+            return Ok(None);
+        }
+        let strings = self.section_names()?;
+        let shim = Shim::new(&self.data_source);
+        let result = erased::find_section_by_name(&shim, self.e_shoff, self.e_shnum, strings, name);
+        shim.finish(result)
+    }
 }
 
 // ============================================================================
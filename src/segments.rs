@@ -6,12 +6,19 @@
 // Imports
 // ============================================================================
 
-use crate::{Error, Loader, Source};
+use core::iter::FusedIterator;
+use core::ops::Range;
+
+use crate::erased::{self, Shim};
+use crate::{checked_range, Error, Loader, Source};
 
 // ============================================================================
 // Constants
 // ============================================================================
 
+/// The most `PT_LOAD` segments `Loader::iter_load_segments_sorted` can sort.
+pub const MAX_SORTED_LOAD_SEGMENTS: usize = 32;
+
 // ============================================================================
 // Static Variables
 // ============================================================================
@@ -21,7 +28,7 @@ use crate::{Error, Loader, Source};
 // ============================================================================
 
 /// Represents a program header
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Header {
     p_type: u32,
     p_offset: u32,
@@ -53,35 +60,63 @@ impl Header {
     pub const PT_PHDR: u32 = 0x00000006;
     /// Thread-Local Storage template.
     pub const PT_TLS: u32 = 0x00000007;
+    /// Start of the OS-specific range.
+    pub const PT_LOOS: u32 = 0x60000000;
+    /// Exception handling frame header.
+    pub const PT_GNU_EH_FRAME: u32 = 0x6474E550;
     /// Stack.
     pub const PT_GNU_STACK: u32 = 0x6474E551;
+    /// Read-only after relocation.
+    pub const PT_GNU_RELRO: u32 = 0x6474E552;
+    /// End of the OS-specific range.
+    pub const PT_HIOS: u32 = 0x6FFFFFFF;
+    /// Start of the processor-specific range.
+    pub const PT_LOPROC: u32 = 0x70000000;
+    /// ARM exception unwinding index table.
+    pub const PT_ARM_EXIDX: u32 = 0x70000001;
+    /// End of the processor-specific range.
+    pub const PT_HIPROC: u32 = 0x7FFFFFFF;
+
+    /// Segment is executable.
+    pub const PF_X: u32 = 0x1;
+    /// Segment is writable.
+    pub const PF_W: u32 = 0x2;
+    /// Segment is readable.
+    pub const PF_R: u32 = 0x4;
 
     /// Create a new section header.
     pub fn new<DS>(loader: &Loader<DS>, idx: u16) -> Result<Self, Error<DS::Error>>
     where
         DS: Source,
     {
-        let ph_table_offset = loader.e_phoff + u32::from(Self::SIZE_IN_BYTES) * u32::from(idx);
-
-        let p_type = loader.data_source.read_u32_le(ph_table_offset)?;
-        let p_offset = loader.data_source.read_u32_le(ph_table_offset + 0x04)?;
-        let p_vaddr = loader.data_source.read_u32_le(ph_table_offset + 0x08)?;
-        let p_paddr = loader.data_source.read_u32_le(ph_table_offset + 0x0C)?;
-        let p_filesz = loader.data_source.read_u32_le(ph_table_offset + 0x10)?;
-        let p_memsz = loader.data_source.read_u32_le(ph_table_offset + 0x14)?;
-        let p_flags = loader.data_source.read_u32_le(ph_table_offset + 0x18)?;
-        let p_align = loader.data_source.read_u32_le(ph_table_offset + 0x1C)?;
-
-        Ok(Self {
-            p_type,
-            p_offset,
-            p_vaddr,
-            p_paddr,
-            p_filesz,
-            p_memsz,
-            p_flags,
-            p_align,
-        })
+        let shim = Shim::new(&loader.data_source);
+        let result = erased::read_program_header(&shim, loader.e_phoff, loader.e_phnum, idx);
+        shim.finish(result)
+    }
+
+    /// Decode a program header from the bytes of a program header table
+    /// entry.
+    ///
+    /// `bytes` must hold a whole entry, of `SIZE_IN_BYTES` bytes.
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Self {
+        let word = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        Self {
+            p_type: word(0x00),
+            p_offset: word(0x04),
+            p_vaddr: word(0x08),
+            p_paddr: word(0x0C),
+            p_filesz: word(0x10),
+            p_memsz: word(0x14),
+            p_flags: word(0x18),
+            p_align: word(0x1C),
+        }
     }
 
     /// Get the `p_type` field.
@@ -91,6 +126,11 @@ impl Header {
         self.p_type
     }
 
+    /// Get the `p_type` field, as a `SegmentType`.
+    pub fn segment_type(&self) -> SegmentType {
+        SegmentType::from(self.p_type)
+    }
+
     /// Get the `p_offset` field
     ///
     /// This is the start of the segment data within this ELF file.
@@ -128,12 +168,17 @@ impl Header {
 
     /// Get the `p_flags` field
     ///
-    /// A bitfield indicating whether the segment is executable (`0x01`),
-    /// writable (`0x02`) or readable (`0x04`).
+    /// A bitfield indicating whether the segment is executable (`PF_X`),
+    /// writable (`PF_W`) or readable (`PF_R`).
     pub fn p_flags(&self) -> u32 {
         self.p_flags
     }
 
+    /// Get the `p_flags` field, as a `SegmentFlags`
+    pub fn flags(&self) -> SegmentFlags {
+        SegmentFlags(self.p_flags)
+    }
+
     /// Get the `p_align` field
     ///
     /// 0 or 1 means no alignment, otherwise is a power-of-2 indicating
@@ -141,6 +186,485 @@ impl Header {
     pub fn p_align(&self) -> u32 {
         self.p_align
     }
+
+    /// Get the range of file offsets this segment's data occupies.
+    ///
+    /// This is `p_offset..p_offset + p_filesz`, or `None` if that would wrap
+    /// past `0xFFFF_FFFF`.
+    pub fn file_range(&self) -> Option<Range<u32>> {
+        checked_range(self.p_offset, self.p_filesz)
+    }
+
+    /// Get the range of physical addresses this segment occupies in memory.
+    ///
+    /// This is `p_paddr..p_paddr + p_memsz`, or `None` if that would wrap
+    /// past `0xFFFF_FFFF`.
+    pub fn mem_range(&self) -> Option<Range<u32>> {
+        checked_range(self.p_paddr, self.p_memsz)
+    }
+
+    /// Get the range of virtual addresses this segment occupies in memory.
+    ///
+    /// This is `p_vaddr..p_vaddr + p_memsz`, or `None` if that would wrap
+    /// past `0xFFFF_FFFF`.
+    pub fn vaddr_range(&self) -> Option<Range<u32>> {
+        checked_range(self.p_vaddr, self.p_memsz)
+    }
+
+    /// Is this virtual address within the segment?
+    ///
+    /// Always false if the segment's virtual address range wraps.
+    pub fn contains_vaddr(&self, addr: u32) -> bool {
+        self.vaddr_range().is_some_and(|r| r.contains(&addr))
+    }
+
+    /// Does this segment overlap `other` in physical memory?
+    ///
+    /// Empty segments overlap nothing. A segment whose range wraps is
+    /// treated as overlapping everything, as we can't say where it ends.
+    pub fn overlaps_mem(&self, other: &Header) -> bool {
+        match (self.mem_range(), other.mem_range()) {
+            (Some(a), Some(b)) => {
+                !a.is_empty() && !b.is_empty() && a.start < b.end && b.start < a.end
+            }
+            _ => true,
+        }
+    }
+}
+
+/// The kinds of segment.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SegmentType {
+    /// `PT_NULL`
+    Null,
+    /// `PT_LOAD`
+    Load,
+    /// `PT_DYNAMIC`
+    Dynamic,
+    /// `PT_INTERP`
+    Interp,
+    /// `PT_NOTE`
+    Note,
+    /// `PT_SHLIB`
+    Shlib,
+    /// `PT_PHDR`
+    Phdr,
+    /// `PT_TLS`
+    Tls,
+    /// `PT_GNU_STACK`
+    GnuStack,
+    /// `PT_GNU_RELRO`
+    GnuRelro,
+    /// `PT_GNU_EH_FRAME`
+    GnuEhFrame,
+    /// `PT_ARM_EXIDX`
+    ArmExidx,
+    /// Some other type in the OS-specific range
+    Os(u32),
+    /// Some other type in the processor-specific range
+    Proc(u32),
+    /// A type we don't know about
+    Unknown(u32),
+}
+
+impl SegmentType {
+    /// Get the raw `p_type` value.
+    pub fn as_u32(self) -> u32 {
+        match self {
+            SegmentType::Null => Header::PT_NULL,
+            SegmentType::Load => Header::PT_LOAD,
+            SegmentType::Dynamic => Header::PT_DYNAMIC,
+            SegmentType::Interp => Header::PT_INTERP,
+            SegmentType::Note => Header::PT_NOTE,
+            SegmentType::Shlib => Header::PT_SHLIB,
+            SegmentType::Phdr => Header::PT_PHDR,
+            SegmentType::Tls => Header::PT_TLS,
+            SegmentType::GnuStack => Header::PT_GNU_STACK,
+            SegmentType::GnuRelro => Header::PT_GNU_RELRO,
+            SegmentType::GnuEhFrame => Header::PT_GNU_EH_FRAME,
+            SegmentType::ArmExidx => Header::PT_ARM_EXIDX,
+            SegmentType::Os(value) | SegmentType::Proc(value) | SegmentType::Unknown(value) => {
+                value
+            }
+        }
+    }
+
+    /// Get the name of the type, e.g. `PT_LOAD`.
+    pub fn name(self) -> &'static str {
+        match self {
+            SegmentType::Null => "PT_NULL",
+            SegmentType::Load => "PT_LOAD",
+            SegmentType::Dynamic => "PT_DYNAMIC",
+            SegmentType::Interp => "PT_INTERP",
+            SegmentType::Note => "PT_NOTE",
+            SegmentType::Shlib => "PT_SHLIB",
+            SegmentType::Phdr => "PT_PHDR",
+            SegmentType::Tls => "PT_TLS",
+            SegmentType::GnuStack => "PT_GNU_STACK",
+            SegmentType::GnuRelro => "PT_GNU_RELRO",
+            SegmentType::GnuEhFrame => "PT_GNU_EH_FRAME",
+            SegmentType::ArmExidx => "PT_ARM_EXIDX",
+            SegmentType::Os(_) => "PT_LOOS+",
+            SegmentType::Proc(_) => "PT_LOPROC+",
+            SegmentType::Unknown(_) => "PT_???",
+        }
+    }
+}
+
+impl From<u32> for SegmentType {
+    fn from(value: u32) -> SegmentType {
+        match value {
+            Header::PT_NULL => SegmentType::Null,
+            Header::PT_LOAD => SegmentType::Load,
+            Header::PT_DYNAMIC => SegmentType::Dynamic,
+            Header::PT_INTERP => SegmentType::Interp,
+            Header::PT_NOTE => SegmentType::Note,
+            Header::PT_SHLIB => SegmentType::Shlib,
+            Header::PT_PHDR => SegmentType::Phdr,
+            Header::PT_TLS => SegmentType::Tls,
+            Header::PT_GNU_STACK => SegmentType::GnuStack,
+            Header::PT_GNU_RELRO => SegmentType::GnuRelro,
+            Header::PT_GNU_EH_FRAME => SegmentType::GnuEhFrame,
+            Header::PT_ARM_EXIDX => SegmentType::ArmExidx,
+            Header::PT_LOOS..=Header::PT_HIOS => SegmentType::Os(value),
+            Header::PT_LOPROC..=Header::PT_HIPROC => SegmentType::Proc(value),
+            _ => SegmentType::Unknown(value),
+        }
+    }
+}
+
+/// The permissions for a segment.
+///
+/// Displays like `r-x`, as `objdump -p` does.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub struct SegmentFlags(u32);
+
+impl SegmentFlags {
+    /// Make some segment flags from the raw `p_flags` value.
+    pub fn new(p_flags: u32) -> SegmentFlags {
+        SegmentFlags(p_flags)
+    }
+
+    /// Get the raw flags.
+    pub fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Is this segment readable?
+    pub fn is_readable(self) -> bool {
+        self.0 & Header::PF_R != 0
+    }
+
+    /// Is this segment writable?
+    pub fn is_writable(self) -> bool {
+        self.0 & Header::PF_W != 0
+    }
+
+    /// Is this segment executable?
+    pub fn is_executable(self) -> bool {
+        self.0 & Header::PF_X != 0
+    }
+
+    /// Is this segment both writable and executable?
+    ///
+    /// A careful loader would refuse to load such a segment.
+    pub fn is_wx(self) -> bool {
+        self.is_writable() && self.is_executable()
+    }
+}
+
+impl core::fmt::Display for SegmentFlags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        use core::fmt::Write;
+        f.write_char(if self.is_readable() { 'r' } else { '-' })?;
+        f.write_char(if self.is_writable() { 'w' } else { '-' })?;
+        f.write_char(if self.is_executable() { 'x' } else { '-' })
+    }
+}
+
+impl core::fmt::Debug for SegmentFlags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "SegmentFlags({})", self)
+    }
+}
+
+/// What we sort `PT_LOAD` segments by.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    p_paddr: u32,
+    p_memsz: u32,
+    index: u16,
+}
+
+/// Allows you to iterate through the `PT_LOAD` segments in address order.
+///
+/// Created with `loader.iter_load_segments_sorted()`.
+pub struct IterSortedSegments<'a, DS> {
+    parent: &'a Loader<DS>,
+    keys: [SortKey; MAX_SORTED_LOAD_SEGMENTS],
+    num_keys: usize,
+    next_key: usize,
+}
+
+impl<DS> IterSortedSegments<'_, DS> {
+    /// The indices of the segments we haven't given out yet, in address
+    /// order.
+    pub(crate) fn indices(&self) -> impl Iterator<Item = u16> + '_ {
+        self.keys[self.next_key..self.num_keys]
+            .iter()
+            .map(|key| key.index)
+    }
+}
+
+impl<'a, DS> Iterator for IterSortedSegments<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<Header, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_key >= self.num_keys {
+            return None;
+        }
+        let key = self.keys[self.next_key];
+        self.next_key += 1;
+        Some(Header::new(self.parent, key.index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.num_keys - self.next_key;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, DS> ExactSizeIterator for IterSortedSegments<'a, DS> where DS: Source {}
+
+/// Allows you to iterate through a copy of the program header table, held
+/// in a buffer.
+///
+/// This does no I/O, so it gives headers rather than `Result`s.
+///
+/// Created with `loader.program_headers_buffered()`.
+#[derive(Debug, Clone)]
+pub struct BufferedProgramHeaders<'a> {
+    entries: core::slice::ChunksExact<'a, u8>,
+}
+
+impl Iterator for BufferedProgramHeaders<'_> {
+    type Item = Header;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(Header::from_bytes)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.entries.nth(n).map(Header::from_bytes)
+    }
+}
+
+impl DoubleEndedIterator for BufferedProgramHeaders<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.entries.next_back().map(Header::from_bytes)
+    }
+}
+
+impl ExactSizeIterator for BufferedProgramHeaders<'_> {}
+
+impl FusedIterator for BufferedProgramHeaders<'_> {}
+
+/// Where the program header table lives once the file is loaded.
+///
+/// These are the values a runtime expects in `AT_PHDR`, `AT_PHNUM` and
+/// `AT_PHENT`.
+///
+/// Created with `loader.phdr_info()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PhdrInfo {
+    vaddr: u32,
+    count: u16,
+    entry_size: u16,
+}
+
+impl PhdrInfo {
+    /// Get the virtual address of the table, after loading.
+    pub fn vaddr(&self) -> u32 {
+        self.vaddr
+    }
+
+    /// Get the number of entries in the table.
+    pub fn count(&self) -> u16 {
+        self.count
+    }
+
+    /// Get the size of each entry in the table, in bytes.
+    pub fn entry_size(&self) -> u16 {
+        self.entry_size
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Create an iterator through the `PT_LOAD` segments, in order of
+    /// ascending `p_paddr`.
+    ///
+    /// Segments at the same address are ordered by `p_memsz` (so empty
+    /// segments come first), and then by their position in the program
+    /// header table.
+    ///
+    /// This doesn't need an allocator, so it can only handle up to
+    /// `MAX_SORTED_LOAD_SEGMENTS` segments. If there are more, you get
+    /// `Error::TooManySegments`.
+    pub fn iter_load_segments_sorted(
+        &self,
+    ) -> Result<IterSortedSegments<'_, DS>, Error<DS::Error>> {
+        let mut keys = [SortKey::default(); MAX_SORTED_LOAD_SEGMENTS];
+        let mut num_keys = 0;
+        for (index, ph) in self.iter_program_headers().enumerate() {
+            let ph = ph?;
+            if ph.p_type() != Header::PT_LOAD {
+                continue;
+            }
+            let Some(key) = keys.get_mut(num_keys) else {
+                return Err(Error::TooManySegments);
+            };
+            *key = SortKey {
+                p_paddr: ph.p_paddr(),
+                p_memsz: ph.p_memsz(),
+                index: index as u16,
+            };
+            num_keys += 1;
+        }
+
+        // Selection sort - there aren't many segments
+        let keys_to_sort = &mut keys[0..num_keys];
+        for start in 0..keys_to_sort.len() {
+            let mut smallest = start;
+            for candidate in start + 1..keys_to_sort.len() {
+                if keys_to_sort[candidate] < keys_to_sort[smallest] {
+                    smallest = candidate;
+                }
+            }
+            keys_to_sort.swap(start, smallest);
+        }
+
+        Ok(IterSortedSegments {
+            parent: self,
+            keys,
+            num_keys,
+            next_key: 0,
+        })
+    }
+
+    /// Read the whole program header table into `buffer`, and iterate
+    /// through it.
+    ///
+    /// This is one read from the source, rather than one for each field of
+    /// each header. The
+    /// table is `e_phnum * 0x20` bytes, and if `buffer` is smaller than that
+    /// you get `Error::BufferTooSmall`, which says how big it needs to be.
+    /// If you don't have the memory to spare, use `iter_program_headers`.
+    pub fn program_headers_buffered<'a>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<BufferedProgramHeaders<'a>, Error<DS::Error>> {
+        let needed = usize::from(self.e_phnum) * usize::from(Header::SIZE_IN_BYTES);
+        let Some(buffer) = buffer.get_mut(0..needed) else {
+            return Err(Error::BufferTooSmall { needed });
+        };
+        self.data_source.read(self.e_phoff, buffer)?;
+        let buffer: &'a [u8] = buffer;
+        let entries = buffer.chunks_exact(usize::from(Header::SIZE_IN_BYTES));
+        Ok(BufferedProgramHeaders { entries })
+    }
+
+    /// Get the path of the program interpreter, if any.
+    ///
+    /// This is stored in the `PT_INTERP` segment. Dynamically linked Linux
+    /// binaries have one (e.g. `/lib/ld-linux.so.3`), but Neotron executables
+    /// should not.
+    ///
+    /// If `buffer` is too small for the whole segment, you get
+    /// `Error::BufferTooSmall`, which says how big it needs to be.
+    pub fn interpreter<'a>(
+        &self,
+        buffer: &'a mut [u8],
+    ) -> Result<Option<&'a str>, Error<DS::Error>> {
+        let Some(ph) = self.find_program_header_by_type(Header::PT_INTERP)? else {
+            return Ok(None);
+        };
+        let needed = ph.p_filesz() as usize;
+        let Some(buffer) = buffer.get_mut(0..needed) else {
+            return Err(Error::BufferTooSmall { needed });
+        };
+        self.data_source.read(ph.p_offset(), buffer)?;
+        // The string should be null-terminated, but we don't insist
+        let len = buffer.iter().position(|b| *b == 0).unwrap_or(needed);
+        match core::str::from_utf8(&buffer[0..len]) {
+            Ok(s) => Ok(Some(s)),
+            Err(_) => Err(Error::InvalidString),
+        }
+    }
+
+    /// Find where the program header table will be in memory, once the file
+    /// is loaded.
+    ///
+    /// We use the `PT_PHDR` segment if there is one. If not, we look for a
+    /// `PT_LOAD` segment whose file contents include the whole table, and
+    /// work out the address from that. If neither is found, the table isn't
+    /// loaded and you get `None`.
+    pub fn phdr_info(&self) -> Result<Option<PhdrInfo>, Error<DS::Error>> {
+        let vaddr = match self.find_program_header_by_type(Header::PT_PHDR)? {
+            Some(ph) => Some(ph.p_vaddr()),
+            None => self.phdr_vaddr_from_load_segments()?,
+        };
+        Ok(vaddr.map(|vaddr| PhdrInfo {
+            vaddr,
+            count: self.e_phnum,
+            entry_size: Header::SIZE_IN_BYTES,
+        }))
+    }
+
+    /// Copy the raw program header table into `dest`.
+    ///
+    /// If `dest` is too small for the whole table, you get
+    /// `Error::BufferTooSmall`, which says how big it needs to be. Any bytes
+    /// in `dest` beyond the table are left alone.
+    pub fn copy_phdr_table(&self, dest: &mut [u8]) -> Result<(), Error<DS::Error>> {
+        let needed = usize::from(self.e_phnum) * usize::from(Header::SIZE_IN_BYTES);
+        let Some(dest) = dest.get_mut(0..needed) else {
+            return Err(Error::BufferTooSmall { needed });
+        };
+        self.data_source.read(self.e_phoff, dest)?;
+        Ok(())
+    }
+
+    /// Work out the address of the program header table from the `PT_LOAD`
+    /// segment that contains it, if any.
+    fn phdr_vaddr_from_load_segments(&self) -> Result<Option<u32>, Error<DS::Error>> {
+        let table_len = u32::from(self.e_phnum) * u32::from(Header::SIZE_IN_BYTES);
+        // `Loader::new` checked the whole table fits below 4 GiB
+        let table = self.e_phoff..self.e_phoff + table_len;
+        for ph in self.iter_program_headers() {
+            let ph = ph?;
+            if ph.p_type() != Header::PT_LOAD {
+                continue;
+            }
+            let Some(file_range) = ph.file_range() else {
+                continue;
+            };
+            if file_range.start <= table.start && table.end <= file_range.end {
+                let offset = table.start - file_range.start;
+                return Ok(Some(ph.p_vaddr().wrapping_add(offset)));
+            }
+        }
+        Ok(None)
+    }
 }
 
 // ============================================================================
@@ -0,0 +1,73 @@
+//! Zero-copy access, for when the whole file is already in memory.
+//!
+//! If your `Source` is a `&[u8]` (e.g. the file is in XIP flash), you can get
+//! at section and segment contents without copying them into a buffer.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::ops::Range;
+
+use crate::{traits::SliceError, Error, Loader, ProgramHeader, SectionHeader};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+impl<'a> Loader<&'a [u8]> {
+    /// Get the contents of a section, as part of the original slice.
+    ///
+    /// `SHT_NOBITS` sections (like `.bss`) have no contents in the file, so
+    /// you get an empty slice. Compressed sections give
+    /// `Error::CompressedSection`, and sections which run past the end of the
+    /// slice give `Error::DataOutOfBounds`.
+    pub fn section_bytes(&self, sh: &SectionHeader) -> Result<&'a [u8], Error<SliceError>> {
+        if sh.is_compressed() {
+            return Err(Error::CompressedSection);
+        }
+        if !sh.occupies_file_space() {
+            return Ok(&[]);
+        }
+        self.sub_slice(sh.file_range())
+    }
+
+    /// Get the contents of a segment from the file, as part of the original
+    /// slice.
+    ///
+    /// This is the `p_filesz` bytes held in the file. Segments which run past
+    /// the end of the slice give `Error::DataOutOfBounds`.
+    pub fn segment_file_bytes(&self, ph: &ProgramHeader) -> Result<&'a [u8], Error<SliceError>> {
+        self.sub_slice(ph.file_range())
+    }
+
+    /// Get a range of bytes from the original slice.
+    ///
+    /// `None` is for a range which wrapped.
+    fn sub_slice(&self, range: Option<Range<u32>>) -> Result<&'a [u8], Error<SliceError>> {
+        let range = range.ok_or(Error::DataOutOfBounds)?;
+        self.data_source
+            .get(range.start as usize..range.end as usize)
+            .ok_or(Error::DataOutOfBounds)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,138 @@
+//! Helpers which collect the headers into a `heapless::Vec`.
+//!
+//! These are only available with the `heapless` feature. They save you from
+//! counting the headers and juggling an array and an index, when you don't
+//! have an allocator to `collect()` into.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use heapless::Vec;
+
+use crate::{Error, Loader, ProgramHeader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// A copy of the parts of an ELF file needed to start it.
+///
+/// This holds the entry point, and up to `P` program headers and `S`
+/// section headers. It doesn't borrow the data source, so you can close the
+/// file and still have the headers - although reading the contents of a
+/// segment or section still needs a `Loader`.
+///
+/// Created with `loader.snapshot()`.
+#[derive(Debug, Clone)]
+pub struct HeaderSnapshot<const P: usize, const S: usize> {
+    e_type: u16,
+    e_entry: u32,
+    program_headers: Vec<ProgramHeader, P>,
+    section_headers: Vec<SectionHeader, S>,
+}
+
+impl<const P: usize, const S: usize> HeaderSnapshot<P, S> {
+    /// The type of file (e.g. `ET_EXEC`)
+    pub fn e_type(&self) -> u16 {
+        self.e_type
+    }
+
+    /// The entry point
+    pub fn e_entry(&self) -> u32 {
+        self.e_entry
+    }
+
+    /// The program headers, in the order they are in the file
+    pub fn program_headers(&self) -> &[ProgramHeader] {
+        &self.program_headers
+    }
+
+    /// The section headers, in the order they are in the file
+    pub fn section_headers(&self) -> &[SectionHeader] {
+        &self.section_headers
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Read all the program headers into a `heapless::Vec`.
+    ///
+    /// If there are more than `N`, you get `Error::BufferTooSmall`, which
+    /// says how many there are.
+    pub fn program_headers_heapless<const N: usize>(
+        &self,
+    ) -> Result<Vec<ProgramHeader, N>, Error<DS::Error>> {
+        try_collect(self.iter_program_headers())
+    }
+
+    /// Read all the section headers into a `heapless::Vec`.
+    ///
+    /// If there are more than `N`, you get `Error::BufferTooSmall`, which
+    /// says how many there are.
+    pub fn section_headers_heapless<const N: usize>(
+        &self,
+    ) -> Result<Vec<SectionHeader, N>, Error<DS::Error>> {
+        try_collect(self.iter_section_headers())
+    }
+
+    /// Copy the entry point and all the headers, so they can outlive the
+    /// data source.
+    ///
+    /// If there are more than `P` program headers or `S` section headers,
+    /// you get `Error::BufferTooSmall`, which says how many there are.
+    pub fn snapshot<const P: usize, const S: usize>(
+        &self,
+    ) -> Result<HeaderSnapshot<P, S>, Error<DS::Error>> {
+        Ok(HeaderSnapshot {
+            e_type: self.e_type,
+            e_entry: self.e_entry,
+            program_headers: self.program_headers_heapless()?,
+            section_headers: self.section_headers_heapless()?,
+        })
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Collect the items from `iter`.
+///
+/// If there are more than `N`, you get `Error::BufferTooSmall`, which says
+/// how many there are.
+fn try_collect<T, I, E, const N: usize>(iter: I) -> Result<Vec<T, N>, Error<E>>
+where
+    I: ExactSizeIterator<Item = Result<T, Error<E>>>,
+    E: core::fmt::Debug,
+{
+    let needed = iter.len();
+    if needed > N {
+        return Err(Error::BufferTooSmall { needed });
+    }
+    let mut items = Vec::new();
+    for item in iter {
+        if items.push(item?).is_err() {
+            return Err(Error::BufferTooSmall { needed });
+        }
+    }
+    Ok(items)
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,148 @@
+//! Code and types for handling string tables.
+//!
+//! These live in `SHT_STRTAB` sections (like `.strtab`, `.dynstr` and
+//! `.shstrtab`), and are just a pool of null-terminated strings.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Allows you to walk through the strings in a string table.
+///
+/// Each string is read into the scratch buffer, so this can't be an
+/// `Iterator` - call `next_string()` until it returns `None`.
+///
+/// Created with `section_header.iter_strings()`.
+pub struct IterStrings<'a, 'b, DS> {
+    parent: &'a Loader<DS>,
+    scratch: &'b mut [u8],
+    start_offset: u32,
+    next_offset: u32,
+    end_offset: u32,
+    current_offset: u32,
+}
+
+impl<'a, 'b, DS> IterStrings<'a, 'b, DS>
+where
+    DS: Source,
+{
+    /// Get the next string in the table.
+    ///
+    /// Strings which don't fit in the scratch buffer give
+    /// `Error::BufferTooSmall`, which says how big it needs to be, and then
+    /// we carry on with the next string. `current_offset()` says where the
+    /// string was. If
+    /// the last string in the table isn't null-terminated, you get
+    /// `Error::InvalidString` and then we stop.
+    pub fn next_string(&mut self) -> Option<Result<&str, Error<DS::Error>>> {
+        if self.next_offset >= self.end_offset {
+            return None;
+        }
+        if self.scratch.is_empty() {
+            self.next_offset = self.end_offset;
+            return Some(Err(Error::BufferTooSmall { needed: 1 }));
+        }
+        let offset = self.next_offset;
+        self.current_offset = offset - self.start_offset;
+        let len = self.scratch.len().min((self.end_offset - offset) as usize);
+        if let Err(e) = self
+            .parent
+            .data_source
+            .read(offset, &mut self.scratch[0..len])
+        {
+            self.next_offset = self.end_offset;
+            return Some(Err(Error::Source(e)));
+        }
+        if let Some(nul) = self.scratch[0..len].iter().position(|b| *b == 0) {
+            self.next_offset = offset + nul as u32 + 1;
+            let s = core::str::from_utf8(&self.scratch[0..nul]).map_err(|_| Error::InvalidString);
+            return Some(s);
+        }
+        // It doesn't fit, so find the end of it
+        Some(Err(self.skip_string(offset + len as u32)))
+    }
+
+    /// The offset within the table of the string most recently returned by
+    /// `next_string()`.
+    ///
+    /// This is the value you'd find in a `st_name` or `sh_name` field.
+    pub fn current_offset(&self) -> u32 {
+        self.current_offset
+    }
+
+    /// Skip over the rest of an over-long string, which we have read up to
+    /// `offset`, returning the error to report.
+    fn skip_string(&mut self, mut offset: u32) -> Error<DS::Error> {
+        let string_offset = self.next_offset;
+        while offset < self.end_offset {
+            let len = self.scratch.len().min((self.end_offset - offset) as usize);
+            let chunk = &mut self.scratch[0..len];
+            if let Err(e) = self.parent.data_source.read(offset, chunk) {
+                self.next_offset = self.end_offset;
+                return Error::Source(e);
+            }
+            if let Some(nul) = chunk.iter().position(|b| *b == 0) {
+                let end = offset + nul as u32;
+                self.next_offset = end + 1;
+                return Error::BufferTooSmall {
+                    needed: (end - string_offset) as usize + 1,
+                };
+            }
+            offset += len as u32;
+        }
+        // The string runs off the end of the table
+        self.next_offset = self.end_offset;
+        Error::InvalidString
+    }
+}
+
+impl SectionHeader {
+    /// Create an iterator through the strings in this string table.
+    ///
+    /// The section must be an `SHT_STRTAB` section. Each string is read into
+    /// `scratch`, so it must be big enough for the longest string you want
+    /// to see, plus its null terminator.
+    pub fn iter_strings<'a, 'b, DS: Source>(
+        &self,
+        loader: &'a Loader<DS>,
+        scratch: &'b mut [u8],
+    ) -> Result<IterStrings<'a, 'b, DS>, Error<DS::Error>> {
+        if self.sh_type() != SectionHeader::SHT_STRTAB {
+            return Err(Error::WrongSectionType);
+        }
+        Ok(IterStrings {
+            parent: loader,
+            scratch,
+            start_offset: self.sh_offset(),
+            next_offset: self.sh_offset(),
+            end_offset: self.sh_offset().saturating_add(self.sh_size()),
+            current_offset: 0,
+        })
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
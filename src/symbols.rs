@@ -0,0 +1,598 @@
+//! Code and types for handling Symbols.
+//!
+//! These live in the symbol table section (`SHT_SYMTAB`), or for dynamically
+//! linked files, the dynamic symbol table section (`SHT_DYNSYM`). They are
+//! useful for finding well-known objects and functions within an executable.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use core::iter::FusedIterator;
+
+use crate::{checked_range, Error, Loader, SectionHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// Represents an entry in a symbol table.
+#[derive(Debug, Clone)]
+pub struct Symbol {
+    st_name_offset: u32,
+    st_value: u32,
+    st_size: u32,
+    st_info: u8,
+    st_other: u8,
+    st_shndx: u16,
+    st_shndx_ext: Option<u32>,
+    is_arm: bool,
+}
+
+impl Symbol {
+    /// Size of a symbol table entry
+    pub const SIZE_IN_BYTES: u16 = 0x10;
+
+    /// Local symbol, not visible outside the object file.
+    pub const STB_LOCAL: u8 = 0;
+    /// Global symbol, visible to all object files being combined.
+    pub const STB_GLOBAL: u8 = 1;
+    /// Weak symbol, like a global symbol but with lower precedence.
+    pub const STB_WEAK: u8 = 2;
+
+    /// Symbol type is not specified.
+    pub const STT_NOTYPE: u8 = 0;
+    /// Symbol is a data object, such as a variable or an array.
+    pub const STT_OBJECT: u8 = 1;
+    /// Symbol is a function or other executable code.
+    pub const STT_FUNC: u8 = 2;
+    /// Symbol is associated with a section.
+    pub const STT_SECTION: u8 = 3;
+    /// Symbol gives the name of the source file.
+    pub const STT_FILE: u8 = 4;
+    /// Symbol is an uninitialised common block.
+    pub const STT_COMMON: u8 = 5;
+    /// Symbol is a Thread-Local Storage template.
+    pub const STT_TLS: u8 = 6;
+
+    /// Create a new symbol, by reading entry `idx` from the given symbol
+    /// table section.
+    ///
+    /// If `st_shndx` is `SHN_XINDEX`, the real section index is read from
+    /// `shndx`, which should be the `SHT_SYMTAB_SHNDX` section for this symbol
+    /// table (see `Loader::symtab_shndx`).
+    pub fn new<DS>(
+        loader: &Loader<DS>,
+        symtab: &SectionHeader,
+        idx: u32,
+        shndx: Option<&SectionHeader>,
+    ) -> Result<Self, Error<DS::Error>>
+    where
+        DS: Source,
+    {
+        // The whole entry must fit below 4 GiB
+        let symbol_offset = idx
+            .checked_mul(u32::from(Self::SIZE_IN_BYTES))
+            .and_then(|offset| offset.checked_add(symtab.sh_offset()))
+            .filter(|offset| checked_range(*offset, u32::from(Self::SIZE_IN_BYTES)).is_some())
+            .ok_or(Error::DataOutOfBounds)?;
+
+        let st_name_offset = loader.data_source.read_u32_le(symbol_offset)?;
+        let st_value = loader.data_source.read_u32_le(symbol_offset + 0x04)?;
+        let st_size = loader.data_source.read_u32_le(symbol_offset + 0x08)?;
+        let st_info = loader.data_source.read_u8(symbol_offset + 0x0C)?;
+        let st_other = loader.data_source.read_u8(symbol_offset + 0x0D)?;
+        let st_shndx = loader.data_source.read_u16_le(symbol_offset + 0x0E)?;
+        let st_shndx_ext = match shndx {
+            Some(shndx) if st_shndx == SectionHeader::SHN_XINDEX => {
+                loader.extended_shndx(shndx, idx)?
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            st_name_offset,
+            st_value,
+            st_size,
+            st_info,
+            st_other,
+            st_shndx,
+            st_shndx_ext,
+            is_arm: loader.e_machine == Loader::<DS>::EM_ARM,
+        })
+    }
+
+    /// Get the `st_name` field.
+    ///
+    /// This is the offset of the name within the linked string table.
+    pub fn st_name_offset(&self) -> u32 {
+        self.st_name_offset
+    }
+
+    /// Get the string name for this symbol.
+    ///
+    /// The `symtab` must be the symbol table this symbol was read from, as
+    /// the name lives in the string table that it links to. If that isn't a
+    /// string table, you get `Error::BadStringTable`, and if the link is
+    /// beyond the section header table, `Error::SectionIndexOutOfRange`. We
+    /// never read beyond the end of the string table, however big `buffer`
+    /// is.
+    pub fn st_name<'a, DS: Source>(
+        &self,
+        loader: &Loader<DS>,
+        symtab: &SectionHeader,
+        buffer: &'a mut [u8],
+    ) -> Result<&'a str, Error<DS::Error>> {
+        let string_section_header = loader.string_table_for(symtab)?;
+        if self.st_name_offset >= string_section_header.sh_size() {
+            return Err(Error::StringOffsetOutOfRange);
+        }
+        let strings = string_section_header
+            .file_range()
+            .ok_or(Error::DataOutOfBounds)?;
+        // Can't overflow, as the offset is within the table
+        let string_start = strings.start + self.st_name_offset;
+        loader.read_str_in(string_start, strings.end, buffer)
+    }
+
+    /// Get the `st_value` field.
+    ///
+    /// For an executable, this is the address of the symbol.
+    pub fn st_value(&self) -> u32 {
+        self.st_value
+    }
+
+    /// Get the value to use when calling this symbol.
+    ///
+    /// This is the raw `st_value`, so for ARM Thumb functions bit 0 is set,
+    /// as required by a `BLX` instruction.
+    pub fn value_for_call(&self) -> u32 {
+        self.st_value
+    }
+
+    /// Get the address of this symbol in memory.
+    ///
+    /// On ARM, function symbols have bit 0 set to indicate Thumb code. This
+    /// function clears that bit, so you get the address of the first
+    /// instruction. For other symbols this is the same as `st_value`.
+    pub fn address(&self) -> u32 {
+        if self.is_arm && self.st_type() == Self::STT_FUNC {
+            self.st_value & !1
+        } else {
+            self.st_value
+        }
+    }
+
+    /// Get the `st_size` field.
+    ///
+    /// This is the size of the object or function, or zero if unknown.
+    pub fn st_size(&self) -> u32 {
+        self.st_size
+    }
+
+    /// Get the `st_info` field.
+    ///
+    /// This holds both the binding (upper four bits) and the type (lower four
+    /// bits).
+    pub fn st_info(&self) -> u8 {
+        self.st_info
+    }
+
+    /// Get the symbol binding, e.g. `STB_GLOBAL`.
+    pub fn st_bind(&self) -> u8 {
+        self.st_info >> 4
+    }
+
+    /// Get the symbol type, e.g. `STT_FUNC`.
+    pub fn st_type(&self) -> u8 {
+        self.st_info & 0x0F
+    }
+
+    /// Get the `st_other` field.
+    ///
+    /// The lower two bits give the symbol visibility.
+    pub fn st_other(&self) -> u8 {
+        self.st_other
+    }
+
+    /// Get the `st_shndx` field.
+    ///
+    /// This is the index of the section this symbol is defined in, or one of
+    /// the special `SHN_*` values.
+    pub fn st_shndx(&self) -> u16 {
+        self.st_shndx
+    }
+
+    /// Get the full index of the section this symbol is defined in.
+    ///
+    /// This is `st_shndx`, unless that is `SHN_XINDEX`, in which case it is
+    /// the entry for this symbol in the `SHT_SYMTAB_SHNDX` section. If there
+    /// is no such section, you get `SHN_XINDEX`.
+    pub fn resolved_shndx(&self) -> u32 {
+        self.st_shndx_ext
+            .unwrap_or_else(|| u32::from(self.st_shndx))
+    }
+
+    /// Get the section this symbol is defined in.
+    ///
+    /// Extended section indices are resolved, so you only get
+    /// `SymbolSection::Extended` if the `SHT_SYMTAB_SHNDX` section is
+    /// missing.
+    pub fn section(&self) -> SymbolSection {
+        match self.st_shndx_ext {
+            Some(idx) => SymbolSection::Section(idx),
+            None => SymbolSection::from(self.st_shndx),
+        }
+    }
+
+    /// Is this symbol undefined?
+    ///
+    /// Undefined symbols must be provided by something else - for Neotron
+    /// applications, the OS.
+    pub fn is_undefined(&self) -> bool {
+        self.section() == SymbolSection::Undefined
+    }
+}
+
+/// Which section a symbol is defined in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SymbolSection {
+    /// The symbol is not defined in this file (`SHN_UNDEF`)
+    Undefined,
+    /// The symbol has an absolute value, which is not relocated (`SHN_ABS`)
+    Absolute,
+    /// The symbol is a common block, not yet allocated (`SHN_COMMON`)
+    Common,
+    /// The real section index should be in an `SHT_SYMTAB_SHNDX` section
+    /// (`SHN_XINDEX`), but we couldn't find one
+    Extended,
+    /// The symbol is defined in the section with this index
+    Section(u32),
+}
+
+impl From<u16> for SymbolSection {
+    fn from(st_shndx: u16) -> SymbolSection {
+        match st_shndx {
+            SectionHeader::SHN_UNDEF => SymbolSection::Undefined,
+            SectionHeader::SHN_ABS => SymbolSection::Absolute,
+            SectionHeader::SHN_COMMON => SymbolSection::Common,
+            SectionHeader::SHN_XINDEX => SymbolSection::Extended,
+            idx => SymbolSection::Section(u32::from(idx)),
+        }
+    }
+}
+
+/// The result of looking up which function an address belongs to.
+///
+/// Created with `loader.symbolize()`.
+#[derive(Debug, Clone)]
+pub struct SymbolMatch<'a> {
+    name: &'a str,
+    value: u32,
+    offset: u32,
+}
+
+impl<'a> SymbolMatch<'a> {
+    /// The name of the function containing the address.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// The start address of the function containing the address.
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// How far the address is from the start of the function.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+}
+
+/// Allows you to iterate through the symbols in a symbol table.
+///
+/// If a symbol can't be read, you get that error and then the iterator
+/// stops, rather than trying (and probably failing) to read the rest.
+///
+/// Created with `loader.iter_symbols()`, `loader.iter_dynamic_symbols()` or
+/// `loader.iter_symbols_in()`.
+pub struct IterSymbols<'a, DS> {
+    parent: &'a Loader<DS>,
+    table: SectionHeader,
+    shndx: Option<SectionHeader>,
+    next_symbol: u32,
+    num_symbols: u32,
+}
+
+impl<'a, DS> IterSymbols<'a, DS> {
+    /// The symbol table section we are iterating through.
+    ///
+    /// You need this to look up the names of the symbols.
+    pub fn table(&self) -> &SectionHeader {
+        &self.table
+    }
+}
+
+impl<'a, DS> Iterator for IterSymbols<'a, DS>
+where
+    DS: Source,
+{
+    type Item = Result<Symbol, Error<DS::Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_symbol == self.num_symbols {
+            return None;
+        }
+
+        let current_symbol = self.next_symbol;
+        self.next_symbol = self.next_symbol.wrapping_add(1);
+
+        let result = Symbol::new(
+            self.parent,
+            &self.table,
+            current_symbol,
+            self.shndx.as_ref(),
+        );
+        if result.is_err() {
+            // Don't keep reading a table we can't read
+            self.next_symbol = self.num_symbols;
+        }
+        Some(result)
+    }
+}
+
+impl<'a, DS> FusedIterator for IterSymbols<'a, DS> where DS: Source {}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Create a symbol iterator.
+    ///
+    /// This walks the `SHT_SYMTAB` section. If the file has no symbol table
+    /// (e.g. it has been stripped), the iterator is empty.
+    pub fn iter_symbols(&self) -> Result<IterSymbols<'_, DS>, Error<DS::Error>> {
+        match self.first_section_of_type(SectionHeader::SHT_SYMTAB)? {
+            Some((idx, table)) => self.symbols_in(table, Some(u32::from(idx))),
+            None => self.symbols_in(SectionHeader::default(), None),
+        }
+    }
+
+    /// Create a dynamic symbol iterator.
+    ///
+    /// This walks the `SHT_DYNSYM` section, which holds the symbols used for
+    /// run-time binding. If the file has no dynamic symbol table, the iterator
+    /// is empty.
+    pub fn iter_dynamic_symbols(&self) -> Result<IterSymbols<'_, DS>, Error<DS::Error>> {
+        match self.first_section_of_type(SectionHeader::SHT_DYNSYM)? {
+            Some((idx, table)) => self.symbols_in(table, Some(u32::from(idx))),
+            None => self.symbols_in(SectionHeader::default(), None),
+        }
+    }
+
+    /// Create an iterator through the symbols in the given section.
+    ///
+    /// The section must be an `SHT_SYMTAB` or an `SHT_DYNSYM` section.
+    pub fn iter_symbols_in(
+        &self,
+        section: &SectionHeader,
+    ) -> Result<IterSymbols<'_, DS>, Error<DS::Error>> {
+        match section.sh_type() {
+            SectionHeader::SHT_SYMTAB | SectionHeader::SHT_DYNSYM => {
+                let index = self.section_index_of(section)?;
+                self.symbols_in(section.clone(), index)
+            }
+            _ => Err(Error::WrongSectionType),
+        }
+    }
+
+    /// Find a symbol with the given name.
+    ///
+    /// If there is both a local and a global symbol with this name, the
+    /// global one is returned. Otherwise the first match is returned. Use
+    /// `Symbol::is_undefined` to check if the match is only a reference to a
+    /// symbol which something else must provide.
+    ///
+    /// The `scratch` buffer is used to compare the names in chunks, so it can
+    /// be any non-zero length. Longer buffers mean fewer reads.
+    pub fn find_symbol_by_name(
+        &self,
+        name: &str,
+        scratch: &mut [u8],
+    ) -> Result<Option<Symbol>, Error<DS::Error>> {
+        let symbols = self.iter_symbols()?;
+        self.find_symbol_in(symbols, name, scratch)
+    }
+
+    /// Find the function that contains the given address.
+    ///
+    /// We look for the `STT_FUNC` symbol with the highest start address that
+    /// is not above `addr`, and which is large enough to contain `addr`. If
+    /// no such function exists, we fall back to the closest function with a
+    /// size of zero (i.e. unknown), if there is one.
+    ///
+    /// The name of the function is written into `scratch`, which must be large
+    /// enough to hold it.
+    pub fn symbolize<'a>(
+        &self,
+        addr: u32,
+        scratch: &'a mut [u8],
+    ) -> Result<Option<SymbolMatch<'a>>, Error<DS::Error>> {
+        let symbols = self.iter_symbols()?;
+        let symtab = symbols.table().clone();
+        let mut best_sized: Option<(Symbol, u32)> = None;
+        let mut best_unsized: Option<(Symbol, u32)> = None;
+        for symbol in symbols {
+            let symbol = symbol?;
+            if symbol.st_type() != Symbol::STT_FUNC || symbol.is_undefined() {
+                continue;
+            }
+            let start = symbol.address();
+            if start > addr {
+                continue;
+            }
+            let best = if symbol.st_size() == 0 {
+                &mut best_unsized
+            } else if addr - start < symbol.st_size() {
+                &mut best_sized
+            } else {
+                continue;
+            };
+            if best
+                .as_ref()
+                .map_or(true, |(_, best_start)| start > *best_start)
+            {
+                *best = Some((symbol, start));
+            }
+        }
+
+        let Some((symbol, start)) = best_sized.or(best_unsized) else {
+            return Ok(None);
+        };
+
+        let name = symbol.st_name(self, &symtab, scratch)?;
+        Ok(Some(SymbolMatch {
+            name,
+            value: start,
+            offset: addr - start,
+        }))
+    }
+
+    /// Find the `SHT_SYMTAB_SHNDX` section for the symbol table with the
+    /// given section index.
+    ///
+    /// This holds the real section index of each symbol whose `st_shndx` is
+    /// `SHN_XINDEX`, and links to its symbol table. Returns `None` if there
+    /// is no such section.
+    pub fn symtab_shndx(
+        &self,
+        symtab_index: u32,
+    ) -> Result<Option<SectionHeader>, Error<DS::Error>> {
+        for sh in self.iter_sections_of_type(SectionHeader::SHT_SYMTAB_SHNDX) {
+            let sh = sh?;
+            if sh.sh_link() == symtab_index {
+                return Ok(Some(sh));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Look up the extended section index for entry `idx` in the given
+    /// `SHT_SYMTAB_SHNDX` section.
+    ///
+    /// Returns `None` if the section is too short.
+    fn extended_shndx(
+        &self,
+        shndx: &SectionHeader,
+        idx: u32,
+    ) -> Result<Option<u32>, Error<DS::Error>> {
+        if idx >= shndx.sh_size() / 4 {
+            return Ok(None);
+        }
+        let entries = shndx.file_range().ok_or(Error::DataOutOfBounds)?;
+        // Can't overflow, as the entry is within the section
+        let entry = self.data_source.read_u32_le(entries.start + idx * 4)?;
+        Ok(Some(entry))
+    }
+
+    /// Find the index of the given section in the section header table.
+    fn section_index_of(&self, section: &SectionHeader) -> Result<Option<u32>, Error<DS::Error>> {
+        for (idx, sh) in self.iter_section_headers().enumerate() {
+            if sh? == *section {
+                return Ok(Some(idx as u32));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Iterate through the symbols in the given symbol table section.
+    ///
+    /// If we know the index of the section, we look up its
+    /// `SHT_SYMTAB_SHNDX` section once here, rather than for each symbol.
+    pub(crate) fn symbols_in(
+        &self,
+        table: SectionHeader,
+        table_index: Option<u32>,
+    ) -> Result<IterSymbols<'_, DS>, Error<DS::Error>> {
+        let shndx = match table_index {
+            Some(idx) => self.symtab_shndx(idx)?,
+            None => None,
+        };
+        let num_symbols = table.sh_size() / u32::from(Symbol::SIZE_IN_BYTES);
+        Ok(IterSymbols {
+            parent: self,
+            table,
+            shndx,
+            next_symbol: 0,
+            num_symbols,
+        })
+    }
+
+    /// Search the given symbol table for a symbol with the given name.
+    ///
+    /// Global symbols are preferred over local symbols.
+    pub(crate) fn find_symbol_in(
+        &self,
+        symbols: IterSymbols<'_, DS>,
+        name: &str,
+        scratch: &mut [u8],
+    ) -> Result<Option<Symbol>, Error<DS::Error>> {
+        if symbols.num_symbols == 0 {
+            return Ok(None);
+        }
+        let strtab = self.string_table_for(symbols.table())?;
+        let mut local_match = None;
+        for symbol in symbols {
+            let symbol = symbol?;
+            if !self.symbol_name_eq(&symbol, &strtab, name, scratch)? {
+                continue;
+            }
+            if symbol.st_bind() == Symbol::STB_GLOBAL {
+                return Ok(Some(symbol));
+            }
+            if local_match.is_none() {
+                local_match = Some(symbol);
+            }
+        }
+        Ok(local_match)
+    }
+
+    /// Check if the given symbol has the given name.
+    ///
+    /// The `strtab` is the string table linked to the symbol's symbol table.
+    pub(crate) fn symbol_name_eq(
+        &self,
+        symbol: &Symbol,
+        strtab: &SectionHeader,
+        name: &str,
+        scratch: &mut [u8],
+    ) -> Result<bool, Error<DS::Error>> {
+        if symbol.st_name_offset() >= strtab.sh_size() {
+            return Ok(false);
+        }
+        let strings = strtab.file_range().ok_or(Error::DataOutOfBounds)?;
+        // Can't overflow, as the offset is within the table
+        let start = strings.start + symbol.st_name_offset();
+        self.str_eq(start, strings.end, name.as_bytes(), scratch)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,229 @@
+//! Code for finding an ELF file inside a TAR archive.
+//!
+//! Neotron app bundles are uncompressed TAR archives. A [`TarSource`] finds
+//! one member of the archive, and lets you read it as though it were a file
+//! on its own - you can pass it straight to `Loader::new`.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::traits::{SubSource, SubSourceError};
+use crate::Source;
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The size of a TAR header, and the unit that member data is padded to.
+const BLOCK_SIZE: u32 = 512;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// A [`Source`] for one member of a TAR archive.
+///
+/// Created with `TarSource::open`.
+#[derive(Debug, Clone)]
+pub struct TarSource<DS> {
+    window: SubSource<DS>,
+}
+
+impl<DS> TarSource<DS>
+where
+    DS: Source,
+{
+    /// Where the name field starts, in a header
+    const NAME: usize = 0;
+    /// The length of the name field
+    const NAME_LEN: usize = 100;
+    /// Where the size field starts, in a header
+    const SIZE: usize = 124;
+    /// The length of the size field
+    const SIZE_LEN: usize = 12;
+    /// Where the checksum field starts, in a header
+    const CHECKSUM: usize = 148;
+    /// The length of the checksum field
+    const CHECKSUM_LEN: usize = 8;
+    /// Where the type flag is, in a header
+    const TYPE_FLAG: usize = 156;
+    /// Where the magic field starts, in a header
+    const MAGIC: usize = 257;
+    /// Where the name prefix field starts, in a `ustar` header
+    const PREFIX: usize = 345;
+    /// The length of the name prefix field
+    const PREFIX_LEN: usize = 155;
+
+    /// Find the member called `member_name` in the TAR archive in `inner`.
+    ///
+    /// Only regular files are matched. Names are compared exactly, with any
+    /// `ustar` prefix joined on with a `/`.
+    pub fn open(inner: DS, member_name: &str) -> Result<TarSource<DS>, TarError<DS::Error>> {
+        let mut offset = 0u32;
+        let mut header = [0u8; BLOCK_SIZE as usize];
+        loop {
+            if let Some(len) = inner.len() {
+                if offset >= len {
+                    // Ran out of archive without an end marker
+                    return Err(TarError::MemberNotFound);
+                }
+            }
+            inner.read(offset, &mut header).map_err(TarError::Source)?;
+            if header.iter().all(|b| *b == 0) {
+                // An empty block marks the end of the archive
+                return Err(TarError::MemberNotFound);
+            }
+            if !Self::checksum_ok(&header) {
+                return Err(TarError::BadHeader { offset });
+            }
+            let size = Self::parse_octal(&header[Self::SIZE..Self::SIZE + Self::SIZE_LEN])
+                .ok_or(TarError::BadHeader { offset })?;
+            let data_offset = offset
+                .checked_add(BLOCK_SIZE)
+                .ok_or(TarError::BadHeader { offset })?;
+            if data_offset.checked_add(size).is_none() {
+                // The member's data would run past 4 GiB
+                return Err(TarError::BadHeader { offset });
+            }
+            let is_file = matches!(header[Self::TYPE_FLAG], b'0' | b'\0');
+            if is_file && Self::name_matches(&header, member_name) {
+                return Ok(TarSource {
+                    window: SubSource::new(inner, data_offset, size),
+                });
+            }
+            let padded_size = size
+                .checked_next_multiple_of(BLOCK_SIZE)
+                .ok_or(TarError::BadHeader { offset })?;
+            offset = data_offset
+                .checked_add(padded_size)
+                .ok_or(TarError::BadHeader { offset })?;
+        }
+    }
+
+    /// Where the member's data starts, in the archive.
+    pub fn member_offset(&self) -> u32 {
+        self.window.start()
+    }
+
+    /// Get the archive back.
+    pub fn into_inner(self) -> DS {
+        self.window.into_inner()
+    }
+
+    /// Check the header checksum.
+    ///
+    /// The checksum is the sum of all the bytes in the header, with the
+    /// checksum field counted as spaces.
+    fn checksum_ok(header: &[u8; BLOCK_SIZE as usize]) -> bool {
+        let checksum_field = Self::CHECKSUM..Self::CHECKSUM + Self::CHECKSUM_LEN;
+        let Some(expected) = Self::parse_octal(&header[checksum_field.clone()]) else {
+            return false;
+        };
+        let sum: u32 = header
+            .iter()
+            .enumerate()
+            .map(|(idx, b)| {
+                if checksum_field.contains(&idx) {
+                    u32::from(b' ')
+                } else {
+                    u32::from(*b)
+                }
+            })
+            .sum();
+        sum == expected
+    }
+
+    /// Parse a number stored as ASCII octal digits.
+    ///
+    /// The digits may have leading spaces, and end at a space, a null or the
+    /// end of the field. An empty field is zero.
+    fn parse_octal(field: &[u8]) -> Option<u32> {
+        let mut digits = field.iter().skip_while(|b| **b == b' ');
+        let mut value = 0u32;
+        for b in digits.by_ref() {
+            match b {
+                b'0'..=b'7' => {
+                    value = value.checked_mul(8)?.checked_add(u32::from(b - b'0'))?;
+                }
+                b' ' | b'\0' => break,
+                _ => return None,
+            }
+        }
+        Some(value)
+    }
+
+    /// Is this header for a member with the given name?
+    fn name_matches(header: &[u8; BLOCK_SIZE as usize], member_name: &str) -> bool {
+        let name = Self::field_str(&header[Self::NAME..Self::NAME + Self::NAME_LEN]);
+        let is_ustar = header[Self::MAGIC..].starts_with(b"ustar");
+        let prefix = if is_ustar {
+            Self::field_str(&header[Self::PREFIX..Self::PREFIX + Self::PREFIX_LEN])
+        } else {
+            &[]
+        };
+        let wanted = member_name.as_bytes();
+        if prefix.is_empty() {
+            return name == wanted;
+        }
+        // Looking for "<prefix>/<name>"
+        wanted.len() == prefix.len() + 1 + name.len()
+            && wanted.starts_with(prefix)
+            && wanted[prefix.len()] == b'/'
+            && wanted.ends_with(name)
+    }
+
+    /// Get the part of a field before the first null.
+    fn field_str(field: &[u8]) -> &[u8] {
+        let len = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+        &field[0..len]
+    }
+}
+
+impl<DS> Source for TarSource<DS>
+where
+    DS: Source,
+{
+    type Error = SubSourceError<DS::Error>;
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.window.read(offset, buffer)
+    }
+
+    fn len(&self) -> Option<u32> {
+        self.window.len()
+    }
+}
+
+/// The ways opening a [`TarSource`] can fail
+#[derive(Debug, Clone)]
+pub enum TarError<E>
+where
+    E: core::fmt::Debug,
+{
+    /// We couldn't read the archive
+    Source(E),
+    /// The archive has no regular file with the given name
+    MemberNotFound,
+    /// The header at this offset is malformed
+    BadHeader {
+        /// Where the header is, in the archive
+        offset: u32,
+    },
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -4,6 +4,8 @@
 // Imports
 // ============================================================================
 
+use core::cell::{Cell, RefCell};
+
 // ============================================================================
 // Constants
 // ============================================================================
@@ -31,6 +33,20 @@ pub trait Source {
     /// to fill `buffer` completely, otherwise an error is returned.
     fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error>;
 
+    /// How many bytes this source holds, if we know.
+    ///
+    /// This is used to reject sections which claim to be bigger than the
+    /// file, before we allocate memory for them. The default says we don't
+    /// know.
+    fn len(&self) -> Option<u32> {
+        None
+    }
+
+    /// Is this source empty, if we know?
+    fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
     /// Read a 32-bit big-endian value.
     fn read_u32_be(&self, offset: u32) -> Result<u32, Self::Error> {
         let mut bytes = [0; 4];
@@ -67,20 +83,262 @@ pub trait Source {
     }
 }
 
+/// Describes something we can read data from, which changes as we read.
+///
+/// This is for drivers with state, like a seek cursor or a bus, which need
+/// `&mut self` to read. Wrap one in a [`Shared`] to use it as a [`Source`].
+pub trait SourceMut {
+    type Error: core::fmt::Debug;
+
+    /// Read some bytes from the source.
+    ///
+    /// The bytes are read from the given offset, and there must be enough data
+    /// to fill `buffer` completely, otherwise an error is returned.
+    fn read(&mut self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// How many bytes this source holds, if we know.
+    ///
+    /// The default says we don't know.
+    fn len(&mut self) -> Option<u32> {
+        None
+    }
+
+    /// Is this source empty, if we know?
+    fn is_empty(&mut self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+}
+
 impl Source for &[u8] {
     type Error = SliceError;
 
     fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
-        let desired_len = buffer.len();
-        assert!(offset < (usize::MAX - desired_len) as u32);
         let offset = offset as usize;
-        if let Some(sub_slice) = self.get(offset..offset + desired_len) {
+        let end = offset.checked_add(buffer.len()).ok_or(SliceError)?;
+        if let Some(sub_slice) = self.get(offset..end) {
             buffer.copy_from_slice(sub_slice);
             Ok(())
         } else {
             Err(SliceError)
         }
     }
+
+    fn len(&self) -> Option<u32> {
+        Some(u32::try_from(<[u8]>::len(self)).unwrap_or(u32::MAX))
+    }
+}
+
+/// A [`Source`] which is a window onto part of another [`Source`].
+///
+/// Offset zero in the window is offset `start` in the inner source, and the
+/// window is `len` bytes long.
+#[derive(Debug, Clone)]
+pub struct SubSource<DS> {
+    inner: DS,
+    start: u32,
+    len: u32,
+}
+
+impl<DS> SubSource<DS> {
+    /// Make a window `len` bytes long, starting at `start` in `inner`.
+    pub fn new(inner: DS, start: u32, len: u32) -> SubSource<DS> {
+        SubSource { inner, start, len }
+    }
+
+    /// Where the window starts, in the inner source.
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Get the inner source back.
+    pub fn into_inner(self) -> DS {
+        self.inner
+    }
+}
+
+impl<DS> Source for SubSource<DS>
+where
+    DS: Source,
+{
+    type Error = SubSourceError<DS::Error>;
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let end = u32::try_from(buffer.len())
+            .ok()
+            .and_then(|len| offset.checked_add(len))
+            .ok_or(SubSourceError::OutOfRange)?;
+        if end > self.len {
+            return Err(SubSourceError::OutOfRange);
+        }
+        // The window might run past 4 GiB in the inner source
+        let inner_offset = self
+            .start
+            .checked_add(offset)
+            .ok_or(SubSourceError::OutOfRange)?;
+        self.inner
+            .read(inner_offset, buffer)
+            .map_err(SubSourceError::Inner)
+    }
+
+    fn len(&self) -> Option<u32> {
+        Some(self.len)
+    }
+}
+
+/// The error raised if you are reading from a [`SubSource`].
+#[derive(Debug, Clone)]
+pub enum SubSourceError<E>
+where
+    E: core::fmt::Debug,
+{
+    /// The read went past the end of the window
+    OutOfRange,
+    /// The inner source failed
+    Inner(E),
+}
+
+/// A [`Source`] which tries each read again if it fails.
+///
+/// This is for media which sometimes fail and then work on the next go,
+/// like an SD card with the odd CRC error. Each read is tried up to
+/// `retries` more times before we give up and return the last error.
+///
+/// By default every error is retried. If you give a classifier, only errors
+/// it says are retryable are tried again - the rest (like reading past the
+/// end) are returned straight away.
+#[derive(Debug)]
+pub struct RetrySource<DS>
+where
+    DS: Source,
+{
+    inner: DS,
+    retries: u32,
+    is_retryable: Option<fn(&DS::Error) -> bool>,
+    retry_count: Cell<u32>,
+}
+
+impl<DS> RetrySource<DS>
+where
+    DS: Source,
+{
+    /// Retry any failed read from `inner` up to `retries` times.
+    pub fn new(inner: DS, retries: u32) -> RetrySource<DS> {
+        RetrySource {
+            inner,
+            retries,
+            is_retryable: None,
+            retry_count: Cell::new(0),
+        }
+    }
+
+    /// Retry failed reads from `inner` up to `retries` times, but only if
+    /// `is_retryable` says the error is worth retrying.
+    pub fn with_classifier(
+        inner: DS,
+        retries: u32,
+        is_retryable: fn(&DS::Error) -> bool,
+    ) -> RetrySource<DS> {
+        RetrySource {
+            inner,
+            retries,
+            is_retryable: Some(is_retryable),
+            retry_count: Cell::new(0),
+        }
+    }
+
+    /// How many times we have retried a read, in total.
+    pub fn retry_count(&self) -> u32 {
+        self.retry_count.get()
+    }
+
+    /// Get the inner source back.
+    pub fn into_inner(self) -> DS {
+        self.inner
+    }
+}
+
+impl<DS> Source for RetrySource<DS>
+where
+    DS: Source,
+{
+    type Error = DS::Error;
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let mut retries_left = self.retries;
+        loop {
+            match self.inner.read(offset, buffer) {
+                Ok(()) => return Ok(()),
+                Err(e) => {
+                    let retryable = self.is_retryable.map_or(true, |f| f(&e));
+                    if !retryable || retries_left == 0 {
+                        return Err(e);
+                    }
+                    retries_left -= 1;
+                    self.retry_count
+                        .set(self.retry_count.get().saturating_add(1));
+                }
+            }
+        }
+    }
+
+    fn len(&self) -> Option<u32> {
+        self.inner.len()
+    }
+}
+
+/// A [`Source`] made from a [`SourceMut`].
+///
+/// The inner source lives in a `RefCell`, so you don't need one in your
+/// driver. If a read somehow starts while another is still going, it gets
+/// `SharedError::Busy` rather than a panic.
+#[derive(Debug)]
+pub struct Shared<T> {
+    inner: RefCell<T>,
+}
+
+impl<T> Shared<T>
+where
+    T: SourceMut,
+{
+    /// Share `inner`.
+    pub fn new(inner: T) -> Shared<T> {
+        Shared {
+            inner: RefCell::new(inner),
+        }
+    }
+
+    /// Get the inner source back.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<T> Source for Shared<T>
+where
+    T: SourceMut,
+{
+    type Error = SharedError<T::Error>;
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        let mut inner = self.inner.try_borrow_mut().map_err(|_| SharedError::Busy)?;
+        inner.read(offset, buffer).map_err(SharedError::Inner)
+    }
+
+    fn len(&self) -> Option<u32> {
+        self.inner.try_borrow_mut().ok()?.len()
+    }
+}
+
+/// The error raised if you are reading from a [`Shared`].
+#[derive(Debug, Clone)]
+pub enum SharedError<E>
+where
+    E: core::fmt::Debug,
+{
+    /// Another read was already using the inner source
+    Busy,
+    /// The inner source failed
+    Inner(E),
 }
 
 // ============================================================================
@@ -0,0 +1,179 @@
+//! Code for writing the loadable image as UF2 blocks.
+//!
+//! UF2 is the format taken by drag-and-drop bootloaders, like the one on the
+//! RP2040. Each 512 byte block carries 256 bytes of data for one 256 byte
+//! aligned page of flash.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+/// The size of a UF2 block
+pub const UF2_BLOCK_SIZE: usize = 512;
+
+/// How much data we put in each UF2 block
+pub const UF2_PAYLOAD_SIZE: u32 = 256;
+
+/// The first magic number, at the start of the block
+const MAGIC_START0: u32 = 0x0A32_4655;
+
+/// The second magic number, after the first
+const MAGIC_START1: u32 = 0x9E5D_5157;
+
+/// The magic number at the end of the block
+const MAGIC_END: u32 = 0x0AB1_6F30;
+
+/// The flag which says the file size field holds a family ID
+const FLAG_FAMILY_ID_PRESENT: u32 = 0x0000_2000;
+
+/// Where the data starts, in a block
+const DATA_OFFSET: usize = 32;
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Write the `PT_LOAD` segments out as UF2 blocks.
+    ///
+    /// Each finished block is passed to `emit`, in order. Every block holds
+    /// one 256 byte aligned page, at the segment's `p_paddr`. Any part of a
+    /// page not covered by a segment's file contents is zero. Pages with no
+    /// file contents at all (like those only holding `.bss`) are left out.
+    ///
+    /// The blocks are counted before any are emitted, so you get
+    /// `Error::BadSegment` without any blocks if segments overlap. Returns
+    /// how many blocks were emitted.
+    pub fn write_uf2<F>(&self, family_id: u32, mut emit: F) -> Result<u32, Error<DS::Error>>
+    where
+        F: FnMut(&[u8; UF2_BLOCK_SIZE]),
+    {
+        let num_blocks = self.count_uf2_blocks()?;
+
+        let mut payload = [0u8; UF2_PAYLOAD_SIZE as usize];
+        let mut current_page = None;
+        let mut block_no = 0;
+        for ph in self.iter_load_segments_sorted()? {
+            let ph = ph?;
+            let mut done = 0;
+            while done < ph.p_filesz() {
+                let address = ph.p_paddr() + done;
+                let page = address & !(UF2_PAYLOAD_SIZE - 1);
+                let offset_in_page = address - page;
+                let chunk_len = (UF2_PAYLOAD_SIZE - offset_in_page).min(ph.p_filesz() - done);
+                if current_page != Some(page) {
+                    if let Some(current_page) = current_page {
+                        let block =
+                            uf2_block(current_page, block_no, num_blocks, family_id, &payload);
+                        emit(&block);
+                        block_no += 1;
+                    }
+                    payload.fill(0);
+                    current_page = Some(page);
+                }
+                let chunk =
+                    &mut payload[offset_in_page as usize..(offset_in_page + chunk_len) as usize];
+                self.data_source.read(ph.p_offset() + done, chunk)?;
+                done += chunk_len;
+            }
+        }
+        if let Some(current_page) = current_page {
+            let block = uf2_block(current_page, block_no, num_blocks, family_id, &payload);
+            emit(&block);
+            block_no += 1;
+        }
+
+        debug_assert_eq!(num_blocks, block_no);
+        Ok(block_no)
+    }
+
+    /// Work out how many UF2 blocks `write_uf2` will emit, from the segment
+    /// addresses and file sizes.
+    fn count_uf2_blocks(&self) -> Result<u32, Error<DS::Error>> {
+        let mut num_blocks = 0;
+        // The last page we counted
+        let mut last_page = None;
+        // The end of the last segment's file contents
+        let mut last_end = 0;
+        for ph in self.iter_load_segments_sorted()? {
+            let ph = ph?;
+            if ph.p_filesz() == 0 {
+                continue;
+            }
+            let mem_range = ph.mem_range().ok_or(Error::BadSegment)?;
+            if ph.file_range().is_none() || ph.p_filesz() > ph.p_memsz() {
+                return Err(Error::BadSegment);
+            }
+            // Only the file contents get written
+            let end = mem_range.start + ph.p_filesz();
+            if mem_range.start < last_end {
+                // Overlaps the segment before
+                return Err(Error::BadSegment);
+            }
+            last_end = end;
+            let mut first_page = ph.p_paddr() & !(UF2_PAYLOAD_SIZE - 1);
+            let final_page = (end - 1) & !(UF2_PAYLOAD_SIZE - 1);
+            if last_page == Some(first_page) {
+                // Shares a page with the segment before
+                first_page += UF2_PAYLOAD_SIZE;
+            }
+            if final_page >= first_page {
+                num_blocks += (final_page - first_page) / UF2_PAYLOAD_SIZE + 1;
+            }
+            last_page = Some(final_page);
+        }
+        Ok(num_blocks)
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+/// Build one UF2 block.
+fn uf2_block(
+    target_addr: u32,
+    block_no: u32,
+    num_blocks: u32,
+    family_id: u32,
+    payload: &[u8; UF2_PAYLOAD_SIZE as usize],
+) -> [u8; UF2_BLOCK_SIZE] {
+    let mut block = [0u8; UF2_BLOCK_SIZE];
+    let header = [
+        MAGIC_START0,
+        MAGIC_START1,
+        FLAG_FAMILY_ID_PRESENT,
+        target_addr,
+        UF2_PAYLOAD_SIZE,
+        block_no,
+        num_blocks,
+        family_id,
+    ];
+    for (field, value) in block.chunks_exact_mut(4).zip(header) {
+        field.copy_from_slice(&value.to_le_bytes());
+    }
+    block[DATA_OFFSET..DATA_OFFSET + payload.len()].copy_from_slice(payload);
+    block[UF2_BLOCK_SIZE - 4..].copy_from_slice(&MAGIC_END.to_le_bytes());
+    block
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
@@ -0,0 +1,224 @@
+//! Code for checking a Cortex-M vector table.
+//!
+//! Bare images start their first executable segment with a vector table. The
+//! first word is the initial stack pointer, and the second is the reset
+//! vector, which is where execution starts.
+
+// ============================================================================
+// Imports
+// ============================================================================
+
+use crate::{Error, Loader, ProgramHeader, Source};
+
+// ============================================================================
+// Constants
+// ============================================================================
+
+// ============================================================================
+// Static Variables
+// ============================================================================
+
+// ============================================================================
+// Types
+// ============================================================================
+
+/// A region of memory, like the RAM the stack must be in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    start: u32,
+    len: u32,
+}
+
+impl MemoryRegion {
+    /// Make a region `len` bytes long, starting at `start`.
+    pub const fn new(start: u32, len: u32) -> MemoryRegion {
+        MemoryRegion { start, len }
+    }
+
+    /// Get the address of the start of the region.
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Get the length of the region, in bytes.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Is the region empty?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Is this address within the region?
+    pub fn contains(&self, addr: u32) -> bool {
+        addr.checked_sub(self.start)
+            .is_some_and(|offset| offset < self.len)
+    }
+
+    /// Is this a valid initial stack pointer for this region?
+    ///
+    /// The stack grows down, so the stack pointer can be one past the end of
+    /// the region.
+    fn contains_stack_top(&self, sp: u32) -> bool {
+        sp.checked_sub(self.start)
+            .is_some_and(|offset| offset > 0 && offset <= self.len)
+    }
+}
+
+/// What's wrong with a vector table.
+///
+/// Given in `Error::BadVectorTable`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VectorTableFault {
+    /// There are no executable `PT_LOAD` segments, so no vector table
+    NoExecutableSegment,
+    /// The initial stack pointer isn't in RAM
+    StackPointerOutsideRam,
+    /// The initial stack pointer isn't 8-byte aligned
+    StackPointerMisaligned,
+    /// The reset vector doesn't have the Thumb bit set
+    ResetVectorNotThumb,
+    /// The reset vector doesn't point into an executable segment
+    ResetVectorNotExecutable,
+}
+
+/// The interesting parts of a valid vector table.
+///
+/// Created with `loader.check_vector_table()`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct VectorTableInfo {
+    table_addr: u32,
+    initial_sp: u32,
+    reset_vector: u32,
+    entry_matches: bool,
+}
+
+impl VectorTableInfo {
+    /// Get the address of the vector table.
+    pub fn table_addr(&self) -> u32 {
+        self.table_addr
+    }
+
+    /// Get the initial stack pointer (word 0).
+    pub fn initial_sp(&self) -> u32 {
+        self.initial_sp
+    }
+
+    /// Get the reset vector (word 1), including the Thumb bit.
+    pub fn reset_vector(&self) -> u32 {
+        self.reset_vector
+    }
+
+    /// Does the reset vector match `e_entry`?
+    ///
+    /// It doesn't have to, but if it doesn't, the image starts somewhere
+    /// different depending on how it is booted.
+    pub fn entry_matches(&self) -> bool {
+        self.entry_matches
+    }
+}
+
+impl<DS> Loader<DS>
+where
+    DS: Source,
+{
+    /// Check the Cortex-M vector table at the start of the lowest executable
+    /// `PT_LOAD` segment.
+    ///
+    /// The initial stack pointer must be 8-byte aligned, and within `ram` (or
+    /// just past the end of it). The reset vector must have the Thumb bit
+    /// set, and point into an executable `PT_LOAD` segment. If not, you get
+    /// `Error::BadVectorTable`. A reset vector which doesn't match `e_entry`
+    /// is allowed - see `VectorTableInfo::entry_matches`.
+    pub fn check_vector_table(
+        &self,
+        ram: MemoryRegion,
+    ) -> Result<VectorTableInfo, Error<DS::Error>> {
+        let mut table_addr = None;
+        for ph in self.iter_program_headers() {
+            let ph = ph?;
+            if Self::is_executable_load(&ph) && table_addr.map_or(true, |addr| ph.p_vaddr() < addr)
+            {
+                table_addr = Some(ph.p_vaddr());
+            }
+        }
+        let table_addr =
+            table_addr.ok_or(Error::BadVectorTable(VectorTableFault::NoExecutableSegment))?;
+
+        let mut words = [0u8; 8];
+        self.read_at_vaddr(table_addr, &mut words)?;
+        let initial_sp = u32::from_le_bytes([words[0], words[1], words[2], words[3]]);
+        let reset_vector = u32::from_le_bytes([words[4], words[5], words[6], words[7]]);
+        trace!(
+            "vector table at 0x{:08x}: initial SP 0x{:08x}, reset vector 0x{:08x}",
+            table_addr,
+            initial_sp,
+            reset_vector
+        );
+
+        if !ram.contains_stack_top(initial_sp) {
+            debug!(
+                "initial SP 0x{:08x} is outside RAM at 0x{:08x} (0x{:x} bytes)",
+                initial_sp,
+                ram.start(),
+                ram.len()
+            );
+            return Err(Error::BadVectorTable(
+                VectorTableFault::StackPointerOutsideRam,
+            ));
+        }
+        if initial_sp % 8 != 0 {
+            debug!("initial SP 0x{:08x} is not 8-byte aligned", initial_sp);
+            return Err(Error::BadVectorTable(
+                VectorTableFault::StackPointerMisaligned,
+            ));
+        }
+        if reset_vector & 1 == 0 {
+            debug!("reset vector 0x{:08x} is not a Thumb address", reset_vector);
+            return Err(Error::BadVectorTable(VectorTableFault::ResetVectorNotThumb));
+        }
+        let reset_addr = reset_vector & !1;
+        let mut executable = false;
+        for ph in self.iter_program_headers() {
+            let ph = ph?;
+            if Self::is_executable_load(&ph) && ph.contains_vaddr(reset_addr) {
+                executable = true;
+                break;
+            }
+        }
+        if !executable {
+            debug!(
+                "reset vector 0x{:08x} is not in an executable segment",
+                reset_vector
+            );
+            return Err(Error::BadVectorTable(
+                VectorTableFault::ResetVectorNotExecutable,
+            ));
+        }
+
+        Ok(VectorTableInfo {
+            table_addr,
+            initial_sp,
+            reset_vector,
+            entry_matches: reset_addr == self.e_entry & !1,
+        })
+    }
+
+    /// Is this an executable `PT_LOAD` segment?
+    fn is_executable_load(ph: &ProgramHeader) -> bool {
+        ph.p_type() == ProgramHeader::PT_LOAD && ph.flags().is_executable()
+    }
+}
+
+// ============================================================================
+// Functions
+// ============================================================================
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+// ============================================================================
+// End of File
+// ============================================================================
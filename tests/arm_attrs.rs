@@ -0,0 +1,129 @@
+mod common;
+
+use common::{ElfBuilder, Section};
+use neotron_loader::{ArmAttributes, CpuProfile, Error, Loader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// Build an attributes section, with the given vendor subsections.
+fn attributes(subsections: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = vec![b'A'];
+    for (vendor, tags) in subsections {
+        // File-scope sub-subsection
+        let mut file = vec![1];
+        file.extend_from_slice(&(tags.len() as u32 + 5).to_le_bytes());
+        file.extend_from_slice(tags);
+        let len = 4 + vendor.len() + 1 + file.len();
+        out.extend_from_slice(&(len as u32).to_le_bytes());
+        out.extend_from_slice(vendor.as_bytes());
+        out.push(0);
+        out.extend_from_slice(&file);
+    }
+    out
+}
+
+fn fixture(data: Vec<u8>) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section::new(".ARM.attributes", 0x7000_0003, data));
+    elf.build()
+}
+
+#[test]
+fn test_elf() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let attrs = loader.arm_attributes().unwrap().unwrap();
+    assert_eq!(Some(ArmAttributes::CPU_ARCH_V6S_M), attrs.cpu_arch());
+    assert_eq!(
+        Some(ArmAttributes::PROFILE_MICROCONTROLLER),
+        attrs.cpu_arch_profile()
+    );
+    assert_eq!(None, attrs.fp_arch());
+    assert_eq!(None, attrs.abi_vfp_args());
+    assert!(attrs.compatible_with(CpuProfile::ArmV6M));
+    assert!(attrs.compatible_with(CpuProfile::ArmV7EMFp));
+    assert!(attrs.compatible_with(CpuProfile::ArmV8MBaseline));
+}
+
+#[test]
+fn v7em_with_fpu() {
+    let tags: &[u8] = &[
+        // Tag_CPU_name "cortex-m4"
+        5, b'c', b'o', b'r', b't', b'e', b'x', b'-', b'm', b'4', 0, //
+        // Tag_CPU_arch v7E-M
+        6, 13, //
+        // Tag_CPU_arch_profile 'M'
+        7, b'M', //
+        // Tag_THUMB_ISA_use
+        9, 2, //
+        // Tag_FP_arch VFPv4-D16
+        10, 6, //
+        // Tag_compatibility
+        32, 1, b'g', b'n', b'u', 0, //
+        // Tag_ABI_VFP_args VFP registers
+        28, 1, //
+        // An unknown, even tag with a multi-byte value
+        34, 0x80, 0x01, //
+        // Tag_conformance "2.09"
+        67, b'2', b'.', b'0', b'9', 0, //
+        // An unknown, odd tag with a multi-byte tag number
+        0x81, 0x01, b'x', 0,
+    ];
+    let data = fixture(attributes(&[
+        // Another vendor's subsection must be skipped
+        ("gnu", &[4, 1, 2]),
+        ("aeabi", tags),
+    ]));
+    let loader = Loader::new(&data[..]).unwrap();
+    let attrs = loader.arm_attributes().unwrap().unwrap();
+    assert_eq!(Some(ArmAttributes::CPU_ARCH_V7E_M), attrs.cpu_arch());
+    assert_eq!(
+        Some(ArmAttributes::PROFILE_MICROCONTROLLER),
+        attrs.cpu_arch_profile()
+    );
+    assert_eq!(Some(6), attrs.fp_arch());
+    assert_eq!(Some(ArmAttributes::VFP_ARGS_VFP), attrs.abi_vfp_args());
+
+    assert!(!attrs.compatible_with(CpuProfile::ArmV6M));
+    assert!(!attrs.compatible_with(CpuProfile::ArmV7M));
+    assert!(!attrs.compatible_with(CpuProfile::ArmV7EM));
+    assert!(attrs.compatible_with(CpuProfile::ArmV7EMFp));
+    assert!(!attrs.compatible_with(CpuProfile::ArmV8MBaseline));
+    assert!(attrs.compatible_with(CpuProfile::ArmV8MMainlineFp));
+}
+
+#[test]
+fn a_profile_incompatible() {
+    let data = fixture(attributes(&[("aeabi", &[6, 10, 7, b'A'])]));
+    let loader = Loader::new(&data[..]).unwrap();
+    let attrs = loader.arm_attributes().unwrap().unwrap();
+    assert!(!attrs.compatible_with(CpuProfile::ArmV8MMainlineFp));
+}
+
+#[test]
+fn no_attributes() {
+    let data = ElfBuilder::new().build();
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(loader.arm_attributes().unwrap().is_none());
+}
+
+#[test]
+fn malformed_attributes() {
+    // Wrong format version
+    let mut data = attributes(&[("aeabi", &[6, 12])]);
+    data[0] = b'B';
+    let data = fixture(data);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(loader.arm_attributes(), Err(Error::BadAttributes)));
+
+    // Subsection runs off the end of the section
+    let mut data = attributes(&[("aeabi", &[6, 12])]);
+    data[1] = 0xFF;
+    let data = fixture(data);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(loader.arm_attributes(), Err(Error::BadAttributes)));
+
+    // Value runs off the end of the section
+    let data = fixture(attributes(&[("aeabi", &[5, b'x'])]));
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(loader.arm_attributes(), Err(Error::BadAttributes)));
+}
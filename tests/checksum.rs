@@ -0,0 +1,65 @@
+mod common;
+
+use common::{ElfBuilder, Section};
+use neotron_loader::{Error, Loader, SectionChecksum, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+#[test]
+fn test_elf_checksums() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut out = [SectionChecksum::default(); 8];
+    let count = loader.section_checksums(&mut out).unwrap();
+    // .text, .rodata and .data - .bss and .uninit are NOBITS
+    assert_eq!(3, count);
+    let summary: Vec<(u16, u32, u32, u32)> = out[0..count]
+        .iter()
+        .map(|c| (c.index(), c.addr(), c.len(), c.crc()))
+        .collect();
+    assert_eq!(
+        vec![
+            (1, 0x2000_1000, 0x444, 0x3745_ea85),
+            (2, 0x2000_1444, 0x38, 0xc1c8_7163),
+            (3, 0x2000_147c, 0, 0),
+        ],
+        summary
+    );
+    assert!(out[2].is_empty());
+}
+
+#[test]
+fn not_enough_space() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut out = [SectionChecksum::default(); 2];
+    assert!(matches!(
+        loader.section_checksums(&mut out),
+        Err(Error::BufferTooSmall { needed: 3 })
+    ));
+}
+
+#[test]
+fn known_crc() {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_flags: SectionHeader::SHF_ALLOC,
+        sh_addr: 0x1000,
+        ..Section::new(
+            ".rodata",
+            SectionHeader::SHT_PROGBITS,
+            b"123456789".to_vec(),
+        )
+    });
+    // Not allocated, so not checksummed
+    elf.add_section(Section::new(
+        ".comment",
+        SectionHeader::SHT_PROGBITS,
+        b"x\0".to_vec(),
+    ));
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut out = [SectionChecksum::default(); 1];
+    assert_eq!(1, loader.section_checksums(&mut out).unwrap());
+    // The standard CRC32 check value
+    assert_eq!(0xCBF4_3926, out[0].crc());
+    assert_eq!(9, out[0].len());
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use common::{ElfBuilder, Section};
+use neotron_loader::{Error, Loader, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+fn comment_fixture(contents: &[u8]) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_flags: SectionHeader::SHF_MERGE | SectionHeader::SHF_STRINGS,
+        sh_entsize: 1,
+        ..Section::new(".comment", SectionHeader::SHT_PROGBITS, contents.to_vec())
+    });
+    elf.build()
+}
+
+#[test]
+fn test_elf_comment() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let comment = loader.find_section_by_name(".comment").unwrap().unwrap();
+    assert_eq!(0x13, comment.sh_size());
+    let mut buffer = [0u8; 32];
+    assert_eq!(
+        Some("Linker: LLD 15.0.7"),
+        loader.toolchain_comment(&mut buffer).unwrap()
+    );
+    let mut buffer = [0u8; 18];
+    assert_eq!(
+        Some("Linker: LLD 15.0.7"),
+        loader.toolchain_comment(&mut buffer).unwrap()
+    );
+    let mut buffer = [0u8; 17];
+    assert!(matches!(
+        loader.toolchain_comment(&mut buffer),
+        Err(Error::BufferTooSmall { needed: 18 })
+    ));
+}
+
+#[test]
+fn no_comment() {
+    let data = ElfBuilder::new().build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut buffer = [0u8; 32];
+    assert_eq!(None, loader.toolchain_comment(&mut buffer).unwrap());
+}
+
+#[test]
+fn several_comments() {
+    // GNU tools start the section with an empty string
+    let data = comment_fixture(b"\0GCC: (Arm GNU Toolchain 12.2) 12.2.1\0rustc version 1.70.0\0");
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut buffer = [0u8; 64];
+    assert_eq!(
+        Some("GCC: (Arm GNU Toolchain 12.2) 12.2.1"),
+        loader.toolchain_comment(&mut buffer).unwrap()
+    );
+}
+
+#[test]
+fn invalid_comment() {
+    let data = comment_fixture(b"LLD \xff\xfe 15\xe2\x82\0");
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut buffer = [0u8; 64];
+    assert!(matches!(
+        loader.toolchain_comment(&mut buffer),
+        Err(Error::InvalidString)
+    ));
+    assert_eq!(
+        Some("LLD ?? 15??"),
+        loader.toolchain_comment_lossy(&mut buffer).unwrap()
+    );
+}
@@ -0,0 +1,373 @@
+//! Helpers for building synthetic ELF files in tests.
+//!
+//! Not every test uses every helper.
+
+#![allow(dead_code)]
+
+use std::cell::Cell;
+
+pub const ET_REL: u16 = 1;
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN: u16 = 3;
+pub const ET_CORE: u16 = 4;
+pub const EM_ARM: u16 = 0x28;
+
+/// A section to put in a synthetic ELF file.
+///
+/// Sections get the index of their position in `ElfBuilder::sections`, plus
+/// one (for the null section).
+#[derive(Debug, Clone, Default)]
+pub struct Section {
+    pub name: String,
+    pub sh_type: u32,
+    pub sh_flags: u32,
+    pub sh_addr: u32,
+    pub sh_link: u32,
+    pub sh_info: u32,
+    pub sh_addralign: u32,
+    pub sh_entsize: u32,
+    pub data: Vec<u8>,
+    /// Overrides the size, e.g. for `SHT_NOBITS` sections.
+    pub sh_size: Option<u32>,
+}
+
+impl Section {
+    pub fn new(name: &str, sh_type: u32, data: Vec<u8>) -> Section {
+        Section {
+            name: name.to_owned(),
+            sh_type,
+            data,
+            ..Default::default()
+        }
+    }
+}
+
+/// A segment to put in a synthetic ELF file.
+#[derive(Debug, Clone, Default)]
+pub struct Segment {
+    pub p_type: u32,
+    pub p_flags: u32,
+    pub p_vaddr: u32,
+    pub p_paddr: u32,
+    /// Defaults to the file size.
+    pub p_memsz: Option<u32>,
+    pub p_align: u32,
+    pub data: Vec<u8>,
+    /// Cover the file contents of this section index, instead of `data`.
+    pub section: Option<usize>,
+}
+
+/// Builds a 32-bit little-endian ELF file.
+#[derive(Debug, Clone)]
+pub struct ElfBuilder {
+    pub e_type: u16,
+    pub e_machine: u16,
+    pub e_entry: u32,
+    pub sections: Vec<Section>,
+    pub segments: Vec<Segment>,
+    /// Overrides the index of the section name string table.
+    pub e_shstrndx: Option<u16>,
+}
+
+impl Default for ElfBuilder {
+    fn default() -> Self {
+        ElfBuilder {
+            e_type: ET_EXEC,
+            e_machine: EM_ARM,
+            e_entry: 0,
+            sections: Vec::new(),
+            segments: Vec::new(),
+            e_shstrndx: None,
+        }
+    }
+}
+
+fn align_vec(out: &mut Vec<u8>, align: u32) {
+    let align = align.max(1) as usize;
+    while out.len() % align != 0 {
+        out.push(0);
+    }
+}
+
+pub fn put_u16(out: &mut [u8], offset: usize, value: u16) {
+    out[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub fn put_u32(out: &mut [u8], offset: usize, value: u32) {
+    out[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+impl ElfBuilder {
+    pub fn new() -> ElfBuilder {
+        ElfBuilder::default()
+    }
+
+    /// Add a section, returning its index in the section header table.
+    pub fn add_section(&mut self, section: Section) -> usize {
+        self.sections.push(section);
+        self.sections.len()
+    }
+
+    /// Add a segment, returning its index in the program header table.
+    pub fn add_segment(&mut self, segment: Segment) -> usize {
+        self.segments.push(segment);
+        self.segments.len() - 1
+    }
+
+    /// The index the section name string table will get.
+    pub fn shstrtab_index(&self) -> usize {
+        self.sections.len() + 1
+    }
+
+    pub fn build(&self) -> Vec<u8> {
+        let phoff = 0x34;
+        let mut out = vec![0u8; phoff + self.segments.len() * 0x20];
+
+        // Segment data
+        let mut segment_offsets = Vec::new();
+        for segment in &self.segments {
+            align_vec(&mut out, 4);
+            segment_offsets.push(out.len());
+            out.extend_from_slice(&segment.data);
+        }
+
+        // Section data
+        let mut section_offsets = Vec::new();
+        for section in &self.sections {
+            align_vec(&mut out, section.sh_addralign);
+            section_offsets.push(out.len());
+            if section.sh_type != 8 {
+                out.extend_from_slice(&section.data);
+            }
+        }
+
+        // Section names
+        let mut shstrtab = StrTab::new();
+        let mut name_offsets = Vec::new();
+        for section in &self.sections {
+            name_offsets.push(shstrtab.add(&section.name));
+        }
+        let shstrtab_name = shstrtab.add(".shstrtab");
+        let shstrtab_offset = out.len();
+        out.extend_from_slice(&shstrtab.data);
+
+        // Section headers
+        align_vec(&mut out, 4);
+        let shoff = out.len();
+        out.extend_from_slice(&[0u8; 0x28]);
+        for (idx, section) in self.sections.iter().enumerate() {
+            let mut sh = [0u8; 0x28];
+            let size = section.sh_size.unwrap_or(section.data.len() as u32);
+            put_u32(&mut sh, 0x00, name_offsets[idx]);
+            put_u32(&mut sh, 0x04, section.sh_type);
+            put_u32(&mut sh, 0x08, section.sh_flags);
+            put_u32(&mut sh, 0x0C, section.sh_addr);
+            put_u32(&mut sh, 0x10, section_offsets[idx] as u32);
+            put_u32(&mut sh, 0x14, size);
+            put_u32(&mut sh, 0x18, section.sh_link);
+            put_u32(&mut sh, 0x1C, section.sh_info);
+            put_u32(&mut sh, 0x20, section.sh_addralign);
+            put_u32(&mut sh, 0x24, section.sh_entsize);
+            out.extend_from_slice(&sh);
+        }
+        let mut sh = [0u8; 0x28];
+        put_u32(&mut sh, 0x00, shstrtab_name);
+        put_u32(&mut sh, 0x04, 3);
+        put_u32(&mut sh, 0x10, shstrtab_offset as u32);
+        put_u32(&mut sh, 0x14, shstrtab.data.len() as u32);
+        put_u32(&mut sh, 0x20, 1);
+        out.extend_from_slice(&sh);
+
+        // Program headers
+        for (idx, segment) in self.segments.iter().enumerate() {
+            let (offset, filesz) = match segment.section {
+                Some(section_idx) => {
+                    let section = &self.sections[section_idx - 1];
+                    let size = if section.sh_type == 8 {
+                        0
+                    } else {
+                        section.sh_size.unwrap_or(section.data.len() as u32)
+                    };
+                    (section_offsets[section_idx - 1] as u32, size)
+                }
+                None => (segment_offsets[idx] as u32, segment.data.len() as u32),
+            };
+            let ph = phoff + idx * 0x20;
+            put_u32(&mut out, ph, segment.p_type);
+            put_u32(&mut out, ph + 0x04, offset);
+            put_u32(&mut out, ph + 0x08, segment.p_vaddr);
+            put_u32(&mut out, ph + 0x0C, segment.p_paddr);
+            put_u32(&mut out, ph + 0x10, filesz);
+            put_u32(&mut out, ph + 0x14, segment.p_memsz.unwrap_or(filesz));
+            put_u32(&mut out, ph + 0x18, segment.p_flags);
+            put_u32(&mut out, ph + 0x1C, segment.p_align);
+        }
+
+        // ELF header
+        out[0..4].copy_from_slice(b"\x7fELF");
+        out[4] = 1; // 32-bit
+        out[5] = 1; // little-endian
+        out[6] = 1; // version 1
+        put_u16(&mut out, 0x10, self.e_type);
+        put_u16(&mut out, 0x12, self.e_machine);
+        put_u32(&mut out, 0x14, 1);
+        put_u32(&mut out, 0x18, self.e_entry);
+        put_u32(
+            &mut out,
+            0x1C,
+            if self.segments.is_empty() {
+                0
+            } else {
+                phoff as u32
+            },
+        );
+        put_u32(&mut out, 0x20, shoff as u32);
+        put_u16(&mut out, 0x28, 0x34);
+        put_u16(&mut out, 0x2A, 0x20);
+        put_u16(&mut out, 0x2C, self.segments.len() as u16);
+        put_u16(&mut out, 0x2E, 0x28);
+        put_u16(&mut out, 0x30, self.sections.len() as u16 + 2);
+        put_u16(
+            &mut out,
+            0x32,
+            self.e_shstrndx.unwrap_or(self.shstrtab_index() as u16),
+        );
+
+        out
+    }
+}
+
+/// Builds a string table.
+#[derive(Debug, Clone)]
+pub struct StrTab {
+    pub data: Vec<u8>,
+}
+
+impl StrTab {
+    pub fn new() -> StrTab {
+        StrTab { data: vec![0] }
+    }
+
+    /// Add a string, returning its offset.
+    pub fn add(&mut self, s: &str) -> u32 {
+        if s.is_empty() {
+            return 0;
+        }
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(s.as_bytes());
+        self.data.push(0);
+        offset
+    }
+}
+
+/// Encode a symbol table entry.
+pub fn symbol(st_name: u32, st_value: u32, st_size: u32, st_info: u8, st_shndx: u16) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    put_u32(&mut out, 0x00, st_name);
+    put_u32(&mut out, 0x04, st_value);
+    put_u32(&mut out, 0x08, st_size);
+    out[0x0C] = st_info;
+    put_u16(&mut out, 0x0E, st_shndx);
+    out
+}
+
+/// Encode a list of 32-bit words.
+pub fn words(values: &[u32]) -> Vec<u8> {
+    values.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
+
+/// Encode a note record, with padding.
+pub fn note(name: &[u8], n_type: u32, desc: &[u8]) -> Vec<u8> {
+    let mut out = words(&[name.len() as u32, desc.len() as u32, n_type]);
+    out.extend_from_slice(name);
+    align_vec(&mut out, 4);
+    out.extend_from_slice(desc);
+    align_vec(&mut out, 4);
+    out
+}
+
+/// A source which counts how many reads are performed.
+///
+/// Give the loader a reference, so you can still check the count.
+pub struct CountingSource<'a> {
+    pub data: &'a [u8],
+    pub reads: Cell<usize>,
+}
+
+impl<'a> CountingSource<'a> {
+    pub fn new(data: &'a [u8]) -> CountingSource<'a> {
+        CountingSource {
+            data,
+            reads: Cell::new(0),
+        }
+    }
+
+    pub fn reads(&self) -> usize {
+        self.reads.get()
+    }
+}
+
+impl neotron_loader::Source for &CountingSource<'_> {
+    type Error = neotron_loader::traits::SliceError;
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.reads.set(self.reads.get() + 1);
+        self.data.read(offset, buffer)
+    }
+}
+
+/// The errors a [`MockSource`] gives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockError {
+    /// A fault we injected, which might go away if you try again
+    Transient,
+    /// The read went past the end of the data
+    OutOfRange,
+}
+
+/// A source which fails the next few reads, to test error handling.
+///
+/// Give the loader a reference, so you can still inject faults.
+pub struct MockSource<'a> {
+    pub data: &'a [u8],
+    pub failures: Cell<u32>,
+    pub reads: Cell<usize>,
+}
+
+impl<'a> MockSource<'a> {
+    pub fn new(data: &'a [u8]) -> MockSource<'a> {
+        MockSource {
+            data,
+            failures: Cell::new(0),
+            reads: Cell::new(0),
+        }
+    }
+
+    /// Make the next `count` reads fail with `MockError::Transient`.
+    pub fn fail_next(&self, count: u32) {
+        self.failures.set(count);
+    }
+
+    pub fn reads(&self) -> usize {
+        self.reads.get()
+    }
+}
+
+impl neotron_loader::Source for &MockSource<'_> {
+    type Error = MockError;
+
+    fn read(&self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.reads.set(self.reads.get() + 1);
+        if self.failures.get() > 0 {
+            self.failures.set(self.failures.get() - 1);
+            return Err(MockError::Transient);
+        }
+        self.data
+            .read(offset, buffer)
+            .map_err(|_| MockError::OutOfRange)
+    }
+
+    fn len(&self) -> Option<u32> {
+        Some(self.data.len() as u32)
+    }
+}
@@ -0,0 +1,133 @@
+mod common;
+
+use common::{note, put_u16, words, ElfBuilder, Segment, ET_CORE};
+use neotron_loader::{Error, Loader, LoaderOptions, Note, ProgramHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// An ARM `prstatus` descriptor, with r0-r15 set to `0x1000 + n` and the
+/// status register set.
+fn prstatus() -> Vec<u8> {
+    let mut desc = vec![0u8; 72];
+    // si_signo = SIGSEGV
+    desc[0] = 11;
+    let mut regs: Vec<u32> = (0..16).map(|n| 0x1000 + n).collect();
+    regs[13] = 0x2000_7F00;
+    regs[14] = 0x0000_1235;
+    regs[15] = 0x0000_1400;
+    // cpsr, then orig_r0
+    regs.extend([0x6100_0000, 0]);
+    desc.extend(words(&regs));
+    // pr_fpvalid
+    desc.extend(words(&[0]));
+    desc
+}
+
+/// A tiny core dump: a note with the registers, then some stack and some
+/// data.
+fn core_fixture(desc: &[u8]) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.e_type = ET_CORE;
+    let mut notes = note(b"CORE\0", 3, &[0; 8]);
+    notes.extend(note(b"CORE\0", Note::NT_PRSTATUS, desc));
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        data: notes,
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_vaddr: 0x2000_7F00,
+        data: (0..=255).collect(),
+        ..Default::default()
+    });
+    // Right after the stack
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_vaddr: 0x2000_8000,
+        data: vec![0xAB; 16],
+        p_memsz: Some(32),
+        ..Default::default()
+    });
+    let mut data = elf.build();
+    // No sections
+    put_u16(&mut data, 0x2E, 0);
+    put_u16(&mut data, 0x30, 0);
+    put_u16(&mut data, 0x32, 0);
+    data
+}
+
+fn open(data: &[u8]) -> Loader<&[u8]> {
+    Loader::new_with_options(data, &LoaderOptions::new().allow_core(true)).unwrap()
+}
+
+#[test]
+fn core_needs_option() {
+    let data = core_fixture(&prstatus());
+    assert!(matches!(Loader::new(&data[..]), Err(Error::WrongElfFile)));
+    let loader = open(&data);
+    assert!(loader.is_core());
+    assert_eq!(Loader::<&[u8]>::ET_CORE, loader.e_type());
+    assert_eq!(0, loader.iter_section_headers().count());
+}
+
+#[test]
+fn registers() {
+    let data = core_fixture(&prstatus());
+    let loader = open(&data);
+    let regs = loader.core_registers().unwrap().unwrap();
+    assert_eq!(0x1000, regs.regs()[0]);
+    assert_eq!(0x100C, regs.regs()[12]);
+    assert_eq!(0x2000_7F00, regs.sp());
+    assert_eq!(0x0000_1235, regs.lr());
+    assert_eq!(0x0000_1400, regs.pc());
+    assert_eq!(0x6100_0000, regs.xpsr());
+}
+
+#[test]
+fn short_prstatus() {
+    let desc = prstatus();
+    let data = core_fixture(&desc[0..140]);
+    let loader = open(&data);
+    assert!(matches!(loader.core_registers(), Err(Error::BadNote)));
+}
+
+#[test]
+fn no_prstatus() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(None, loader.core_registers().unwrap());
+}
+
+#[test]
+fn read_memory() {
+    let data = core_fixture(&prstatus());
+    let loader = open(&data);
+    let mut buffer = [0u8; 4];
+    loader.read_at_vaddr(0x2000_7F10, &mut buffer).unwrap();
+    assert_eq!([0x10, 0x11, 0x12, 0x13], buffer);
+
+    // Across the two segments
+    loader.read_at_vaddr(0x2000_7FFE, &mut buffer).unwrap();
+    assert_eq!([0xFE, 0xFF, 0xAB, 0xAB], buffer);
+
+    // Not saved in the file
+    assert!(matches!(
+        loader.read_at_vaddr(0x2000_800E, &mut buffer),
+        Err(Error::UnmappedAddress { vaddr: 0x2000_8010 })
+    ));
+    assert!(matches!(
+        loader.read_at_vaddr(0x1000_0000, &mut buffer),
+        Err(Error::UnmappedAddress { vaddr: 0x1000_0000 })
+    ));
+
+    loader.read_at_vaddr(0x1234_5678, &mut []).unwrap();
+}
+
+#[test]
+fn read_memory_test_elf() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let rodata = loader.find_section_by_name(".rodata").unwrap().unwrap();
+    let mut buffer = vec![0u8; rodata.sh_size() as usize];
+    loader.read_at_vaddr(rodata.sh_addr(), &mut buffer).unwrap();
+    assert_eq!(loader.section_bytes(&rodata).unwrap(), &buffer[..]);
+}
@@ -0,0 +1,82 @@
+#![cfg(feature = "demangle")]
+
+use neotron_loader::{demangle::Demangle, Loader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+fn demangled_symbol(loader: &Loader<&[u8]>, raw: &str) -> String {
+    let mut scratch = [0u8; 16];
+    let symbol = loader
+        .find_symbol_by_name(raw, &mut scratch)
+        .unwrap()
+        .unwrap();
+    let symtab = loader.iter_symbols().unwrap().table().clone();
+    let mut out = String::new();
+    symbol.demangled_name(loader, &symtab, &mut out).unwrap();
+    out
+}
+
+#[test]
+fn rust_symbols() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(
+        "core::panicking::panic",
+        demangled_symbol(&loader, "_ZN4core9panicking5panic17h12f26557d2236b3cE")
+    );
+    assert_eq!(
+        "<&neotron_sdk::File as core::fmt::Write>::write_str",
+        demangled_symbol(
+            &loader,
+            "_ZN58_$LT$$RF$neotron_sdk..File$u20$as$u20$core..fmt..Write$GT$9write_str17ha367f4975c8ec66fE"
+        )
+    );
+    assert_eq!(
+        "core::ptr::drop_in_place<&mut &neotron_sdk::File>",
+        demangled_symbol(
+            &loader,
+            "_ZN4core3ptr54drop_in_place$LT$$RF$mut$u20$$RF$neotron_sdk..File$GT$17h7bd228dec942da1dE"
+        )
+    );
+    assert_eq!(
+        "neotron_sdk::API.0",
+        demangled_symbol(&loader, "_ZN11neotron_sdk3API17h3d0c94defc28acbaE.0")
+    );
+    // v0 names work too, without the crate disambiguators
+    assert_eq!(
+        "core::panicking::panic",
+        format!("{}", Demangle::new("_RNvNtCs1234_4core9panicking5panic"))
+    );
+}
+
+#[test]
+fn c_symbols() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(
+        "__aeabi_memcpy",
+        demangled_symbol(&loader, "__aeabi_memcpy")
+    );
+    assert_eq!(
+        "rust_begin_unwind",
+        demangled_symbol(&loader, "rust_begin_unwind")
+    );
+    assert!(!Demangle::new("rust_begin_unwind").is_rust());
+}
+
+#[test]
+fn symbolize_demangled() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut scratch = [0u8; 16];
+    let symbol = loader
+        .find_symbol_by_name("_ZN11neotron_sdk7get_api17h4e0ebeb2696c80c6E", &mut scratch)
+        .unwrap()
+        .unwrap();
+    let mut buffer = [0u8; 128];
+    let found = loader
+        .symbolize(symbol.address() + 2, &mut buffer)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        "neotron_sdk::get_api",
+        format!("{}", found.demangled_name())
+    );
+}
@@ -0,0 +1,248 @@
+mod common;
+
+use common::{words, ElfBuilder, Section, Segment, StrTab};
+use neotron_loader::{DynamicTag, Error, Loader, ProgramHeader, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// A position-independent executable with the given dynamic section.
+fn fixture(entries: &[(i32, u32)]) -> Vec<u8> {
+    let dynamic: Vec<u32> = entries
+        .iter()
+        .flat_map(|(tag, value)| [*tag as u32, *value])
+        .collect();
+    let mut elf = ElfBuilder::new();
+    elf.e_type = common::ET_DYN;
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_DYNAMIC,
+        p_flags: 0x6,
+        p_vaddr: 0x200,
+        p_paddr: 0x200,
+        p_align: 4,
+        data: words(&dynamic),
+        ..Default::default()
+    });
+    // Something after the dynamic section, which looks like more entries
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        data: words(&[DynamicTag::DT_FLAGS as u32, 0xDEAD_BEEF]),
+        ..Default::default()
+    });
+    elf.build()
+}
+
+#[test]
+fn iterate_dynamic_tags() {
+    let data = fixture(&[
+        (DynamicTag::DT_STRTAB, 0x100),
+        (DynamicTag::DT_SYMTAB, 0x180),
+        (DynamicTag::DT_REL, 0x300),
+        (DynamicTag::DT_RELSZ, 0x40),
+        (DynamicTag::DT_RELENT, 8),
+        (DynamicTag::DT_NULL, 0),
+        // Padding after DT_NULL must be ignored
+        (DynamicTag::DT_INIT, 0x1234),
+    ]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let tags: Vec<DynamicTag> = loader
+        .iter_dynamic_tags()
+        .unwrap()
+        .map(|t| t.unwrap())
+        .collect();
+    assert_eq!(5, tags.len());
+    assert_eq!(DynamicTag::DT_STRTAB, tags[0].tag());
+    assert_eq!(0x100, tags[0].value());
+    assert_eq!(DynamicTag::DT_RELENT, tags[4].tag());
+    assert_eq!(8, tags[4].value());
+}
+
+#[test]
+fn dynamic_tags_without_terminator() {
+    let data = fixture(&[
+        (DynamicTag::DT_STRTAB, 0x100),
+        (DynamicTag::DT_SYMTAB, 0x180),
+    ]);
+    let loader = Loader::new(&data[..]).unwrap();
+    // We must stop at the end of the segment, not carry on into the next one
+    assert_eq!(2, loader.iter_dynamic_tags().unwrap().count());
+    assert_eq!(None, loader.dynamic_value(DynamicTag::DT_FLAGS).unwrap());
+}
+
+#[test]
+fn dynamic_value() {
+    let data = fixture(&[
+        (DynamicTag::DT_FLAGS, 0x8),
+        (DynamicTag::DT_NEEDED, 1),
+        (DynamicTag::DT_NEEDED, 9),
+        (DynamicTag::DT_NULL, 0),
+    ]);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert_eq!(
+        Some(0x8),
+        loader.dynamic_value(DynamicTag::DT_FLAGS).unwrap()
+    );
+    // The first one wins
+    assert_eq!(
+        Some(1),
+        loader.dynamic_value(DynamicTag::DT_NEEDED).unwrap()
+    );
+    assert_eq!(None, loader.dynamic_value(DynamicTag::DT_INIT).unwrap());
+}
+
+#[test]
+fn dynamic_segment_that_wraps() {
+    let mut data = fixture(&[(DynamicTag::DT_NULL, 0)]);
+    let e_phoff = Loader::new(&data[..]).unwrap().e_phoff() as usize;
+    common::put_u32(&mut data, e_phoff + 0x04, 0xFFFF_FFF0);
+    common::put_u32(&mut data, e_phoff + 0x10, 0x20);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(
+        loader.iter_dynamic_tags(),
+        Err(Error::BadDynamicSection)
+    ));
+}
+
+#[test]
+fn no_dynamic_segment() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(0, loader.iter_dynamic_tags().unwrap().count());
+    assert_eq!(None, loader.dynamic_value(DynamicTag::DT_STRTAB).unwrap());
+}
+
+/// A plugin which depends on one shared library, with the string table
+/// loaded at an address which doesn't match its file offset.
+fn needed_fixture() -> Vec<u8> {
+    let mut dynstr = StrTab::new();
+    let soname = dynstr.add("plugin.so");
+    let needed = dynstr.add("libneotron.so.1");
+
+    let mut elf = ElfBuilder::new();
+    elf.e_type = common::ET_DYN;
+    let dynstr_idx = elf.add_section(Section {
+        sh_flags: 0x2,
+        sh_addr: 0x1000,
+        ..Section::new(".dynstr", SectionHeader::SHT_STRTAB, dynstr.data)
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_flags: 0x4,
+        p_vaddr: 0x1000,
+        p_paddr: 0x1000,
+        section: Some(dynstr_idx),
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_DYNAMIC,
+        p_flags: 0x6,
+        data: words(&[
+            DynamicTag::DT_SONAME as u32,
+            soname,
+            DynamicTag::DT_NEEDED as u32,
+            needed,
+            DynamicTag::DT_STRTAB as u32,
+            0x1000,
+            DynamicTag::DT_NULL as u32,
+            0,
+        ]),
+        ..Default::default()
+    });
+    elf.build()
+}
+
+#[test]
+fn iterate_needed_libraries() {
+    let data = needed_fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(loader.has_needed_libraries().unwrap());
+    let needed: Vec<_> = loader.iter_needed().unwrap().map(|n| n.unwrap()).collect();
+    assert_eq!(1, needed.len());
+    let mut buffer = [0u8; 32];
+    assert_eq!(
+        "libneotron.so.1",
+        needed[0].name(&loader, &mut buffer).unwrap()
+    );
+}
+
+#[test]
+fn needed_without_string_table() {
+    let data = fixture(&[(DynamicTag::DT_NEEDED, 1), (DynamicTag::DT_NULL, 0)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(loader.has_needed_libraries().unwrap());
+    let mut needed = loader.iter_needed().unwrap();
+    assert!(matches!(
+        needed.next(),
+        Some(Err(neotron_loader::Error::BadDynamicSection))
+    ));
+}
+
+#[test]
+fn no_needed_libraries() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert!(!loader.has_needed_libraries().unwrap());
+    assert_eq!(0, loader.iter_needed().unwrap().count());
+}
+
+#[test]
+fn needed_library_name_with_large_buffer() {
+    let data = needed_fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let needed = loader.iter_needed().unwrap().next().unwrap().unwrap();
+    // Much bigger than the rest of the file, so we must stop at the end of
+    // the string table
+    let mut buffer = [0u8; 4096];
+    assert_eq!(
+        "libneotron.so.1",
+        needed.name(&loader, &mut buffer).unwrap()
+    );
+}
+
+#[test]
+fn needed_library_name_bounded_by_strsz() {
+    let mut dynstr = StrTab::new();
+    let needed = dynstr.add("libneotron.so.1");
+    let mut elf = ElfBuilder::new();
+    elf.e_type = common::ET_DYN;
+    let dynstr_idx = elf.add_section(Section {
+        sh_flags: 0x2,
+        sh_addr: 0x1000,
+        ..Section::new(".dynstr", SectionHeader::SHT_STRTAB, dynstr.data)
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_flags: 0x4,
+        p_vaddr: 0x1000,
+        p_paddr: 0x1000,
+        section: Some(dynstr_idx),
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_DYNAMIC,
+        p_flags: 0x6,
+        data: words(&[
+            DynamicTag::DT_NEEDED as u32,
+            needed,
+            DynamicTag::DT_NEEDED as u32,
+            0x100,
+            DynamicTag::DT_STRTAB as u32,
+            0x1000,
+            // Cuts the name short
+            DynamicTag::DT_STRSZ as u32,
+            needed + 4,
+            DynamicTag::DT_NULL as u32,
+            0,
+        ]),
+        ..Default::default()
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut needed = loader.iter_needed().unwrap();
+    let mut buffer = [0u8; 32];
+    assert!(matches!(
+        needed.next().unwrap().unwrap().name(&loader, &mut buffer),
+        Err(neotron_loader::Error::InvalidString)
+    ));
+    assert!(matches!(
+        needed.next(),
+        Some(Err(neotron_loader::Error::StringOffsetOutOfRange))
+    ));
+}
@@ -0,0 +1,126 @@
+#![cfg(feature = "embedded-io")]
+
+use embedded_io::{ErrorKind, ErrorType, Read, Seek, SeekFrom};
+use neotron_loader::{
+    eio::{EioSource, EioSourceError},
+    Error, Loader, Source,
+};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// A file in memory, which reads through `embedded-io`'s `&[u8]` reader.
+struct Cursor {
+    data: &'static [u8],
+    position: usize,
+    /// The most bytes each read gives back
+    max_read: usize,
+}
+
+impl Cursor {
+    fn new(data: &'static [u8]) -> Cursor {
+        Cursor {
+            data,
+            position: 0,
+            max_read: usize::MAX,
+        }
+    }
+}
+
+impl ErrorType for Cursor {
+    type Error = ErrorKind;
+}
+
+impl Read for Cursor {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        let mut rest = self.data.get(self.position..).unwrap_or(&[]);
+        let len = buf.len().min(self.max_read);
+        let n = Read::read(&mut rest, &mut buf[0..len]).unwrap_or_else(|e| match e {});
+        self.position += n;
+        Ok(n)
+    }
+}
+
+impl Seek for Cursor {
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, ErrorKind> {
+        let position = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => (self.data.len() as u64).checked_add_signed(n),
+            SeekFrom::Current(n) => (self.position as u64).checked_add_signed(n),
+        };
+        let position = position.ok_or(ErrorKind::InvalidInput)?;
+        self.position = position as usize;
+        Ok(position)
+    }
+}
+
+/// A reader whose seeks always fail.
+struct NoSeek;
+
+impl ErrorType for NoSeek {
+    type Error = ErrorKind;
+}
+
+impl Read for NoSeek {
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize, ErrorKind> {
+        Ok(0)
+    }
+}
+
+impl Seek for NoSeek {
+    fn seek(&mut self, _pos: SeekFrom) -> Result<u64, ErrorKind> {
+        Err(ErrorKind::Unsupported)
+    }
+}
+
+#[test]
+fn load_from_cursor() {
+    let loader = Loader::new(EioSource::new(Cursor::new(ELF_DATA))).unwrap();
+    assert_eq!(loader.e_entry(), 0x200012a9);
+    let text = loader.find_section_by_name(".text").unwrap().unwrap();
+    assert_eq!(text.sh_addr(), 0x2000_1000);
+}
+
+#[test]
+fn short_reads() {
+    let mut cursor = Cursor::new(ELF_DATA);
+    cursor.max_read = 3;
+    let source = EioSource::new(cursor);
+    let mut buffer = [0u8; 52];
+    source.read(0, &mut buffer).unwrap();
+    assert_eq!(&buffer[..], &ELF_DATA[0..52]);
+    assert_eq!(source.len(), Some(ELF_DATA.len() as u32));
+    // Reads go to the offset asked for, wherever the cursor was left
+    source.read(4, &mut buffer[0..4]).unwrap();
+    assert_eq!(&buffer[0..4], &ELF_DATA[4..8]);
+}
+
+#[test]
+fn read_past_end() {
+    let source = EioSource::new(Cursor::new(ELF_DATA));
+    let mut buffer = [0u8; 8];
+    let offset = ELF_DATA.len() as u32 - 4;
+    assert!(matches!(
+        source.read(offset, &mut buffer),
+        Err(EioSourceError::UnexpectedEof)
+    ));
+}
+
+#[test]
+fn truncated_file() {
+    let source = EioSource::new(Cursor::new(&ELF_DATA[0..40]));
+    assert!(matches!(
+        Loader::new(source),
+        Err(Error::Source(EioSourceError::UnexpectedEof))
+    ));
+}
+
+#[test]
+fn seek_fails() {
+    let source = EioSource::new(NoSeek);
+    let mut buffer = [0u8; 4];
+    assert!(matches!(
+        source.read(0, &mut buffer),
+        Err(EioSourceError::Seek(ErrorKind::Unsupported))
+    ));
+    assert_eq!(source.len(), None);
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use common::{words, CountingSource, ElfBuilder, Section, Segment};
+use neotron_loader::{ExidxEntry, Loader, ProgramHeader, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+const EXIDX_ADDR: u32 = 0x2000_0100;
+
+/// Encode a 31-bit offset from `place` to `target`.
+fn prel31(place: u32, target: u32) -> u32 {
+    target.wrapping_sub(place) & 0x7FFF_FFFF
+}
+
+/// Encode an exception index table, with the given function start addresses
+/// and data words.
+fn table(entries: &[(u32, u32)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (idx, (start, data)) in entries.iter().enumerate() {
+        let place = EXIDX_ADDR + idx as u32 * 8;
+        out.extend(words(&[prel31(place, *start), *data]));
+    }
+    out
+}
+
+/// The entries in our fixture.
+fn entries() -> Vec<(u32, u32)> {
+    vec![
+        // Inline unwinding instructions
+        (0x2000_0000, 0x80A8_B0B0),
+        // Unwinding instructions in .ARM.extab at 0x2000_0200
+        (0x2000_0040, prel31(EXIDX_ADDR + 12, 0x2000_0200)),
+        // Can't be unwound
+        (0x2000_0080, ExidxEntry::EXIDX_CANTUNWIND),
+        // Marks the end of the code
+        (0x2000_0100, ExidxEntry::EXIDX_CANTUNWIND),
+    ]
+}
+
+/// An executable built with unwinding tables, with code at 0x2000_0000 and
+/// the exception index table at 0x2000_0100.
+fn fixture(with_segment: bool) -> Vec<u8> {
+    fixture_with(with_segment, &entries())
+}
+
+fn fixture_with(with_segment: bool, entries: &[(u32, u32)]) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    let text = elf.add_section(Section {
+        sh_flags: 0x6,
+        sh_addr: 0x2000_0000,
+        sh_addralign: 4,
+        ..Section::new(".text", SectionHeader::SHT_PROGBITS, vec![0; 0x100])
+    });
+    let exidx = elf.add_section(Section {
+        sh_flags: 0x82,
+        sh_addr: 0x2000_0100,
+        sh_link: text as u32,
+        sh_addralign: 4,
+        ..Section::new(".ARM.exidx", SectionHeader::SHT_ARM_EXIDX, table(entries))
+    });
+    if with_segment {
+        elf.add_segment(Segment {
+            p_type: ProgramHeader::PT_ARM_EXIDX,
+            p_flags: 0x4,
+            p_vaddr: 0x2000_0100,
+            p_paddr: 0x2000_0100,
+            p_align: 4,
+            section: Some(exidx),
+            ..Default::default()
+        });
+    }
+    elf.build()
+}
+
+#[test]
+fn from_segment() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let ph = loader.iter_program_headers().next().unwrap().unwrap();
+    assert_eq!(ProgramHeader::PT_ARM_EXIDX, ph.p_type());
+    assert_eq!(Some((EXIDX_ADDR, 32)), loader.arm_exidx().unwrap());
+}
+
+#[test]
+fn from_section() {
+    let data = fixture(false);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert_eq!(Some((EXIDX_ADDR, 32)), loader.arm_exidx().unwrap());
+}
+
+#[test]
+fn no_exidx() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(None, loader.arm_exidx().unwrap());
+    let attributes = loader
+        .find_section_by_name(".ARM.attributes")
+        .unwrap()
+        .unwrap();
+    assert_eq!(SectionHeader::SHT_ARM_ATTRIBUTES, attributes.sh_type());
+}
+
+#[test]
+fn lookup_start_of_function() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let entry = loader.exidx_entry_for(0x2000_0000).unwrap().unwrap();
+    assert_eq!(EXIDX_ADDR, entry.address());
+    assert_eq!(0x2000_0000, entry.function_start());
+    assert_eq!(Some(0x2000_0040), entry.function_end());
+    assert!(entry.is_inline());
+    assert!(!entry.is_cant_unwind());
+    assert_eq!(None, entry.extab_address());
+    assert_eq!(0x80A8_B0B0, entry.data());
+}
+
+#[test]
+fn lookup_middle_of_function() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let entry = loader.exidx_entry_for(0x2000_005A).unwrap().unwrap();
+    assert_eq!(0x2000_0040, entry.function_start());
+    assert_eq!(Some(0x2000_0080), entry.function_end());
+    assert!(!entry.is_inline());
+    assert_eq!(Some(0x2000_0200), entry.extab_address());
+
+    // Just before the next function
+    let entry = loader.exidx_entry_for(0x2000_00FE).unwrap().unwrap();
+    assert_eq!(0x2000_0080, entry.function_start());
+    assert!(entry.is_cant_unwind());
+
+    // Works via the section as well
+    let data = fixture(false);
+    let loader = Loader::new(&data[..]).unwrap();
+    let entry = loader.exidx_entry_for(0x2000_005A).unwrap().unwrap();
+    assert_eq!(0x2000_0040, entry.function_start());
+}
+
+#[test]
+fn lookup_past_the_end() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let entry = loader.exidx_entry_for(0x2000_1000).unwrap().unwrap();
+    assert_eq!(0x2000_0100, entry.function_start());
+    assert_eq!(None, entry.function_end());
+    assert!(entry.is_cant_unwind());
+}
+
+#[test]
+fn lookup_before_the_start() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(loader.exidx_entry_for(0x1FFF_FFFE).unwrap().is_none());
+
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert!(loader.exidx_entry_for(0x2000_1000).unwrap().is_none());
+}
+
+#[test]
+fn lookup_backwards_offset() {
+    // Code after the table means a negative offset
+    let data = fixture_with(true, &[(0x1000_0000, 1), (0x3000_0000, 1)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let entry = loader.exidx_entry_for(0x1000_0010).unwrap().unwrap();
+    assert_eq!(0x1000_0000, entry.function_start());
+    assert_eq!(Some(0x3000_0000), entry.function_end());
+    let entry = loader.exidx_entry_for(0x3000_0010).unwrap().unwrap();
+    assert_eq!(0x3000_0000, entry.function_start());
+}
+
+#[test]
+fn lookup_is_logarithmic() {
+    let entries: Vec<(u32, u32)> = (0..1024)
+        .map(|idx| (0x2000_0000 + idx * 0x10, 0x80B0_B0B0))
+        .collect();
+    let data = fixture_with(true, &entries);
+    let source = CountingSource::new(&data);
+    let loader = Loader::new(&source).unwrap();
+    let before = source.reads();
+    let entry = loader.exidx_entry_for(0x2000_1234).unwrap().unwrap();
+    assert_eq!(0x2000_1230, entry.function_start());
+    assert_eq!(Some(0x2000_1240), entry.function_end());
+    // Finding the table takes a few reads, then at most 11 for the search
+    // and 3 for the entry itself
+    let reads = source.reads() - before;
+    assert!(reads < 32, "took {} reads", reads);
+}
+
+#[test]
+fn lookup_in_wrapping_table() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let ph = loader.e_phoff() as usize;
+    // The table would wrap past the top of memory, and then past the end
+    // of the address space in the file
+    for field in [0x08, 0x04] {
+        let mut bad = data.clone();
+        common::put_u32(&mut bad, ph + field, 0xFFFF_FFF0);
+        let loader = Loader::new(&bad[..]).unwrap();
+        assert!(matches!(
+            loader.exidx_entry_for(0x2000_0010),
+            Err(neotron_loader::Error::DataOutOfBounds)
+        ));
+    }
+}
@@ -0,0 +1,54 @@
+#![cfg(feature = "arbitrary")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use neotron_loader::{FuzzElf, Loader};
+
+/// Make some repeatable random bytes (xorshift32).
+fn random_bytes(seed: u32, len: usize) -> Vec<u8> {
+    let mut state = seed | 1;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+/// Make a file, the way a fuzz target would.
+fn fuzz_elf(data: &[u8]) -> FuzzElf {
+    FuzzElf::arbitrary(&mut Unstructured::new(data)).unwrap()
+}
+
+#[test]
+fn empty_input_is_valid() {
+    let elf = fuzz_elf(&[]);
+    let loader = Loader::new(elf.as_bytes()).unwrap();
+    assert_eq!(loader.e_phnum(), 0);
+    assert_eq!(loader.e_shnum(), 0);
+}
+
+#[test]
+fn same_input_same_file() {
+    let data = random_bytes(1, 500);
+    assert_eq!(fuzz_elf(&data).as_bytes(), fuzz_elf(&data).as_bytes());
+}
+
+#[test]
+fn random_inputs() {
+    let mut parsed = 0;
+    for seed in 0..2000 {
+        let data = random_bytes(seed, 64 + (seed as usize % 1024));
+        let elf = fuzz_elf(&data);
+        let Ok(loader) = Loader::new(elf.as_bytes()) else {
+            continue;
+        };
+        parsed += 1;
+        // Walk the tables, as the fuzz target does
+        loader.iter_program_headers().for_each(drop);
+        loader.iter_section_headers().for_each(drop);
+    }
+    // Most files should get past `Loader::new`
+    assert!(parsed > 1000, "only {parsed} parsed");
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use common::{symbol, words, ElfBuilder, Section, StrTab, ET_REL};
+use neotron_loader::{Error, Loader, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// An object file built with `-ffunction-sections`, with an inline function
+/// in a COMDAT group.
+fn fixture() -> Vec<u8> {
+    let mut strtab = StrTab::new();
+    let inline_fn = strtab.add("_ZN3app6helper17h0123456789abcdefE");
+
+    let mut elf = ElfBuilder::new();
+    elf.e_type = ET_REL;
+    let group_idx = elf.sections.len() + 1;
+    let text = group_idx + 1;
+    let rel_text = group_idx + 2;
+    let strtab_idx = group_idx + 3;
+    let symtab = group_idx + 4;
+    elf.add_section(Section {
+        sh_link: symtab as u32,
+        sh_info: 1,
+        sh_entsize: 4,
+        sh_addralign: 4,
+        ..Section::new(
+            ".group",
+            SectionHeader::SHT_GROUP,
+            words(&[SectionHeader::GRP_COMDAT, text as u32, rel_text as u32]),
+        )
+    });
+    elf.add_section(Section {
+        sh_flags: 0x206,
+        sh_addralign: 2,
+        ..Section::new(
+            ".text._ZN3app6helper17h0123456789abcdefE",
+            SectionHeader::SHT_PROGBITS,
+            vec![0x70, 0x47],
+        )
+    });
+    elf.add_section(Section {
+        sh_flags: 0x240,
+        sh_link: symtab as u32,
+        sh_info: text as u32,
+        sh_entsize: 8,
+        ..Section::new(
+            ".rel.text._ZN3app6helper17h0123456789abcdefE",
+            SectionHeader::SHT_REL,
+            Vec::new(),
+        )
+    });
+    elf.add_section(Section::new(
+        ".strtab",
+        SectionHeader::SHT_STRTAB,
+        strtab.data.clone(),
+    ));
+    let mut symbols = Vec::new();
+    symbols.extend(symbol(0, 0, 0, 0, 0));
+    symbols.extend(symbol(inline_fn, 1, 2, 0x22, text as u16));
+    elf.add_section(Section {
+        sh_link: strtab_idx as u32,
+        sh_info: 1,
+        sh_entsize: 16,
+        sh_addralign: 4,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, symbols)
+    });
+    elf.build()
+}
+
+#[test]
+fn group_members() {
+    let data = fixture();
+    let loader = Loader::new_object(&data[..]).unwrap();
+    let group = loader.find_section_by_name(".group").unwrap().unwrap();
+    let members = group.iter_group_members(&loader).unwrap();
+    assert!(members.is_comdat());
+    assert_eq!(SectionHeader::GRP_COMDAT, members.flags());
+    assert_eq!(5, members.symbol_table());
+    assert_eq!(1, members.signature());
+    let members: Vec<u32> = members.map(|m| m.unwrap()).collect();
+    assert_eq!(vec![2, 3], members);
+
+    let mut buffer = [0u8; 64];
+    let text = SectionHeader::new(&loader, members[0] as u16).unwrap();
+    assert_eq!(
+        ".text._ZN3app6helper17h0123456789abcdefE",
+        text.sh_name(&loader, &mut buffer).unwrap()
+    );
+    assert!(text.flags().contains(SectionHeader::SHF_GROUP));
+}
+
+#[test]
+fn group_signature() {
+    let data = fixture();
+    let loader = Loader::new_object(&data[..]).unwrap();
+    let group = loader.find_section_by_name(".group").unwrap().unwrap();
+    let signature = group.group_signature(&loader).unwrap();
+    let symtab = SectionHeader::new(&loader, group.sh_link() as u16).unwrap();
+    let mut buffer = [0u8; 64];
+    assert_eq!(
+        "_ZN3app6helper17h0123456789abcdefE",
+        signature.st_name(&loader, &symtab, &mut buffer).unwrap()
+    );
+}
+
+#[test]
+fn not_a_group() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let text = loader.find_section_by_name(".text").unwrap().unwrap();
+    assert!(matches!(
+        text.iter_group_members(&loader),
+        Err(Error::WrongSectionType)
+    ));
+    assert!(matches!(
+        text.group_signature(&loader),
+        Err(Error::WrongSectionType)
+    ));
+}
+
+#[test]
+fn bad_entry_size() {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_entsize: 8,
+        ..Section::new(".group", SectionHeader::SHT_GROUP, words(&[1, 2]))
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let group = loader.find_section_by_name(".group").unwrap().unwrap();
+    assert!(matches!(
+        group.iter_group_members(&loader),
+        Err(Error::BadGroupSection)
+    ));
+}
+
+#[test]
+fn stops_after_error() {
+    let mut elf = ElfBuilder::new();
+    let idx = elf.add_section(Section {
+        sh_entsize: 4,
+        ..Section::new(".group", SectionHeader::SHT_GROUP, words(&[1, 2]))
+    });
+    let mut data = elf.build();
+    // Make the section run far off the end of the file
+    let e_shoff = Loader::new(&data[..]).unwrap().e_shoff() as usize;
+    common::put_u32(&mut data, e_shoff + idx * 0x28 + 0x14, 0x1000);
+    let loader = Loader::new(&data[..]).unwrap();
+    let group = loader.find_section_by_name(".group").unwrap().unwrap();
+    let members: Vec<_> = group.iter_group_members(&loader).unwrap().collect();
+    assert!(members.len() < 100, "{} members", members.len());
+    assert!(members.last().unwrap().is_err());
+    assert!(members[..members.len() - 1].iter().all(|m| m.is_ok()));
+}
+
+#[test]
+fn group_that_wraps() {
+    let mut elf = ElfBuilder::new();
+    let idx = elf.add_section(Section {
+        sh_entsize: 4,
+        ..Section::new(".group", SectionHeader::SHT_GROUP, words(&[1, 2]))
+    });
+    let mut data = elf.build();
+    let e_shoff = Loader::new(&data[..]).unwrap().e_shoff() as usize;
+    common::put_u32(&mut data, e_shoff + idx * 0x28 + 0x10, 0xFFFF_FFF0);
+    common::put_u32(&mut data, e_shoff + idx * 0x28 + 0x14, 0x20);
+    let loader = Loader::new(&data[..]).unwrap();
+    let group = loader.find_section_by_name(".group").unwrap().unwrap();
+    assert!(matches!(
+        group.iter_group_members(&loader),
+        Err(Error::BadGroupSection)
+    ));
+}
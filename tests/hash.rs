@@ -0,0 +1,400 @@
+mod common;
+
+use common::{symbol, words, ElfBuilder, Section, StrTab};
+use neotron_loader::{
+    hash::{elf_hash, gnu_hash},
+    Loader, SectionHeader,
+};
+
+const NAMES: [&str; 5] = [
+    "plugin_init",
+    "plugin_exit",
+    "plugin_version",
+    "helper",
+    "printf",
+];
+
+/// Build a SysV hash table for symbols 1..=names.len().
+fn sysv_hash(names: &[&str], nbucket: u32) -> Vec<u8> {
+    let nchain = names.len() as u32 + 1;
+    let mut buckets = vec![0u32; nbucket as usize];
+    let mut chains = vec![0u32; nchain as usize];
+    for (idx, name) in names.iter().enumerate() {
+        let sym_idx = idx as u32 + 1;
+        let bucket = (elf_hash(name.as_bytes()) % nbucket) as usize;
+        // Push onto the front of the chain
+        chains[sym_idx as usize] = buckets[bucket];
+        buckets[bucket] = sym_idx;
+    }
+    let mut table = vec![nbucket, nchain];
+    table.extend(buckets);
+    table.extend(chains);
+    words(&table)
+}
+
+/// An executable with a dynamic symbol table, and optionally a hash table.
+fn fixture(with_hash: bool) -> Vec<u8> {
+    let mut dynstr = StrTab::new();
+    let mut dynsym = symbol(0, 0, 0, 0, 0).to_vec();
+    for (idx, name) in NAMES.iter().enumerate() {
+        let name_offset = dynstr.add(name);
+        // Global functions
+        dynsym.extend(symbol(
+            name_offset,
+            0x2000_1001 + idx as u32 * 0x10,
+            8,
+            0x12,
+            1,
+        ));
+    }
+
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_flags: 0x6,
+        sh_addr: 0x2000_1000,
+        ..Section::new(".text", SectionHeader::SHT_PROGBITS, vec![0; 0x100])
+    });
+    let dynsym_idx = elf.add_section(Section {
+        sh_link: 3,
+        sh_info: 1,
+        sh_entsize: 16,
+        sh_addralign: 4,
+        ..Section::new(".dynsym", SectionHeader::SHT_DYNSYM, dynsym)
+    });
+    elf.add_section(Section::new(
+        ".dynstr",
+        SectionHeader::SHT_STRTAB,
+        dynstr.data,
+    ));
+    if with_hash {
+        elf.add_section(Section {
+            sh_link: dynsym_idx as u32,
+            sh_entsize: 4,
+            sh_addralign: 4,
+            ..Section::new(".hash", SectionHeader::SHT_HASH, sysv_hash(&NAMES, 3))
+        });
+    }
+    elf.build()
+}
+
+#[test]
+fn known_hashes() {
+    assert_eq!(0, elf_hash(b""));
+    assert_eq!(0x0779_05a6, elf_hash(b"printf"));
+    assert_eq!(0x0006_cf04, elf_hash(b"exit"));
+    assert_eq!(0x0c33_5095, elf_hash(b"freelocale"));
+}
+
+#[test]
+fn hash_lookup_finds_symbols() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut scratch = [0u8; 8];
+    for (idx, name) in NAMES.iter().enumerate() {
+        let symbol = loader.hash_lookup(name, &mut scratch).unwrap().unwrap();
+        assert_eq!(0x2000_1001 + idx as u32 * 0x10, symbol.st_value());
+    }
+}
+
+#[test]
+fn hash_lookup_missing_symbol() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut scratch = [0u8; 8];
+    assert!(loader
+        .hash_lookup("plugin_reset", &mut scratch)
+        .unwrap()
+        .is_none());
+    assert!(loader.hash_lookup("", &mut scratch).unwrap().is_none());
+}
+
+#[test]
+fn hash_lookup_without_hash_table() {
+    let data = fixture(false);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut scratch = [0u8; 8];
+    let symbol = loader.hash_lookup("helper", &mut scratch).unwrap().unwrap();
+    assert_eq!(0x2000_1031, symbol.st_value());
+    assert!(loader
+        .hash_lookup("plugin_reset", &mut scratch)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn hash_lookup_detects_loops() {
+    let mut data = fixture(true);
+    // Point every chain entry at symbol 1, which loops forever
+    let loader = Loader::new(&data[..]).unwrap();
+    let hash_offset = loader
+        .iter_section_headers()
+        .map(|sh| sh.unwrap())
+        .find(|sh| sh.sh_type() == SectionHeader::SHT_HASH)
+        .unwrap()
+        .sh_offset() as usize;
+    let chains = hash_offset + 8 + 3 * 4;
+    for idx in 0..6 {
+        common::put_u32(&mut data, chains + idx * 4, 1);
+    }
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut scratch = [0u8; 8];
+    assert!(matches!(
+        loader.hash_lookup("plugin_reset", &mut scratch),
+        Err(neotron_loader::Error::BadHashTable)
+    ));
+}
+
+const BLOOM_SIZE: u32 = 2;
+const BLOOM_SHIFT: u32 = 5;
+const GNU_NBUCKETS: u32 = 3;
+
+/// Sort names into GNU hash bucket order, as the symbol table must be.
+fn gnu_sorted_names() -> Vec<&'static str> {
+    let mut names = NAMES.to_vec();
+    names.sort_by_key(|name| gnu_hash(name.as_bytes()) % GNU_NBUCKETS);
+    names
+}
+
+fn bloom_bits(hash: u32) -> (usize, u32) {
+    let word = ((hash / 32) % BLOOM_SIZE) as usize;
+    let mask = (1 << (hash % 32)) | (1 << ((hash >> BLOOM_SHIFT) % 32));
+    (word, mask)
+}
+
+/// Build a GNU hash table for the given names, which are symbols 1.. in the
+/// symbol table.
+fn gnu_hash_table(names: &[&str]) -> Vec<u8> {
+    let symoffset = 1;
+    let mut bloom = vec![0u32; BLOOM_SIZE as usize];
+    let mut buckets = vec![0u32; GNU_NBUCKETS as usize];
+    let mut chain = Vec::new();
+    for (idx, name) in names.iter().enumerate() {
+        let hash = gnu_hash(name.as_bytes());
+        let (word, mask) = bloom_bits(hash);
+        bloom[word] |= mask;
+        let bucket = (hash % GNU_NBUCKETS) as usize;
+        if buckets[bucket] == 0 {
+            buckets[bucket] = idx as u32 + symoffset;
+        }
+        let is_last = names
+            .get(idx + 1)
+            .map(|next| gnu_hash(next.as_bytes()) % GNU_NBUCKETS != bucket as u32)
+            .unwrap_or(true);
+        chain.push(if is_last { hash | 1 } else { hash & !1 });
+    }
+    let mut table = vec![GNU_NBUCKETS, symoffset, BLOOM_SIZE, BLOOM_SHIFT];
+    table.extend(bloom);
+    table.extend(buckets);
+    table.extend(chain);
+    words(&table)
+}
+
+/// An executable with a GNU hash table as its first section.
+///
+/// If `with_sysv_hash` is set, we also add an empty SysV hash table, which
+/// finds nothing.
+fn gnu_fixture(with_sysv_hash: bool) -> Vec<u8> {
+    let names = gnu_sorted_names();
+    let mut dynstr = StrTab::new();
+    let mut dynsym = symbol(0, 0, 0, 0, 0).to_vec();
+    for name in names.iter() {
+        let name_offset = dynstr.add(name);
+        dynsym.extend(symbol(name_offset, gnu_value(name), 8, 0x12, 2));
+    }
+
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_link: 3,
+        sh_addralign: 4,
+        ..Section::new(
+            ".gnu.hash",
+            SectionHeader::SHT_GNU_HASH,
+            gnu_hash_table(&names),
+        )
+    });
+    elf.add_section(Section {
+        sh_flags: 0x6,
+        sh_addr: 0x2000_1000,
+        ..Section::new(".text", SectionHeader::SHT_PROGBITS, vec![0; 0x100])
+    });
+    elf.add_section(Section {
+        sh_link: 4,
+        sh_info: 1,
+        sh_entsize: 16,
+        sh_addralign: 4,
+        ..Section::new(".dynsym", SectionHeader::SHT_DYNSYM, dynsym)
+    });
+    elf.add_section(Section::new(
+        ".dynstr",
+        SectionHeader::SHT_STRTAB,
+        dynstr.data,
+    ));
+    if with_sysv_hash {
+        elf.add_section(Section {
+            sh_link: 3,
+            sh_entsize: 4,
+            sh_addralign: 4,
+            ..Section::new(".hash", SectionHeader::SHT_HASH, words(&[0, 0]))
+        });
+    }
+    elf.build()
+}
+
+/// The address we gave each symbol in the GNU hash fixture.
+fn gnu_value(name: &str) -> u32 {
+    let idx = NAMES.iter().position(|n| *n == name).unwrap();
+    0x2000_1001 + idx as u32 * 0x10
+}
+
+#[test]
+fn known_gnu_hashes() {
+    assert_eq!(0x0000_1505, gnu_hash(b""));
+    assert_eq!(0x156b_2bb8, gnu_hash(b"printf"));
+    assert_eq!(0x7c96_7e3f, gnu_hash(b"exit"));
+    assert_eq!(0x49fe_b217, gnu_hash(b"freelocale"));
+}
+
+#[test]
+fn gnu_hash_lookup_finds_symbols() {
+    let data = gnu_fixture(false);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut scratch = [0u8; 8];
+    for name in NAMES.iter() {
+        let symbol = loader.hash_lookup(name, &mut scratch).unwrap().unwrap();
+        assert_eq!(gnu_value(name), symbol.st_value());
+    }
+    assert!(loader
+        .hash_lookup("plugin_reset", &mut scratch)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn gnu_hash_preferred_over_sysv_hash() {
+    let data = gnu_fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut scratch = [0u8; 8];
+    // The SysV table is empty, so this only works if we use the GNU table
+    let symbol = loader
+        .hash_lookup("plugin_exit", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!(gnu_value("plugin_exit"), symbol.st_value());
+}
+
+#[test]
+fn gnu_hash_bloom_filter_rejects_quickly() {
+    let data = gnu_fixture(false);
+    let table = gnu_hash_table(&gnu_sorted_names());
+    let bloom: Vec<u32> = table[16..16 + 4 * BLOOM_SIZE as usize]
+        .chunks(4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .collect();
+    // Find a name the bloom filter rejects
+    let missing = (0..100)
+        .map(|n| format!("missing_{}", n))
+        .find(|name| {
+            let (word, mask) = bloom_bits(gnu_hash(name.as_bytes()));
+            bloom[word] & mask != mask
+        })
+        .unwrap();
+
+    let source = common::CountingSource::new(&data);
+    let loader = Loader::new(&source).unwrap();
+    let mut scratch = [0u8; 8];
+
+    let before = source.reads();
+    assert!(loader
+        .hash_lookup(&missing, &mut scratch)
+        .unwrap()
+        .is_none());
+    let negative_reads = source.reads() - before;
+
+    let before = source.reads();
+    assert!(loader
+        .hash_lookup("plugin_version", &mut scratch)
+        .unwrap()
+        .is_some());
+    let positive_reads = source.reads() - before;
+
+    // Finding the section costs two section headers (ten reads each), then we
+    // need the four header words and one bloom filter word, and nothing else.
+    assert_eq!(25, negative_reads);
+    assert!(negative_reads < positive_reads);
+}
+
+/// Find the section header and the data of the first section of the given
+/// type, as offsets into the file.
+fn section_offsets(data: &[u8], sh_type: u32) -> (usize, usize) {
+    let loader = Loader::new(data).unwrap();
+    let (idx, sh) = loader
+        .iter_section_headers()
+        .map(|sh| sh.unwrap())
+        .enumerate()
+        .find(|(_idx, sh)| sh.sh_type() == sh_type)
+        .unwrap();
+    let header = loader.e_shoff() as usize + idx * 0x28;
+    (header, sh.sh_offset() as usize)
+}
+
+#[test]
+fn hash_lookup_rejects_oversized_tables() {
+    let mut scratch = [0u8; 8];
+    let data = fixture(true);
+    let (_header, table) = section_offsets(&data, SectionHeader::SHT_HASH);
+
+    // So many buckets that the chains would start past 4 GiB
+    let mut bad = data.clone();
+    common::put_u32(&mut bad, table, 0x4000_0001);
+    let loader = Loader::new(&bad[..]).unwrap();
+    assert!(matches!(
+        loader.hash_lookup("helper", &mut scratch),
+        Err(neotron_loader::Error::BadHashTable)
+    ));
+
+    // More chain entries than the section holds
+    let mut bad = data.clone();
+    common::put_u32(&mut bad, table + 4, 7);
+    let loader = Loader::new(&bad[..]).unwrap();
+    assert!(matches!(
+        loader.hash_lookup("helper", &mut scratch),
+        Err(neotron_loader::Error::BadHashTable)
+    ));
+}
+
+#[test]
+fn gnu_hash_lookup_rejects_oversized_tables() {
+    let mut scratch = [0u8; 8];
+    let data = gnu_fixture(false);
+    let (header, table) = section_offsets(&data, SectionHeader::SHT_GNU_HASH);
+
+    // A bloom filter which wraps past 4 GiB
+    let mut bad = data.clone();
+    common::put_u32(&mut bad, table + 8, 0x4000_0001);
+    let loader = Loader::new(&bad[..]).unwrap();
+    assert!(matches!(
+        loader.hash_lookup("helper", &mut scratch),
+        Err(neotron_loader::Error::BadHashTable)
+    ));
+
+    // More buckets than the section holds
+    let mut bad = data.clone();
+    common::put_u32(&mut bad, table, 0x1000);
+    let loader = Loader::new(&bad[..]).unwrap();
+    assert!(matches!(
+        loader.hash_lookup("helper", &mut scratch),
+        Err(neotron_loader::Error::BadHashTable)
+    ));
+
+    // A section too short to hold the whole chain, so the last symbol's
+    // chain entry is missing
+    let mut bad = data.clone();
+    let sh_size = u32::from_le_bytes(bad[header + 0x14..header + 0x18].try_into().unwrap());
+    common::put_u32(&mut bad, header + 0x14, sh_size - 4);
+    let loader = Loader::new(&bad[..]).unwrap();
+    let last = *gnu_sorted_names().last().unwrap();
+    assert!(matches!(
+        loader.hash_lookup(last, &mut scratch),
+        Err(neotron_loader::Error::BadHashTable)
+    ));
+}
@@ -0,0 +1,488 @@
+mod common;
+
+use common::CountingSource;
+use neotron_loader::{
+    traits::{SubSource, SubSourceError},
+    Error, IndexKind, Loader, OwnedIterProgramHeaders, ProgramHeader, SectionHeader, Source,
+};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+#[test]
+fn section_size_hint() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut iter = loader.iter_section_headers();
+    assert_eq!((20, Some(20)), iter.size_hint());
+    assert_eq!(20, iter.len());
+    iter.next().unwrap().unwrap();
+    iter.next().unwrap().unwrap();
+    assert_eq!((18, Some(18)), iter.size_hint());
+    assert_eq!(18, iter.by_ref().count());
+    assert_eq!((0, Some(0)), iter.size_hint());
+    assert!(iter.next().is_none());
+    assert_eq!(0, iter.len());
+}
+
+#[test]
+fn program_size_hint() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut iter = loader.iter_program_headers();
+    assert_eq!((6, Some(6)), iter.size_hint());
+    iter.next().unwrap().unwrap();
+    assert_eq!(5, iter.len());
+    assert_eq!(5, iter.by_ref().count());
+    assert_eq!((0, Some(0)), iter.size_hint());
+}
+
+#[test]
+fn section_nth() {
+    let source = CountingSource::new(ELF_DATA);
+    let loader = Loader::new(&source).unwrap();
+
+    let before = source.reads();
+    loader.iter_section_headers().next().unwrap().unwrap();
+    let one_header = source.reads() - before;
+
+    let mut iter = loader.iter_section_headers();
+    let before = source.reads();
+    let fifth = iter.nth(4).unwrap().unwrap();
+    assert_eq!(one_header, source.reads() - before);
+    assert_eq!(15, iter.len());
+
+    let mut buffer = [0u8; 32];
+    assert_eq!(".bss", fifth.sh_name(&loader, &mut buffer).unwrap());
+    let sixth = iter.next().unwrap().unwrap();
+    assert_eq!(".uninit", sixth.sh_name(&loader, &mut buffer).unwrap());
+
+    // Skipping off the end
+    assert!(iter.nth(100).is_none());
+    assert_eq!(0, iter.len());
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn program_nth() {
+    let source = CountingSource::new(ELF_DATA);
+    let loader = Loader::new(&source).unwrap();
+
+    let before = source.reads();
+    loader.iter_program_headers().next().unwrap().unwrap();
+    let one_header = source.reads() - before;
+
+    let mut iter = loader.iter_program_headers();
+    let before = source.reads();
+    let fifth = iter.nth(4).unwrap().unwrap();
+    assert_eq!(one_header, source.reads() - before);
+    assert!(fifth.flags().is_writable());
+    assert_eq!(1, iter.len());
+    assert!(iter.nth(1).is_none());
+}
+
+#[test]
+fn section_rev() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let forward: Vec<u32> = loader
+        .iter_section_headers()
+        .map(|sh| sh.unwrap().sh_offset())
+        .collect();
+    let mut backward: Vec<u32> = loader
+        .iter_section_headers()
+        .rev()
+        .map(|sh| sh.unwrap().sh_offset())
+        .collect();
+    backward.reverse();
+    assert_eq!(forward, backward);
+
+    // .strtab is last, so this reads just one header
+    let source = CountingSource::new(ELF_DATA);
+    let loader = Loader::new(&source).unwrap();
+    let before = source.reads();
+    let strtab = loader
+        .iter_section_headers()
+        .rfind(|sh| sh.as_ref().unwrap().sh_type() == neotron_loader::SectionHeader::SHT_STRTAB)
+        .unwrap()
+        .unwrap();
+    assert_eq!(10, source.reads() - before);
+    let mut buffer = [0u8; 32];
+    assert_eq!(".strtab", strtab.sh_name(&loader, &mut buffer).unwrap());
+}
+
+#[test]
+fn program_rev() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let forward: Vec<u32> = loader
+        .iter_program_headers()
+        .map(|ph| ph.unwrap().p_offset())
+        .collect();
+    let mut backward: Vec<u32> = loader
+        .iter_program_headers()
+        .rev()
+        .map(|ph| ph.unwrap().p_offset())
+        .collect();
+    backward.reverse();
+    assert_eq!(forward, backward);
+}
+
+#[test]
+fn alternating() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let all: Vec<u32> = loader
+        .iter_section_headers()
+        .map(|sh| sh.unwrap().sh_offset())
+        .collect();
+    let mut iter = loader.iter_section_headers();
+    let mut front = Vec::new();
+    let mut back = Vec::new();
+    while let Some(sh) = iter.next() {
+        front.push(sh.unwrap().sh_offset());
+        assert_eq!(all.len() - front.len() - back.len(), iter.len());
+        match iter.next_back() {
+            Some(sh) => back.push(sh.unwrap().sh_offset()),
+            None => break,
+        }
+    }
+    assert!(iter.next().is_none());
+    assert!(iter.next_back().is_none());
+    back.reverse();
+    front.extend(back);
+    assert_eq!(all, front);
+
+    // Odd number of entries
+    let mut iter = loader.iter_program_headers();
+    assert!(iter.nth(1).is_some());
+    assert!(iter.next_back().is_some());
+    assert!(iter.next().is_some());
+    assert!(iter.next_back().is_some());
+    assert_eq!(1, iter.len());
+    assert!(iter.next_back().is_some());
+    assert!(iter.next().is_none());
+    assert!(iter.next_back().is_none());
+}
+
+#[test]
+fn rev_after_error() {
+    // The last two section headers are cut off
+    let mut data = ELF_DATA.to_vec();
+    let loader = Loader::new(ELF_DATA).unwrap();
+    data.truncate(loader.e_shoff() as usize + 18 * 0x28 + 4);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut iter = loader.iter_section_headers();
+    assert!(iter.next_back().unwrap().is_err());
+    // The iterator stops after an error, from either end
+    assert_eq!(0, iter.len());
+    assert!(iter.next_back().is_none());
+    assert!(iter.next().is_none());
+    // Going forwards, we get all the good ones first
+    let mut iter = loader.iter_section_headers();
+    assert_eq!(18, iter.by_ref().take(18).filter(|sh| sh.is_ok()).count());
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn clone_loader() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let copy = loader.clone();
+    // The headers aren't `PartialEq`, so compare how they print
+    assert!(loader
+        .iter_section_headers()
+        .map(|sh| format!("{:?}", sh.unwrap()))
+        .eq(copy
+            .iter_section_headers()
+            .map(|sh| format!("{:?}", sh.unwrap()))));
+    assert!(loader
+        .iter_program_headers()
+        .map(|ph| format!("{:?}", ph.unwrap()))
+        .eq(copy
+            .iter_program_headers()
+            .map(|ph| format!("{:?}", ph.unwrap()))));
+}
+
+#[test]
+fn map_source() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let (e_type, e_machine, e_entry) = (loader.e_type(), loader.e_machine(), loader.e_entry());
+    let (e_phoff, e_shoff) = (loader.e_phoff(), loader.e_shoff());
+    let (e_phnum, e_shnum) = (loader.e_phnum(), loader.e_shnum());
+
+    let source = CountingSource::new(ELF_DATA);
+    let loader = loader.map_source(|_data| &source);
+    // Nothing is read again to make the new loader
+    assert_eq!(0, source.reads());
+    assert_eq!(e_type, loader.e_type());
+    assert_eq!(e_machine, loader.e_machine());
+    assert_eq!(e_entry, loader.e_entry());
+    assert_eq!(e_phoff, loader.e_phoff());
+    assert_eq!(e_shoff, loader.e_shoff());
+    assert_eq!(e_phnum, loader.e_phnum());
+    assert_eq!(e_shnum, loader.e_shnum());
+    // The cached section names still work, using the new source
+    let text = loader.find_section_by_name(".text").unwrap().unwrap();
+    assert_eq!(0x444, text.sh_size());
+    assert!(source.reads() > 0);
+}
+
+#[test]
+fn sub_source() {
+    // Put some junk before and after the file
+    let mut data = vec![0xAA; 100];
+    data.extend_from_slice(ELF_DATA);
+    data.extend([0xBB; 100]);
+    let window = SubSource::new(&data[..], 100, ELF_DATA.len() as u32);
+    assert_eq!(Some(ELF_DATA.len() as u32), window.len());
+    let mut buffer = [0u8; 2];
+    assert!(matches!(
+        window.read(ELF_DATA.len() as u32 - 1, &mut buffer),
+        Err(SubSourceError::OutOfRange)
+    ));
+    let loader = Loader::new(window).unwrap();
+    let text = loader.find_section_by_name(".text").unwrap().unwrap();
+    assert_eq!(0x444, text.sh_size());
+
+    // A window which runs past 4 GiB
+    let window = SubSource::new(&data[..], 100, u32::MAX);
+    assert!(matches!(
+        window.read(u32::MAX - 10, &mut buffer),
+        Err(SubSourceError::OutOfRange)
+    ));
+}
+
+/// A resumable installer, which does one segment per step.
+struct Installer {
+    headers: OwnedIterProgramHeaders<&'static [u8]>,
+    seen: Vec<u32>,
+}
+
+impl Installer {
+    fn step(&mut self) -> bool {
+        match self.headers.next() {
+            Some(ph) => {
+                self.seen.push(ph.unwrap().p_type());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn take_two(
+    mut headers: OwnedIterProgramHeaders<&'static [u8]>,
+) -> OwnedIterProgramHeaders<&'static [u8]> {
+    headers.next().unwrap().unwrap();
+    headers.next().unwrap().unwrap();
+    headers
+}
+
+#[test]
+fn owned_program_headers() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let headers = take_two(loader.into_program_headers());
+    assert_eq!(4, headers.len());
+
+    // Carry on in another thread
+    let mut installer = Installer {
+        headers,
+        seen: Vec::new(),
+    };
+    let installer = std::thread::spawn(move || {
+        installer.step();
+        installer
+    })
+    .join()
+    .unwrap();
+    assert_eq!(
+        0x20001000,
+        installer
+            .headers
+            .loader()
+            .iter_program_headers()
+            .nth(2)
+            .unwrap()
+            .unwrap()
+            .p_paddr()
+    );
+
+    let mut installer = installer;
+    while installer.step() {}
+    assert_eq!(
+        vec![
+            ProgramHeader::PT_LOAD,
+            ProgramHeader::PT_LOAD,
+            ProgramHeader::PT_LOAD,
+            ProgramHeader::PT_GNU_STACK
+        ],
+        installer.seen
+    );
+
+    // Get the loader back and start again
+    let loader = installer.headers.into_inner();
+    assert_eq!(6, loader.iter_program_headers().count());
+}
+
+#[test]
+fn owned_section_headers() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut sections = loader.into_section_headers();
+    assert_eq!(20, sections.len());
+    let first = sections.next().unwrap().unwrap();
+    assert_eq!(0, first.sh_size());
+    let strtab = sections.next_back().unwrap().unwrap();
+    assert_eq!(0x70c4, strtab.sh_offset());
+    // Skip `.text`
+    let rodata = sections.nth(1).unwrap().unwrap();
+    assert_eq!(0x38, rodata.sh_size());
+    assert_eq!(16, sections.len());
+
+    // Borrow the loader part way through
+    assert_eq!(20, sections.loader().iter_section_headers().count());
+    assert_eq!(16, sections.by_ref().count());
+    let loader = sections.into_inner();
+    assert_eq!(20, loader.e_shnum());
+}
+
+#[test]
+fn sections_stop_after_error() {
+    // Cut the file off part way through the sixth section header
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let cut = loader.e_shoff() as usize + 5 * 40 + 20;
+    let source = CountingSource::new(&ELF_DATA[0..cut]);
+    let loader = Loader::new(&source).unwrap();
+
+    let mut iter = loader.iter_section_headers();
+    for _ in 0..5 {
+        iter.next().unwrap().unwrap();
+    }
+    assert!(iter.next().unwrap().is_err());
+    let before = source.reads();
+    assert!(iter.next().is_none());
+    assert!(iter.next_back().is_none());
+    assert_eq!(0, iter.len());
+    assert_eq!(before, source.reads());
+
+    let results: Vec<_> = loader.iter_section_headers().collect();
+    assert_eq!(6, results.len());
+    assert_eq!(1, results.iter().filter(|r| r.is_err()).count());
+
+    // From the back, the first item is the error
+    let mut iter = loader.iter_section_headers();
+    assert!(iter.next_back().unwrap().is_err());
+    assert!(iter.next().is_none());
+
+    // The indexed accessor still works for what's left
+    assert!(neotron_loader::SectionHeader::new(&loader, 4).is_ok());
+}
+
+#[test]
+fn program_headers_stop_after_error() {
+    // Cut the file off part way through the third program header
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let cut = loader.e_phoff() as usize + 2 * 32 + 10;
+    let source = CountingSource::new(&ELF_DATA[0..cut]);
+    let loader = Loader::new(&source).unwrap();
+
+    let before = source.reads();
+    let results: Vec<_> = loader.iter_program_headers().collect();
+    assert_eq!(3, results.len());
+    assert!(results[2].is_err());
+    let reads = source.reads() - before;
+
+    // The owned iterator does the same
+    let mut owned = loader.into_program_headers();
+    let before = source.reads();
+    assert_eq!(3, owned.by_ref().count());
+    assert!(owned.next().is_none());
+    assert_eq!(reads, source.reads() - before);
+}
+
+#[test]
+fn section_header_bad_index() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert!(SectionHeader::new(&loader, 19).is_ok());
+    for idx in [20, 1000, u16::MAX] {
+        assert!(matches!(
+            SectionHeader::new(&loader, idx),
+            Err(Error::BadIndex {
+                kind: IndexKind::Section,
+                index,
+                count: 20,
+            }) if index == idx
+        ));
+    }
+}
+
+#[test]
+fn program_header_bad_index() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert!(ProgramHeader::new(&loader, 5).is_ok());
+    for idx in [6, 1000, u16::MAX] {
+        assert!(matches!(
+            ProgramHeader::new(&loader, idx),
+            Err(Error::BadIndex {
+                kind: IndexKind::ProgramHeader,
+                index,
+                count: 6,
+            }) if index == idx
+        ));
+    }
+}
+
+#[test]
+fn program_headers_buffered() {
+    let source = CountingSource::new(ELF_DATA);
+    let loader = Loader::new(&source).unwrap();
+    let mut buffer = [0u8; 256];
+    let before = source.reads();
+    let buffered: Vec<ProgramHeader> = loader
+        .program_headers_buffered(&mut buffer)
+        .unwrap()
+        .collect();
+    assert_eq!(1, source.reads() - before);
+    let lazy: Vec<ProgramHeader> = loader
+        .iter_program_headers()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(6, buffered.len());
+    assert_eq!(lazy, buffered);
+
+    // Backwards, and skipping
+    let mut iter = loader.program_headers_buffered(&mut buffer).unwrap();
+    assert_eq!(6, iter.len());
+    assert_eq!(Some(&lazy[5]), iter.next_back().as_ref());
+    assert_eq!(Some(&lazy[2]), iter.nth(2).as_ref());
+    assert_eq!(2, iter.len());
+}
+
+#[test]
+fn program_headers_buffered_too_small() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut buffer = [0u8; 0xBF];
+    assert!(matches!(
+        loader.program_headers_buffered(&mut buffer),
+        Err(Error::BufferTooSmall { needed: 0xC0 })
+    ));
+}
+
+#[test]
+fn section_headers_buffered() {
+    let source = CountingSource::new(ELF_DATA);
+    let loader = Loader::new(&source).unwrap();
+    let mut buffer = [0u8; 20 * 0x28];
+    let before = source.reads();
+    let buffered: Vec<SectionHeader> = loader
+        .section_headers_buffered(&mut buffer)
+        .unwrap()
+        .collect();
+    assert_eq!(1, source.reads() - before);
+    let lazy: Vec<SectionHeader> = loader
+        .iter_section_headers()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(20, buffered.len());
+    assert_eq!(lazy, buffered);
+
+    let mut buffer = [0u8; 20 * 0x28 - 1];
+    assert!(matches!(
+        loader.section_headers_buffered(&mut buffer),
+        Err(Error::BufferTooSmall { needed: 0x320 })
+    ));
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "heapless")]
+
+use neotron_loader::{Error, HeaderSnapshot, Loader, ProgramHeader, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+#[test]
+fn program_headers_exact() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let headers = loader.program_headers_heapless::<6>().unwrap();
+    assert_eq!(headers.len(), 6);
+    let expected: Vec<ProgramHeader> = loader.iter_program_headers().map(Result::unwrap).collect();
+    assert_eq!(format!("{:?}", headers.as_slice()), format!("{expected:?}"));
+}
+
+#[test]
+fn program_headers_too_few() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert!(matches!(
+        loader.program_headers_heapless::<5>(),
+        Err(Error::BufferTooSmall { needed: 6 })
+    ));
+}
+
+#[test]
+fn section_headers_exact() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let headers = loader.section_headers_heapless::<20>().unwrap();
+    assert_eq!(headers.len(), 20);
+    assert_eq!(headers.capacity(), 20);
+    assert_eq!(headers[17].sh_type(), SectionHeader::SHT_SYMTAB);
+}
+
+#[test]
+fn section_headers_too_few() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert!(matches!(
+        loader.section_headers_heapless::<19>(),
+        Err(Error::BufferTooSmall { needed: 20 })
+    ));
+}
+
+#[test]
+fn spare_space() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let headers = loader.program_headers_heapless::<16>().unwrap();
+    assert_eq!(headers.len(), 6);
+    assert_eq!((&headers).into_iter().count(), 6);
+}
+
+#[test]
+fn snapshot_outlives_source() {
+    let snapshot: HeaderSnapshot<6, 20> = {
+        let data = ELF_DATA.to_vec();
+        let loader = Loader::new(&data[..]).unwrap();
+        loader.snapshot().unwrap()
+    };
+    assert_eq!(snapshot.e_entry(), 0x200012a9);
+    assert_eq!(snapshot.program_headers().len(), 6);
+    assert_eq!(snapshot.section_headers().len(), 20);
+    assert_eq!(snapshot.program_headers()[2].p_paddr(), 0x2000_1000);
+}
+
+#[test]
+fn snapshot_too_small() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert!(matches!(
+        loader.snapshot::<6, 19>(),
+        Err(Error::BufferTooSmall { needed: 20 })
+    ));
+}
@@ -0,0 +1,90 @@
+use neotron_loader::{Error, Loader, ProgramHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+#[test]
+fn rodata() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let rodata = loader.find_section_by_name(".rodata").unwrap().unwrap();
+    let mut out = String::new();
+    loader.hexdump_section(&rodata, 0..0x38, &mut out).unwrap();
+    assert_eq!(
+        out,
+        "\
+20001444  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 0a 00 00 00  |Hello, world....|
+20001454  f9 11 00 20 04 00 00 00  04 00 00 00 fb 11 00 20  |... ........... |
+20001464  09 12 00 20 89 12 00 20  50 61 6e 69 63 21 0a 00  |... ... Panic!..|
+20001474  6c 14 00 20 07 00 00 00                           |l.. ....|
+"
+    );
+}
+
+#[test]
+fn rodata_clamped() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let rodata = loader.find_section_by_name(".rodata").unwrap().unwrap();
+    let mut out = String::new();
+    loader
+        .hexdump_section(&rodata, 0x28..0x1000, &mut out)
+        .unwrap();
+    assert_eq!(
+        out,
+        "\
+2000146c  50 61 6e 69 63 21 0a 00  6c 14 00 20 07 00 00 00  |Panic!..l.. ....|
+"
+    );
+    let mut out = String::new();
+    loader
+        .hexdump_section(&rodata, 0x100..0x200, &mut out)
+        .unwrap();
+    assert_eq!(out, "");
+}
+
+#[test]
+fn bss_is_zeroes() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let bss = loader.find_section_by_name(".bss").unwrap().unwrap();
+    let mut out = String::new();
+    loader.hexdump_section(&bss, 0..0x100, &mut out).unwrap();
+    assert_eq!(
+        out,
+        "2000147c  00 00 00 00                                       |....|\n"
+    );
+}
+
+#[test]
+fn segment() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let rodata_segment = loader
+        .iter_program_headers()
+        .map(Result::unwrap)
+        .find(|ph| ph.p_type() == ProgramHeader::PT_LOAD && ph.p_paddr() == 0x2000_1444)
+        .unwrap();
+    let mut out = String::new();
+    loader
+        .hexdump_segment(&rodata_segment, 0..0x10, &mut out)
+        .unwrap();
+    assert_eq!(
+        out,
+        "20001444  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 0a 00 00 00  |Hello, world....|\n"
+    );
+}
+
+/// A writer which is always full.
+struct Full;
+
+impl core::fmt::Write for Full {
+    fn write_str(&mut self, _s: &str) -> core::fmt::Result {
+        Err(core::fmt::Error)
+    }
+}
+
+#[test]
+fn write_failed() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let rodata = loader.find_section_by_name(".rodata").unwrap().unwrap();
+    assert!(matches!(
+        loader.hexdump_section(&rodata, 0..0x38, Full),
+        Err(Error::WriteFailed)
+    ));
+}
@@ -0,0 +1,119 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use std::collections::BTreeMap;
+
+use common::{ElfBuilder, Segment};
+use neotron_loader::{Loader, ProgramHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// What we got back from decoding some Intel HEX.
+#[derive(Debug, Default)]
+struct Decoded {
+    bytes: BTreeMap<u32, u8>,
+    entry: Option<u32>,
+    records: Vec<(u8, usize)>,
+}
+
+/// Decode Intel HEX, checking the checksums and the format as we go.
+fn decode(hex: &str) -> Decoded {
+    let mut decoded = Decoded::default();
+    let mut upper = 0u32;
+    let mut seen_eof = false;
+    for line in hex.split_terminator("\r\n") {
+        assert!(!seen_eof, "data after EOF record");
+        let line = line.strip_prefix(':').expect("record starts with ':'");
+        let raw: Vec<u8> = (0..line.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&line[i..i + 2], 16).unwrap())
+            .collect();
+        let sum = raw.iter().fold(0u8, |acc, b| acc.wrapping_add(*b));
+        assert_eq!(0, sum, "bad checksum on {line}");
+        let len = raw[0] as usize;
+        assert_eq!(len + 5, raw.len());
+        let address = u32::from(u16::from_be_bytes([raw[1], raw[2]]));
+        let record_type = raw[3];
+        let data = &raw[4..4 + len];
+        decoded.records.push((record_type, len));
+        match record_type {
+            0x00 => {
+                assert!(len <= 32);
+                for (idx, b) in data.iter().enumerate() {
+                    let addr = (upper | address) + idx as u32;
+                    assert!(decoded.bytes.insert(addr, *b).is_none());
+                }
+            }
+            0x01 => seen_eof = true,
+            0x04 => upper = u32::from(u16::from_be_bytes([data[0], data[1]])) << 16,
+            0x05 => decoded.entry = Some(u32::from_be_bytes(data.try_into().unwrap())),
+            _ => panic!("unexpected record type {record_type}"),
+        }
+    }
+    assert!(seen_eof);
+    decoded
+}
+
+fn to_ihex(loader: &Loader<&[u8]>) -> String {
+    let mut out = Vec::new();
+    loader.write_ihex(&mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let decoded = decode(&to_ihex(&loader));
+    assert_eq!(Some(loader.e_entry()), decoded.entry);
+
+    let mut expected = BTreeMap::new();
+    for ph in loader.iter_program_headers() {
+        let ph = ph.unwrap();
+        if ph.p_type() != ProgramHeader::PT_LOAD {
+            continue;
+        }
+        let bytes = loader.segment_file_bytes(&ph).unwrap();
+        for (idx, b) in bytes.iter().enumerate() {
+            expected.insert(ph.p_paddr() + idx as u32, *b);
+        }
+    }
+    assert_eq!(expected, decoded.bytes);
+}
+
+#[test]
+fn crosses_64k_boundary() {
+    let mut elf = ElfBuilder::new();
+    elf.e_entry = 0x0002_0001;
+    let data: Vec<u8> = (0..40).collect();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x0001_FFF8,
+        p_memsz: Some(0x100),
+        data: data.clone(),
+        ..Default::default()
+    });
+    let elf = elf.build();
+    let loader = Loader::new(&elf[..]).unwrap();
+    let decoded = decode(&to_ihex(&loader));
+    assert_eq!(
+        vec![
+            (0x04, 2),
+            (0x00, 8),
+            (0x04, 2),
+            (0x00, 16),
+            (0x00, 16),
+            (0x05, 4),
+            (0x01, 0)
+        ],
+        decoded.records
+    );
+    let expected: BTreeMap<u32, u8> = data
+        .iter()
+        .enumerate()
+        .map(|(idx, b)| (0x0001_FFF8 + idx as u32, *b))
+        .collect();
+    // The zeroed part isn't written
+    assert_eq!(expected, decoded.bytes);
+    assert_eq!(Some(0x0002_0001), decoded.entry);
+}
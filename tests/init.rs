@@ -0,0 +1,138 @@
+mod common;
+
+use common::{words, ElfBuilder, Section};
+use neotron_loader::{Error, Loader, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// An executable with constructors and destructors.
+fn fixture(preinit: bool) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_flags: 0x6,
+        sh_addralign: 4,
+        ..Section::new(".text", SectionHeader::SHT_PROGBITS, vec![0; 32])
+    });
+    if preinit {
+        elf.add_section(Section {
+            sh_flags: 0x3,
+            sh_addralign: 4,
+            sh_entsize: 4,
+            ..Section::new(
+                ".preinit_array",
+                SectionHeader::SHT_PREINIT_ARRAY,
+                words(&[0x2000_0001]),
+            )
+        });
+    }
+    elf.add_section(Section {
+        sh_flags: 0x3,
+        sh_addralign: 4,
+        sh_entsize: 4,
+        ..Section::new(
+            ".init_array",
+            SectionHeader::SHT_INIT_ARRAY,
+            words(&[0x2000_0005, 0x2000_0009]),
+        )
+    });
+    elf.add_section(Section {
+        sh_flags: 0x3,
+        sh_addralign: 4,
+        sh_entsize: 4,
+        ..Section::new(
+            ".fini_array",
+            SectionHeader::SHT_FINI_ARRAY,
+            words(&[0x2000_0011, 0x2000_0015, 0x2000_0019]),
+        )
+    });
+    elf.build()
+}
+
+#[test]
+fn init_functions() {
+    let data = fixture(false);
+    let loader = Loader::new(&data[..]).unwrap();
+    let functions: Vec<u32> = loader
+        .iter_init_functions()
+        .unwrap()
+        .map(|f| f.unwrap())
+        .collect();
+    assert_eq!(vec![0x2000_0005, 0x2000_0009], functions);
+}
+
+#[test]
+fn stops_after_error() {
+    let mut data = fixture(false);
+    // Make `.init_array` (section 2) run far off the end of the file
+    let e_shoff = Loader::new(&data[..]).unwrap().e_shoff() as usize;
+    common::put_u32(&mut data, e_shoff + 2 * 0x28 + 0x14, 0x1000);
+    let loader = Loader::new(&data[..]).unwrap();
+    let functions: Vec<_> = loader.iter_init_functions().unwrap().collect();
+    assert!(functions.len() < 100, "{} functions", functions.len());
+    assert_eq!(0x2000_0005, *functions[0].as_ref().unwrap());
+    assert!(functions.last().unwrap().is_err());
+}
+
+#[test]
+fn array_that_wraps() {
+    let mut data = fixture(false);
+    // Make `.init_array` (section 2) wrap past 4 GiB
+    let e_shoff = Loader::new(&data[..]).unwrap().e_shoff() as usize;
+    common::put_u32(&mut data, e_shoff + 2 * 0x28 + 0x10, 0xFFFF_FFF0);
+    common::put_u32(&mut data, e_shoff + 2 * 0x28 + 0x14, 0x20);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(
+        loader.iter_init_functions(),
+        Err(Error::BadArraySection)
+    ));
+}
+
+#[test]
+fn preinit_functions_come_first() {
+    let data = fixture(true);
+    let loader = Loader::new(&data[..]).unwrap();
+    let functions: Vec<u32> = loader
+        .iter_init_functions()
+        .unwrap()
+        .map(|f| f.unwrap())
+        .collect();
+    assert_eq!(vec![0x2000_0001, 0x2000_0005, 0x2000_0009], functions);
+}
+
+#[test]
+fn fini_functions_reversed() {
+    let data = fixture(false);
+    let loader = Loader::new(&data[..]).unwrap();
+    let functions: Vec<u32> = loader
+        .iter_fini_functions()
+        .unwrap()
+        .map(|f| f.unwrap())
+        .collect();
+    assert_eq!(vec![0x2000_0019, 0x2000_0015, 0x2000_0011], functions);
+}
+
+#[test]
+fn no_arrays() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(0, loader.iter_init_functions().unwrap().count());
+    assert_eq!(0, loader.iter_fini_functions().unwrap().count());
+}
+
+#[test]
+fn bad_entry_size() {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_entsize: 8,
+        ..Section::new(
+            ".init_array",
+            SectionHeader::SHT_INIT_ARRAY,
+            words(&[0x2000_0005, 0x2000_0009]),
+        )
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(
+        loader.iter_init_functions(),
+        Err(Error::BadArraySection)
+    ));
+}
@@ -0,0 +1,691 @@
+mod common;
+
+use common::{ElfBuilder, Segment};
+use neotron_loader::{
+    traits::SliceError, Error, LoadOptions, LoadProgress, Loader, MapError, ProgramHeader,
+    SegmentDisposition,
+};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+fn load_segments(loader: &Loader<&[u8]>) -> Vec<ProgramHeader> {
+    loader
+        .iter_program_headers()
+        .map(|ph| ph.unwrap())
+        .filter(|ph| ph.p_type() == ProgramHeader::PT_LOAD)
+        .collect()
+}
+
+#[test]
+fn copy_text_segment() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let text = load_segments(&loader)
+        .into_iter()
+        .find(|ph| ph.flags().is_executable())
+        .unwrap();
+    let mut dest = vec![0xAAu8; text.p_memsz() as usize + 16];
+    loader.copy_segment(&text, &mut dest).unwrap();
+    let start = text.p_offset() as usize;
+    let end = start + text.p_filesz() as usize;
+    assert_eq!(&ELF_DATA[start..end], &dest[0..text.p_memsz() as usize]);
+    // Untouched
+    assert!(dest[text.p_memsz() as usize..].iter().all(|b| *b == 0xAA));
+}
+
+#[test]
+fn copy_bss_segment() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let bss = load_segments(&loader)
+        .into_iter()
+        .find(|ph| ph.flags().is_writable())
+        .unwrap();
+    assert_eq!(0, bss.p_filesz());
+    assert_eq!(4, bss.p_memsz());
+    let mut dest = [0xAAu8; 6];
+    loader.copy_segment(&bss, &mut dest).unwrap();
+    assert_eq!([0, 0, 0, 0, 0xAA, 0xAA], dest);
+
+    let mut dest = [0xAAu8; 3];
+    assert!(matches!(
+        loader.copy_segment(&bss, &mut dest),
+        Err(Error::BufferTooSmall { needed: 4 })
+    ));
+}
+
+#[test]
+fn copy_whole_image() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let base = 0x2000_0000;
+    let mut ram = vec![0xAAu8; 0x1480];
+    for ph in load_segments(&loader) {
+        loader.copy_segment_at(&ph, &mut ram, base).unwrap();
+    }
+    assert_eq!(&ELF_DATA[0x1000..0x1444], &ram[0x1000..0x1444]);
+    assert_eq!(&ELF_DATA[0x1444..0x147c], &ram[0x1444..0x147c]);
+    assert_eq!([0u8; 4], ram[0x147c..0x1480]);
+    // The gap between the headers and .text isn't touched
+    assert!(ram[0xf4..0x1000].iter().all(|b| *b == 0xAA));
+
+    // Too small, or in the wrong place
+    let mut ram = vec![0u8; 0x147e];
+    let segments = load_segments(&loader);
+    assert!(matches!(
+        loader.copy_segment_at(&segments[3], &mut ram, base),
+        Err(Error::BufferTooSmall { needed: 0x1480 })
+    ));
+    assert!(matches!(
+        loader.copy_segment_at(&segments[0], &mut ram, base + 1),
+        Err(Error::AddressNotMapped { addr: 0x2000_0000 })
+    ));
+}
+
+#[test]
+fn large_segment() {
+    // Bigger than one chunk
+    let contents: Vec<u8> = (0..2000u32).map(|x| x as u8).collect();
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_memsz: Some(2100),
+        data: contents.clone(),
+        ..Default::default()
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let ph = loader.iter_program_headers().next().unwrap().unwrap();
+    let mut dest = vec![0xAAu8; 2100];
+    loader.copy_segment(&ph, &mut dest).unwrap();
+    assert_eq!(&contents[..], &dest[0..2000]);
+    assert!(dest[2000..].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn filesz_bigger_than_memsz() {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_memsz: Some(4),
+        data: vec![1; 8],
+        ..Default::default()
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let ph = loader.iter_program_headers().next().unwrap().unwrap();
+    let mut dest = [0u8; 16];
+    assert!(matches!(
+        loader.copy_segment(&ph, &mut dest),
+        Err(Error::BadSegment)
+    ));
+}
+
+#[test]
+fn copy_segment_wrapping_offset() {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        data: vec![1; 0x100],
+        ..Default::default()
+    });
+    let mut data = elf.build();
+    let e_phoff = Loader::new(&data[..]).unwrap().e_phoff() as usize;
+    common::put_u32(&mut data, e_phoff + 0x04, 0xFFFF_FFF0);
+    let loader = Loader::new(&data[..]).unwrap();
+    let ph = loader.iter_program_headers().next().unwrap().unwrap();
+    let mut dest = [0u8; 0x100];
+    assert!(matches!(
+        loader.copy_segment(&ph, &mut dest),
+        Err(Error::BadSegment)
+    ));
+}
+
+const PT_VENDOR_CONFIG: u32 = 0x7000_0099;
+
+/// An executable with a code segment, a vendor-specific segment of
+/// configuration data, a note and a stack segment.
+fn vendor_fixture() -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x1000,
+        p_memsz: Some(8),
+        data: vec![1, 2, 3, 4],
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: PT_VENDOR_CONFIG,
+        p_paddr: 0x2000,
+        data: vec![0xC0, 0xF1, 0x60],
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        p_paddr: 0x3000,
+        data: vec![9; 12],
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_GNU_STACK,
+        ..Default::default()
+    });
+    elf.build()
+}
+
+/// What happened during a load: the result, the writes made and the segment
+/// types the handler saw.
+type LoadOutcome = (Result<(), Error<SliceError>>, Vec<(u32, Vec<u8>)>, Vec<u32>);
+
+fn load_with(all: bool, disposition: SegmentDisposition) -> LoadOutcome {
+    let data = vendor_fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut writes = Vec::new();
+    let mut asked = Vec::new();
+    let write_fn = |addr: u32, bytes: &[u8]| {
+        writes.push((addr, bytes.to_vec()));
+        Ok(())
+    };
+    let handler = |ph: &ProgramHeader| {
+        asked.push(ph.p_type());
+        disposition
+    };
+    let result = if all {
+        loader.load_with_handler_all(write_fn, handler)
+    } else {
+        loader.load_with_handler(write_fn, handler)
+    };
+    (result, writes, asked)
+}
+
+#[test]
+fn load_only_load_segments() {
+    let data = vendor_fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut writes = Vec::new();
+    loader
+        .load(|addr, bytes| {
+            writes.push((addr, bytes.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        vec![(0x1000, vec![1, 2, 3, 4]), (0x1004, vec![0, 0, 0, 0])],
+        writes
+    );
+}
+
+#[test]
+fn handler_skip() {
+    let (result, writes, asked) = load_with(false, SegmentDisposition::Skip);
+    result.unwrap();
+    assert_eq!(vec![PT_VENDOR_CONFIG], asked);
+    assert_eq!(2, writes.len());
+}
+
+#[test]
+fn handler_load_anyway() {
+    let (result, writes, asked) = load_with(false, SegmentDisposition::LoadAnyway);
+    result.unwrap();
+    assert_eq!(vec![PT_VENDOR_CONFIG], asked);
+    assert_eq!(
+        vec![
+            (0x1000, vec![1, 2, 3, 4]),
+            (0x1004, vec![0, 0, 0, 0]),
+            (0x2000, vec![0xC0, 0xF1, 0x60]),
+        ],
+        writes
+    );
+}
+
+#[test]
+fn handler_fail() {
+    let (result, writes, asked) = load_with(false, SegmentDisposition::Fail);
+    assert!(matches!(
+        result,
+        Err(Error::SegmentRejected {
+            p_type: PT_VENDOR_CONFIG
+        })
+    ));
+    assert_eq!(vec![PT_VENDOR_CONFIG], asked);
+    // The segment before was loaded
+    assert_eq!(2, writes.len());
+}
+
+#[test]
+fn handler_sees_all() {
+    let (result, writes, asked) = load_with(true, SegmentDisposition::Skip);
+    result.unwrap();
+    assert_eq!(
+        vec![
+            PT_VENDOR_CONFIG,
+            ProgramHeader::PT_NOTE,
+            ProgramHeader::PT_GNU_STACK
+        ],
+        asked
+    );
+    assert_eq!(2, writes.len());
+}
+
+#[test]
+fn load_matches_copy() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let base = 0x2000_0000;
+    let mut streamed = vec![0xAAu8; 0x1480];
+    loader
+        .load(|addr, bytes| {
+            let start = (addr - base) as usize;
+            streamed[start..start + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        })
+        .unwrap();
+    let mut copied = vec![0xAAu8; 0x1480];
+    for ph in load_segments(&loader) {
+        loader.copy_segment_at(&ph, &mut copied, base).unwrap();
+    }
+    assert_eq!(copied, streamed);
+}
+
+/// Load `test.elf` into an image of memory, in one go.
+fn one_shot_image(loader: &Loader<&[u8]>) -> Vec<u8> {
+    let base = 0x2000_0000;
+    let mut image = vec![0xAAu8; 0x1480];
+    loader
+        .load(|addr, bytes| {
+            let start = (addr - base) as usize;
+            image[start..start + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        })
+        .unwrap();
+    image
+}
+
+#[test]
+fn load_in_steps() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let base = 0x2000_0000;
+    let mut image = vec![0xAAu8; 0x1480];
+    let mut writes = Vec::new();
+    let mut state = loader
+        .load_state(|addr, bytes| {
+            writes.push(bytes.len());
+            let start = (addr - base) as usize;
+            image[start..start + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        })
+        .unwrap();
+    let total = 0xf4 + 0x444 + 0x38 + 4;
+    assert_eq!(
+        LoadProgress::InProgress { done: 0, total },
+        state.progress()
+    );
+
+    let mut last_done = 0;
+    let entry = loop {
+        match state.step(64).unwrap() {
+            LoadProgress::InProgress { done, total: t } => {
+                assert_eq!(total, t);
+                assert!(done > last_done && done - last_done <= 64);
+                last_done = done;
+            }
+            LoadProgress::Complete { entry } => break entry,
+        }
+    };
+    // Stepping again does nothing
+    assert_eq!(LoadProgress::Complete { entry }, state.step(64).unwrap());
+
+    assert_eq!(loader.e_entry(), entry);
+    assert!(writes.iter().all(|len| *len <= 64));
+    assert_eq!(total, writes.iter().sum::<usize>() as u32);
+    assert_eq!(one_shot_image(&loader), image);
+}
+
+#[test]
+fn load_state_can_move_and_retry() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let base = 0x2000_0000;
+    let mut image = vec![0xAAu8; 0x1480];
+    let mut calls = 0;
+    let state = loader
+        .load_state(|addr, bytes| {
+            calls += 1;
+            // Every third write fails the first time
+            if calls % 3 == 0 {
+                return Err(Error::WriteFailed);
+            }
+            let start = (addr - base) as usize;
+            image[start..start + bytes.len()].copy_from_slice(bytes);
+            Ok(())
+        })
+        .unwrap();
+
+    let mut states = vec![state];
+    let mut errors = 0;
+    loop {
+        // Move the state about between steps
+        let mut state = states.pop().unwrap();
+        let result = state.step(100);
+        states.push(state);
+        match result {
+            Ok(LoadProgress::Complete { .. }) => break,
+            Ok(LoadProgress::InProgress { .. }) => {}
+            Err(Error::WriteFailed) => errors += 1,
+            Err(e) => panic!("{e:?}"),
+        }
+    }
+    drop(states);
+    assert!(errors > 0);
+    assert_eq!(one_shot_image(&loader), image);
+}
+
+#[test]
+fn load_state_bad_segment() {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0xFFFF_FFF0,
+        data: vec![1; 32],
+        ..Default::default()
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(
+        loader.load_state(|_addr, _bytes| Ok(())),
+        Err(Error::BadSegment)
+    ));
+}
+
+#[test]
+fn load_state_skips_wrapping_note() {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        data: vec![0; 0x100],
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x2000_0000,
+        data: vec![1; 32],
+        ..Default::default()
+    });
+    let mut data = elf.build();
+    // The note wraps past 4 GiB, but we don't load it, so we don't care
+    let e_phoff = Loader::new(&data[..]).unwrap().e_phoff() as usize;
+    common::put_u32(&mut data, e_phoff + 0x04, 0xFFFF_FFF0);
+    common::put_u32(&mut data, e_phoff + 0x10, 0x100);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(loader.load(|_addr, _bytes| Ok(())).is_ok());
+    let mut written = Vec::new();
+    {
+        let mut state = loader
+            .load_state(|addr, bytes| {
+                written.push((addr, bytes.len()));
+                Ok(())
+            })
+            .unwrap();
+        assert!(state.step(64).is_ok());
+    }
+    assert_eq!(vec![(0x2000_0000, 32)], written);
+}
+
+/// Two segments with a 0x100 byte gap between them, listed in reverse order.
+fn gap_fixture(second_paddr: u32) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: second_paddr,
+        data: vec![2; 0x40],
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x1000,
+        p_memsz: Some(0x100),
+        data: vec![1; 0x80],
+        ..Default::default()
+    });
+    elf.build()
+}
+
+/// The result of a load, and the writes it made.
+type LoadWrites = (Result<(), Error<SliceError>>, Vec<(u32, Vec<u8>)>);
+
+fn load_writes(loader: &Loader<&[u8]>, options: &LoadOptions) -> LoadWrites {
+    let mut writes = Vec::new();
+    let result = loader.load_with_options(
+        |addr, bytes| {
+            writes.push((addr, bytes.to_vec()));
+            Ok(())
+        },
+        options,
+    );
+    (result, writes)
+}
+
+#[test]
+fn fill_gaps() {
+    let data = gap_fixture(0x1200);
+    let loader = Loader::new(&data[..]).unwrap();
+    let (result, writes) = load_writes(&loader, &LoadOptions::new().fill_gaps(0xFF));
+    result.unwrap();
+    assert_eq!(
+        writes,
+        vec![
+            (0x1000, vec![1; 0x80]),
+            (0x1080, vec![0; 0x80]),
+            (0x1100, vec![0xFF; 0x100]),
+            (0x1200, vec![2; 0x40]),
+        ]
+    );
+}
+
+#[test]
+fn default_options_match_load() {
+    let data = gap_fixture(0x1200);
+    let loader = Loader::new(&data[..]).unwrap();
+    let (result, writes) = load_writes(&loader, &LoadOptions::new());
+    result.unwrap();
+    let mut expected = Vec::new();
+    loader
+        .load(|addr, bytes| {
+            expected.push((addr, bytes.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(writes, expected);
+}
+
+#[test]
+fn fill_gaps_adjacent() {
+    let data = gap_fixture(0x1100);
+    let loader = Loader::new(&data[..]).unwrap();
+    let (result, writes) = load_writes(&loader, &LoadOptions::new().fill_gaps(0xFF));
+    result.unwrap();
+    assert!(writes.iter().all(|(_addr, bytes)| !bytes.contains(&0xFF)));
+}
+
+#[test]
+fn fill_gaps_overlap() {
+    let data = gap_fixture(0x10F0);
+    let loader = Loader::new(&data[..]).unwrap();
+    let (result, writes) = load_writes(&loader, &LoadOptions::new().fill_gaps(0xFF));
+    assert!(matches!(result, Err(Error::BadSegment)));
+    // Only the first segment was loaded
+    assert!(writes.iter().all(|(addr, _bytes)| *addr < 0x1100));
+}
+
+#[test]
+fn fill_gaps_wrapping_segment() {
+    let data = gap_fixture(0xFFFF_FFE0);
+    let loader = Loader::new(&data[..]).unwrap();
+    let (result, _writes) = load_writes(&loader, &LoadOptions::new().fill_gaps(0xFF));
+    assert!(matches!(result, Err(Error::BadSegment)));
+}
+
+#[test]
+fn load_state_fill_gaps() {
+    let data = gap_fixture(0x1200);
+    let loader = Loader::new(&data[..]).unwrap();
+    let (result, expected) = load_writes(&loader, &LoadOptions::new().fill_gaps(0xFF));
+    result.unwrap();
+    let mut image = vec![0xAAu8; 0x240];
+    let mut state = loader
+        .load_state_with_options(
+            |addr, bytes| {
+                assert!(bytes.len() <= 0x30);
+                let start = (addr - 0x1000) as usize;
+                image[start..start + bytes.len()].copy_from_slice(bytes);
+                Ok(())
+            },
+            &LoadOptions::new().fill_gaps(0xFF),
+        )
+        .unwrap();
+    assert_eq!(
+        LoadProgress::InProgress {
+            done: 0,
+            total: 0x240
+        },
+        state.progress()
+    );
+    while let LoadProgress::InProgress { .. } = state.step(0x30).unwrap() {}
+    let mut expected_image = Vec::new();
+    for (_addr, bytes) in expected {
+        expected_image.extend(bytes);
+    }
+    assert_eq!(expected_image, image);
+}
+
+#[test]
+fn load_state_fill_gaps_overlap() {
+    let data = gap_fixture(0x10F0);
+    let loader = Loader::new(&data[..]).unwrap();
+    // Overlapping segments are fine without filling
+    assert!(loader.load_state(|_addr, _bytes| Ok(())).is_ok());
+    assert!(matches!(
+        loader.load_state_with_options(|_addr, _bytes| Ok(()), &LoadOptions::new().fill_gaps(0xFF)),
+        Err(Error::BadSegment)
+    ));
+}
+
+/// The application is linked for 64 KiB of RAM at 0x2000_0000, but the
+/// second half is really at 0x2400_0000.
+fn two_banks(link_addr: u32) -> Result<u32, MapError> {
+    match link_addr {
+        0x2000_0000..=0x2000_7FFF => Ok(link_addr),
+        0x2000_8000..=0x2000_FFFF => Ok(link_addr - 0x2000_8000 + 0x2400_0000),
+        _ => Err(MapError),
+    }
+}
+
+/// A file with a segment at each of these addresses.
+fn banked_fixture(segments: &[(u32, usize)]) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.e_entry = 0x2000_8001;
+    for (idx, (p_paddr, len)) in segments.iter().enumerate() {
+        elf.add_segment(Segment {
+            p_type: ProgramHeader::PT_LOAD,
+            p_paddr: *p_paddr,
+            data: vec![idx as u8 + 1; *len],
+            ..Default::default()
+        });
+    }
+    elf.build()
+}
+
+#[test]
+fn load_translated() {
+    let data = banked_fixture(&[(0x2000_0000, 0x100), (0x2000_8000, 0x40)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut writes = Vec::new();
+    let entry = loader
+        .load_translated(
+            |addr, bytes| {
+                writes.push((addr, bytes.to_vec()));
+                Ok(())
+            },
+            two_banks,
+        )
+        .unwrap();
+    assert_eq!(entry, 0x2400_0001);
+    assert_eq!(
+        writes,
+        vec![(0x2000_0000, vec![1; 0x100]), (0x2400_0000, vec![2; 0x40])]
+    );
+}
+
+#[test]
+fn load_translated_straddling_segment() {
+    let data = banked_fixture(&[(0x2000_0000, 0x100), (0x2000_7F00, 0x200)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut writes = 0;
+    let result = loader.load_translated(
+        |_addr, _bytes| {
+            writes += 1;
+            Ok(())
+        },
+        two_banks,
+    );
+    assert!(matches!(
+        result,
+        Err(Error::SegmentNotContiguous {
+            p_paddr: 0x2000_7F00
+        })
+    ));
+    // Nothing is written if any segment is bad
+    assert_eq!(writes, 0);
+}
+
+#[test]
+fn load_translated_unmapped() {
+    let data = banked_fixture(&[(0x1000, 0x10)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(
+        loader.load_translated(|_addr, _bytes| Ok(()), two_banks),
+        Err(Error::AddressNotMapped { addr: 0x1000 })
+    ));
+}
+
+#[test]
+fn load_translated_fill_gaps() {
+    let data = banked_fixture(&[(0x2000_8000, 0x40), (0x2000_8100, 0x40)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut writes = Vec::new();
+    loader
+        .load_translated_with_options(
+            |addr, bytes| {
+                writes.push((addr, bytes.to_vec()));
+                Ok(())
+            },
+            two_banks,
+            &LoadOptions::new().fill_gaps(0xFF),
+        )
+        .unwrap();
+    assert_eq!(
+        writes,
+        vec![
+            (0x2400_0000, vec![1; 0x40]),
+            (0x2400_0040, vec![0xFF; 0xC0]),
+            (0x2400_0100, vec![2; 0x40]),
+        ]
+    );
+}
+
+#[test]
+fn load_translated_straddling_gap() {
+    let data = banked_fixture(&[(0x2000_7F00, 0x80), (0x2000_8080, 0x80)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut writes = 0;
+    let mut write_fn = |_addr, _bytes: &[u8]| {
+        writes += 1;
+        Ok(())
+    };
+    // The segments are fine, but the gap between them isn't
+    assert!(loader.load_translated(&mut write_fn, two_banks).is_ok());
+    let result =
+        loader.load_translated_with_options(write_fn, two_banks, &LoadOptions::new().fill_gaps(0));
+    assert!(matches!(
+        result,
+        Err(Error::SegmentNotContiguous {
+            p_paddr: 0x2000_7F80
+        })
+    ));
+    assert_eq!(writes, 2);
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "log")]
+
+use std::cell::RefCell;
+
+use log::{Level, Log, Metadata, Record};
+use neotron_loader::{Error, Loader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+std::thread_local! {
+    /// Each test runs on its own thread, so gets its own records
+    static RECORDS: RefCell<Vec<(Level, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keeps every record from this crate, for the thread that made it.
+struct Capture;
+
+impl Log for Capture {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target().starts_with("neotron_loader")
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            RECORDS.with_borrow_mut(|r| r.push((record.level(), record.args().to_string())));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static CAPTURE: Capture = Capture;
+
+/// Run `f`, and return every record it produced.
+fn records_from<F>(f: F) -> Vec<(Level, String)>
+where
+    F: FnOnce(),
+{
+    // Only the first test to get here sets the logger
+    let _ = log::set_logger(&CAPTURE);
+    log::set_max_level(log::LevelFilter::Trace);
+    RECORDS.with_borrow_mut(|r| r.clear());
+    f();
+    RECORDS.take()
+}
+
+#[test]
+fn parse_test_elf() {
+    let records = records_from(|| {
+        Loader::new(ELF_DATA).unwrap();
+    });
+    // One trace per check we pass, then the header and the section names
+    assert_eq!(records.len(), 10, "{records:#?}");
+    assert!(records[0..8]
+        .iter()
+        .all(|(l, msg)| *l == Level::Trace && msg.ends_with("ok")));
+    assert_eq!(records[8].0, Level::Debug);
+    assert!(records[8].1.contains("e_entry=0x200012a9"), "{records:#?}");
+    assert_eq!(records[9].0, Level::Trace);
+    assert!(records[9].1.contains("0x00006ff4"), "{records:#?}");
+}
+
+#[test]
+fn reject_bad_magic() {
+    let mut data = ELF_DATA.to_vec();
+    data[0] = 0;
+    let records = records_from(|| {
+        assert!(matches!(Loader::new(&data[..]), Err(Error::NotAnElfFile)));
+    });
+    assert_eq!(records.len(), 1, "{records:#?}");
+    assert_eq!(records[0].0, Level::Debug);
+}
+
+#[test]
+fn reject_bad_machine() {
+    let mut data = ELF_DATA.to_vec();
+    data[0x12] = 0x03;
+    let records = records_from(|| {
+        assert!(matches!(Loader::new(&data[..]), Err(Error::WrongElfFile)));
+    });
+    let levels: Vec<Level> = records.iter().map(|(l, _)| *l).collect();
+    assert_eq!(
+        levels,
+        [Level::Trace, Level::Trace, Level::Trace, Level::Debug],
+        "{records:#?}"
+    );
+    assert!(records[3].1.contains("e_machine is 0x0003"), "{records:#?}");
+}
+
+#[test]
+fn load_test_elf() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let records = records_from(|| {
+        loader.load(|_addr, _data| Ok(())).unwrap();
+    });
+    let traces = records.iter().filter(|(l, _)| *l == Level::Trace).count();
+    let debugs = records.iter().filter(|(l, _)| *l == Level::Debug).count();
+    // One trace per segment, and a debug for each one we load
+    assert_eq!(traces, 6, "{records:#?}");
+    assert_eq!(debugs, 4, "{records:#?}");
+    assert!(
+        records
+            .iter()
+            .any(|(_, msg)| msg.contains("segment 2:") && msg.contains("0x20001000")),
+        "{records:#?}"
+    );
+}
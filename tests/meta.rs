@@ -0,0 +1,99 @@
+mod common;
+
+use common::{ElfBuilder, Section};
+use neotron_loader::{Error, Loader, NeotronMeta, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// Assemble a metadata blob.
+fn meta_blob(name: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::new();
+    blob.extend_from_slice(b"NTRN");
+    blob.extend(1u32.to_le_bytes());
+    blob.extend(0x4000u32.to_le_bytes());
+    blob.extend(0x800u32.to_le_bytes());
+    let mut name_field = [0u8; 32];
+    name_field[0..name.len()].copy_from_slice(name);
+    blob.extend(name_field);
+    for part in [1u16, 2, 3, 0] {
+        blob.extend(part.to_le_bytes());
+    }
+    blob
+}
+
+fn meta_fixture(blob: Vec<u8>) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section::new(
+        ".text",
+        SectionHeader::SHT_PROGBITS,
+        vec![0; 8],
+    ));
+    elf.add_section(Section::new(
+        ".neotron_meta",
+        SectionHeader::SHT_PROGBITS,
+        blob,
+    ));
+    elf.build()
+}
+
+fn metadata(
+    blob: Vec<u8>,
+) -> Result<Option<NeotronMeta>, Error<neotron_loader::traits::SliceError>> {
+    let data = meta_fixture(blob);
+    let loader = Loader::new(&data[..]).unwrap();
+    loader.neotron_metadata()
+}
+
+#[test]
+fn present() {
+    let meta = metadata(meta_blob(b"Snake")).unwrap().unwrap();
+    assert_eq!(0x4000, meta.heap_size());
+    assert_eq!(0x800, meta.stack_size());
+    assert_eq!("Snake", meta.name());
+    assert_eq!((1, 2, 3), meta.version());
+}
+
+#[test]
+fn longest_name() {
+    let name = [b'x'; 32];
+    let meta = metadata(meta_blob(&name)).unwrap().unwrap();
+    assert_eq!(32, meta.name().len());
+}
+
+#[test]
+fn absent() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(None, loader.neotron_metadata().unwrap());
+}
+
+#[test]
+fn corrupt() {
+    let good = meta_blob(b"Snake");
+
+    let mut bad_magic = good.clone();
+    bad_magic[0] = b'X';
+
+    let mut bad_version = good.clone();
+    bad_version[4] = 2;
+
+    let too_short = good[0..0x37].to_vec();
+
+    let mut bad_utf8 = good.clone();
+    bad_utf8[0x10] = 0xFF;
+
+    let mut junk_after_name = good.clone();
+    junk_after_name[0x20] = b'!';
+
+    for blob in [bad_magic, bad_version, too_short, bad_utf8, junk_after_name] {
+        assert!(matches!(metadata(blob), Err(Error::BadNeotronMeta)));
+    }
+}
+
+#[test]
+fn longer_section() {
+    // Later additions go at the end
+    let mut blob = meta_blob(b"Snake");
+    blob.extend([0xAA; 16]);
+    let meta = metadata(blob).unwrap().unwrap();
+    assert_eq!("Snake", meta.name());
+}
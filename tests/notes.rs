@@ -0,0 +1,209 @@
+mod common;
+
+use common::{note, words, ElfBuilder, Segment};
+use neotron_loader::{Error, Loader, Note, ProgramHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// An executable with two notes, one of which needs padding.
+fn fixture() -> Vec<u8> {
+    let mut notes = note(b"GNU\0", 1, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    notes.extend(note(
+        b"NTRN\0",
+        0x100,
+        &[0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF],
+    ));
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        p_flags: 0x4,
+        p_align: 4,
+        data: notes,
+        ..Default::default()
+    });
+    elf.build()
+}
+
+#[test]
+fn iterate_notes() {
+    let data = fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let notes: Vec<Note> = loader.iter_notes().map(|n| n.unwrap()).collect();
+    assert_eq!(2, notes.len());
+
+    let mut buffer = [0u8; 16];
+    assert_eq!("GNU", notes[0].name(&loader, &mut buffer).unwrap());
+    assert_eq!(1, notes[0].n_type());
+    assert_eq!(
+        &[1, 2, 3, 4, 5, 6, 7, 8],
+        notes[0].descriptor(&loader, &mut buffer).unwrap()
+    );
+
+    // The name of this note is padded from 5 to 8 bytes
+    assert_eq!("NTRN", notes[1].name(&loader, &mut buffer).unwrap());
+    assert_eq!(5, notes[1].name_len());
+    assert_eq!(notes[1].name_offset() + 8, notes[1].desc_offset());
+    assert_eq!(0x100, notes[1].n_type());
+    assert_eq!(
+        &[0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF],
+        notes[1].descriptor(&loader, &mut buffer).unwrap()
+    );
+}
+
+#[test]
+fn note_buffer_too_small() {
+    let data = fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let note = loader.iter_notes().next().unwrap().unwrap();
+    let mut buffer = [0u8; 4];
+    assert!(matches!(
+        note.descriptor(&loader, &mut buffer),
+        Err(Error::BufferTooSmall { needed: 8 })
+    ));
+}
+
+#[test]
+fn malformed_note_stops_iteration() {
+    let mut notes = note(b"GNU\0", 3, &[1, 2, 3, 4]);
+    // This descriptor runs off the end of the segment
+    notes.extend(words(&[4, 0x1000, 1]));
+    notes.extend(b"GNU\0");
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        data: notes,
+        ..Default::default()
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut notes = loader.iter_notes();
+    assert!(notes.next().unwrap().is_ok());
+    assert!(matches!(notes.next(), Some(Err(Error::BadNote))));
+    assert!(notes.next().is_none());
+}
+
+#[test]
+fn no_notes() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(0, loader.iter_notes().count());
+}
+
+const BUILD_ID: [u8; 20] = [
+    0x8f, 0x2e, 0x3a, 0x1c, 0x55, 0x60, 0x0b, 0xd4, 0x7e, 0x91, 0x02, 0x33, 0xc8, 0x4a, 0xfe, 0x10,
+    0x6d, 0x29, 0x87, 0x44,
+];
+
+#[test]
+fn build_id_from_segment() {
+    // A build ID note, after a note with the same type but a different name
+    let mut notes = note(b"NTRN\0", Note::NT_GNU_BUILD_ID, &[0; 4]);
+    notes.extend(note(b"GNU\0", Note::NT_GNU_BUILD_ID, &BUILD_ID));
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        data: notes,
+        ..Default::default()
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut buffer = [0u8; 32];
+    assert_eq!(Some(&BUILD_ID[..]), loader.build_id(&mut buffer).unwrap());
+
+    let mut buffer = [0u8; 16];
+    assert!(matches!(
+        loader.build_id(&mut buffer),
+        Err(Error::BufferTooSmall { needed: 20 })
+    ));
+}
+
+#[test]
+fn build_id_from_section() {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(common::Section {
+        sh_addralign: 4,
+        ..common::Section::new(
+            ".note.gnu.build-id",
+            neotron_loader::SectionHeader::SHT_NOTE,
+            note(b"GNU\0", Note::NT_GNU_BUILD_ID, &BUILD_ID),
+        )
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut buffer = [0u8; 32];
+    assert_eq!(Some(&BUILD_ID[..]), loader.build_id(&mut buffer).unwrap());
+}
+
+#[test]
+fn no_build_id() {
+    let mut buffer = [0u8; 32];
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(None, loader.build_id(&mut buffer).unwrap());
+
+    let data = fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    assert_eq!(None, loader.build_id(&mut buffer).unwrap());
+}
+
+#[test]
+fn notes_via_section_and_segment() {
+    let mut notes = note(b"GNU\0", Note::NT_GNU_BUILD_ID, &BUILD_ID);
+    notes.extend(note(b"NTRN\0", 0x100, &[0xAA, 0xBB, 0xCC]));
+    let mut elf = ElfBuilder::new();
+    let note_idx = elf.add_section(common::Section {
+        sh_flags: 0x2,
+        sh_addralign: 4,
+        ..common::Section::new(
+            ".note.neotron",
+            neotron_loader::SectionHeader::SHT_NOTE,
+            notes,
+        )
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        p_align: 4,
+        section: Some(note_idx),
+        ..Default::default()
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+
+    let from_segment: Vec<Note> = loader.iter_notes().map(|n| n.unwrap()).collect();
+    let sh = loader
+        .find_section_by_name(".note.neotron")
+        .unwrap()
+        .unwrap();
+    let from_section: Vec<Note> = sh
+        .iter_notes(&loader)
+        .unwrap()
+        .map(|n| n.unwrap())
+        .collect();
+    assert_eq!(2, from_section.len());
+    assert_eq!(from_segment, from_section);
+}
+
+#[test]
+fn section_notes_bounded_by_section() {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(common::Section {
+        sh_addralign: 4,
+        // Only room for the header
+        sh_size: Some(12),
+        ..common::Section::new(
+            ".note.short",
+            neotron_loader::SectionHeader::SHT_NOTE,
+            note(b"GNU\0", 1, &[1, 2, 3, 4]),
+        )
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = loader.find_section_by_name(".note.short").unwrap().unwrap();
+    let mut notes = sh.iter_notes(&loader).unwrap();
+    assert!(matches!(notes.next(), Some(Err(Error::BadNote))));
+    assert!(notes.next().is_none());
+
+    let text = loader.find_section_by_name(".shstrtab").unwrap().unwrap();
+    assert!(matches!(
+        text.iter_notes(&loader),
+        Err(Error::WrongSectionType)
+    ));
+}
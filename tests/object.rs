@@ -0,0 +1,155 @@
+mod common;
+
+use common::{put_u16, put_u32, symbol, words, ElfBuilder, Section, StrTab, ET_REL};
+use neotron_loader::{Error, Loader, LoaderOptions, RelocError, Relocation, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// An object file, as if compiled from a single function which calls
+/// `os_print`.
+fn fixture() -> Vec<u8> {
+    let mut strtab = StrTab::new();
+    let file = strtab.add("app.c");
+    let app_main = strtab.add("app_main");
+    let os_print = strtab.add("os_print");
+
+    let mut elf = ElfBuilder::new();
+    elf.e_type = ET_REL;
+    let text = elf.add_section(Section {
+        sh_flags: 0x6,
+        sh_addralign: 2,
+        ..Section::new(
+            ".text",
+            SectionHeader::SHT_PROGBITS,
+            vec![0x80, 0xB5, 0xFF, 0xF7, 0xFE, 0xFF, 0x80, 0xBD],
+        )
+    });
+    let strtab_idx = elf.add_section(Section::new(
+        ".strtab",
+        SectionHeader::SHT_STRTAB,
+        strtab.data.clone(),
+    ));
+    let mut symbols = Vec::new();
+    symbols.extend(symbol(0, 0, 0, 0, 0));
+    symbols.extend(symbol(file, 0, 0, 0x04, 0xFFF1));
+    symbols.extend(symbol(app_main, 1, 8, 0x12, text as u16));
+    symbols.extend(symbol(os_print, 0, 0, 0x10, 0));
+    let symtab = elf.add_section(Section {
+        sh_link: strtab_idx as u32,
+        sh_info: 2,
+        sh_addralign: 4,
+        sh_entsize: 16,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, symbols)
+    });
+    elf.add_section(Section {
+        sh_link: symtab as u32,
+        sh_info: text as u32,
+        sh_addralign: 4,
+        sh_entsize: 8,
+        ..Section::new(
+            ".rel.text",
+            SectionHeader::SHT_REL,
+            words(&[0x2, (3 << 8) | u32::from(Relocation::R_ARM_THM_CALL)]),
+        )
+    });
+    let mut data = elf.build();
+    // Object files say nothing about program headers
+    put_u32(&mut data, 0x1C, 0);
+    put_u16(&mut data, 0x2A, 0);
+    data
+}
+
+#[test]
+fn rejected_by_default() {
+    let data = fixture();
+    assert!(matches!(Loader::new(&data[..]), Err(Error::WrongElfFile)));
+    assert!(matches!(
+        Loader::new_with_options(&data[..], &LoaderOptions::new()),
+        Err(Error::WrongElfFile)
+    ));
+}
+
+#[test]
+fn accepted_as_object() {
+    let data = fixture();
+    let loader = Loader::new_object(&data[..]).unwrap();
+    assert!(loader.is_object());
+    assert_eq!(Loader::<&[u8]>::ET_REL, loader.e_type());
+
+    let loader =
+        Loader::new_with_options(&data[..], &LoaderOptions::new().allow_object(true)).unwrap();
+    assert!(loader.is_object());
+}
+
+#[test]
+fn executables_still_accepted() {
+    let loader = Loader::new_object(ELF_DATA).unwrap();
+    assert!(!loader.is_object());
+    assert_eq!(6, loader.iter_program_headers().count());
+}
+
+#[test]
+fn no_program_headers() {
+    let mut data = fixture();
+    // Even if the header claims there are some
+    put_u32(&mut data, 0x1C, 0x34);
+    put_u16(&mut data, 0x2C, 3);
+    let loader = Loader::new_object(&data[..]).unwrap();
+    assert_eq!(0, loader.e_phnum());
+    assert_eq!(0, loader.iter_program_headers().count());
+    assert_eq!(0, loader.iter_notes().count());
+    assert_eq!(0, loader.iter_dynamic_tags().unwrap().count());
+    assert!(!loader.has_needed_libraries().unwrap());
+    assert!(loader.vaddr_to_offset(0x1).unwrap().is_none());
+    let mut buffer = [0u8; 64];
+    assert!(loader.interpreter(&mut buffer).unwrap().is_none());
+    assert!(loader.build_id(&mut buffer).unwrap().is_none());
+}
+
+#[test]
+fn sections_and_symbols() {
+    let data = fixture();
+    let loader = Loader::new_object(&data[..]).unwrap();
+    let mut buffer = [0u8; 32];
+    let mut names = Vec::new();
+    for sh in loader.iter_section_headers() {
+        let sh = sh.unwrap();
+        names.push(sh.sh_name(&loader, &mut buffer).unwrap().to_owned());
+    }
+    assert_eq!(
+        vec!["", ".text", ".strtab", ".symtab", ".rel.text", ".shstrtab"],
+        names
+    );
+
+    let text = loader.find_section_by_name(".text").unwrap().unwrap();
+    assert_eq!(8, text.sh_size());
+
+    let symbol = loader
+        .find_symbol_by_name("app_main", &mut buffer)
+        .unwrap()
+        .unwrap();
+    assert_eq!(1, symbol.st_value());
+    assert_eq!(1, symbol.st_shndx());
+    assert_eq!(4, loader.iter_symbols().unwrap().count());
+}
+
+#[test]
+fn relocations() {
+    let data = fixture();
+    let loader = Loader::new_object(&data[..]).unwrap();
+    let sh = loader.find_section_by_name(".rel.text").unwrap().unwrap();
+    let relocs = sh.iter_relocations(&loader).unwrap();
+    assert_eq!(1, relocs.target_section());
+    let relocs: Vec<Relocation> = relocs.map(|r| r.unwrap()).collect();
+    assert_eq!(1, relocs.len());
+    assert_eq!(2, relocs[0].r_offset());
+    assert_eq!(Relocation::R_ARM_THM_CALL, relocs[0].r_type());
+    assert_eq!(3, relocs[0].r_sym());
+
+    // Can't apply them, as they are section-relative
+    let mut image = [0u8; 8];
+    assert!(matches!(
+        loader.apply_relocations(&mut image, 0, 0, |_| None),
+        Err(RelocError::Loader(Error::WrongElfFile))
+    ));
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "alloc")]
+
+use neotron_loader::{Error, Loader, ProgramHeader, SectionHeader};
+
+mod common;
+
+use common::put_u32;
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+#[test]
+fn rodata_vec() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let rodata = loader.find_section_by_name(".rodata").unwrap().unwrap();
+    let start = rodata.sh_offset() as usize;
+    let end = start + rodata.sh_size() as usize;
+    let bytes = loader.section_bytes_vec(&rodata).unwrap();
+    assert_eq!(&ELF_DATA[start..end], &bytes[..]);
+}
+
+#[test]
+fn bss_vec() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let bss = loader.find_section_by_name(".bss").unwrap().unwrap();
+    assert_eq!(4, bss.sh_size());
+    assert!(loader.section_bytes_vec(&bss).unwrap().is_empty());
+}
+
+#[test]
+fn segment_vec() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    for ph in loader.iter_program_headers() {
+        let ph = ph.unwrap();
+        let bytes = loader.segment_bytes_vec(&ph).unwrap();
+        let start = ph.p_offset() as usize;
+        assert_eq!(&ELF_DATA[start..start + ph.p_filesz() as usize], &bytes[..]);
+    }
+    let text = loader
+        .iter_program_headers()
+        .map(|ph| ph.unwrap())
+        .find(|ph| ph.p_type() == ProgramHeader::PT_LOAD && ph.flags().is_executable())
+        .unwrap();
+    assert_eq!(0x444, loader.segment_bytes_vec(&text).unwrap().len());
+}
+
+#[test]
+fn huge_section() {
+    // Make .rodata claim to be almost 4 GiB long
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let header = loader.e_shoff() as usize + 2 * usize::from(SectionHeader::SIZE_IN_BYTES);
+    let mut data = ELF_DATA.to_vec();
+    put_u32(&mut data, header + 0x14, 0xFFFF_0000);
+    let loader = Loader::new(&data[..]).unwrap();
+    let rodata = SectionHeader::new(&loader, 2).unwrap();
+    assert!(matches!(
+        loader.section_bytes_vec(&rodata),
+        Err(Error::DataOutOfBounds)
+    ));
+}
+
+#[test]
+fn name_string() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let names: Vec<String> = loader
+        .iter_section_headers()
+        .map(|sh| sh.unwrap().sh_name_string(&loader).unwrap())
+        .collect();
+    assert_eq!("", names[0]);
+    assert_eq!(".text", names[1]);
+    assert_eq!(".ARM.attributes", names[13]);
+}
@@ -0,0 +1,444 @@
+mod common;
+
+use common::{symbol, words, ElfBuilder, Section, StrTab};
+use neotron_loader::relocations::ShortName;
+use neotron_loader::{
+    Error, Loader, Rel, Rela, RelocError, Relocation, RelocationType, SectionHeader,
+};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// An executable linked with `--emit-relocs`, with a `.rel.text` and a
+/// `.rela.data` section.
+fn fixture() -> Vec<u8> {
+    let mut strtab = StrTab::new();
+    let start = strtab.add("start");
+    let counter = strtab.add("counter");
+
+    let mut elf = ElfBuilder::new();
+    let text = elf.add_section(Section {
+        sh_flags: 0x6,
+        sh_addralign: 4,
+        ..Section::new(".text", SectionHeader::SHT_PROGBITS, vec![0; 16])
+    });
+    let data = elf.add_section(Section {
+        sh_flags: 0x3,
+        sh_addralign: 4,
+        ..Section::new(".data", SectionHeader::SHT_PROGBITS, vec![0; 8])
+    });
+    let strtab_idx = elf.add_section(Section::new(
+        ".strtab",
+        SectionHeader::SHT_STRTAB,
+        strtab.data.clone(),
+    ));
+    let mut symbols = Vec::new();
+    symbols.extend(symbol(0, 0, 0, 0, 0));
+    symbols.extend(symbol(start, 0, 4, 0x12, text as u16));
+    symbols.extend(symbol(counter, 4, 4, 0x11, data as u16));
+    let symtab = elf.add_section(Section {
+        sh_link: strtab_idx as u32,
+        sh_info: 1,
+        sh_addralign: 4,
+        sh_entsize: 16,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, symbols)
+    });
+    elf.add_section(Section {
+        sh_link: symtab as u32,
+        sh_info: text as u32,
+        sh_addralign: 4,
+        sh_entsize: 8,
+        ..Section::new(
+            ".rel.text",
+            SectionHeader::SHT_REL,
+            // R_ARM_ABS32 against start, R_ARM_THM_CALL against counter
+            words(&[0x4, (1 << 8) | 2, 0x8, (2 << 8) | 10]),
+        )
+    });
+    elf.add_section(Section {
+        sh_link: symtab as u32,
+        sh_info: data as u32,
+        sh_addralign: 4,
+        sh_entsize: 12,
+        ..Section::new(
+            ".rela.data",
+            SectionHeader::SHT_RELA,
+            words(&[0x0, (1 << 8) | 2, (-4i32) as u32, 0x4, 0x0100_0002, 0x10]),
+        )
+    });
+    elf.build()
+}
+
+#[test]
+fn rel_entries() {
+    let data = fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = loader.find_section_by_name(".rel.text").unwrap().unwrap();
+    let relocs = sh.iter_relocations(&loader).unwrap();
+    assert_eq!(1, relocs.target_section());
+    assert_eq!(4, relocs.symbol_table());
+    let relocs: Vec<Relocation> = relocs.map(|r| r.unwrap()).collect();
+    assert_eq!(2, relocs.len());
+    assert_eq!(0x4, relocs[0].r_offset());
+    assert_eq!(2, relocs[0].r_type());
+    assert_eq!(1, relocs[0].r_sym());
+    assert_eq!(None, relocs[0].r_addend());
+    assert_eq!(0x8, relocs[1].r_offset());
+    assert_eq!(10, relocs[1].r_type());
+    assert_eq!(2, relocs[1].r_sym());
+    let Relocation::Rel(rel) = &relocs[1] else {
+        panic!("Expected a REL entry, got {:?}", relocs[1]);
+    };
+    assert_eq!(10, rel.r_type());
+    assert_eq!(8, Rel::SIZE_IN_BYTES);
+}
+
+#[test]
+fn rela_entries() {
+    let data = fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = loader.find_section_by_name(".rela.data").unwrap().unwrap();
+    let relocs = sh.iter_relocations(&loader).unwrap();
+    assert_eq!(2, relocs.target_section());
+    assert_eq!(4, relocs.symbol_table());
+    let relocs: Vec<Relocation> = relocs.map(|r| r.unwrap()).collect();
+    assert_eq!(2, relocs.len());
+    assert_eq!(Some(-4), relocs[0].r_addend());
+    assert_eq!(1, relocs[0].r_sym());
+    // The symbol index is the top 24 bits of `r_info`
+    assert_eq!(0x0001_0000, relocs[1].r_sym());
+    assert_eq!(2, relocs[1].r_type());
+    let Relocation::Rela(rela) = &relocs[1] else {
+        panic!("Expected a RELA entry, got {:?}", relocs[1]);
+    };
+    assert_eq!(0x10, rela.r_addend());
+    assert_eq!(12, Rela::SIZE_IN_BYTES);
+}
+
+#[test]
+fn wrong_section_type() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let sh = loader.find_section_by_name(".text").unwrap().unwrap();
+    assert!(matches!(
+        sh.iter_relocations(&loader),
+        Err(Error::WrongSectionType)
+    ));
+}
+
+#[test]
+fn bad_entry_size() {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        // This is the size of a RELA entry, not a REL entry
+        sh_entsize: 12,
+        ..Section::new(".rel.text", SectionHeader::SHT_REL, words(&[0; 3]))
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = loader.find_section_by_name(".rel.text").unwrap().unwrap();
+    assert!(matches!(
+        sh.iter_relocations(&loader),
+        Err(Error::BadRelocationSection)
+    ));
+}
+
+#[test]
+fn partial_entry_ignored() {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_entsize: 8,
+        ..Section::new(
+            ".rel.text",
+            SectionHeader::SHT_REL,
+            words(&[0x10, 0x0000_0102, 0x20]),
+        )
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = loader.find_section_by_name(".rel.text").unwrap().unwrap();
+    let relocs: Vec<Relocation> = sh
+        .iter_relocations(&loader)
+        .unwrap()
+        .map(|r| r.unwrap())
+        .collect();
+    assert_eq!(1, relocs.len());
+    assert_eq!(0x10, relocs[0].r_offset());
+}
+
+#[test]
+fn stops_after_error() {
+    let mut elf = ElfBuilder::new();
+    let idx = elf.add_section(Section {
+        sh_entsize: 8,
+        ..Section::new(
+            ".rel.text",
+            SectionHeader::SHT_REL,
+            words(&[0x10, 0x0000_0102]),
+        )
+    });
+    let mut data = elf.build();
+    // Make the section run far off the end of the file
+    let e_shoff = Loader::new(&data[..]).unwrap().e_shoff() as usize;
+    common::put_u32(&mut data, e_shoff + idx * 0x28 + 0x14, 0xFFFF_FFF0);
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = loader.find_section_by_name(".rel.text").unwrap().unwrap();
+    let relocs: Vec<_> = sh.iter_relocations(&loader).unwrap().collect();
+    assert!(relocs.len() < 100, "{} entries", relocs.len());
+    assert!(relocs.last().unwrap().is_err());
+    assert!(relocs[..relocs.len() - 1].iter().all(|r| r.is_ok()));
+}
+
+#[test]
+fn relocation_type_round_trip() {
+    for raw in 0..=255u8 {
+        if let Some(ty) = RelocationType::from_u8(raw) {
+            assert_eq!(raw, ty.as_u8());
+        }
+    }
+    assert_eq!(
+        Some(RelocationType::Relative),
+        RelocationType::from_u8(Relocation::R_ARM_RELATIVE)
+    );
+    assert_eq!(23, Relocation::R_ARM_RELATIVE);
+    assert_eq!(None, RelocationType::from_u8(0xFF));
+}
+
+#[test]
+fn relocation_type_names() {
+    let expected = [
+        (Relocation::R_ARM_NONE, "R_ARM_NONE"),
+        (Relocation::R_ARM_ABS32, "R_ARM_ABS32"),
+        (Relocation::R_ARM_REL32, "R_ARM_REL32"),
+        (Relocation::R_ARM_THM_CALL, "R_ARM_THM_CALL"),
+        (Relocation::R_ARM_CALL, "R_ARM_CALL"),
+        (Relocation::R_ARM_JUMP24, "R_ARM_JUMP24"),
+        (Relocation::R_ARM_THM_JUMP24, "R_ARM_THM_JUMP24"),
+        (Relocation::R_ARM_TARGET1, "R_ARM_TARGET1"),
+        (Relocation::R_ARM_RELATIVE, "R_ARM_RELATIVE"),
+        (Relocation::R_ARM_GOT_BREL, "R_ARM_GOT_BREL"),
+    ];
+    for (raw, name) in expected {
+        let ty = RelocationType::from_u8(raw).unwrap();
+        assert_eq!(name, ty.name());
+        assert_eq!(name, format!("{}", ty));
+    }
+}
+
+#[test]
+fn relocation_type_support() {
+    assert!(RelocationType::Abs32.is_supported_by_loader());
+    assert!(RelocationType::Relative.is_supported_by_loader());
+    assert!(RelocationType::Target1.is_supported_by_loader());
+    assert!(!RelocationType::ThmCall.is_supported_by_loader());
+    assert!(!RelocationType::MovtAbs.is_supported_by_loader());
+
+    let data = fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = loader.find_section_by_name(".rel.text").unwrap().unwrap();
+    let types: Vec<Option<RelocationType>> = sh
+        .iter_relocations(&loader)
+        .unwrap()
+        .map(|r| r.unwrap().relocation_type())
+        .collect();
+    assert_eq!(
+        vec![Some(RelocationType::Abs32), Some(RelocationType::ThmCall)],
+        types
+    );
+}
+
+/// A plugin, linked at 0x1000, with relocations against an undefined symbol
+/// (`os_print`), a defined symbol (`table`) and the load address.
+fn plugin_fixture(extra_relocs: &[u32]) -> Vec<u8> {
+    let mut strtab = StrTab::new();
+    let os_print = strtab.add("os_print");
+    let table = strtab.add("table");
+
+    let mut elf = ElfBuilder::new();
+    elf.e_type = common::ET_DYN;
+    let data = elf.add_section(Section {
+        sh_flags: 0x3,
+        sh_addr: 0x1000,
+        sh_addralign: 4,
+        ..Section::new(
+            ".data",
+            SectionHeader::SHT_PROGBITS,
+            words(&[0, 0x1010, 4, 0, 0xAAAA_AAAA, 0xBBBB_BBBB]),
+        )
+    });
+    let strtab_idx = elf.add_section(Section::new(
+        ".dynstr",
+        SectionHeader::SHT_STRTAB,
+        strtab.data.clone(),
+    ));
+    let mut symbols = Vec::new();
+    symbols.extend(symbol(0, 0, 0, 0, 0));
+    symbols.extend(symbol(os_print, 0, 0, 0x12, 0));
+    symbols.extend(symbol(table, 0x1010, 8, 0x11, data as u16));
+    let dynsym = elf.add_section(Section {
+        sh_link: strtab_idx as u32,
+        sh_info: 1,
+        sh_addralign: 4,
+        sh_entsize: 16,
+        ..Section::new(".dynsym", SectionHeader::SHT_DYNSYM, symbols)
+    });
+    let mut relocs = vec![
+        0x1000,
+        (1 << 8) | u32::from(Relocation::R_ARM_ABS32),
+        0x1004,
+        u32::from(Relocation::R_ARM_RELATIVE),
+        0x1008,
+        (2 << 8) | u32::from(Relocation::R_ARM_ABS32),
+        0x100C,
+        (1 << 8) | u32::from(Relocation::R_ARM_GLOB_DAT),
+        0x1010,
+        u32::from(Relocation::R_ARM_NONE),
+    ];
+    relocs.extend_from_slice(extra_relocs);
+    elf.add_section(Section {
+        sh_flags: 0x2,
+        sh_link: dynsym as u32,
+        sh_addralign: 4,
+        sh_entsize: 8,
+        ..Section::new(".rel.dyn", SectionHeader::SHT_REL, words(&relocs))
+    });
+    elf.build()
+}
+
+/// The `.data` section of the plugin, as copied into RAM.
+fn plugin_image() -> Vec<u8> {
+    words(&[0, 0x1010, 4, 0, 0xAAAA_AAAA, 0xBBBB_BBBB])
+}
+
+fn resolve_os(name: &str) -> Option<u32> {
+    if name == "os_print" {
+        Some(0x0800_1235)
+    } else {
+        None
+    }
+}
+
+#[test]
+fn apply_relocations() {
+    let data = plugin_fixture(&[]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut image = plugin_image();
+    let mut names = Vec::new();
+    let count = loader
+        .apply_relocations(&mut image, 0x1000, 0x2000_0000, |name| {
+            names.push(name.to_owned());
+            resolve_os(name)
+        })
+        .unwrap();
+    assert_eq!(4, count);
+    assert_eq!(vec!["os_print", "os_print"], names);
+    assert_eq!(
+        words(&[
+            // S + A
+            0x0800_1235,
+            // B + A
+            0x2000_0010,
+            // S + A, with S moved along with the image
+            0x2000_0014,
+            // S
+            0x0800_1235,
+            0xAAAA_AAAA,
+            0xBBBB_BBBB,
+        ]),
+        image
+    );
+}
+
+#[test]
+fn apply_relocations_in_place() {
+    // Loading at the link address only changes the undefined symbols
+    let data = plugin_fixture(&[]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut image = plugin_image();
+    loader
+        .apply_relocations(&mut image, 0x1000, 0x1000, resolve_os)
+        .unwrap();
+    assert_eq!(
+        words(&[
+            0x0800_1235,
+            0x1010,
+            0x1014,
+            0x0800_1235,
+            0xAAAA_AAAA,
+            0xBBBB_BBBB
+        ]),
+        image
+    );
+}
+
+#[test]
+fn apply_rel32() {
+    let data = plugin_fixture(&[0x1014, (2 << 8) | u32::from(Relocation::R_ARM_REL32)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut image = plugin_image();
+    image[0x14..0x18].copy_from_slice(&0u32.to_le_bytes());
+    loader
+        .apply_relocations(&mut image, 0x1000, 0x2000_0000, resolve_os)
+        .unwrap();
+    // S + A - P = 0x2000_0010 - 0x2000_0014
+    assert_eq!(&(-4i32).to_le_bytes(), &image[0x14..0x18]);
+}
+
+#[test]
+fn unresolved_symbol() {
+    let data = plugin_fixture(&[]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut image = plugin_image();
+    match loader.apply_relocations(&mut image, 0x1000, 0x2000_0000, |_| None) {
+        Err(RelocError::UnresolvedSymbol(name)) => assert_eq!("os_print", name.as_str()),
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn unsupported_relocation() {
+    let data = plugin_fixture(&[0x1010, (1 << 8) | u32::from(Relocation::R_ARM_THM_CALL)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut image = plugin_image();
+    match loader.apply_relocations(&mut image, 0x1000, 0x2000_0000, resolve_os) {
+        Err(RelocError::UnsupportedType {
+            r_type,
+            symbol: Some(name),
+        }) => {
+            assert_eq!(Relocation::R_ARM_THM_CALL, r_type);
+            assert_eq!("os_print", name.as_str());
+        }
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
+
+#[test]
+fn relocation_out_of_bounds() {
+    let data = plugin_fixture(&[0x1016, u32::from(Relocation::R_ARM_RELATIVE)]);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut image = plugin_image();
+    assert!(matches!(
+        loader.apply_relocations(&mut image, 0x1000, 0x2000_0000, resolve_os),
+        Err(RelocError::OutOfBounds { r_offset: 0x1016 })
+    ));
+}
+
+#[test]
+fn no_relocations() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut image = [0u8; 16];
+    assert_eq!(
+        0,
+        loader
+            .apply_relocations(&mut image, 0x2000_0000, 0x2001_0000, |_| None)
+            .unwrap()
+    );
+}
+
+#[test]
+fn short_name_truncates() {
+    let name = ShortName::new("_ZN11neotron_sdk7get_api17h4e0ebeb2696c80c6E");
+    assert_eq!("_ZN11neotron_sdk7get_api17h4e0eb", name.as_str());
+    assert_eq!(ShortName::CAPACITY, name.as_str().len());
+    // Never split a character
+    let name = ShortName::new("aéééééééééééééééé");
+    assert_eq!(31, name.as_str().len());
+}
@@ -0,0 +1,78 @@
+mod common;
+
+use common::{MockError, MockSource};
+use neotron_loader::{traits::RetrySource, Error, Loader, Source};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+fn is_transient(e: &MockError) -> bool {
+    *e == MockError::Transient
+}
+
+#[test]
+fn fails_twice_then_succeeds() {
+    let mock = MockSource::new(ELF_DATA);
+    let source = RetrySource::new(&mock, 3);
+    mock.fail_next(2);
+    let loader = Loader::new(source).unwrap();
+    assert_eq!(loader.e_entry(), 0x200012a9);
+    mock.fail_next(2);
+    let mut loaded = 0;
+    loader
+        .load(|_addr, bytes| {
+            loaded += bytes.len();
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(loaded, 0xf4 + 0x444 + 0x38 + 4);
+    let mut retries = 0;
+    loader.map_source(|source| {
+        retries = source.retry_count();
+        source
+    });
+    assert_eq!(retries, 4);
+}
+
+#[test]
+fn always_fails() {
+    let mock = MockSource::new(ELF_DATA);
+    let source = RetrySource::new(&mock, 3);
+    mock.fail_next(u32::MAX);
+    assert!(matches!(
+        Loader::new(source),
+        Err(Error::Source(MockError::Transient))
+    ));
+    // The first go, and three more
+    assert_eq!(mock.reads(), 4);
+}
+
+#[test]
+fn classifier_stops_retries() {
+    let mock = MockSource::new(ELF_DATA);
+    let source = RetrySource::with_classifier(&mock, 3, is_transient);
+    let mut buffer = [0u8; 4];
+    assert_eq!(
+        source.read(ELF_DATA.len() as u32, &mut buffer),
+        Err(MockError::OutOfRange)
+    );
+    assert_eq!(mock.reads(), 1);
+    assert_eq!(source.retry_count(), 0);
+
+    mock.fail_next(1);
+    source.read(0, &mut buffer).unwrap();
+    assert_eq!(&buffer, b"\x7fELF");
+    assert_eq!(source.retry_count(), 1);
+}
+
+#[test]
+fn no_classifier_retries_everything() {
+    let mock = MockSource::new(ELF_DATA);
+    let source = RetrySource::new(&mock, 2);
+    let mut buffer = [0u8; 4];
+    assert_eq!(
+        source.read(ELF_DATA.len() as u32, &mut buffer),
+        Err(MockError::OutOfRange)
+    );
+    assert_eq!(mock.reads(), 3);
+    assert_eq!(source.retry_count(), 2);
+}
@@ -0,0 +1,779 @@
+mod common;
+
+use common::{put_u16, put_u32, CountingSource, ElfBuilder, Section};
+use neotron_loader::{CompressionHeader, Error, Loader, SectionHeader, SectionType};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// `test.elf`, after `llvm-objcopy --compress-debug-sections=zlib`.
+static ZDEBUG_ELF_DATA: &[u8] = include_bytes!("../test-zdebug.elf");
+
+fn section(loader: &Loader<&[u8]>, name: &str) -> SectionHeader {
+    loader.find_section_by_name(name).unwrap().unwrap()
+}
+
+#[test]
+fn text_flags() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let flags = section(&loader, ".text").flags();
+    assert!(flags.is_alloc());
+    assert!(flags.is_executable());
+    assert!(!flags.is_writable());
+    assert!(!flags.is_tls());
+    assert_eq!(
+        SectionHeader::SHF_ALLOC | SectionHeader::SHF_EXECINSTR,
+        flags.bits()
+    );
+    assert_eq!("AX", format!("{}", flags));
+}
+
+#[test]
+fn debug_info_flags() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let flags = section(&loader, ".debug_info").flags();
+    assert!(!flags.is_alloc());
+    assert!(!flags.is_executable());
+    assert!(!flags.is_writable());
+    assert_eq!(0, flags.bits());
+    assert_eq!("", format!("{}", flags));
+}
+
+#[test]
+fn other_flags() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!("WA", format!("{}", section(&loader, ".bss").flags()));
+    assert_eq!("MS", format!("{}", section(&loader, ".comment").flags()));
+    // Raw access is still available
+    assert_eq!(0x30, section(&loader, ".comment").sh_flags());
+}
+
+#[test]
+fn section_type_round_trip() {
+    let known = [
+        (SectionHeader::SHT_NULL, SectionType::Null, "SHT_NULL"),
+        (
+            SectionHeader::SHT_PROGBITS,
+            SectionType::Progbits,
+            "SHT_PROGBITS",
+        ),
+        (SectionHeader::SHT_SYMTAB, SectionType::Symtab, "SHT_SYMTAB"),
+        (SectionHeader::SHT_STRTAB, SectionType::Strtab, "SHT_STRTAB"),
+        (SectionHeader::SHT_RELA, SectionType::Rela, "SHT_RELA"),
+        (SectionHeader::SHT_HASH, SectionType::Hash, "SHT_HASH"),
+        (
+            SectionHeader::SHT_DYNAMIC,
+            SectionType::Dynamic,
+            "SHT_DYNAMIC",
+        ),
+        (SectionHeader::SHT_NOTE, SectionType::Note, "SHT_NOTE"),
+        (SectionHeader::SHT_NOBITS, SectionType::Nobits, "SHT_NOBITS"),
+        (SectionHeader::SHT_REL, SectionType::Rel, "SHT_REL"),
+        (SectionHeader::SHT_SHLIB, SectionType::Shlib, "SHT_SHLIB"),
+        (SectionHeader::SHT_DYNSYM, SectionType::Dynsym, "SHT_DYNSYM"),
+        (
+            SectionHeader::SHT_INIT_ARRAY,
+            SectionType::InitArray,
+            "SHT_INIT_ARRAY",
+        ),
+        (
+            SectionHeader::SHT_FINI_ARRAY,
+            SectionType::FiniArray,
+            "SHT_FINI_ARRAY",
+        ),
+        (
+            SectionHeader::SHT_PREINIT_ARRAY,
+            SectionType::PreinitArray,
+            "SHT_PREINIT_ARRAY",
+        ),
+        (SectionHeader::SHT_GROUP, SectionType::Group, "SHT_GROUP"),
+        (
+            SectionHeader::SHT_SYMTAB_SHNDX,
+            SectionType::SymtabShndx,
+            "SHT_SYMTAB_SHNDX",
+        ),
+        (
+            SectionHeader::SHT_GNU_HASH,
+            SectionType::GnuHash,
+            "SHT_GNU_HASH",
+        ),
+        (
+            SectionHeader::SHT_ARM_EXIDX,
+            SectionType::ArmExidx,
+            "SHT_ARM_EXIDX",
+        ),
+        (
+            SectionHeader::SHT_ARM_PREEMPTMAP,
+            SectionType::ArmPreemptmap,
+            "SHT_ARM_PREEMPTMAP",
+        ),
+        (
+            SectionHeader::SHT_ARM_ATTRIBUTES,
+            SectionType::ArmAttributes,
+            "SHT_ARM_ATTRIBUTES",
+        ),
+        (
+            SectionHeader::SHT_ARM_DEBUGOVERLAY,
+            SectionType::ArmDebugoverlay,
+            "SHT_ARM_DEBUGOVERLAY",
+        ),
+        (
+            SectionHeader::SHT_ARM_OVERLAYSECTION,
+            SectionType::ArmOverlaysection,
+            "SHT_ARM_OVERLAYSECTION",
+        ),
+        (0x6000_0010, SectionType::Os(0x6000_0010), "SHT_LOOS+"),
+        (0x7000_0010, SectionType::Proc(0x7000_0010), "SHT_LOPROC+"),
+        (0x8000_0010, SectionType::User(0x8000_0010), "SHT_LOUSER+"),
+        (0x13, SectionType::Unknown(0x13), "SHT_???"),
+    ];
+    for (raw, ty, name) in known {
+        assert_eq!(ty, SectionType::from(raw));
+        assert_eq!(raw, ty.as_u32());
+        assert_eq!(name, ty.name());
+        assert_eq!(name, format!("{}", ty));
+    }
+}
+
+#[test]
+fn test_elf_section_types() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(
+        SectionType::Progbits,
+        section(&loader, ".text").section_type()
+    );
+    assert_eq!(SectionType::Nobits, section(&loader, ".bss").section_type());
+    assert_eq!(
+        SectionType::ArmAttributes,
+        section(&loader, ".ARM.attributes").section_type()
+    );
+    assert_eq!(
+        SectionType::Symtab,
+        section(&loader, ".symtab").section_type()
+    );
+}
+
+#[test]
+fn display_section_header() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(
+        "SHT_PROGBITS       addr=0x20001000 off=0x001000 size=0x000444 flags=AX",
+        format!("{}", section(&loader, ".text"))
+    );
+}
+
+#[test]
+fn compressed_section() {
+    let loader = Loader::new(ZDEBUG_ELF_DATA).unwrap();
+    let debug_info = section(&loader, ".debug_info");
+    assert!(debug_info.is_compressed());
+    assert_eq!("C", format!("{}", debug_info.flags()));
+    let header = debug_info.compression(&loader).unwrap().unwrap();
+    assert_eq!(CompressionHeader::ELFCOMPRESS_ZLIB, header.ch_type());
+    // The same size as the uncompressed section in test.elf
+    assert_eq!(0x125f, header.ch_size());
+    assert_eq!(1, header.ch_addralign());
+    assert_eq!(debug_info.sh_offset() + 12, header.payload_offset());
+    assert_eq!(debug_info.sh_size() - 12, header.payload_size());
+    // A zlib stream starts with 0x78
+    assert_eq!(0x78, ZDEBUG_ELF_DATA[header.payload_offset() as usize]);
+
+    let mut buffer = [0u8; 16];
+    assert!(matches!(
+        debug_info.read_data(&loader, 0, &mut buffer),
+        Err(Error::CompressedSection)
+    ));
+}
+
+#[test]
+fn uncompressed_section() {
+    let loader = Loader::new(ZDEBUG_ELF_DATA).unwrap();
+    let comment = section(&loader, ".comment");
+    assert!(!comment.is_compressed());
+    assert_eq!(None, comment.compression(&loader).unwrap());
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert_eq!(
+        None,
+        section(&loader, ".debug_info")
+            .compression(&loader)
+            .unwrap()
+    );
+}
+
+#[test]
+fn read_data() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let comment = section(&loader, ".comment");
+    let mut buffer = [0u8; 64];
+    assert_eq!(
+        b"Linker: LLD 15.0.7\0",
+        comment.read_data(&loader, 0, &mut buffer).unwrap()
+    );
+    // Reads stop at the end of the section
+    assert_eq!(b"7\0", comment.read_data(&loader, 17, &mut buffer).unwrap());
+    assert!(comment
+        .read_data(&loader, 19, &mut buffer)
+        .unwrap()
+        .is_empty());
+    // Reads stop at the end of the buffer
+    let mut buffer = [0u8; 6];
+    assert_eq!(
+        b"Linker",
+        comment.read_data(&loader, 0, &mut buffer).unwrap()
+    );
+    // No file contents for .bss
+    let bss = section(&loader, ".bss");
+    assert!(bss.read_data(&loader, 0, &mut buffer).unwrap().is_empty());
+}
+
+#[test]
+fn name_at_end_of_file() {
+    // Move .shstrtab to the very end of the file
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let shstrtab = section(&loader, ".shstrtab");
+    let start = shstrtab.sh_offset() as usize;
+    let end = start + shstrtab.sh_size() as usize;
+    let mut data = ELF_DATA.to_vec();
+    data.extend_from_slice(&ELF_DATA[start..end]);
+    let header = loader.e_shoff() as usize + 18 * 0x28;
+    put_u32(&mut data, header + 0x10, ELF_DATA.len() as u32);
+
+    let loader = Loader::new(&data[..]).unwrap();
+    let strtab = SectionHeader::new(&loader, 19).unwrap();
+    // The buffer is bigger than what's left of the file
+    let mut buffer = [0u8; 64];
+    assert_eq!(".strtab", strtab.sh_name(&loader, &mut buffer).unwrap());
+    // Only just big enough
+    let mut buffer = [0u8; 8];
+    assert_eq!(".strtab", strtab.sh_name(&loader, &mut buffer).unwrap());
+    let mut buffer = [0u8; 7];
+    assert!(matches!(
+        strtab.sh_name(&loader, &mut buffer),
+        Err(Error::BufferTooSmall { needed: 8 })
+    ));
+}
+
+#[test]
+fn name_past_end_of_table() {
+    // Section names are .text = 1, .names = 7, .junk = 14, .shstrtab = 20
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section::new(".text", SectionHeader::SHT_PROGBITS, vec![0]));
+    // Our own name table, which cuts off .junk and has no .shstrtab
+    let names = elf.add_section(Section::new(
+        ".names",
+        SectionHeader::SHT_STRTAB,
+        b"\0.text\0.names\0.ju".to_vec(),
+    ));
+    // Straight after the name table, with a null in it
+    elf.add_section(Section::new(
+        ".junk",
+        SectionHeader::SHT_PROGBITS,
+        b"nk\0".to_vec(),
+    ));
+    elf.e_shstrndx = Some(names as u16);
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+
+    let mut buffer = [0u8; 32];
+    let sections: Vec<SectionHeader> = loader
+        .iter_section_headers()
+        .map(|sh| sh.unwrap())
+        .collect();
+    assert_eq!(".text", sections[1].sh_name(&loader, &mut buffer).unwrap());
+    assert_eq!(".names", sections[2].sh_name(&loader, &mut buffer).unwrap());
+    // This would be ".junk" if we read past the table
+    assert!(matches!(
+        sections[3].sh_name(&loader, &mut buffer),
+        Err(Error::InvalidString)
+    ));
+    assert!(matches!(
+        sections[4].sh_name(&loader, &mut buffer),
+        Err(Error::StringOffsetOutOfRange)
+    ));
+}
+
+#[test]
+fn long_name() {
+    let long_name = format!(".text.{}", "a".repeat(64));
+    assert_eq!(70, long_name.len());
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section::new(".text", SectionHeader::SHT_PROGBITS, vec![0]));
+    elf.add_section(Section::new(
+        &long_name,
+        SectionHeader::SHT_PROGBITS,
+        vec![0],
+    ));
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let short = SectionHeader::new(&loader, 1).unwrap();
+    let long = SectionHeader::new(&loader, 2).unwrap();
+
+    let mut buffer = [0u8; 16];
+    assert_eq!(".text", short.sh_name(&loader, &mut buffer).unwrap());
+    assert_eq!(5, short.sh_name_len(&loader).unwrap());
+    assert!(matches!(
+        long.sh_name(&loader, &mut buffer),
+        Err(Error::BufferTooSmall { needed: 71 })
+    ));
+    let needed = long.sh_name_len(&loader).unwrap();
+    assert_eq!(70, needed);
+
+    // Retry with a buffer of the right size
+    let mut buffer = vec![0u8; needed + 1];
+    assert_eq!(long_name, long.sh_name(&loader, &mut buffer).unwrap());
+}
+
+#[test]
+fn name_len_unterminated() {
+    let mut elf = ElfBuilder::new();
+    let names = elf.add_section(Section::new(
+        ".names",
+        SectionHeader::SHT_STRTAB,
+        b"\0.names.and.then.some.more.without.an.end".to_vec(),
+    ));
+    elf.e_shstrndx = Some(names as u16);
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = SectionHeader::new(&loader, 1).unwrap();
+    assert!(matches!(sh.sh_name_len(&loader), Err(Error::InvalidString)));
+}
+
+#[test]
+fn name_eq() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let text = section(&loader, ".text");
+    assert!(text.name_eq(&loader, ".text").unwrap());
+    assert!(!text.name_eq(&loader, ".tex").unwrap());
+    assert!(!text.name_eq(&loader, ".textx").unwrap());
+    assert!(!text.name_eq(&loader, "").unwrap());
+    assert!(!text.name_eq(&loader, ".data").unwrap());
+
+    let null = SectionHeader::new(&loader, 0).unwrap();
+    assert!(null.name_eq(&loader, "").unwrap());
+    assert!(!null.name_eq(&loader, ".text").unwrap());
+}
+
+#[test]
+fn name_eq_stops_early() {
+    let long_name = format!(".text.{}", "a".repeat(64));
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section::new(
+        &long_name,
+        SectionHeader::SHT_PROGBITS,
+        vec![0],
+    ));
+    let data = elf.build();
+    let source = CountingSource::new(&data);
+    let loader = Loader::new(&source).unwrap();
+    let sh = SectionHeader::new(&loader, 1).unwrap();
+
+    // One chunk is enough to spot the difference
+    let before = source.reads();
+    assert!(!sh.name_eq(&loader, ".data").unwrap());
+    assert_eq!(1, source.reads() - before);
+
+    // A match has to read the whole name
+    let before = source.reads();
+    assert!(sh.name_eq(&loader, &long_name).unwrap());
+    assert_eq!(5, source.reads() - before);
+}
+
+#[test]
+fn name_bytes() {
+    let long_name = format!(".text.{}", "a".repeat(64));
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section::new(
+        &long_name,
+        SectionHeader::SHT_PROGBITS,
+        vec![0],
+    ));
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = SectionHeader::new(&loader, 1).unwrap();
+
+    let bytes: Vec<u8> = sh.name_bytes(&loader).map(|b| b.unwrap()).collect();
+    assert_eq!(long_name.as_bytes(), &bytes[..]);
+    let mut name = String::new();
+    sh.write_name(&loader, &mut name).unwrap();
+    assert_eq!(long_name, name);
+    let display = format!("{}", sh.display(&loader));
+    assert!(display.starts_with(&long_name));
+    assert!(
+        display.ends_with("SHT_PROGBITS       addr=0x00000000 off=0x000034 size=0x000001 flags=")
+    );
+}
+
+#[test]
+fn name_bytes_unterminated() {
+    let mut elf = ElfBuilder::new();
+    let names = elf.add_section(Section::new(
+        ".names",
+        SectionHeader::SHT_STRTAB,
+        b"\0.names.and.then.some.more.without.an.end".to_vec(),
+    ));
+    elf.e_shstrndx = Some(names as u16);
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = SectionHeader::new(&loader, 1).unwrap();
+    let bytes: Vec<_> = sh.name_bytes(&loader).collect();
+    // Every byte of the name, then an error
+    let name_len = ".names.and.then.some.more.without.an.end".len();
+    assert_eq!(name_len + 1, bytes.len());
+    assert!(bytes[0..name_len].iter().all(|b| b.is_ok()));
+    assert!(matches!(bytes[name_len], Err(Error::InvalidString)));
+    let mut name = String::new();
+    assert!(sh.write_name(&loader, &mut name).is_err());
+}
+
+#[test]
+fn invalid_utf8_name() {
+    let mut elf = ElfBuilder::new();
+    let names = elf.add_section(Section::new(
+        ".names",
+        SectionHeader::SHT_STRTAB,
+        // ".na", a bad byte, a truncated 3-byte sequence, "é", and a
+        // truncated 2-byte sequence at the end.
+        b"\0.na\xffe\xe2\x82s\xc3\xa9\xc3\0".to_vec(),
+    ));
+    elf.e_shstrndx = Some(names as u16);
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sh = SectionHeader::new(&loader, 1).unwrap();
+    let mut name = String::new();
+    sh.write_name(&loader, &mut name).unwrap();
+    assert_eq!(".na\u{fffd}e\u{fffd}s\u{e9}\u{fffd}", name);
+    let mut buffer = [0u8; 32];
+    assert!(matches!(
+        sh.sh_name(&loader, &mut buffer),
+        Err(Error::InvalidString)
+    ));
+}
+
+#[test]
+fn shstrndx_not_a_string_table() {
+    // Point e_shstrndx at .text
+    let mut data = ELF_DATA.to_vec();
+    put_u16(&mut data, 0x32, 1);
+    let loader = Loader::new(&data[..]).unwrap();
+    let text = SectionHeader::new(&loader, 1).unwrap();
+    let mut buffer = [0u8; 32];
+    assert!(matches!(
+        text.sh_name(&loader, &mut buffer),
+        Err(Error::BadStringTable)
+    ));
+    assert!(matches!(
+        text.sh_name_len(&loader),
+        Err(Error::BadStringTable)
+    ));
+    assert!(matches!(
+        text.name_eq(&loader, ".text"),
+        Err(Error::BadStringTable)
+    ));
+    assert!(matches!(
+        loader.find_section_by_name(".text"),
+        Err(Error::BadStringTable)
+    ));
+    // Everything else still works
+    assert_eq!(20, loader.iter_section_headers().count());
+    assert_eq!(6, loader.iter_program_headers().count());
+}
+
+#[test]
+fn named_sections() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut scratch = [0u8; 32];
+    let mut sections = loader.iter_named_sections(&mut scratch);
+    let mut names = Vec::new();
+    while let Some(item) = sections.next_section() {
+        let (sh, name) = item.unwrap();
+        names.push((sh.sh_offset(), name.to_owned()));
+    }
+    assert_eq!(20, names.len());
+    for (idx, sh) in loader.iter_section_headers().enumerate() {
+        let sh = sh.unwrap();
+        let mut buffer = [0u8; 32];
+        let name = sh.sh_name(&loader, &mut buffer).unwrap();
+        assert_eq!((sh.sh_offset(), name.to_owned()), names[idx]);
+    }
+    assert_eq!(".ARM.attributes", names[13].1);
+}
+
+#[test]
+fn named_sections_too_long() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut scratch = [0u8; 8];
+    let mut sections = loader.iter_named_sections(&mut scratch);
+    let mut ok = Vec::new();
+    let mut too_long = Vec::new();
+    while let Some(item) = sections.next_section() {
+        match item {
+            Ok((_sh, name)) => ok.push(name.to_owned()),
+            Err(Error::BufferTooSmall { needed }) => {
+                too_long.push((sections.current_index(), needed))
+            }
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+    assert_eq!(
+        vec!["", ".text", ".rodata", ".data", ".bss", ".uninit", ".symtab", ".strtab"],
+        ok
+    );
+    // .debug_abbrev is section 6
+    assert_eq!((6, 14), too_long[0]);
+    // .shstrtab is section 18
+    assert_eq!(Some(&(18, 10)), too_long.last());
+    assert_eq!(12, too_long.len());
+}
+
+#[test]
+fn first_section_of_type() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let (idx, symtab) = loader
+        .first_section_of_type(SectionHeader::SHT_SYMTAB)
+        .unwrap()
+        .unwrap();
+    assert_eq!(17, idx);
+    assert_eq!(0x6d14, symtab.sh_offset());
+    // The section names come first
+    let (idx, strtab) = loader
+        .first_section_of_type(SectionHeader::SHT_STRTAB)
+        .unwrap()
+        .unwrap();
+    assert_eq!(18, idx);
+    assert_eq!(0x6ff4, strtab.sh_offset());
+    assert!(loader
+        .first_section_of_type(SectionHeader::SHT_DYNSYM)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn iter_sections_of_type() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let offsets: Vec<u32> = loader
+        .iter_sections_of_type(SectionHeader::SHT_STRTAB)
+        .map(|sh| sh.unwrap().sh_offset())
+        .collect();
+    assert_eq!(vec![0x6ff4, 0x70c4], offsets);
+    assert_eq!(
+        13,
+        loader
+            .iter_sections_of_type(SectionHeader::SHT_PROGBITS)
+            .count()
+    );
+    assert_eq!(
+        0,
+        loader
+            .iter_sections_of_type(SectionHeader::SHT_NOTE)
+            .count()
+    );
+}
+
+#[test]
+fn iter_sections_of_type_errors() {
+    // Claim there are more sections than the file holds
+    let mut data = ELF_DATA.to_vec();
+    put_u16(&mut data, 0x30, 22);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut iter = loader.iter_sections_of_type(SectionHeader::SHT_STRTAB);
+    assert!(iter.next().unwrap().is_ok());
+    assert!(iter.next().unwrap().is_ok());
+    assert!(iter.next().unwrap().is_err());
+}
+
+#[test]
+fn bss_ranges() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let bss = section(&loader, ".bss");
+    assert!(bss.is_nobits());
+    assert!(!bss.occupies_file_space());
+    let file_range = bss.file_range().unwrap();
+    assert!(file_range.is_empty());
+    assert_eq!(bss.sh_offset(), file_range.start);
+    assert_eq!(Some(0x2000_147c..0x2000_1480), bss.addr_range());
+    assert!(bss.contains_addr(0x2000_147c));
+    assert!(bss.contains_addr(0x2000_147f));
+    assert!(!bss.contains_addr(0x2000_1480));
+
+    let rodata = section(&loader, ".rodata");
+    assert!(!bss.overlaps(&rodata));
+    assert!(!rodata.overlaps(&bss));
+    assert!(bss.overlaps(&bss));
+}
+
+#[test]
+fn debug_info_ranges() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let debug_info = section(&loader, ".debug_info");
+    assert!(!debug_info.is_nobits());
+    assert!(debug_info.occupies_file_space());
+    assert_eq!(Some(0x1677..0x1677 + 0x125f), debug_info.file_range());
+    // Not loaded, so no addresses
+    assert_eq!(Some(0..0), debug_info.addr_range());
+    assert!(!debug_info.contains_addr(0));
+    let debug_abbrev = section(&loader, ".debug_abbrev");
+    assert!(!debug_info.overlaps(&debug_abbrev));
+    assert!(!debug_info.overlaps(&debug_info));
+
+    // The empty `.data` section has an address, but no contents
+    let data = section(&loader, ".data");
+    assert!(!data.is_nobits());
+    assert!(!data.occupies_file_space());
+    assert!(data.addr_range().unwrap().is_empty());
+}
+
+#[test]
+fn section_ranges_that_wrap() {
+    let mut elf = ElfBuilder::new();
+    let idx = elf.add_section(Section {
+        sh_addr: 0xFFFF_FF00,
+        sh_size: Some(0x100),
+        ..Section::new(".wraps", SectionHeader::SHT_PROGBITS, vec![])
+    });
+    elf.add_section(Section {
+        sh_addr: 0xFFFF_FF00,
+        sh_size: Some(0xFF),
+        ..Section::new(".top", SectionHeader::SHT_PROGBITS, vec![0; 0xFF])
+    });
+    let mut data = elf.build();
+    // Move the contents of the first section to the end of the file space
+    let loader = Loader::new(&data[..]).unwrap();
+    let shoff = loader.e_shoff() as usize;
+    put_u32(&mut data, shoff + idx * 40 + 0x10, 0xFFFF_FFF0);
+
+    let loader = Loader::new(&data[..]).unwrap();
+    let wraps = section(&loader, ".wraps");
+    assert_eq!(None, wraps.file_range());
+    assert_eq!(None, wraps.addr_range());
+    assert!(!wraps.contains_addr(0xFFFF_FF00));
+    let mut buffer = [0u8; 4];
+    assert!(matches!(
+        wraps.read_data(&loader, 0, &mut buffer),
+        Err(Error::DataOutOfBounds)
+    ));
+
+    let top = section(&loader, ".top");
+    assert_eq!(Some(0xFFFF_FF00..0xFFFF_FFFF), top.addr_range());
+    assert!(top.contains_addr(0xFFFF_FFFE));
+    assert!(!top.contains_addr(0xFFFF_FFFF));
+    assert!(top.overlaps(&wraps));
+    assert!(wraps.overlaps(&top));
+}
+
+#[test]
+fn linked_sections() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let symtab = section(&loader, ".symtab");
+    let strtab = symtab.linked_section(&loader).unwrap().unwrap();
+    assert_eq!(strtab.sh_type(), SectionHeader::SHT_STRTAB);
+    assert!(strtab.name_eq(&loader, ".strtab").unwrap());
+    // In a symbol table, sh_info counts the local symbols
+    assert!(matches!(
+        symtab.info_section(&loader),
+        Err(Error::WrongSectionType)
+    ));
+    assert!(section(&loader, ".text")
+        .linked_section(&loader)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn linked_section_out_of_range() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut data = ELF_DATA.to_vec();
+    // .symtab is section 17
+    put_u32(
+        &mut data,
+        loader.e_shoff() as usize + 17 * 0x28 + 0x18,
+        1000,
+    );
+    let loader = Loader::new(&data[..]).unwrap();
+    let symtab = section(&loader, ".symtab");
+    assert!(matches!(
+        symtab.linked_section(&loader),
+        Err(Error::SectionIndexOutOfRange { index: 1000 })
+    ));
+    assert!(matches!(
+        symtab.linked_section_lenient(&loader),
+        Err(Error::SectionIndexOutOfRange { index: 1000 })
+    ));
+    let symbol = loader.iter_symbols().unwrap().nth(1).unwrap().unwrap();
+    let mut buffer = [0u8; 64];
+    assert!(matches!(
+        symbol.st_name(&loader, &symtab, &mut buffer),
+        Err(Error::SectionIndexOutOfRange { index: 1000 })
+    ));
+}
+
+#[test]
+fn relocation_section_links() {
+    let mut elf = ElfBuilder::new();
+    let text = elf.add_section(Section::new(
+        ".text",
+        SectionHeader::SHT_PROGBITS,
+        vec![0; 8],
+    ));
+    let strtab = elf.add_section(Section::new(".strtab", SectionHeader::SHT_STRTAB, vec![0]));
+    let symtab = elf.add_section(Section {
+        sh_link: strtab as u32,
+        sh_entsize: 16,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, vec![0; 16])
+    });
+    elf.add_section(Section {
+        sh_link: symtab as u32,
+        sh_info: text as u32,
+        sh_entsize: 8,
+        ..Section::new(".rel.text", SectionHeader::SHT_REL, vec![])
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let rel = section(&loader, ".rel.text");
+    let linked = rel.linked_section(&loader).unwrap().unwrap();
+    assert_eq!(linked.sh_type(), SectionHeader::SHT_SYMTAB);
+    let patched = rel.info_section(&loader).unwrap().unwrap();
+    assert!(patched.name_eq(&loader, ".text").unwrap());
+
+    // A relocation section which links to the wrong kind of section
+    let mut elf = ElfBuilder::new();
+    let text = elf.add_section(Section::new(
+        ".text",
+        SectionHeader::SHT_PROGBITS,
+        vec![0; 8],
+    ));
+    elf.add_section(Section {
+        sh_link: text as u32,
+        sh_info: text as u32,
+        sh_entsize: 8,
+        ..Section::new(".rel.text", SectionHeader::SHT_REL, vec![])
+    });
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let rel = section(&loader, ".rel.text");
+    assert!(matches!(
+        rel.linked_section(&loader),
+        Err(Error::WrongSectionType)
+    ));
+    let linked = rel.linked_section_lenient(&loader).unwrap().unwrap();
+    assert_eq!(linked.sh_type(), SectionHeader::SHT_PROGBITS);
+}
+
+#[test]
+fn wrapping_section_names() {
+    let mut data = ELF_DATA.to_vec();
+    // Make the section name table wrap past 4 GiB
+    let e_shoff = Loader::new(ELF_DATA).unwrap().e_shoff() as usize;
+    let e_shstrndx = usize::from(u16::from_le_bytes([data[0x32], data[0x33]]));
+    put_u32(&mut data, e_shoff + e_shstrndx * 0x28 + 0x10, 0xFFFF_FFF0);
+    put_u32(&mut data, e_shoff + e_shstrndx * 0x28 + 0x14, 0x1000);
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut buffer = [0u8; 64];
+    let sh = SectionHeader::new(&loader, 10).unwrap();
+    assert!(matches!(
+        sh.sh_name(&loader, &mut buffer),
+        Err(Error::BadStringTable)
+    ));
+    assert!(matches!(
+        loader.find_section_by_name(".text"),
+        Err(Error::BadStringTable)
+    ));
+}
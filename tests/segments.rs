@@ -0,0 +1,421 @@
+mod common;
+
+use common::{put_u32, ElfBuilder, Segment};
+use neotron_loader::{Error, Loader, ProgramHeader, SegmentFlags, SegmentType};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// A Linux-style executable, with a program interpreter.
+fn interp_fixture() -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_INTERP,
+        p_flags: 0x4,
+        p_align: 1,
+        data: b"/lib/ld-linux.so.3\0".to_vec(),
+        ..Default::default()
+    });
+    elf.build()
+}
+
+#[test]
+fn interpreter() {
+    let data = interp_fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut buffer = [0xFFu8; 64];
+    assert_eq!(
+        Some("/lib/ld-linux.so.3"),
+        loader.interpreter(&mut buffer).unwrap()
+    );
+    // Exactly big enough
+    let mut buffer = [0u8; 19];
+    assert_eq!(
+        Some("/lib/ld-linux.so.3"),
+        loader.interpreter(&mut buffer).unwrap()
+    );
+}
+
+#[test]
+fn interpreter_buffer_too_small() {
+    let data = interp_fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    let mut buffer = [0u8; 8];
+    assert!(matches!(
+        loader.interpreter(&mut buffer),
+        Err(Error::BufferTooSmall { needed: 19 })
+    ));
+}
+
+#[test]
+fn no_interpreter() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut buffer = [0u8; 64];
+    assert_eq!(None, loader.interpreter(&mut buffer).unwrap());
+}
+
+#[test]
+fn segment_flags() {
+    let cases = [
+        (0, "---", false, false, false),
+        (ProgramHeader::PF_X, "--x", false, false, true),
+        (ProgramHeader::PF_W, "-w-", false, true, false),
+        (
+            ProgramHeader::PF_W | ProgramHeader::PF_X,
+            "-wx",
+            false,
+            true,
+            true,
+        ),
+        (ProgramHeader::PF_R, "r--", true, false, false),
+        (
+            ProgramHeader::PF_R | ProgramHeader::PF_X,
+            "r-x",
+            true,
+            false,
+            true,
+        ),
+        (
+            ProgramHeader::PF_R | ProgramHeader::PF_W,
+            "rw-",
+            true,
+            true,
+            false,
+        ),
+        (
+            ProgramHeader::PF_R | ProgramHeader::PF_W | ProgramHeader::PF_X,
+            "rwx",
+            true,
+            true,
+            true,
+        ),
+    ];
+    for (bits, text, r, w, x) in cases {
+        let flags = SegmentFlags::new(bits);
+        assert_eq!(bits, flags.bits());
+        assert_eq!(text, format!("{}", flags));
+        assert_eq!(format!("SegmentFlags({})", text), format!("{:?}", flags));
+        assert_eq!(r, flags.is_readable());
+        assert_eq!(w, flags.is_writable());
+        assert_eq!(x, flags.is_executable());
+        assert_eq!(w && x, flags.is_wx());
+    }
+}
+
+#[test]
+fn test_elf_segment_flags() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let flags: Vec<String> = loader
+        .iter_program_headers()
+        .map(|ph| format!("{}", ph.unwrap().flags()))
+        .collect();
+    assert_eq!(vec!["r--", "r--", "r-x", "r--", "rw-", "rw-"], flags);
+}
+
+#[test]
+fn segment_type_round_trip() {
+    let known = [
+        (ProgramHeader::PT_NULL, SegmentType::Null, "PT_NULL"),
+        (ProgramHeader::PT_LOAD, SegmentType::Load, "PT_LOAD"),
+        (
+            ProgramHeader::PT_DYNAMIC,
+            SegmentType::Dynamic,
+            "PT_DYNAMIC",
+        ),
+        (ProgramHeader::PT_INTERP, SegmentType::Interp, "PT_INTERP"),
+        (ProgramHeader::PT_NOTE, SegmentType::Note, "PT_NOTE"),
+        (ProgramHeader::PT_SHLIB, SegmentType::Shlib, "PT_SHLIB"),
+        (ProgramHeader::PT_PHDR, SegmentType::Phdr, "PT_PHDR"),
+        (ProgramHeader::PT_TLS, SegmentType::Tls, "PT_TLS"),
+        (
+            ProgramHeader::PT_GNU_STACK,
+            SegmentType::GnuStack,
+            "PT_GNU_STACK",
+        ),
+        (
+            ProgramHeader::PT_GNU_RELRO,
+            SegmentType::GnuRelro,
+            "PT_GNU_RELRO",
+        ),
+        (
+            ProgramHeader::PT_GNU_EH_FRAME,
+            SegmentType::GnuEhFrame,
+            "PT_GNU_EH_FRAME",
+        ),
+        (
+            ProgramHeader::PT_ARM_EXIDX,
+            SegmentType::ArmExidx,
+            "PT_ARM_EXIDX",
+        ),
+        (0x6000_0001, SegmentType::Os(0x6000_0001), "PT_LOOS+"),
+        (0x7FFF_FFFF, SegmentType::Proc(0x7FFF_FFFF), "PT_LOPROC+"),
+        (0x8, SegmentType::Unknown(0x8), "PT_???"),
+        (0xFFFF_FFFF, SegmentType::Unknown(0xFFFF_FFFF), "PT_???"),
+    ];
+    for (raw, ty, name) in known {
+        assert_eq!(ty, SegmentType::from(raw));
+        assert_eq!(raw, ty.as_u32());
+        assert_eq!(name, ty.name());
+    }
+}
+
+#[test]
+fn test_elf_segment_types() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let types: Vec<SegmentType> = loader
+        .iter_program_headers()
+        .map(|ph| ph.unwrap().segment_type())
+        .collect();
+    assert_eq!(
+        vec![
+            SegmentType::Phdr,
+            SegmentType::Load,
+            SegmentType::Load,
+            SegmentType::Load,
+            SegmentType::Load,
+            SegmentType::GnuStack
+        ],
+        types
+    );
+}
+
+fn load_segment(p_paddr: u32, p_memsz: u32) -> Segment {
+    Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_flags: 0x4,
+        p_vaddr: p_paddr,
+        p_paddr,
+        p_memsz: Some(p_memsz),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn load_segments_sorted() {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(load_segment(0x2000_0000, 0x100)); // 0
+    elf.add_segment(load_segment(0x1000_0000, 0x40)); // 1
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_NOTE,
+        ..Default::default()
+    }); // 2
+    elf.add_segment(load_segment(0x1800_0000, 0x10)); // 3
+    elf.add_segment(load_segment(0x1000_0000, 0)); // 4
+    elf.add_segment(load_segment(0x1800_0000, 0x10)); // 5
+    elf.add_segment(load_segment(0x0800_0000, 0x20)); // 6
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let sorted = loader.iter_load_segments_sorted().unwrap();
+    assert_eq!(6, sorted.len());
+    let sorted: Vec<(u32, u32)> = sorted
+        .map(|ph| {
+            let ph = ph.unwrap();
+            (ph.p_paddr(), ph.p_memsz())
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (0x0800_0000, 0x20),
+            (0x1000_0000, 0),
+            (0x1000_0000, 0x40),
+            (0x1800_0000, 0x10),
+            (0x1800_0000, 0x10),
+            (0x2000_0000, 0x100),
+        ],
+        sorted
+    );
+
+    // Ties are broken by file order
+    let offsets: Vec<u32> = loader
+        .iter_program_headers()
+        .map(|ph| ph.unwrap().p_offset())
+        .collect();
+    let tied: Vec<u32> = loader
+        .iter_load_segments_sorted()
+        .unwrap()
+        .skip(3)
+        .take(2)
+        .map(|ph| ph.unwrap().p_offset())
+        .collect();
+    assert_eq!(vec![offsets[3], offsets[5]], tied);
+}
+
+#[test]
+fn load_segments_sorted_test_elf() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let addresses: Vec<u32> = loader
+        .iter_load_segments_sorted()
+        .unwrap()
+        .map(|ph| ph.unwrap().p_paddr())
+        .collect();
+    assert!(!addresses.is_empty());
+    assert!(addresses.windows(2).all(|w| w[0] <= w[1]));
+}
+
+#[test]
+fn too_many_load_segments() {
+    let mut elf = ElfBuilder::new();
+    for idx in 0..neotron_loader::segments::MAX_SORTED_LOAD_SEGMENTS as u32 {
+        elf.add_segment(load_segment(0x1000 * (64 - idx), 0x10));
+    }
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    assert_eq!(32, loader.iter_load_segments_sorted().unwrap().count());
+
+    elf.add_segment(load_segment(0, 0x10));
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(
+        loader.iter_load_segments_sorted(),
+        Err(Error::TooManySegments)
+    ));
+}
+
+/// Make a file with one segment, and patch in the given fields.
+fn ranges_fixture(
+    p_offset: u32,
+    p_filesz: u32,
+    p_vaddr: u32,
+    p_paddr: u32,
+    p_memsz: u32,
+) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        ..Default::default()
+    });
+    let mut data = elf.build();
+    let ph = 0x34;
+    put_u32(&mut data, ph + 0x04, p_offset);
+    put_u32(&mut data, ph + 0x08, p_vaddr);
+    put_u32(&mut data, ph + 0x0C, p_paddr);
+    put_u32(&mut data, ph + 0x10, p_filesz);
+    put_u32(&mut data, ph + 0x14, p_memsz);
+    data
+}
+
+fn first_segment(data: &[u8]) -> ProgramHeader {
+    let loader = Loader::new(data).unwrap();
+    let ph = loader.iter_program_headers().next().unwrap().unwrap();
+    ph
+}
+
+#[test]
+fn ranges() {
+    let data = ranges_fixture(0x100, 0x20, 0x8000_0000, 0x2000_0000, 0x40);
+    let ph = first_segment(&data);
+    assert_eq!(Some(0x100..0x120), ph.file_range());
+    assert_eq!(Some(0x2000_0000..0x2000_0040), ph.mem_range());
+    assert_eq!(Some(0x8000_0000..0x8000_0040), ph.vaddr_range());
+    assert!(!ph.contains_vaddr(0x7FFF_FFFF));
+    assert!(ph.contains_vaddr(0x8000_0000));
+    assert!(ph.contains_vaddr(0x8000_003F));
+    assert!(!ph.contains_vaddr(0x8000_0040));
+}
+
+#[test]
+fn ranges_ending_at_top() {
+    let data = ranges_fixture(0xFFFF_FF00, 0xFF, 0xFFFF_FFF0, 0xFFFF_FFFE, 0xF);
+    let ph = first_segment(&data);
+    assert_eq!(Some(0xFFFF_FF00..0xFFFF_FFFF), ph.file_range());
+    assert_eq!(None, ph.mem_range());
+    assert_eq!(Some(0xFFFF_FFF0..0xFFFF_FFFF), ph.vaddr_range());
+    assert!(ph.contains_vaddr(0xFFFF_FFFE));
+    assert!(!ph.contains_vaddr(0xFFFF_FFFF));
+}
+
+#[test]
+fn ranges_that_wrap() {
+    let data = ranges_fixture(0xFFFF_FF00, 0x100, 0xFFFF_FFF0, 0x1000, 0x10);
+    let ph = first_segment(&data);
+    assert_eq!(None, ph.file_range());
+    assert_eq!(Some(0x1000..0x1010), ph.mem_range());
+    assert_eq!(None, ph.vaddr_range());
+    assert!(!ph.contains_vaddr(0xFFFF_FFF0));
+    assert!(!ph.contains_vaddr(0));
+
+    let data = ranges_fixture(0, 0, 0, 0xFFFF_FFFF, 0xFFFF_FFFF);
+    let ph = first_segment(&data);
+    assert_eq!(None, ph.mem_range());
+}
+
+#[test]
+fn overlaps_mem() {
+    let a = first_segment(&ranges_fixture(0, 0, 0, 0x1000, 0x100));
+    let b = first_segment(&ranges_fixture(0, 0, 0, 0x10FF, 0x100));
+    let c = first_segment(&ranges_fixture(0, 0, 0, 0x1100, 0x100));
+    let empty = first_segment(&ranges_fixture(0, 0, 0, 0x1080, 0));
+    let top = first_segment(&ranges_fixture(0, 0, 0, 0xFFFF_FF00, 0xFF));
+    let wraps = first_segment(&ranges_fixture(0, 0, 0, 0xFFFF_FF00, 0x100));
+    assert!(a.overlaps_mem(&b));
+    assert!(b.overlaps_mem(&a));
+    assert!(b.overlaps_mem(&c));
+    assert!(!a.overlaps_mem(&c));
+    assert!(!c.overlaps_mem(&a));
+    assert!(!a.overlaps_mem(&empty));
+    assert!(!empty.overlaps_mem(&empty));
+    assert!(top.overlaps_mem(&top));
+    assert!(!top.overlaps_mem(&a));
+    assert!(wraps.overlaps_mem(&a));
+    assert!(a.overlaps_mem(&wraps));
+}
+
+#[test]
+fn vaddr_to_offset_at_top() {
+    let data = ranges_fixture(0x40, 0x10, 0xFFFF_FFF0, 0, 0x0F);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert_eq!(Some(0x40), loader.vaddr_to_offset(0xFFFF_FFF0).unwrap());
+    assert_eq!(Some(0x4E), loader.vaddr_to_offset(0xFFFF_FFFE).unwrap());
+    assert_eq!(None, loader.vaddr_to_offset(0xFFFF_FFFF).unwrap());
+}
+
+#[test]
+fn load_wrapping_segment() {
+    let data = ranges_fixture(0x40, 0x10, 0, 0xFFFF_FFF8, 0x10);
+    let loader = Loader::new(&data[..]).unwrap();
+    assert!(matches!(
+        loader.load(|_addr, _bytes| Ok(())),
+        Err(Error::BadSegment)
+    ));
+}
+
+#[test]
+fn phdr_info() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let info = loader.phdr_info().unwrap().unwrap();
+    assert_eq!(info.vaddr(), 0x2000_0034);
+    assert_eq!(info.count(), 6);
+    assert_eq!(info.entry_size(), 0x20);
+}
+
+#[test]
+fn phdr_info_without_pt_phdr() {
+    // Turn the PT_PHDR into a PT_NULL, so we have to use the first PT_LOAD
+    let mut data = ELF_DATA.to_vec();
+    put_u32(&mut data, 0x34, ProgramHeader::PT_NULL);
+    let loader = Loader::new(&data[..]).unwrap();
+    let info = loader.phdr_info().unwrap().unwrap();
+    assert_eq!(info.vaddr(), 0x2000_0034);
+    assert_eq!(info.count(), 6);
+}
+
+#[test]
+fn phdr_info_not_loaded() {
+    let data = interp_fixture();
+    let loader = Loader::new(&data[..]).unwrap();
+    assert_eq!(loader.phdr_info().unwrap(), None);
+}
+
+#[test]
+fn copy_phdr_table() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let mut buffer = [0xFFu8; 0xC4];
+    loader.copy_phdr_table(&mut buffer).unwrap();
+    assert_eq!(&buffer[0..0xC0], &ELF_DATA[0x34..0xF4]);
+    assert_eq!(&buffer[0xC0..], &[0xFF; 4]);
+
+    let mut buffer = [0u8; 0xBF];
+    assert!(matches!(
+        loader.copy_phdr_table(&mut buffer),
+        Err(Error::BufferTooSmall { needed: 0xC0 })
+    ));
+}
@@ -0,0 +1,84 @@
+use neotron_loader::{
+    traits::{Shared, SharedError, SourceMut},
+    Error, Loader,
+};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// A driver which has to seek before it can read, like a file on an SD
+/// card.
+struct SeekingReader {
+    data: &'static [u8],
+    cursor: usize,
+    seeks: usize,
+}
+
+impl SeekingReader {
+    fn new(data: &'static [u8]) -> SeekingReader {
+        SeekingReader {
+            data,
+            cursor: 0,
+            seeks: 0,
+        }
+    }
+
+    fn seek(&mut self, offset: u32) -> Result<(), ()> {
+        if offset as usize > self.data.len() {
+            return Err(());
+        }
+        if self.cursor != offset as usize {
+            self.cursor = offset as usize;
+            self.seeks += 1;
+        }
+        Ok(())
+    }
+}
+
+impl SourceMut for SeekingReader {
+    type Error = ();
+
+    fn read(&mut self, offset: u32, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        self.seek(offset)?;
+        let bytes = self
+            .data
+            .get(self.cursor..self.cursor + buffer.len())
+            .ok_or(())?;
+        buffer.copy_from_slice(bytes);
+        self.cursor += buffer.len();
+        Ok(())
+    }
+
+    fn len(&mut self) -> Option<u32> {
+        Some(self.data.len() as u32)
+    }
+}
+
+#[test]
+fn parse_through_shared() {
+    let loader = Loader::new(Shared::new(SeekingReader::new(ELF_DATA))).unwrap();
+    assert_eq!(loader.e_entry(), 0x200012a9);
+    assert_eq!(loader.iter_program_headers().count(), 6);
+    let text = loader.find_section_by_name(".text").unwrap().unwrap();
+    assert_eq!(text.sh_addr(), 0x2000_1000);
+    let mut loaded = 0;
+    loader
+        .load(|_addr, bytes| {
+            loaded += bytes.len();
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(loaded, 0xf4 + 0x444 + 0x38 + 4);
+
+    let mut seeks = 0;
+    loader.map_source(|shared| {
+        seeks = shared.into_inner().seeks;
+        Shared::new(SeekingReader::new(ELF_DATA))
+    });
+    assert!(seeks > 0);
+}
+
+#[test]
+fn inner_error() {
+    let result = Loader::new(Shared::new(SeekingReader::new(&ELF_DATA[0..20])));
+    assert!(matches!(result, Err(Error::Source(SharedError::Inner(())))));
+}
@@ -0,0 +1,48 @@
+mod common;
+
+use common::put_u32;
+use neotron_loader::{Error, Loader, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+#[test]
+fn sections_match_buffered_reads() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    for sh in loader.iter_section_headers() {
+        let sh = sh.unwrap();
+        let bytes = loader.section_bytes(&sh).unwrap();
+        let mut buffer = vec![0u8; sh.sh_size() as usize];
+        let buffered = sh.read_data(&loader, 0, &mut buffer).unwrap();
+        assert_eq!(buffered, bytes);
+    }
+    let bss = loader.find_section_by_name(".bss").unwrap().unwrap();
+    assert!(loader.section_bytes(&bss).unwrap().is_empty());
+}
+
+#[test]
+fn segments_match_buffered_reads() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    for ph in loader.iter_program_headers() {
+        let ph = ph.unwrap();
+        let bytes = loader.segment_file_bytes(&ph).unwrap();
+        let mut buffer = vec![0u8; ph.p_filesz() as usize];
+        neotron_loader::Source::read(&ELF_DATA, ph.p_offset(), &mut buffer).unwrap();
+        assert_eq!(&buffer[..], bytes);
+        // It really is the original data
+        assert_eq!(ELF_DATA[ph.p_offset() as usize..].as_ptr(), bytes.as_ptr());
+    }
+}
+
+#[test]
+fn out_of_bounds() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let header = loader.e_shoff() as usize + 2 * usize::from(SectionHeader::SIZE_IN_BYTES);
+    let mut data = ELF_DATA.to_vec();
+    put_u32(&mut data, header + 0x14, 0xFFFF_0000);
+    let loader = Loader::new(&data[..]).unwrap();
+    let rodata = SectionHeader::new(&loader, 2).unwrap();
+    assert!(matches!(
+        loader.section_bytes(&rodata),
+        Err(Error::DataOutOfBounds)
+    ));
+}
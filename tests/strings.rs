@@ -0,0 +1,105 @@
+mod common;
+
+use common::{ElfBuilder, Section};
+use neotron_loader::{Error, Loader, SectionHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+#[test]
+fn section_names() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let shstrtab = loader.find_section_by_name(".shstrtab").unwrap().unwrap();
+    let mut scratch = [0u8; 32];
+    let mut strings = shstrtab.iter_strings(&loader, &mut scratch).unwrap();
+    let mut names = Vec::new();
+    while let Some(s) = strings.next_string() {
+        let s = s.unwrap().to_owned();
+        names.push((strings.current_offset(), s));
+    }
+    assert_eq!(20, names.len());
+    assert_eq!((0, String::new()), names[0]);
+    assert_eq!((1, String::from(".text")), names[1]);
+    assert_eq!((0xc8, String::from(".strtab")), names[19]);
+
+    // Every section name is in there
+    for sh in loader.iter_section_headers() {
+        let sh = sh.unwrap();
+        let mut buffer = [0u8; 32];
+        let name = sh.sh_name(&loader, &mut buffer).unwrap();
+        assert!(names.contains(&(sh.sh_name_offset(), name.to_owned())));
+    }
+}
+
+#[test]
+fn string_too_long() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let shstrtab = loader.find_section_by_name(".shstrtab").unwrap().unwrap();
+    let mut scratch = [0u8; 8];
+    let mut strings = shstrtab.iter_strings(&loader, &mut scratch).unwrap();
+    let mut count = 0;
+    let mut too_long = Vec::new();
+    while let Some(s) = strings.next_string() {
+        count += 1;
+        match s {
+            Ok(s) => assert!(s.len() < 8),
+            Err(Error::BufferTooSmall { needed }) => {
+                too_long.push((strings.current_offset(), needed))
+            }
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+    // We still see every string
+    assert_eq!(20, count);
+    // .debug_abbrev
+    assert_eq!((0x22, 14), too_long[0]);
+    assert_eq!(12, too_long.len());
+}
+
+#[test]
+fn missing_terminator() {
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section::new(
+        ".strtab",
+        SectionHeader::SHT_STRTAB,
+        b"\0first\0second".to_vec(),
+    ));
+    // Something else straight after, which does have a null in it
+    elf.add_section(Section::new(
+        ".data",
+        SectionHeader::SHT_PROGBITS,
+        b"\0junk\0".to_vec(),
+    ));
+    let data = elf.build();
+    let loader = Loader::new(&data[..]).unwrap();
+    let strtab = loader.find_section_by_name(".strtab").unwrap().unwrap();
+    for scratch_len in [4, 64] {
+        let mut scratch = vec![0u8; scratch_len];
+        let mut strings = strtab.iter_strings(&loader, &mut scratch).unwrap();
+        assert_eq!("", strings.next_string().unwrap().unwrap());
+        if scratch_len == 4 {
+            assert!(matches!(
+                strings.next_string(),
+                Some(Err(Error::BufferTooSmall { needed: 6 }))
+            ));
+            assert_eq!(1, strings.current_offset());
+        } else {
+            assert_eq!("first", strings.next_string().unwrap().unwrap());
+        }
+        assert!(matches!(
+            strings.next_string(),
+            Some(Err(Error::InvalidString))
+        ));
+        assert!(strings.next_string().is_none());
+    }
+}
+
+#[test]
+fn not_a_string_table() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let text = loader.find_section_by_name(".text").unwrap().unwrap();
+    let mut scratch = [0u8; 8];
+    assert!(matches!(
+        text.iter_strings(&loader, &mut scratch),
+        Err(Error::WrongSectionType)
+    ));
+}
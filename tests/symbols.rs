@@ -0,0 +1,536 @@
+mod common;
+
+use common::{put_u32, symbol, words, ElfBuilder, Section, StrTab};
+use neotron_loader::{SectionHeader, SymbolSection};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+#[test]
+fn iterate_symbols() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    let symbols = loader.iter_symbols().unwrap();
+    let symtab = symbols.table().clone();
+    let symbols: Result<Vec<neotron_loader::Symbol>, _> = symbols.collect();
+    let symbols = symbols.unwrap();
+    assert_eq!(46, symbols.len());
+
+    // 43: 20001001    44 FUNC    GLOBAL DEFAULT    1 neotron_main
+    let mut buffer = [0u8; 64];
+    assert_eq!(
+        "neotron_main",
+        symbols[43].st_name(&loader, &symtab, &mut buffer).unwrap()
+    );
+    assert_eq!(0x2000_1001, symbols[43].st_value());
+    assert_eq!(44, symbols[43].st_size());
+    assert_eq!(neotron_loader::Symbol::STT_FUNC, symbols[43].st_type());
+    assert_eq!(neotron_loader::Symbol::STB_GLOBAL, symbols[43].st_bind());
+    assert_eq!(1, symbols[43].st_shndx());
+}
+
+#[test]
+fn find_global_symbol() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    // Deliberately small, so names are compared over several chunks
+    let mut scratch = [0u8; 4];
+    let symbol = loader
+        .find_symbol_by_name("app_entry", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!(0x2000_12a9, symbol.st_value());
+    assert_eq!(20, symbol.st_size());
+    assert_eq!(neotron_loader::Symbol::STB_GLOBAL, symbol.st_bind());
+}
+
+#[test]
+fn find_local_symbol() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    let mut scratch = [0u8; 16];
+    let symbol = loader
+        .find_symbol_by_name("rust_begin_unwind", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!(0x2000_12bd, symbol.st_value());
+    assert_eq!(neotron_loader::Symbol::STB_LOCAL, symbol.st_bind());
+}
+
+#[test]
+fn find_missing_symbol() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    let mut scratch = [0u8; 16];
+    assert!(loader
+        .find_symbol_by_name("not_a_real_symbol", &mut scratch)
+        .unwrap()
+        .is_none());
+    // A prefix of a real name must not match
+    assert!(loader
+        .find_symbol_by_name("neotron_mai", &mut scratch)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn symbolize_addresses() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    let mut scratch = [0u8; 64];
+
+    // 43: 20001001    44 FUNC    GLOBAL DEFAULT    1 neotron_main
+    let m = loader
+        .symbolize(0x2000_101c, &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!("neotron_main", m.name());
+    assert_eq!(0x2000_1000, m.value());
+    assert_eq!(0x1c, m.offset());
+
+    // The first instruction, without the Thumb bit
+    let m = loader
+        .symbolize(0x2000_1000, &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!("neotron_main", m.name());
+    assert_eq!(0, m.offset());
+
+    // 44: 200012a9    20 FUNC    GLOBAL DEFAULT    1 app_entry
+    let m = loader
+        .symbolize(0x2000_12b2, &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!("app_entry", m.name());
+    assert_eq!(0x0a, m.offset());
+}
+
+#[test]
+fn symbolize_unknown_addresses() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    let mut scratch = [0u8; 64];
+
+    // Padding between the end of one write_fmt (0x20001288, 30 bytes) and
+    // app_entry (0x200012a8)
+    assert!(loader
+        .symbolize(0x2000_12a6, &mut scratch)
+        .unwrap()
+        .is_none());
+    // Way outside the image
+    assert!(loader
+        .symbolize(0x1000_0000, &mut scratch)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn thumb_function_addresses() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    let mut scratch = [0u8; 16];
+
+    // 10: 200012bd    48 FUNC    LOCAL  DEFAULT    1 rust_begin_unwind
+    let symbol = loader
+        .find_symbol_by_name("rust_begin_unwind", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!(0x2000_12bd, symbol.value_for_call());
+    assert_eq!(0x2000_12bc, symbol.address());
+
+    // 45: 2000147c     0 NOTYPE  GLOBAL DEFAULT  ABS __sidata
+    let symbol = loader
+        .find_symbol_by_name("__sidata", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!(0x2000_147c, symbol.value_for_call());
+    assert_eq!(0x2000_147c, symbol.address());
+}
+
+/// A position-independent plugin, with a stripped-down symbol table and two
+/// exported dynamic symbols.
+fn plugin_fixture() -> Vec<u8> {
+    let mut dynstr = StrTab::new();
+    let mut dynsym = symbol(0, 0, 0, 0, 0).to_vec();
+    dynsym.extend(symbol(dynstr.add("plugin_init"), 0x0000_0101, 16, 0x12, 1));
+    dynsym.extend(symbol(dynstr.add("plugin_data"), 0x0000_0200, 4, 0x11, 1));
+
+    let mut strtab = StrTab::new();
+    let mut symtab = symbol(0, 0, 0, 0, 0).to_vec();
+    symtab.extend(symbol(strtab.add("local_helper"), 0x0000_0181, 8, 0x02, 1));
+
+    let mut elf = ElfBuilder::new();
+    elf.e_type = common::ET_DYN;
+    elf.add_section(Section {
+        sh_flags: 0x6,
+        ..Section::new(".text", SectionHeader::SHT_PROGBITS, vec![0; 0x100])
+    });
+    elf.add_section(Section {
+        sh_link: 3,
+        sh_info: 1,
+        sh_entsize: 16,
+        sh_addralign: 4,
+        ..Section::new(".dynsym", SectionHeader::SHT_DYNSYM, dynsym)
+    });
+    elf.add_section(Section::new(
+        ".dynstr",
+        SectionHeader::SHT_STRTAB,
+        dynstr.data,
+    ));
+    elf.add_section(Section {
+        sh_link: 5,
+        sh_info: 1,
+        sh_entsize: 16,
+        sh_addralign: 4,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, symtab)
+    });
+    elf.add_section(Section::new(
+        ".strtab",
+        SectionHeader::SHT_STRTAB,
+        strtab.data,
+    ));
+    elf.build()
+}
+
+#[test]
+fn iterate_dynamic_symbols() {
+    let data = plugin_fixture();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    assert_eq!(neotron_loader::Loader::<&[u8]>::ET_DYN, loader.e_type());
+
+    let symbols = loader.iter_dynamic_symbols().unwrap();
+    let dynsym = symbols.table().clone();
+    assert_eq!(SectionHeader::SHT_DYNSYM, dynsym.sh_type());
+    let symbols: Vec<_> = symbols.map(|s| s.unwrap()).collect();
+    assert_eq!(3, symbols.len());
+
+    let mut buffer = [0u8; 32];
+    assert_eq!(
+        "plugin_init",
+        symbols[1].st_name(&loader, &dynsym, &mut buffer).unwrap()
+    );
+    assert_eq!(0x0000_0101, symbols[1].st_value());
+    assert_eq!(
+        "plugin_data",
+        symbols[2].st_name(&loader, &dynsym, &mut buffer).unwrap()
+    );
+    assert_eq!(neotron_loader::Symbol::STT_OBJECT, symbols[2].st_type());
+}
+
+#[test]
+fn iterate_symbols_in_either_table() {
+    let data = plugin_fixture();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let sections: Vec<_> = loader
+        .iter_section_headers()
+        .map(|sh| sh.unwrap())
+        .collect();
+
+    // .dynsym
+    assert_eq!(3, loader.iter_symbols_in(&sections[2]).unwrap().count());
+    // .symtab, whose names are in .strtab, not .dynstr
+    let mut symbols = loader.iter_symbols_in(&sections[4]).unwrap();
+    let symtab = symbols.table().clone();
+    let local = symbols.nth(1).unwrap().unwrap();
+    let mut buffer = [0u8; 32];
+    assert_eq!(
+        "local_helper",
+        local.st_name(&loader, &symtab, &mut buffer).unwrap()
+    );
+    // .text is not a symbol table
+    assert!(matches!(
+        loader.iter_symbols_in(&sections[1]),
+        Err(neotron_loader::Error::WrongSectionType)
+    ));
+}
+
+#[test]
+fn no_dynamic_symbols() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    assert_eq!(0, loader.iter_dynamic_symbols().unwrap().count());
+}
+
+/// An executable with one symbol for each kind of `st_shndx`.
+fn shndx_fixture() -> Vec<u8> {
+    let mut strtab = StrTab::new();
+    let names = ["undefined", "absolute", "common", "extended", "defined"];
+    let offsets: Vec<u32> = names.iter().map(|name| strtab.add(name)).collect();
+    let mut elf = ElfBuilder::new();
+    let strtab_idx = elf.add_section(Section::new(
+        ".strtab",
+        SectionHeader::SHT_STRTAB,
+        strtab.data.clone(),
+    ));
+    let mut symbols = Vec::new();
+    symbols.extend(symbol(0, 0, 0, 0, 0));
+    symbols.extend(symbol(offsets[0], 0, 0, 0x12, SectionHeader::SHN_UNDEF));
+    symbols.extend(symbol(offsets[1], 0x1234, 0, 0x10, SectionHeader::SHN_ABS));
+    symbols.extend(symbol(offsets[2], 4, 16, 0x15, SectionHeader::SHN_COMMON));
+    symbols.extend(symbol(offsets[3], 0, 4, 0x11, SectionHeader::SHN_XINDEX));
+    symbols.extend(symbol(offsets[4], 0x100, 4, 0x12, 3));
+    elf.add_section(Section {
+        sh_link: strtab_idx as u32,
+        sh_info: 1,
+        sh_entsize: 16,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, symbols)
+    });
+    elf.build()
+}
+
+#[test]
+fn symbol_sections() {
+    let data = shndx_fixture();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let sections: Vec<SymbolSection> = loader
+        .iter_symbols()
+        .unwrap()
+        .skip(1)
+        .map(|s| s.unwrap().section())
+        .collect();
+    assert_eq!(
+        vec![
+            SymbolSection::Undefined,
+            SymbolSection::Absolute,
+            SymbolSection::Common,
+            SymbolSection::Extended,
+            SymbolSection::Section(3),
+        ],
+        sections
+    );
+}
+
+/// An object file with a symbol in section 70000, which needs an extended
+/// section index.
+///
+/// A real file with that many sections would be huge, so the section doesn't
+/// exist.
+fn xindex_fixture() -> Vec<u8> {
+    let mut strtab = StrTab::new();
+    let small = strtab.add("small");
+    let big = strtab.add("big");
+    let mut elf = ElfBuilder::new();
+    let strtab_idx = elf.add_section(Section::new(
+        ".strtab",
+        SectionHeader::SHT_STRTAB,
+        strtab.data.clone(),
+    ));
+    let symtab_idx = strtab_idx + 1;
+    let mut symbols = Vec::new();
+    symbols.extend(symbol(0, 0, 0, 0, 0));
+    symbols.extend(symbol(small, 0x100, 4, 0x11, 1));
+    symbols.extend(symbol(big, 0x200, 4, 0x11, SectionHeader::SHN_XINDEX));
+    elf.add_section(Section {
+        sh_link: strtab_idx as u32,
+        sh_info: 1,
+        sh_entsize: 16,
+        sh_addralign: 4,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, symbols)
+    });
+    elf.add_section(Section {
+        sh_link: symtab_idx as u32,
+        sh_entsize: 4,
+        sh_addralign: 4,
+        ..Section::new(
+            ".symtab_shndx",
+            SectionHeader::SHT_SYMTAB_SHNDX,
+            words(&[0, 0, 70000]),
+        )
+    });
+    elf.build()
+}
+
+#[test]
+fn extended_section_index() {
+    let data = xindex_fixture();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let symbols: Vec<_> = loader.iter_symbols().unwrap().map(|s| s.unwrap()).collect();
+    assert_eq!(SectionHeader::SHN_XINDEX, symbols[2].st_shndx());
+    assert_eq!(70000, symbols[2].resolved_shndx());
+    assert_eq!(SymbolSection::Section(70000), symbols[2].section());
+    // Symbols with small indices don't look at the shndx section
+    assert_eq!(1, symbols[1].resolved_shndx());
+    assert_eq!(SymbolSection::Section(1), symbols[1].section());
+
+    let mut scratch = [0u8; 8];
+    let big = loader
+        .find_symbol_by_name("big", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!(70000, big.resolved_shndx());
+}
+
+#[test]
+fn find_undefined_symbol() {
+    let data = shndx_fixture();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let mut scratch = [0u8; 8];
+    let extended = loader
+        .find_symbol_by_name("extended", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        u32::from(SectionHeader::SHN_XINDEX),
+        extended.resolved_shndx()
+    );
+    let undefined = loader
+        .find_symbol_by_name("undefined", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert!(undefined.is_undefined());
+    let defined = loader
+        .find_symbol_by_name("defined", &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert!(!defined.is_undefined());
+    assert_eq!(SymbolSection::Section(3), defined.section());
+
+    // The undefined function at address 0 doesn't cover address 0
+    let mut buffer = [0u8; 32];
+    assert!(loader.symbolize(0, &mut buffer).unwrap().is_none());
+}
+
+#[test]
+fn symtab_not_linked_to_string_table() {
+    let mut elf = ElfBuilder::new();
+    let text = elf.add_section(Section::new(
+        ".text",
+        SectionHeader::SHT_PROGBITS,
+        b"\0main\0".to_vec(),
+    ));
+    let mut symbols = Vec::new();
+    symbols.extend(symbol(0, 0, 0, 0, 0));
+    symbols.extend(symbol(1, 0x100, 4, 0x12, text as u16));
+    elf.add_section(Section {
+        sh_link: text as u32,
+        sh_info: 1,
+        sh_entsize: 16,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, symbols)
+    });
+    let data = elf.build();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let symbols = loader.iter_symbols().unwrap();
+    let symtab = symbols.table().clone();
+    let main = symbols.last().unwrap().unwrap();
+    let mut buffer = [0u8; 16];
+    assert!(matches!(
+        main.st_name(&loader, &symtab, &mut buffer),
+        Err(neotron_loader::Error::BadStringTable)
+    ));
+    assert!(matches!(
+        loader.find_symbol_by_name("main", &mut buffer),
+        Err(neotron_loader::Error::BadStringTable)
+    ));
+}
+
+#[test]
+fn symbol_names_with_large_buffer() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    // Much bigger than what's left of the string table, so we must not read
+    // the whole buffer's worth
+    let mut scratch = [0u8; 4096];
+    let m = loader
+        .symbolize(0x2000_12a8, &mut scratch)
+        .unwrap()
+        .unwrap();
+    assert_eq!("app_entry", m.name());
+    assert_eq!(0, m.offset());
+
+    let symbols = loader.iter_symbols().unwrap();
+    let symtab = symbols.table().clone();
+    let last = symbols.last().unwrap().unwrap();
+    assert!(last.st_name(&loader, &symtab, &mut scratch).is_ok());
+}
+
+/// A symbol table with one symbol, called `main`, in section 1.
+fn wrapping_fixture() -> Vec<u8> {
+    let mut strtab = StrTab::new();
+    let mut symbols = symbol(0, 0, 0, 0, 0).to_vec();
+    symbols.extend(symbol(strtab.add("main"), 0x101, 4, 0x12, 1));
+    let mut elf = ElfBuilder::new();
+    elf.add_section(Section {
+        sh_link: 2,
+        sh_info: 1,
+        sh_entsize: 16,
+        ..Section::new(".symtab", SectionHeader::SHT_SYMTAB, symbols)
+    });
+    elf.add_section(Section::new(
+        ".strtab",
+        SectionHeader::SHT_STRTAB,
+        strtab.data,
+    ));
+    elf.build()
+}
+
+#[test]
+fn string_table_wraps() {
+    let mut data = wrapping_fixture();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let strtab_header = loader.e_shoff() as usize + 2 * 0x28;
+    put_u32(&mut data, strtab_header + 0x10, 0xFFFF_FF00);
+    put_u32(&mut data, strtab_header + 0x14, 0x200);
+
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let symbols = loader.iter_symbols().unwrap();
+    let symtab = symbols.table().clone();
+    let main = symbols.last().unwrap().unwrap();
+    let mut buffer = [0u8; 16];
+    assert!(matches!(
+        main.st_name(&loader, &symtab, &mut buffer),
+        Err(neotron_loader::Error::DataOutOfBounds)
+    ));
+    assert!(matches!(
+        loader.find_symbol_by_name("main", &mut buffer),
+        Err(neotron_loader::Error::DataOutOfBounds)
+    ));
+}
+
+#[test]
+fn symbol_table_wraps() {
+    let mut data = wrapping_fixture();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let symtab_header = loader.e_shoff() as usize + 0x28;
+    put_u32(&mut data, symtab_header + 0x10, 0xFFFF_FFF8);
+
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let mut symbols = loader.iter_symbols().unwrap();
+    assert!(matches!(
+        symbols.next(),
+        Some(Err(neotron_loader::Error::DataOutOfBounds))
+    ));
+    // We stop after the first error
+    assert!(symbols.next().is_none());
+    let mut buffer = [0u8; 16];
+    assert!(matches!(
+        loader.find_symbol_by_name("main", &mut buffer),
+        Err(neotron_loader::Error::DataOutOfBounds)
+    ));
+}
+
+#[test]
+fn extended_section_index_for_other_table() {
+    let mut data = xindex_fixture();
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    let (symtab_idx, symtab) = loader
+        .first_section_of_type(SectionHeader::SHT_SYMTAB)
+        .unwrap()
+        .unwrap();
+    let shndx = loader.symtab_shndx(u32::from(symtab_idx)).unwrap().unwrap();
+    assert_eq!(SectionHeader::SHT_SYMTAB_SHNDX, shndx.sh_type());
+    let big = neotron_loader::Symbol::new(&loader, &symtab, 2, Some(&shndx)).unwrap();
+    assert_eq!(70000, big.resolved_shndx());
+    let big = neotron_loader::Symbol::new(&loader, &symtab, 2, None).unwrap();
+    assert_eq!(SymbolSection::Extended, big.section());
+
+    // Link the shndx section to the string table instead
+    let (shndx_idx, _) = loader
+        .first_section_of_type(SectionHeader::SHT_SYMTAB_SHNDX)
+        .unwrap()
+        .unwrap();
+    let header = loader.e_shoff() as usize + usize::from(shndx_idx) * 0x28;
+    put_u32(&mut data, header + 0x18, u32::from(symtab_idx) - 1);
+    let loader = neotron_loader::Loader::new(&data[..]).unwrap();
+    assert!(loader
+        .symtab_shndx(u32::from(symtab_idx))
+        .unwrap()
+        .is_none());
+    let symbols: Vec<_> = loader.iter_symbols().unwrap().map(|s| s.unwrap()).collect();
+    assert_eq!(SymbolSection::Extended, symbols[2].section());
+    let symbols: Vec<_> = loader
+        .iter_symbols_in(&symtab)
+        .unwrap()
+        .map(|s| s.unwrap())
+        .collect();
+    assert_eq!(SymbolSection::Extended, symbols[2].section());
+}
@@ -0,0 +1,134 @@
+#![cfg(feature = "tar")]
+
+use neotron_loader::{
+    tar::{TarError, TarSource},
+    traits::SubSourceError,
+    Loader, Source,
+};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+/// Make a `ustar` header block for a member.
+fn tar_header(name: &str, prefix: &str, size: usize, type_flag: u8) -> Vec<u8> {
+    let mut header = vec![0u8; 512];
+    header[0..name.len()].copy_from_slice(name.as_bytes());
+    header[100..108].copy_from_slice(b"0000644\0");
+    header[108..116].copy_from_slice(b"0000000\0");
+    header[116..124].copy_from_slice(b"0000000\0");
+    header[124..136].copy_from_slice(format!("{size:011o}\0").as_bytes());
+    header[136..148].copy_from_slice(b"00000000000\0");
+    header[156] = type_flag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    header[148..156].copy_from_slice(b"        ");
+    let sum: u32 = header.iter().map(|b| u32::from(*b)).sum();
+    header[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+    header
+}
+
+/// Add a member, padding the data to a whole number of blocks.
+fn add_member(archive: &mut Vec<u8>, name: &str, prefix: &str, type_flag: u8, data: &[u8]) {
+    archive.extend(tar_header(name, prefix, data.len(), type_flag));
+    archive.extend_from_slice(data);
+    archive.resize(archive.len().next_multiple_of(512), 0);
+}
+
+/// An app bundle: a directory, an icon, some metadata and the ELF.
+fn bundle() -> Vec<u8> {
+    let mut archive = Vec::new();
+    add_member(&mut archive, "app/", "", b'5', &[]);
+    add_member(&mut archive, "app/icon.bmp", "", b'0', &[0x42; 700]);
+    add_member(&mut archive, "app/meta.txt", "", b'0', b"name=test\n");
+    add_member(&mut archive, "app.elf", "bundles/test", b'0', ELF_DATA);
+    // End of archive marker
+    archive.extend([0u8; 1024]);
+    archive
+}
+
+#[test]
+fn find_elf() {
+    let archive = bundle();
+    let source = TarSource::open(&archive[..], "bundles/test/app.elf").unwrap();
+    assert_eq!(Some(ELF_DATA.len() as u32), source.len());
+    assert_eq!(512 * 7, source.member_offset());
+    let loader = Loader::new(source).unwrap();
+    assert_eq!(20, loader.e_shnum());
+    let text = loader.find_section_by_name(".text").unwrap().unwrap();
+    assert_eq!(0x444, text.sh_size());
+}
+
+#[test]
+fn find_member() {
+    let archive = bundle();
+    let source = TarSource::open(&archive[..], "app/meta.txt").unwrap();
+    let mut buffer = [0u8; 10];
+    source.read(0, &mut buffer).unwrap();
+    assert_eq!(b"name=test\n", &buffer);
+    // Can't read past the end of the member
+    assert!(matches!(
+        source.read(1, &mut buffer),
+        Err(SubSourceError::OutOfRange)
+    ));
+}
+
+#[test]
+fn missing_member() {
+    let archive = bundle();
+    for name in ["app.elf", "app", "app/", "bundles/test/app.elf2", "missing"] {
+        assert!(
+            matches!(
+                TarSource::open(&archive[..], name),
+                Err(TarError::MemberNotFound)
+            ),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn missing_end_marker() {
+    let mut archive = bundle();
+    archive.truncate(archive.len() - 1024);
+    assert!(matches!(
+        TarSource::open(&archive[..], "missing"),
+        Err(TarError::MemberNotFound)
+    ));
+}
+
+#[test]
+fn bad_checksum() {
+    let mut archive = bundle();
+    // Corrupt the name of the icon
+    archive[512 + 5] = b'X';
+    assert!(matches!(
+        TarSource::open(&archive[..], "app/meta.txt"),
+        Err(TarError::BadHeader { offset: 512 })
+    ));
+}
+
+#[test]
+fn bad_size() {
+    let mut archive = Vec::new();
+    let mut header = tar_header("a", "", 0, b'0');
+    header[124..136].copy_from_slice(b"0000000009\0\0");
+    header[148..156].copy_from_slice(b"        ");
+    let sum: u32 = header.iter().map(|b| u32::from(*b)).sum();
+    header[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+    archive.extend(header);
+    assert!(matches!(
+        TarSource::open(&archive[..], "a"),
+        Err(TarError::BadHeader { offset: 0 })
+    ));
+}
+
+#[test]
+fn size_wraps() {
+    let mut archive = Vec::new();
+    archive.extend(tar_header("a", "", 0xFFFF_FFFF, b'0'));
+    archive.extend([0u8; 1024]);
+    assert!(matches!(
+        TarSource::open(&archive[..], "a"),
+        Err(TarError::BadHeader { offset: 0 })
+    ));
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::redundant_slicing)]
+
 static ELF_DATA: &[u8] = include_bytes!("../test.elf");
 
 #[test]
@@ -145,3 +147,14 @@ fn section_headers() {
     assert_eq!(0x000_004b8, sections[19].sh_size());
     assert_eq!(0x000_00000, sections[19].sh_addr());
 }
+
+#[test]
+fn find_section_by_name() {
+    let loader = neotron_loader::Loader::new(ELF_DATA).unwrap();
+    let sh = loader.find_section_by_name(".rodata").unwrap().unwrap();
+    assert_eq!(0x2000_1444, sh.sh_addr());
+    let sh = loader.find_section_by_name(".strtab").unwrap().unwrap();
+    assert_eq!(0x000_004b8, sh.sh_size());
+    assert!(loader.find_section_by_name(".rodat").unwrap().is_none());
+    assert!(loader.find_section_by_name(".text.foo").unwrap().is_none());
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{ElfBuilder, Segment};
+use neotron_loader::{Error, Loader, ProgramHeader};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+const RP2040_FAMILY_ID: u32 = 0xE48B_FF56;
+
+fn read_u32(block: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(block[offset..offset + 4].try_into().unwrap())
+}
+
+/// Export some segments, check the blocks are well formed, and return the
+/// address and payload of each one.
+fn export(loader: &Loader<&[u8]>) -> Vec<(u32, Vec<u8>)> {
+    let mut blocks = Vec::new();
+    let count = loader
+        .write_uf2(RP2040_FAMILY_ID, |block| blocks.push(block.to_vec()))
+        .unwrap();
+    assert_eq!(count as usize, blocks.len());
+    blocks
+        .iter()
+        .enumerate()
+        .map(|(idx, block)| {
+            assert_eq!(512, block.len());
+            assert_eq!(0x0A32_4655, read_u32(block, 0));
+            assert_eq!(0x9E5D_5157, read_u32(block, 4));
+            assert_eq!(0x0000_2000, read_u32(block, 8));
+            assert_eq!(256, read_u32(block, 16));
+            assert_eq!(idx as u32, read_u32(block, 20));
+            assert_eq!(count, read_u32(block, 24));
+            assert_eq!(RP2040_FAMILY_ID, read_u32(block, 28));
+            assert_eq!(0x0AB1_6F30, read_u32(block, 508));
+            let addr = read_u32(block, 12);
+            assert_eq!(0, addr % 256);
+            (addr, block[32..32 + 256].to_vec())
+        })
+        .collect()
+}
+
+#[test]
+fn round_trip() {
+    let loader = Loader::new(ELF_DATA).unwrap();
+    let blocks = export(&loader);
+
+    // Put the blocks back into an image of memory
+    let base = 0x2000_0000;
+    let mut image = vec![0u8; 0x1500];
+    let mut last_addr = None;
+    for (addr, payload) in &blocks {
+        assert!(Some(*addr) > last_addr, "blocks in order, no repeats");
+        last_addr = Some(*addr);
+        let start = (addr - base) as usize;
+        image[start..start + 256].copy_from_slice(payload);
+    }
+
+    // The segments are all there
+    for ph in loader.iter_load_segments_sorted().unwrap() {
+        let ph = ph.unwrap();
+        let start = (ph.p_paddr() - base) as usize;
+        assert_eq!(
+            loader.segment_file_bytes(&ph).unwrap(),
+            &image[start..start + ph.p_filesz() as usize]
+        );
+    }
+
+    // The first segment holds the ELF header and program headers, so we
+    // can parse them again
+    let reloaded = Loader::new(&image[..]).unwrap();
+    assert_eq!(loader.e_entry(), reloaded.e_entry());
+    assert!(loader
+        .iter_program_headers()
+        .map(|ph| format!("{:?}", ph.unwrap()))
+        .eq(reloaded
+            .iter_program_headers()
+            .map(|ph| format!("{:?}", ph.unwrap()))));
+}
+
+#[test]
+fn unaligned_segments() {
+    let mut elf = ElfBuilder::new();
+    // Spans two pages, and shares the second with the next segment
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x1010,
+        data: vec![0x11; 300],
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x1140,
+        data: vec![0x22; 4],
+        ..Default::default()
+    });
+    // Only zeroes, so no blocks
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x2000,
+        p_memsz: Some(0x400),
+        ..Default::default()
+    });
+    // Out of order in the file
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x0F00,
+        data: vec![0x33; 1],
+        ..Default::default()
+    });
+    let elf = elf.build();
+    let loader = Loader::new(&elf[..]).unwrap();
+    let blocks = export(&loader);
+
+    let addresses: Vec<u32> = blocks.iter().map(|(addr, _)| *addr).collect();
+    assert_eq!(vec![0x0F00, 0x1000, 0x1100], addresses);
+
+    let mut expected = vec![0u8; 256];
+    expected[0] = 0x33;
+    assert_eq!(expected, blocks[0].1);
+
+    let mut expected = vec![0u8; 256];
+    expected[0x10..].fill(0x11);
+    assert_eq!(expected, blocks[1].1);
+
+    let mut expected = vec![0u8; 256];
+    expected[0..0x3C].fill(0x11);
+    expected[0x40..0x44].fill(0x22);
+    assert_eq!(expected, blocks[2].1);
+}
+
+#[test]
+fn overlapping_segments() {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x1000,
+        data: vec![0x11; 0x20],
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_paddr: 0x1010,
+        data: vec![0x22; 0x20],
+        ..Default::default()
+    });
+    let elf = elf.build();
+    let loader = Loader::new(&elf[..]).unwrap();
+    let mut emitted = 0;
+    assert!(matches!(
+        loader.write_uf2(RP2040_FAMILY_ID, |_block| emitted += 1),
+        Err(Error::BadSegment)
+    ));
+    assert_eq!(0, emitted);
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::{words, ElfBuilder, Segment};
+use neotron_loader::{Error, Loader, MemoryRegion, ProgramHeader, VectorTableFault};
+
+static ELF_DATA: &[u8] = include_bytes!("../test.elf");
+
+const RAM: MemoryRegion = MemoryRegion::new(0x2000_0000, 0x8000);
+
+/// A bare image: a vector table then code in flash, and some data in RAM.
+fn bare_image(initial_sp: u32, reset_vector: u32, e_entry: u32) -> Vec<u8> {
+    let mut elf = ElfBuilder::new();
+    elf.e_entry = e_entry;
+    let mut flash = words(&[initial_sp, reset_vector]);
+    flash.resize(0x200, 0);
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_flags: ProgramHeader::PF_R | ProgramHeader::PF_W,
+        p_vaddr: 0x2000_0000,
+        p_paddr: 0x2000_0000,
+        data: vec![0; 16],
+        ..Default::default()
+    });
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_flags: ProgramHeader::PF_R | ProgramHeader::PF_X,
+        p_vaddr: 0x0000_0000,
+        p_paddr: 0x0000_0000,
+        data: flash,
+        ..Default::default()
+    });
+    elf.build()
+}
+
+fn check(data: &[u8]) -> Result<neotron_loader::VectorTableInfo, VectorTableFault> {
+    let loader = Loader::new(data).unwrap();
+    match loader.check_vector_table(RAM) {
+        Ok(info) => Ok(info),
+        Err(Error::BadVectorTable(fault)) => Err(fault),
+        Err(e) => panic!("{e:?}"),
+    }
+}
+
+#[test]
+fn valid_table() {
+    let data = bare_image(0x2000_8000, 0x0000_0101, 0x0000_0101);
+    let info = check(&data).unwrap();
+    assert_eq!(0, info.table_addr());
+    assert_eq!(0x2000_8000, info.initial_sp());
+    assert_eq!(0x0000_0101, info.reset_vector());
+    assert!(info.entry_matches());
+}
+
+#[test]
+fn entry_mismatch() {
+    let data = bare_image(0x2000_4000, 0x0000_0101, 0x0000_0181);
+    let info = check(&data).unwrap();
+    assert!(!info.entry_matches());
+}
+
+#[test]
+fn invalid_tables() {
+    for (initial_sp, reset_vector, fault) in [
+        (0x2000_8008, 0x101, VectorTableFault::StackPointerOutsideRam),
+        (0x2000_0000, 0x101, VectorTableFault::StackPointerOutsideRam),
+        (0x1000_0000, 0x101, VectorTableFault::StackPointerOutsideRam),
+        (0x2000_7FFC, 0x101, VectorTableFault::StackPointerMisaligned),
+        (0x2000_8000, 0x100, VectorTableFault::ResetVectorNotThumb),
+        (
+            0x2000_8000,
+            0x201,
+            VectorTableFault::ResetVectorNotExecutable,
+        ),
+        (
+            0x2000_8000,
+            0x2000_0001,
+            VectorTableFault::ResetVectorNotExecutable,
+        ),
+    ] {
+        let data = bare_image(initial_sp, reset_vector, reset_vector);
+        assert_eq!(
+            Err(fault),
+            check(&data),
+            "{initial_sp:08x} {reset_vector:08x}"
+        );
+    }
+}
+
+#[test]
+fn no_executable_segment() {
+    let mut elf = ElfBuilder::new();
+    elf.add_segment(Segment {
+        p_type: ProgramHeader::PT_LOAD,
+        p_flags: ProgramHeader::PF_R,
+        data: vec![0; 16],
+        ..Default::default()
+    });
+    let data = elf.build();
+    assert_eq!(Err(VectorTableFault::NoExecutableSegment), check(&data));
+}
+
+#[test]
+fn test_elf_has_no_table() {
+    // test.elf is a Neotron app, so it starts with code, not a vector table
+    let loader = Loader::new(ELF_DATA).unwrap();
+    assert!(matches!(
+        loader.check_vector_table(RAM),
+        Err(Error::BadVectorTable(_))
+    ));
+}
+
+#[test]
+fn memory_region() {
+    let region = MemoryRegion::new(0xFFFF_FF00, 0x100);
+    assert!(region.contains(0xFFFF_FF00));
+    assert!(region.contains(0xFFFF_FFFF));
+    assert!(!region.contains(0xFFFF_FEFF));
+    assert!(!MemoryRegion::new(0x1000, 0).contains(0x1000));
+    assert!(MemoryRegion::new(0x1000, 0).is_empty());
+}